@@ -1,4 +1,42 @@
+use std::fmt;
+
+use crate::gug::NodeMetadata;
+
 /// Debug data associated with a node.
 #[derive(Clone, Debug, Default)]
 #[non_exhaustive]
-pub struct DebugData {}
+pub struct DebugData {
+    /// A human-readable label for the node.
+    pub label: Option<String>,
+}
+
+impl DebugData {
+    /// Creates debug data with the given label.
+    pub fn with_label(label: impl Into<String>) -> Self {
+        Self {
+            label: Some(label.into()),
+        }
+    }
+}
+
+impl NodeMetadata for DebugData {}
+
+/// The byte range of the source text a node was created from, attached as
+/// node metadata by frontends such as the QASM importer.
+///
+/// The default, empty span marks nodes without a known source.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SourceSpan {
+    /// The byte offset of the start of the span.
+    pub start: usize,
+    /// The byte offset one past the end of the span.
+    pub end: usize,
+}
+
+impl fmt::Display for SourceSpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+impl NodeMetadata for SourceSpan {}
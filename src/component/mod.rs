@@ -1,3 +1,6 @@
 pub mod debug;
 pub mod operation;
+pub mod physical;
+pub mod register;
+pub mod symbolic;
 pub mod wire_type;
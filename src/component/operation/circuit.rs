@@ -1,6 +1,9 @@
+use std::borrow::Cow;
+
 use lazy_static::lazy_static;
 
-use crate::component::wire_type::{ConstValue, Signature, WireType};
+use crate::component::symbolic::SymbolicAngle;
+use crate::component::wire_type::{AngleValue, ConstValue, Signature, WireType};
 
 pub(crate) type Param = f64;
 
@@ -16,12 +19,17 @@ pub enum Op {
     Tadj,
     Sadj,
     CX,
+    CZ,
+    /// The Toffoli gate, with two control qubits followed by the target.
+    CCX,
     ZZMax,
+    Swap,
     Reset,
     Input,
     Output,
     Noop(WireType),
     Measure,
+    MeasureReset,
     Barrier,
     AngleAdd,
     AngleMul,
@@ -29,13 +37,51 @@ pub enum Op {
     QuatMul,
     Copy { n_copies: u32, typ: WireType },
     Const(ConstValue),
+    /// An `Angle` given by an expression over free parameters, which can be
+    /// bound to values with [`crate::Gug::bind_symbols`].
+    Symbolic(SymbolicAngle),
     RxF64,
     RzF64,
+    /// A rotation about the X axis by an inlined angle, in half-turns.
+    Rx(AngleValue),
+    /// A rotation about the Z axis by an inlined angle, in half-turns.
+    Rz(AngleValue),
     TK1,
+    /// The general single-qubit gate `U3(θ, φ, λ) = Rz(φ) Ry(θ) Rz(λ)`, up
+    /// to a global phase, with inlined angles in half-turns.
+    U3 {
+        theta: AngleValue,
+        phi: AngleValue,
+        lambda: AngleValue,
+    },
     Rotation,
     ToRotation,
+    /// A reordering of qubit wires, applying no gate. The qubit entering on
+    /// wire `i` leaves on wire `perm[i]`, so backends can absorb it by
+    /// relabelling their qubits. `perm` must be a bijection on `0..n`, which
+    /// is checked by [`crate::Gug::validate`].
+    Permute(Vec<usize>),
     Xor,
+    And,
+    Or,
+    Not,
+    /// Integer equality, producing a `Bool`.
+    Eq,
+    /// Integer less-than comparison, producing a `Bool`.
+    Lt,
+    /// Integer greater-than comparison, producing a `Bool`.
+    Gt,
     Select(WireType),
+    /// Converts a classical value between wire types. Construct it with
+    /// [`Op::cast`] to only allow the conversions of [`WireType::can_cast_to`].
+    Cast { from: WireType, to: WireType },
+    /// A single-qubit gate with an additional control qubit, which comes
+    /// first in the signature.
+    Controlled(Box<Op>),
+    /// A gate applied only when a classical control bit is set, such as the
+    /// output of a `Measure`. The control bit is a `LinearBit` passed through
+    /// the operation, and comes first in the signature.
+    ClassicallyControlled(Box<Op>),
 }
 
 impl PartialEq for Op {
@@ -53,6 +99,34 @@ impl PartialEq for Op {
                 },
             ) => l_n_copies == r_n_copies && l_typ == r_typ,
             (Self::Const(l0), Self::Const(r0)) => l0 == r0,
+            (Self::Symbolic(l0), Self::Symbolic(r0)) => l0 == r0,
+            (
+                Self::Cast {
+                    from: l_from,
+                    to: l_to,
+                },
+                Self::Cast {
+                    from: r_from,
+                    to: r_to,
+                },
+            ) => l_from == r_from && l_to == r_to,
+            (Self::Rx(l0), Self::Rx(r0)) | (Self::Rz(l0), Self::Rz(r0)) => l0 == r0,
+            (
+                Self::U3 {
+                    theta: l_theta,
+                    phi: l_phi,
+                    lambda: l_lambda,
+                },
+                Self::U3 {
+                    theta: r_theta,
+                    phi: r_phi,
+                    lambda: r_lambda,
+                },
+            ) => l_theta == r_theta && l_phi == r_phi && l_lambda == r_lambda,
+            (Self::Select(l0), Self::Select(r0)) => l0 == r0,
+            (Self::Permute(l0), Self::Permute(r0)) => l0 == r0,
+            (Self::Controlled(l0), Self::Controlled(r0)) => l0 == r0,
+            (Self::ClassicallyControlled(l0), Self::ClassicallyControlled(r0)) => l0 == r0,
             _ => core::mem::discriminant(self) == core::mem::discriminant(other),
         }
     }
@@ -81,6 +155,28 @@ pub fn approx_eq(x: f64, y: f64, modulo: u32, tol: f64) -> bool {
     r < tol || r > modulo - tol
 }
 
+/// Returns `true` if `perm` maps `0..perm.len()` onto itself.
+pub(crate) fn is_permutation(perm: &[usize]) -> bool {
+    let mut seen = vec![false; perm.len()];
+    perm.iter()
+        .all(|&j| j < perm.len() && !std::mem::replace(&mut seen[j], true))
+}
+
+/// Returns the angle as a whole number of eighth turns (multiples of π/4),
+/// if it is one.
+pub(crate) fn eighth_turns(angle: &AngleValue) -> Option<i64> {
+    match angle {
+        AngleValue::Rational(r) => {
+            let eighths = r.0 * 4;
+            eighths.is_integer().then(|| eighths.to_integer())
+        }
+        AngleValue::F64(x) => {
+            let eighths = x * 4.0;
+            ((eighths - eighths.round()).abs() < 1e-9).then(|| eighths.round() as i64)
+        }
+    }
+}
+
 fn binary_op(typ: WireType) -> Signature {
     Signature::new_nonlinear(vec![typ, typ], vec![typ])
 }
@@ -97,18 +193,89 @@ impl Op {
         )
     }
 
+    /// Returns the number of input and output ports of the operation.
+    pub fn arity(&self) -> (usize, usize) {
+        self.signature().num_ports()
+    }
+
+    /// Returns the number of qubits the operation acts on.
+    pub fn num_qubits(&self) -> usize {
+        self.signature()
+            .linear
+            .iter()
+            .filter(|&&t| t == WireType::Qubit)
+            .count()
+    }
+
+    /// Returns `true` for operations acting on more than one qubit, which can
+    /// create entanglement between them. Classical controls do not count.
+    pub fn is_entangling(&self) -> bool {
+        self.num_qubits() > 1
+    }
+
     pub fn is_pure_classical(&self) -> bool {
         self.signature().purely_classical()
     }
 
+    /// Returns `true` for Clifford gates. Rotations with an inlined angle are
+    /// Clifford when the angle is a multiple of π/2.
+    pub fn is_clifford(&self) -> bool {
+        match self {
+            Op::H
+            | Op::X
+            | Op::Y
+            | Op::Z
+            | Op::S
+            | Op::Sadj
+            | Op::CX
+            | Op::CZ
+            | Op::ZZMax
+            | Op::Swap
+            | Op::Permute(_) => true,
+            Op::Rx(angle) | Op::Rz(angle) => eighth_turns(angle).map_or(false, |n| n % 2 == 0),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` for single-qubit gates that are diagonal in the
+    /// computational basis.
+    pub fn is_diagonal(&self) -> bool {
+        matches!(
+            self,
+            Op::Z | Op::S | Op::Sadj | Op::T | Op::Tadj | Op::RzF64 | Op::Rz(_)
+        )
+    }
+
+    /// Returns `true` for single-qubit rotations about the X axis.
+    pub fn is_x_rotation(&self) -> bool {
+        matches!(self, Op::X | Op::RxF64 | Op::Rx(_))
+    }
+
+    /// Checks whether two single-qubit gates applied to the same qubit commute.
+    ///
+    /// This is a conservative check: it returns `false` whenever commutation
+    /// cannot be established from the gate kinds alone. Identical gates
+    /// commute, but gates reading parameters from input wires, such as `TK1`,
+    /// are only identical when their inputs are, which is not known here.
+    pub fn commutes_with(&self, other: &Op) -> bool {
+        (self.is_diagonal() && other.is_diagonal())
+            || (self.is_x_rotation() && other.is_x_rotation())
+            || (self == other && self.is_one_qb_gate() && self.signature().purely_linear())
+    }
+
     pub fn signature(&self) -> Signature {
         match self {
             Op::Noop(typ) => Signature::new_linear(vec![*typ]),
             Op::H | Op::Reset | Op::T | Op::S | Op::Tadj | Op::Sadj | Op::X | Op::Y | Op::Z => {
                 ONEQBSIG.clone()
             }
-            Op::CX | Op::ZZMax => TWOQBSIG.clone(),
-            Op::Measure => Signature::new_linear(vec![WireType::Qubit, WireType::LinearBit]),
+            Op::Rx(_) | Op::Rz(_) | Op::U3 { .. } => ONEQBSIG.clone(),
+            Op::CX | Op::CZ | Op::ZZMax | Op::Swap => TWOQBSIG.clone(),
+            Op::CCX => Signature::new_linear(vec![WireType::Qubit; 3]),
+            Op::Permute(perm) => Signature::new_linear(vec![WireType::Qubit; perm.len()]),
+            Op::Measure | Op::MeasureReset => {
+                Signature::new_linear(vec![WireType::Qubit, WireType::LinearBit])
+            }
             Op::AngleAdd | Op::AngleMul => binary_op(WireType::Angle),
             Op::QuatMul => binary_op(WireType::Quat64),
             Op::AngleNeg => Signature::new_nonlinear(vec![WireType::Angle], vec![WireType::Angle]),
@@ -116,6 +283,7 @@ impl Op {
                 Signature::new_nonlinear(vec![*typ], vec![*typ; *n_copies as usize])
             }
             Op::Const(x) => Signature::new_nonlinear(vec![], vec![x.get_type()]),
+            Op::Symbolic(_) => Signature::new_nonlinear(vec![], vec![WireType::Angle]),
 
             Op::RxF64 | Op::RzF64 => {
                 Signature::new(vec![WireType::Qubit], [vec![WireType::Angle], vec![]])
@@ -126,10 +294,25 @@ impl Op {
                 vec![WireType::Angle, WireType::F64, WireType::F64, WireType::F64],
                 vec![WireType::Quat64],
             ),
-            Op::Xor => {
+            Op::Xor | Op::And | Op::Or => {
                 Signature::new_nonlinear(vec![WireType::Bool, WireType::Bool], vec![WireType::Bool])
             }
+            Op::Not => Signature::new_nonlinear(vec![WireType::Bool], vec![WireType::Bool]),
+            Op::Eq | Op::Lt | Op::Gt => {
+                Signature::new_nonlinear(vec![WireType::I64, WireType::I64], vec![WireType::Bool])
+            }
             Op::Select(wt) => Signature::new_nonlinear(vec![WireType::Bool, *wt, *wt], vec![*wt]),
+            Op::Cast { from, to } => Signature::new_nonlinear(vec![*from], vec![*to]),
+            Op::Controlled(op) => {
+                let mut signature = op.signature();
+                signature.linear.insert(0, WireType::Qubit);
+                signature
+            }
+            Op::ClassicallyControlled(op) => {
+                let mut signature = op.signature();
+                signature.linear.insert(0, WireType::LinearBit);
+                signature
+            }
             _ => Default::default(),
         }
     }
@@ -145,12 +328,16 @@ impl Op {
             Op::Tadj => "Tadj",
             Op::Sadj => "Sadj",
             Op::CX => "CX",
+            Op::CZ => "CZ",
+            Op::CCX => "CCX",
             Op::ZZMax => "ZZMax",
+            Op::Swap => "Swap",
             Op::Reset => "Reset",
             Op::Input => "Input",
             Op::Output => "Output",
             Op::Noop(_) => "Noop",
             Op::Measure => "Measure",
+            Op::MeasureReset => "MeasureReset",
             Op::Barrier => "Barrier",
             Op::AngleAdd => "AngleAdd",
             Op::AngleMul => "AngleMul",
@@ -158,16 +345,188 @@ impl Op {
             Op::QuatMul => "QuatMul",
             Op::Copy { .. } => "Copy",
             Op::Const(_) => "Const",
+            Op::Symbolic(_) => "Symbolic",
             Op::RxF64 => "RxF64",
             Op::RzF64 => "RzF64",
+            Op::Rx(_) => "Rx",
+            Op::Rz(_) => "Rz",
             Op::TK1 => "TK1",
+            Op::U3 { .. } => "U3",
             Op::Rotation => "Rotation",
             Op::ToRotation => "ToRotation",
+            Op::Permute(_) => "Permute",
             Op::Xor => "Xor",
+            Op::And => "And",
+            Op::Or => "Or",
+            Op::Not => "Not",
+            Op::Eq => "Eq",
+            Op::Lt => "Lt",
+            Op::Gt => "Gt",
             Op::Select(_) => "Select",
+            Op::Cast { .. } => "Cast",
+            Op::Controlled(_) => "Controlled",
+            Op::ClassicallyControlled(_) => "ClassicallyControlled",
+        }
+    }
+
+    /// Returns the name used to display and count the operation.
+    ///
+    /// This is [`Op::name`], except for controlled and classically controlled
+    /// gates which include the wrapped gate, as in `C[X]`.
+    pub fn display_name(&self) -> Cow<'_, str> {
+        match self {
+            Op::Controlled(op) | Op::ClassicallyControlled(op) => {
+                format!("C[{}]", op.display_name()).into()
+            }
+            op => op.name().into(),
+        }
+    }
+
+    /// Constructs an operation without fields from its [`Op::name`].
+    ///
+    /// Returns `None` for unknown names and for operations that need
+    /// additional data, such as `Noop`, `Copy`, `Const`, `Select` and `Cast`.
+    pub fn from_name(name: &str) -> Option<Op> {
+        Some(match name {
+            "H" => Op::H,
+            "T" => Op::T,
+            "S" => Op::S,
+            "X" => Op::X,
+            "Y" => Op::Y,
+            "Z" => Op::Z,
+            "Tadj" => Op::Tadj,
+            "Sadj" => Op::Sadj,
+            "CX" => Op::CX,
+            "CZ" => Op::CZ,
+            "CCX" => Op::CCX,
+            "ZZMax" => Op::ZZMax,
+            "Swap" => Op::Swap,
+            "Reset" => Op::Reset,
+            "Input" => Op::Input,
+            "Output" => Op::Output,
+            "Measure" => Op::Measure,
+            "MeasureReset" => Op::MeasureReset,
+            "Barrier" => Op::Barrier,
+            "AngleAdd" => Op::AngleAdd,
+            "AngleMul" => Op::AngleMul,
+            "AngleNeg" => Op::AngleNeg,
+            "QuatMul" => Op::QuatMul,
+            "RxF64" => Op::RxF64,
+            "RzF64" => Op::RzF64,
+            "TK1" => Op::TK1,
+            "Rotation" => Op::Rotation,
+            "ToRotation" => Op::ToRotation,
+            "Xor" => Op::Xor,
+            "And" => Op::And,
+            "Or" => Op::Or,
+            "Not" => Op::Not,
+            "Eq" => Op::Eq,
+            "Lt" => Op::Lt,
+            "Gt" => Op::Gt,
+            _ => return None,
+        })
+    }
+
+    /// Returns a `Cast` operation converting `from` values to `to`, or `None`
+    /// if the conversion is not allowed by [`WireType::can_cast_to`].
+    pub fn cast(from: WireType, to: WireType) -> Option<Op> {
+        from.can_cast_to(to).then_some(Op::Cast { from, to })
+    }
+
+    /// Returns the controlled version of a single-qubit unitary gate, with the
+    /// control qubit added as the first wire.
+    ///
+    /// Gates with a dedicated controlled form are mapped to it (`X` to `CX`
+    /// and `Z` to `CZ`), other unitary gates are wrapped in [`Op::Controlled`].
+    /// Returns `None` for non-unitary or multi-qubit operations.
+    pub fn controlled(&self) -> Option<Op> {
+        match self {
+            Op::X => Some(Op::CX),
+            Op::Z => Some(Op::CZ),
+            Op::H
+            | Op::Y
+            | Op::S
+            | Op::Sadj
+            | Op::T
+            | Op::Tadj
+            | Op::RxF64
+            | Op::RzF64
+            | Op::Rx(_)
+            | Op::Rz(_)
+            | Op::TK1
+            | Op::U3 { .. }
+            | Op::Rotation => Some(Op::Controlled(Box::new(self.clone()))),
+            _ => None,
+        }
+    }
+
+    /// Returns the inverse (dagger) of a parameterless unitary gate, or of a
+    /// gate with inlined angles.
+    pub fn inverse(&self) -> Option<Op> {
+        match self {
+            Op::Rx(angle) => Some(Op::Rx(-*angle)),
+            Op::Rz(angle) => Some(Op::Rz(-*angle)),
+            Op::U3 { theta, phi, lambda } => Some(Op::U3 {
+                theta: -*theta,
+                phi: -*lambda,
+                lambda: -*phi,
+            }),
+            Op::H | Op::X | Op::Y | Op::Z | Op::CX | Op::CZ | Op::CCX | Op::Swap => {
+                Some(self.clone())
+            }
+            Op::Permute(perm) => {
+                if !is_permutation(perm) {
+                    return None;
+                }
+                let mut inverse = vec![0; perm.len()];
+                for (i, &j) in perm.iter().enumerate() {
+                    *inverse.get_mut(j)? = i;
+                }
+                Some(Op::Permute(inverse))
+            }
+            Op::S => Some(Op::Sadj),
+            Op::Sadj => Some(Op::S),
+            Op::T => Some(Op::Tadj),
+            Op::Tadj => Some(Op::T),
+            _ => None,
         }
     }
 
+    /// Evaluates a classical operation on constant inputs, returning the
+    /// values of its outputs.
+    ///
+    /// Returns `None` for operations that cannot be evaluated, and when the
+    /// inputs do not match the signature.
+    pub fn eval_classical(&self, inputs: &[ConstValue]) -> Option<Vec<ConstValue>> {
+        use ConstValue::*;
+        let value = match (self, inputs) {
+            (Op::Const(value), []) => value.clone(),
+            (Op::Xor, [Bool(a), Bool(b)]) => Bool(a ^ b),
+            (Op::And, [Bool(a), Bool(b)]) => Bool(*a && *b),
+            (Op::Or, [Bool(a), Bool(b)]) => Bool(*a || *b),
+            (Op::Not, [Bool(a)]) => Bool(!a),
+            (Op::Eq, [I64(a), I64(b)]) => Bool(a == b),
+            (Op::Lt, [I64(a), I64(b)]) => Bool(a < b),
+            (Op::Gt, [I64(a), I64(b)]) => Bool(a > b),
+            (Op::AngleAdd, [Angle(a), Angle(b)]) => Angle(*a + *b),
+            (Op::AngleMul, [Angle(a), Angle(b)]) => Angle(*a * *b),
+            (Op::AngleNeg, [Angle(a)]) => Angle(-*a),
+            (Op::Select(_), [Bool(cond), a, b]) => {
+                if *cond {
+                    a.clone()
+                } else {
+                    b.clone()
+                }
+            }
+            (Op::Cast { from, to }, [value]) if value.get_type() == *from => value.cast(*to)?,
+            (Op::Copy { n_copies, typ }, [value]) if value.get_type() == *typ => {
+                return Some(vec![value.clone(); *n_copies as usize]);
+            }
+            _ => return None,
+        };
+        Some(vec![value])
+    }
+
     pub fn get_params(&self) -> Vec<Param> {
         todo!()
     }
@@ -177,23 +536,249 @@ impl Op {
 mod tests {
     use super::*;
 
-    #[cfg(feature = "pyo3")]
-    fn py_int(i: i32) -> Op {
-        use crate::circuit::py_circuit::PyCustom;
-        Op::Custom(Box::new(PyCustom(Python::with_gil(|py| i.into_py(py)))))
+    #[test]
+    fn from_name() {
+        let ops = [
+            Op::H,
+            Op::T,
+            Op::S,
+            Op::X,
+            Op::Y,
+            Op::Z,
+            Op::Tadj,
+            Op::Sadj,
+            Op::CX,
+            Op::CZ,
+            Op::CCX,
+            Op::ZZMax,
+            Op::Swap,
+            Op::Reset,
+            Op::Input,
+            Op::Output,
+            Op::Measure,
+            Op::MeasureReset,
+            Op::Barrier,
+            Op::AngleAdd,
+            Op::AngleMul,
+            Op::AngleNeg,
+            Op::QuatMul,
+            Op::RxF64,
+            Op::RzF64,
+            Op::TK1,
+            Op::Rotation,
+            Op::ToRotation,
+            Op::Xor,
+            Op::And,
+            Op::Or,
+            Op::Not,
+        ];
+        for op in ops {
+            assert_eq!(Op::from_name(op.name()), Some(op));
+        }
+        assert_eq!(Op::from_name("Noop"), None);
+        assert_eq!(Op::from_name("Copy"), None);
+        assert_eq!(Op::from_name("Const"), None);
+        assert_eq!(Op::from_name("Select"), None);
+        assert_eq!(Op::from_name("NotAGate"), None);
+    }
+
+    #[test]
+    fn clifford() {
+        use crate::component::wire_type::Rational;
+        use num_rational::Rational64;
+
+        assert!(Op::H.is_clifford() && Op::CX.is_clifford() && Op::Sadj.is_clifford());
+        assert!(!Op::T.is_clifford() && !Op::Tadj.is_clifford() && !Op::CCX.is_clifford());
+        assert!(Op::Rz(AngleValue::F64(1.5)).is_clifford());
+        assert!(!Op::Rz(AngleValue::F64(0.25)).is_clifford());
+        let quarter = AngleValue::Rational(Rational(Rational64::new(1, 4)));
+        assert_eq!(eighth_turns(&quarter), Some(1));
+        assert!(!Op::Rx(quarter).is_clifford());
+        assert_eq!(eighth_turns(&AngleValue::F64(0.1)), None);
+    }
+
+    #[test]
+    fn arity() {
+        assert_eq!(Op::CX.num_qubits(), 2);
+        assert_eq!(Op::CCX.num_qubits(), 3);
+        assert_eq!(Op::Measure.num_qubits(), 1);
+        assert_eq!(Op::Xor.num_qubits(), 0);
+        assert_eq!(Op::Measure.arity(), (2, 2));
+        assert_eq!(Op::RzF64.arity(), (2, 1));
+        assert_eq!(Op::Const(ConstValue::Bool(true)).arity(), (0, 1));
+    }
+
+    #[test]
+    fn inverse() {
+        assert_eq!(Op::H.inverse(), Some(Op::H));
+        assert_eq!(Op::T.inverse(), Some(Op::Tadj));
+        assert_eq!(Op::Sadj.inverse(), Some(Op::S));
+        assert_eq!(Op::RzF64.inverse(), None);
+        assert_eq!(
+            Op::Rz(AngleValue::F64(0.25)).inverse(),
+            Some(Op::Rz(AngleValue::F64(-0.25)))
+        );
+        let u3 = |theta, phi, lambda| Op::U3 {
+            theta: AngleValue::F64(theta),
+            phi: AngleValue::F64(phi),
+            lambda: AngleValue::F64(lambda),
+        };
+        assert_eq!(u3(0.5, 0.25, 1.0).inverse(), Some(u3(-0.5, -1.0, -0.25)));
+        assert_eq!(u3(0.5, 0.25, 1.0).signature(), Op::H.signature());
+    }
+
+    #[test]
+    fn permute() {
+        let swap = Op::Permute(vec![1, 0]);
+        assert_eq!(swap.signature(), Op::Swap.signature());
+        assert_eq!(swap.name(), "Permute");
+        assert_eq!(swap.inverse(), Some(swap.clone()));
+
+        // Following a permutation by its inverse returns every wire in place.
+        let perm = vec![2, 0, 3, 1];
+        let Some(Op::Permute(inverse)) = Op::Permute(perm.clone()).inverse() else {
+            panic!("permutations are invertible")
+        };
+        assert_eq!(inverse, vec![1, 3, 0, 2]);
+        assert!((0..4).all(|i| inverse[perm[i]] == i));
+        assert_eq!(Op::Permute(vec![0, 5]).inverse(), None);
+        assert_eq!(Op::Permute(vec![1, 1]).inverse(), None);
+        assert_eq!(Op::Permute(vec![]).inverse(), Some(Op::Permute(vec![])));
+    }
+
+    #[test]
+    fn eval_classical() {
+        use crate::component::wire_type::AngleValue;
+
+        assert_eq!(
+            Op::Xor.eval_classical(&[ConstValue::Bool(true), ConstValue::Bool(true)]),
+            Some(vec![ConstValue::Bool(false)])
+        );
+        assert_eq!(
+            Op::AngleNeg.eval_classical(&[ConstValue::f64_angle(0.5)]),
+            Some(vec![ConstValue::Angle(AngleValue::F64(-0.5))])
+        );
+        assert_eq!(Op::Xor.eval_classical(&[ConstValue::Bool(true)]), None);
+        assert_eq!(Op::H.eval_classical(&[]), None);
+    }
+
+    #[test]
+    fn cast() {
+        let cast = Op::cast(WireType::I64, WireType::F64).unwrap();
+        assert_eq!(
+            cast.signature(),
+            Signature::new_nonlinear(vec![WireType::I64], vec![WireType::F64])
+        );
+        assert!(cast.is_pure_classical());
+        assert_eq!(cast.name(), "Cast");
+        assert_eq!(
+            cast.eval_classical(&[ConstValue::I64(3)]),
+            Some(vec![ConstValue::F64(3.0)])
+        );
+        assert_eq!(cast.eval_classical(&[ConstValue::F64(3.0)]), None);
+
+        assert_eq!(Op::cast(WireType::F64, WireType::I64), None);
+        assert_eq!(Op::cast(WireType::Qubit, WireType::LinearBit), None);
+        assert_ne!(cast, Op::cast(WireType::F64, WireType::Angle).unwrap());
+    }
+
+    #[test]
+    fn boolean_ops() {
+        let (t, f) = (ConstValue::Bool(true), ConstValue::Bool(false));
+        for op in [Op::And, Op::Or] {
+            assert_eq!(op.signature().nonlinear[0], vec![WireType::Bool; 2]);
+            assert_eq!(op.signature().nonlinear[1], vec![WireType::Bool]);
+            assert!(op.is_pure_classical());
+        }
+        assert_eq!(Op::Not.signature().nonlinear[0], vec![WireType::Bool]);
+        assert!(Op::Not.is_pure_classical());
+
+        let eval = |op: Op, inputs: &[ConstValue]| op.eval_classical(inputs).unwrap();
+        assert_eq!(eval(Op::And, &[t.clone(), f.clone()]), vec![f.clone()]);
+        assert_eq!(eval(Op::And, &[t.clone(), t.clone()]), vec![t.clone()]);
+        assert_eq!(eval(Op::Or, &[t.clone(), f.clone()]), vec![t.clone()]);
+        assert_eq!(eval(Op::Or, &[f.clone(), f.clone()]), vec![f.clone()]);
+        assert_eq!(eval(Op::Not, &[f]), vec![t]);
+    }
+
+    #[test]
+    fn comparison_ops() {
+        for op in [Op::Eq, Op::Lt, Op::Gt] {
+            assert_eq!(op.signature().nonlinear[0], vec![WireType::I64; 2]);
+            assert_eq!(op.signature().nonlinear[1], vec![WireType::Bool]);
+            assert!(op.signature().linear.is_empty());
+            assert!(op.is_pure_classical());
+            assert_eq!(Op::from_name(op.name()), Some(op));
+        }
+
+        let eval = |op: Op, a, b| op.eval_classical(&[ConstValue::I64(a), ConstValue::I64(b)]);
+        assert_eq!(eval(Op::Lt, 2, 5), Some(vec![ConstValue::Bool(true)]));
+        assert_eq!(eval(Op::Gt, 2, 5), Some(vec![ConstValue::Bool(false)]));
+        assert_eq!(eval(Op::Eq, 3, 3), Some(vec![ConstValue::Bool(true)]));
+        assert_eq!(Op::Lt.eval_classical(&[ConstValue::Bool(true)]), None);
+    }
+
+    #[test]
+    fn controlled() {
+        let cz = Op::Z.controlled().unwrap();
+        assert_eq!(cz, Op::CZ);
+        assert!(cz.is_two_qb_gate());
+        assert_eq!(cz.signature().num_ports(), Op::CX.signature().num_ports());
+
+        let ch = Op::H.controlled().unwrap();
+        assert_eq!(ch, Op::Controlled(Box::new(Op::H)));
+        assert_eq!(ch.name(), "Controlled");
+        assert_eq!(ch.display_name(), "C[H]");
+
+        let crz = Op::RzF64.controlled().unwrap();
+        assert_eq!(crz.signature().linear, vec![WireType::Qubit; 2]);
+        assert_eq!(crz.signature().nonlinear[0], vec![WireType::Angle]);
+
+        assert_eq!(Op::X.controlled(), Some(Op::CX));
+        assert_eq!(Op::Measure.controlled(), None);
+        assert_eq!(Op::CX.controlled(), None);
+    }
+
+    #[test]
+    fn classically_controlled() {
+        let cx = Op::ClassicallyControlled(Box::new(Op::X));
+        assert_eq!(cx.name(), "ClassicallyControlled");
+        assert_eq!(cx.display_name(), "C[X]");
+        assert_eq!(cx.signature().linear, vec![WireType::LinearBit, WireType::Qubit]);
+        assert_eq!(cx.num_qubits(), 1);
+        assert!(!cx.is_entangling() && !cx.is_pure_classical());
+        assert_ne!(cx, Op::Controlled(Box::new(Op::X)));
+
+        let crz = Op::ClassicallyControlled(Box::new(Op::RzF64));
+        assert_eq!(crz.name(), "ClassicallyControlled");
+        assert_eq!(crz.display_name(), "C[RzF64]");
+        assert_eq!(crz.signature().nonlinear[0], vec![WireType::Angle]);
+        let ccx = Op::ClassicallyControlled(Box::new(Op::CX));
+        assert_eq!(ccx.num_qubits(), 2);
+        assert!(ccx.is_entangling());
+    }
+
+    #[test]
+    fn commutation() {
+        assert!(Op::Rz(AngleValue::F64(0.5)).commutes_with(&Op::T));
+        assert!(Op::H.commutes_with(&Op::H));
+        let u3 = Op::U3 {
+            theta: AngleValue::F64(0.5),
+            phi: AngleValue::F64(0.0),
+            lambda: AngleValue::F64(0.25),
+        };
+        assert!(u3.commutes_with(&u3.clone()));
+        assert!(!Op::H.commutes_with(&Op::X));
+        // The angles of these gates are inputs, which may differ.
+        assert!(!Op::TK1.commutes_with(&Op::TK1));
+        assert!(!Op::Rotation.commutes_with(&Op::Rotation));
     }
 
     #[test]
     fn equality() {
-        #[cfg(feature = "pyo3")]
-        pyo3::prepare_freethreaded_python();
         let ops = [
             Op::Input,
             Op::Output,
-            #[cfg(feature = "pyo3")]
-            py_int(123),
-            #[cfg(feature = "pyo3")]
-            py_int(321),
             #[cfg(feature = "tkcxx")]
             unitary_x(),
             #[cfg(feature = "tkcxx")]
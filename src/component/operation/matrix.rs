@@ -0,0 +1,219 @@
+//! Unitary matrices of circuit operations.
+//!
+//! Multi-qubit matrices use a big-endian qubit ordering: the first qubit wire
+//! of an operation corresponds to the most significant bit of the row and
+//! column indices.
+
+use std::f64::consts::{FRAC_1_SQRT_2, FRAC_PI_4};
+
+use ndarray::{array, Array2};
+use num_complex::Complex64;
+
+use super::circuit::{is_permutation, Op};
+use crate::component::wire_type::AngleValue;
+
+const ZERO: Complex64 = Complex64::new(0.0, 0.0);
+const ONE: Complex64 = Complex64::new(1.0, 0.0);
+const I: Complex64 = Complex64::new(0.0, 1.0);
+
+/// A rotation about the Z axis by `theta` half-turns.
+pub fn rz(theta: f64) -> Array2<Complex64> {
+    let phase = Complex64::from_polar(1.0, theta * std::f64::consts::FRAC_PI_2);
+    array![[phase.conj(), ZERO], [ZERO, phase]]
+}
+
+/// A rotation about the X axis by `theta` half-turns.
+pub fn rx(theta: f64) -> Array2<Complex64> {
+    let half = theta * std::f64::consts::FRAC_PI_2;
+    let c = Complex64::new(half.cos(), 0.0);
+    let s = Complex64::new(0.0, -half.sin());
+    array![[c, s], [s, c]]
+}
+
+/// A diagonal phase gate `diag(1, e^{iπθ})`.
+fn phase(theta: f64) -> Array2<Complex64> {
+    array![
+        [ONE, ZERO],
+        [ZERO, Complex64::from_polar(1.0, theta * std::f64::consts::PI)]
+    ]
+}
+
+/// The matrix moving the qubit on wire `i` to wire `perm[i]`, or `None` if
+/// `perm` is not a permutation.
+fn permutation(perm: &[usize]) -> Option<Array2<Complex64>> {
+    if !is_permutation(perm) {
+        return None;
+    }
+    let n = perm.len();
+    let mut matrix = Array2::zeros((1 << n, 1 << n));
+    for col in 0..1usize << n {
+        let row = (0..n)
+            .filter(|&i| col >> (n - 1 - i) & 1 == 1)
+            .map(|i| 1 << (n - 1 - perm[i]))
+            .sum::<usize>();
+        matrix[[row, col]] = ONE;
+    }
+    Some(matrix)
+}
+
+/// The `U3` gate, with angles in radians.
+fn u3(theta: f64, phi: f64, lambda: f64) -> Array2<Complex64> {
+    let c = Complex64::new((theta / 2.0).cos(), 0.0);
+    let s = Complex64::new((theta / 2.0).sin(), 0.0);
+    let e = |angle: f64| Complex64::from_polar(1.0, angle);
+    array![[c, -e(lambda) * s], [e(phi) * s, e(phi + lambda) * c]]
+}
+
+impl Op {
+    /// Returns the unitary matrix of the operation, given the values of its
+    /// angle inputs in order.
+    ///
+    /// Returns `None` for non-unitary or classical operations, and when too
+    /// few angles are provided.
+    pub fn unitary(&self, angles: &[AngleValue]) -> Option<Array2<Complex64>> {
+        let angle = |i: usize| angles.get(i).map(AngleValue::to_f64);
+        let h = Complex64::new(FRAC_1_SQRT_2, 0.0);
+        let matrix = match self {
+            Op::H => array![[h, h], [h, -h]],
+            Op::X => array![[ZERO, ONE], [ONE, ZERO]],
+            Op::Y => array![[ZERO, -I], [I, ZERO]],
+            Op::Z => phase(1.0),
+            Op::S => phase(0.5),
+            Op::Sadj => phase(-0.5),
+            Op::T => phase(0.25),
+            Op::Tadj => phase(-0.25),
+            Op::Noop(_) => Array2::eye(2),
+            Op::RzF64 => rz(angle(0)?),
+            Op::RxF64 => rx(angle(0)?),
+            Op::Rz(angle) => rz(angle.to_f64()),
+            Op::Rx(angle) => rx(angle.to_f64()),
+            Op::TK1 => rz(angle(0)?).dot(&rx(angle(1)?)).dot(&rz(angle(2)?)),
+            Op::U3 { theta, phi, lambda } => u3(theta.radians(), phi.radians(), lambda.radians()),
+            Op::CX => array![
+                [ONE, ZERO, ZERO, ZERO],
+                [ZERO, ONE, ZERO, ZERO],
+                [ZERO, ZERO, ZERO, ONE],
+                [ZERO, ZERO, ONE, ZERO]
+            ],
+            Op::CZ => Array2::from_diag(&array![ONE, ONE, ONE, -ONE]),
+            Op::CCX => {
+                let mut matrix = Array2::eye(8);
+                matrix.slice_mut(ndarray::s![6.., 6..]).assign(&array![[ZERO, ONE], [ONE, ZERO]]);
+                matrix
+            }
+            Op::Swap => array![
+                [ONE, ZERO, ZERO, ZERO],
+                [ZERO, ZERO, ONE, ZERO],
+                [ZERO, ONE, ZERO, ZERO],
+                [ZERO, ZERO, ZERO, ONE]
+            ],
+            Op::Permute(perm) => permutation(perm)?,
+            Op::ZZMax => {
+                let p = Complex64::from_polar(1.0, -FRAC_PI_4);
+                let m = p.conj();
+                Array2::from_diag(&array![p, m, m, p])
+            }
+            Op::Controlled(op) => {
+                let target = op.unitary(angles)?;
+                if target.dim() != (2, 2) {
+                    return None;
+                }
+                let mut matrix = Array2::eye(4);
+                matrix.slice_mut(ndarray::s![2.., 2..]).assign(&target);
+                matrix
+            }
+            _ => return None,
+        };
+        Some(matrix)
+    }
+}
+
+/// Checks whether two matrices are equal up to a global phase, within `tol`.
+pub fn equal_up_to_phase(a: &Array2<Complex64>, b: &Array2<Complex64>, tol: f64) -> bool {
+    if a.shape() != b.shape() {
+        return false;
+    }
+    // Find the phase from the largest entry of `a`.
+    let Some((idx, _)) = a
+        .indexed_iter()
+        .max_by(|(_, x), (_, y)| x.norm().total_cmp(&y.norm()))
+    else {
+        return true;
+    };
+    if b[idx].norm() < tol {
+        return false;
+    }
+    let phase = a[idx] / b[idx];
+    a.iter()
+        .zip(b.iter())
+        .all(|(x, y)| (x - phase * y).norm() < tol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_identities() {
+        let h = Op::H.unitary(&[]).unwrap();
+        let x = Op::X.unitary(&[]).unwrap();
+        let z = Op::Z.unitary(&[]).unwrap();
+        assert!(equal_up_to_phase(&h.dot(&z).dot(&h), &x, 1e-10));
+
+        let s = Op::S.unitary(&[]).unwrap();
+        assert!(equal_up_to_phase(&s.dot(&s), &z, 1e-10));
+
+        let rz = Op::RzF64.unitary(&[AngleValue::F64(1.0)]).unwrap();
+        assert!(equal_up_to_phase(&rz, &z, 1e-10));
+        assert!(Op::RzF64.unitary(&[]).is_none());
+
+        let cx = Op::CX.unitary(&[]).unwrap();
+        let controlled_x = Op::Controlled(Box::new(Op::X)).unitary(&[]).unwrap();
+        assert!(equal_up_to_phase(&cx, &controlled_x, 1e-10));
+        assert!(Op::Measure.unitary(&[]).is_none());
+    }
+
+    #[test]
+    fn u3_matrix() {
+        let u3 = |theta, phi, lambda| Op::U3 {
+            theta: AngleValue::F64(theta),
+            phi: AngleValue::F64(phi),
+            lambda: AngleValue::F64(lambda),
+        };
+        let close = |a: &Array2<Complex64>, b: &Array2<Complex64>| {
+            a.iter().zip(b.iter()).all(|(x, y)| (x - y).norm() < 1e-10)
+        };
+
+        // U3(π, 0, π) is exactly X, and U3(π/2, 0, π) is exactly H.
+        let x = Op::X.unitary(&[]).unwrap();
+        assert!(close(&u3(1.0, 0.0, 1.0).unitary(&[]).unwrap(), &x));
+        let h = Op::H.unitary(&[]).unwrap();
+        assert!(close(&u3(0.5, 0.0, 1.0).unitary(&[]).unwrap(), &h));
+        // U3(0, 0, λ) is a phase gate, and U3(θ, -π/2, π/2) an X rotation.
+        let t = Op::T.unitary(&[]).unwrap();
+        assert!(close(&u3(0.0, 0.0, 0.25).unitary(&[]).unwrap(), &t));
+        let rx = Op::Rx(AngleValue::F64(0.3)).unitary(&[]).unwrap();
+        assert!(close(&u3(0.3, -0.5, 0.5).unitary(&[]).unwrap(), &rx));
+
+        let gate = u3(0.3, 0.7, -1.2);
+        let matrix = gate.unitary(&[]).unwrap();
+        let dagger = gate.inverse().unwrap().unitary(&[]).unwrap();
+        assert!(close(&matrix.dot(&dagger), &Array2::eye(2)));
+        let adjoint = matrix.t().mapv(|x| x.conj());
+        assert!(close(&dagger, &adjoint));
+    }
+
+    #[test]
+    fn permutation_matrix() {
+        let swap = Op::Swap.unitary(&[]).unwrap();
+        assert_eq!(Op::Permute(vec![1, 0]).unitary(&[]), Some(swap));
+
+        let perm = Op::Permute(vec![2, 0, 1]);
+        let matrix = perm.unitary(&[]).unwrap();
+        let inverse = perm.inverse().unwrap().unitary(&[]).unwrap();
+        assert_eq!(matrix.dot(&inverse), Array2::eye(8));
+        // |100⟩, with the first qubit set, is sent to |001⟩.
+        assert_eq!(matrix[[0b001, 0b100]], ONE);
+        assert!(Op::Permute(vec![0, 0]).unitary(&[]).is_none());
+    }
+}
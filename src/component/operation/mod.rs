@@ -1,6 +1,8 @@
 #![allow(dead_code)]
 
 use std::any::Any;
+use std::borrow::Cow;
+use std::sync::OnceLock;
 
 use downcast_rs::{impl_downcast, Downcast};
 
@@ -9,6 +11,8 @@ use crate::macros::impl_box_clone;
 use super::wire_type::Signature;
 
 pub mod circuit;
+#[cfg(feature = "matrix")]
+pub mod matrix;
 
 /// The operation type for a node in the GUG.
 #[derive(Clone, Debug)]
@@ -31,48 +35,131 @@ impl Op {
         }
     }
 
+    /// Returns the name used to display and count the operation, see
+    /// [`circuit::Op::display_name`].
+    pub fn display_name(&self) -> Cow<'_, str> {
+        match self {
+            Self::Circuit(op) => op.display_name(),
+            op => op.name().into(),
+        }
+    }
+
+    /// Returns `true` for the circuit `Input` and `Output` boundary operations.
+    pub fn is_boundary(&self) -> bool {
+        matches!(
+            self,
+            Self::Circuit(circuit::Op::Input) | Self::Circuit(circuit::Op::Output)
+        )
+    }
+
+    /// Returns the circuit operation, if this is one.
+    pub fn as_circuit(&self) -> Option<&circuit::Op> {
+        match self {
+            Self::Circuit(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Returns the control flow operation, if this is one.
+    pub fn as_control_flow(&self) -> Option<&ControlFlowOp> {
+        match self {
+            Self::ControlFlow(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Returns the opaque operation, if this is one.
+    pub fn as_opaque(&self) -> Option<&dyn CustomOp> {
+        match self {
+            Self::Opaque(op) => Some(&**op),
+            _ => None,
+        }
+    }
+
+    pub fn is_circuit(&self) -> bool {
+        matches!(self, Self::Circuit(_))
+    }
+
+    pub fn is_control_flow(&self) -> bool {
+        matches!(self, Self::ControlFlow(_))
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        matches!(self, Self::Opaque(_))
+    }
+
     pub fn signature(&self) -> Signature {
         match self {
             Self::Circuit(op) => op.signature(),
             Self::Opaque(op) => op.signature(),
-            _ => Default::default(),
+            Self::ControlFlow(op) => op.signature().unwrap_or_default(),
         }
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum ControlFlowOp {
-    /// A conditional operation
+    /// A conditional operation. The first input is a `Bool` predicate, and
+    /// the remaining inputs are passed to the body stored as the children of
+    /// the node, between an `Input` and an `Output` node. The body is only
+    /// applied when the predicate is true, otherwise the inputs are passed
+    /// through unchanged.
     #[non_exhaustive]
     Conditional,
-    /// A loop operation
+    /// A loop operation. The loop body is stored as the children of the
+    /// node, between an `Input` and an `Output` node with matching types.
     #[non_exhaustive]
-    Loop,
+    Loop {
+        /// The number of iterations, if known statically.
+        iterations: Option<usize>,
+    },
 }
 
 impl ControlFlowOp {
     pub fn name(&self) -> &str {
         match self {
             Self::Conditional => "Conditional",
-            Self::Loop => "Loop",
+            Self::Loop { .. } => "Loop",
         }
     }
 
+    /// Control flow operations have no fixed signature: the ports of a node
+    /// are given when adding it, matching the boundary of its region.
     pub fn signature(&self) -> Option<Signature> {
-        todo!()
+        None
     }
 }
 
 impl PartialEq for Op {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
+            (Self::ControlFlow(l0), Self::ControlFlow(r0)) => l0 == r0,
+            (Self::Circuit(l0), Self::Circuit(r0)) => l0 == r0,
             (Self::Opaque(l0), Self::Opaque(r0)) => l0.eq(&**r0),
             _ => core::mem::discriminant(self) == core::mem::discriminant(other),
         }
     }
 }
 
+impl From<circuit::Op> for Op {
+    fn from(op: circuit::Op) -> Self {
+        Self::Circuit(op)
+    }
+}
+
+impl From<ControlFlowOp> for Op {
+    fn from(op: ControlFlowOp) -> Self {
+        Self::ControlFlow(op)
+    }
+}
+
+impl From<Box<dyn CustomOp>> for Op {
+    fn from(op: Box<dyn CustomOp>) -> Self {
+        Self::Opaque(op)
+    }
+}
+
 impl Default for Op {
     fn default() -> Self {
         Self::Circuit(Default::default())
@@ -91,12 +178,228 @@ pub trait CustomOp: Send + Sync + std::fmt::Debug + CustomOpBoxClone + Any + Dow
         Err(ToGUGFail)
     }
 
-    /// Check if two custom ops are equal, by downcasting and comparing the definitions.
+    /// The data needed to rebuild the operation from its name with an
+    /// [`OpRegistry`](crate::extension::OpRegistry), or `None` if the
+    /// operation cannot be serialized.
+    fn payload(&self) -> Option<String> {
+        None
+    }
+
+    /// Check if two custom ops are equal.
+    ///
+    /// The default implementation considers two operations equal when they
+    /// have the same concrete type and the same name. Operations carrying
+    /// more data should override it, downcasting `other` and comparing their
+    /// definitions.
     fn eq(&self, other: &dyn CustomOp) -> bool {
-        let _ = other;
-        false
+        self.as_any().type_id() == other.as_any().type_id() && self.name() == other.name()
+    }
+
+    /// Whether the operation applies a unitary transformation to its qubits,
+    /// with no measurement or other irreversible effect.
+    fn is_unitary(&self) -> bool {
+        true
+    }
+
+    /// Whether the operation only computes on classical values. By default,
+    /// any operation without qubit wires is considered classical.
+    fn is_pure_classical(&self) -> bool {
+        self.signature().purely_classical()
     }
 }
 
 impl_downcast!(CustomOp);
 impl_box_clone!(CustomOp, CustomOpBoxClone);
+
+/// Wraps a custom operation whose [`CustomOp::signature`] is expensive to
+/// compute, computing it on the first call and returning a copy afterwards.
+///
+/// The wrapped operation cannot be modified, so the cached signature never
+/// goes stale. Replacing the operation of a node with [`crate::Gug::set_optype`]
+/// drops the wrapper together with its cache, and cloning the wrapper copies
+/// the cached value.
+#[derive(Clone, Debug)]
+pub struct CachedSignature<T> {
+    op: T,
+    signature: OnceLock<Signature>,
+}
+
+impl<T: CustomOp + Clone> CachedSignature<T> {
+    pub fn new(op: T) -> Self {
+        Self {
+            op,
+            signature: OnceLock::new(),
+        }
+    }
+
+    /// Returns the wrapped operation.
+    pub fn inner(&self) -> &T {
+        &self.op
+    }
+}
+
+impl<T: CustomOp + Clone> CustomOp for CachedSignature<T> {
+    fn name(&self) -> &str {
+        self.op.name()
+    }
+
+    fn signature(&self) -> Signature {
+        self.signature.get_or_init(|| self.op.signature()).clone()
+    }
+
+    fn to_gug(&self) -> Result<crate::Gug, ToGUGFail> {
+        self.op.to_gug()
+    }
+
+    fn payload(&self) -> Option<String> {
+        self.op.payload()
+    }
+
+    fn eq(&self, other: &dyn CustomOp) -> bool {
+        match other.downcast_ref::<Self>() {
+            Some(other) => self.op.eq(&other.op),
+            None => self.op.eq(other),
+        }
+    }
+
+    fn is_unitary(&self) -> bool {
+        self.op.is_unitary()
+    }
+
+    fn is_pure_classical(&self) -> bool {
+        self.op.is_pure_classical()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::component::wire_type::WireType;
+
+    #[derive(Clone, Debug)]
+    struct MyOp;
+
+    impl CustomOp for MyOp {
+        fn name(&self) -> &str {
+            "MyOp"
+        }
+
+        fn signature(&self) -> Signature {
+            Default::default()
+        }
+    }
+
+    #[derive(Clone, Debug, Default)]
+    struct CountingOp(Arc<AtomicUsize>);
+
+    impl CustomOp for CountingOp {
+        fn name(&self) -> &str {
+            "CountingOp"
+        }
+
+        fn signature(&self) -> Signature {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Signature::new_linear(vec![WireType::Qubit])
+        }
+    }
+
+    #[test]
+    fn cached_signature() {
+        let calls = |op: &CountingOp| op.0.load(Ordering::Relaxed);
+
+        let uncached = CountingOp::default();
+        let op = Op::Opaque(Box::new(uncached.clone()));
+        for _ in 0..3 {
+            op.signature();
+        }
+        assert_eq!(calls(&uncached), 3);
+
+        let counter = CountingOp::default();
+        let op = Op::Opaque(Box::new(CachedSignature::new(counter.clone())));
+        for _ in 0..3 {
+            assert_eq!(op.signature().linear, vec![WireType::Qubit]);
+        }
+        assert_eq!(calls(&counter), 1);
+        assert_eq!(op.clone().signature().linear.len(), 1);
+        assert_eq!(calls(&counter), 1);
+        assert_eq!(op.name(), "CountingOp");
+    }
+
+    #[derive(Clone, Debug)]
+    struct Named(&'static str);
+
+    impl CustomOp for Named {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn signature(&self) -> Signature {
+            Default::default()
+        }
+    }
+
+    #[test]
+    fn default_custom_eq() {
+        let op = |name| Op::Opaque(Box::new(Named(name)));
+        assert_eq!(op("a"), op("a"));
+        assert_ne!(op("a"), op("b"));
+        // Operations of different types are never equal, even with the same name.
+        assert_ne!(op("MyOp"), Op::Opaque(Box::new(MyOp)));
+        assert!(Named("a").eq(&Named("a")));
+    }
+
+    #[test]
+    fn from_sub_ops() {
+        let gate: Op = circuit::Op::H.into();
+        assert_eq!(gate, Op::Circuit(circuit::Op::H));
+
+        let cond: Op = ControlFlowOp::Conditional.into();
+        assert!(matches!(cond, Op::ControlFlow(ControlFlowOp::Conditional)));
+
+        let opaque: Op = (Box::new(MyOp) as Box<dyn CustomOp>).into();
+        assert_eq!(opaque.as_opaque().map(|op| op.name()), Some("MyOp"));
+
+        let mut gug = crate::Gug::new();
+        let h = gug.add_node(circuit::Op::H.into());
+        assert_eq!(gug.optype(h), &gate);
+    }
+
+    #[test]
+    fn control_flow_equality() {
+        let twice = Op::ControlFlow(ControlFlowOp::Loop {
+            iterations: Some(2),
+        });
+        let thrice = Op::ControlFlow(ControlFlowOp::Loop {
+            iterations: Some(3),
+        });
+        let cond = Op::ControlFlow(ControlFlowOp::Conditional);
+        assert_eq!(twice, twice.clone());
+        assert_ne!(twice, thrice);
+        assert_ne!(twice, cond);
+        assert_eq!(cond, Op::ControlFlow(ControlFlowOp::Conditional));
+    }
+
+    #[test]
+    fn accessors() {
+        let gate = Op::Circuit(circuit::Op::H);
+        assert_eq!(gate.as_circuit(), Some(&circuit::Op::H));
+        assert!(gate.as_control_flow().is_none());
+        assert!(gate.as_opaque().is_none());
+        assert!(gate.is_circuit() && !gate.is_control_flow() && !gate.is_opaque());
+
+        let cond = Op::ControlFlow(ControlFlowOp::Conditional);
+        assert!(matches!(cond.as_control_flow(), Some(ControlFlowOp::Conditional)));
+        assert!(cond.as_circuit().is_none());
+        assert!(cond.as_opaque().is_none());
+        assert!(cond.is_control_flow() && !cond.is_circuit() && !cond.is_opaque());
+
+        let opaque = Op::Opaque(Box::new(MyOp));
+        assert_eq!(opaque.as_opaque().map(|op| op.name()), Some("MyOp"));
+        assert!(opaque.as_circuit().is_none());
+        assert!(opaque.as_control_flow().is_none());
+        assert!(opaque.is_opaque() && !opaque.is_circuit() && !opaque.is_control_flow());
+    }
+}
@@ -0,0 +1,38 @@
+//! Hardware placement information.
+
+use crate::gug::{NodeMetadata, PortMetadata};
+
+/// The physical qubit a qubit wire is mapped to, attached as port metadata.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PhysicalQubit(pub u32);
+
+impl PhysicalQubit {
+    /// Marker for ports that have not been assigned a physical qubit.
+    pub const UNASSIGNED: PhysicalQubit = PhysicalQubit(u32::MAX);
+
+    /// Returns `true` if this is a real physical qubit index.
+    pub fn is_assigned(&self) -> bool {
+        *self != Self::UNASSIGNED
+    }
+}
+
+impl Default for PhysicalQubit {
+    fn default() -> Self {
+        Self::UNASSIGNED
+    }
+}
+
+impl PortMetadata for PhysicalQubit {}
+
+/// The duration of an operation, in arbitrary time units, attached as node
+/// metadata. Operations without a recorded duration take one unit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GateDuration(pub f64);
+
+impl Default for GateDuration {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl NodeMetadata for GateDuration {}
@@ -0,0 +1,32 @@
+//! Named registers for classical and quantum wires.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::gug::{NodeMetadata, PortMetadata};
+
+/// The classical register bit a wire is stored in, attached as port metadata.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CregBit(pub Option<(String, usize)>);
+
+impl PortMetadata for CregBit {}
+
+/// Names given to the qubit lines of a circuit, mapped to their index among
+/// the qubits of the `Input` node. Attached as metadata of the `Input` node.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QubitNames(pub BTreeMap<String, usize>);
+
+impl NodeMetadata for QubitNames {}
+
+/// The ancilla qubit lines of a circuit, by their index among the qubits of
+/// the `Input` node. Attached as metadata of the `Input` node by
+/// [`Gug::alloc_ancilla`](crate::Gug::alloc_ancilla).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Ancillas {
+    /// Ancillas currently in use.
+    pub allocated: BTreeSet<usize>,
+    /// Freed ancillas that are still used by some gates, and are reused by
+    /// the next allocation. They end in a `Reset`.
+    pub free: BTreeSet<usize>,
+}
+
+impl NodeMetadata for Ancillas {}
@@ -0,0 +1,167 @@
+//! Symbolic angle expressions for parametrised circuits.
+//!
+//! Expressions enter a circuit as the `Angle` output of
+//! [`circuit::Op::Symbolic`](crate::component::operation::circuit::Op::Symbolic)
+//! nodes, whose parameters are bound with [`crate::Gug::bind_symbols`].
+
+use std::collections::HashMap;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use thiserror::Error;
+
+use super::wire_type::AngleValue;
+
+/// An angle expression that may contain free symbolic parameters.
+///
+/// Like [`AngleValue`], the expression denotes a multiple of π, and the values
+/// bound to symbols are interpreted the same way.
+#[derive(Clone, PartialEq, Debug)]
+pub enum SymbolicAngle {
+    /// A concrete angle.
+    Value(AngleValue),
+    /// A named free parameter.
+    Symbol(String),
+    /// The sum of two expressions.
+    Add(Box<SymbolicAngle>, Box<SymbolicAngle>),
+    /// The negation of an expression.
+    Neg(Box<SymbolicAngle>),
+    /// An expression multiplied by a constant.
+    Scale(Box<SymbolicAngle>, AngleValue),
+}
+
+/// Error returned when evaluating an expression with free symbols.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[error("unbound angle parameter {0:?}")]
+pub struct UnboundSymbol(pub String);
+
+impl SymbolicAngle {
+    /// Creates a free parameter.
+    pub fn symbol(name: impl Into<String>) -> Self {
+        Self::Symbol(name.into())
+    }
+
+    /// Substitutes the symbols bound in `env`, folding any subexpression that
+    /// becomes concrete. Unbound symbols are left in place.
+    pub fn bind(&self, env: &HashMap<String, f64>) -> SymbolicAngle {
+        match self {
+            Self::Value(v) => Self::Value(*v),
+            Self::Symbol(name) => match env.get(name) {
+                Some(&x) => Self::Value(AngleValue::F64(x)),
+                None => self.clone(),
+            },
+            Self::Add(a, b) => a.bind(env) + b.bind(env),
+            Self::Neg(a) => -a.bind(env),
+            Self::Scale(a, c) => a.bind(env) * *c,
+        }
+    }
+
+    /// Returns the concrete angle, if the expression has no free symbols.
+    pub fn value(&self) -> Result<AngleValue, UnboundSymbol> {
+        match self {
+            Self::Value(v) => Ok(*v),
+            Self::Symbol(name) => Err(UnboundSymbol(name.clone())),
+            Self::Add(a, b) => Ok(a.value()? + b.value()?),
+            Self::Neg(a) => Ok(-a.value()?),
+            Self::Scale(a, c) => Ok(a.value()? * *c),
+        }
+    }
+
+    /// Returns the angle as a multiple of π, if the expression has no free symbols.
+    pub fn to_f64(&self) -> Result<f64, UnboundSymbol> {
+        Ok(self.value()?.to_f64())
+    }
+
+    /// Returns `true` if the expression contains no free symbols.
+    pub fn is_concrete(&self) -> bool {
+        self.value().is_ok()
+    }
+}
+
+impl From<AngleValue> for SymbolicAngle {
+    fn from(v: AngleValue) -> Self {
+        Self::Value(v)
+    }
+}
+
+impl Add for SymbolicAngle {
+    type Output = SymbolicAngle;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        match (self, rhs) {
+            (Self::Value(x), Self::Value(y)) => Self::Value(x + y),
+            (a, b) => Self::Add(Box::new(a), Box::new(b)),
+        }
+    }
+}
+
+impl Add<AngleValue> for SymbolicAngle {
+    type Output = SymbolicAngle;
+
+    fn add(self, rhs: AngleValue) -> Self::Output {
+        self + Self::Value(rhs)
+    }
+}
+
+impl Sub for SymbolicAngle {
+    type Output = SymbolicAngle;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self + -rhs
+    }
+}
+
+impl Neg for SymbolicAngle {
+    type Output = SymbolicAngle;
+
+    fn neg(self) -> Self::Output {
+        match self {
+            Self::Value(x) => Self::Value(-x),
+            Self::Neg(a) => *a,
+            a => Self::Neg(Box::new(a)),
+        }
+    }
+}
+
+impl Mul<AngleValue> for SymbolicAngle {
+    type Output = SymbolicAngle;
+
+    fn mul(self, rhs: AngleValue) -> Self::Output {
+        match self {
+            Self::Value(x) => Self::Value(x * rhs),
+            a => Self::Scale(Box::new(a), rhs),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_rational::Rational64;
+
+    use super::*;
+    use crate::component::wire_type::Rational;
+
+    #[test]
+    fn bind_and_evaluate() {
+        let half = AngleValue::Rational(Rational(Rational64::new(1, 2)));
+        let expr = SymbolicAngle::symbol("theta") + half;
+        assert!(!expr.is_concrete());
+        assert_eq!(expr.to_f64(), Err(UnboundSymbol("theta".to_string())));
+
+        let env = HashMap::from([("theta".to_string(), 0.25)]);
+        let bound = expr.bind(&env);
+        assert_eq!(bound, SymbolicAngle::Value(AngleValue::F64(0.75)));
+        assert_eq!(bound.to_f64(), Ok(0.75));
+        assert!((bound.value().unwrap().radians() - 0.75 * std::f64::consts::PI).abs() < 1e-12);
+    }
+
+    #[test]
+    fn neg_and_scale() {
+        let two = AngleValue::F64(2.0);
+        let expr = -(SymbolicAngle::symbol("a") * two) - SymbolicAngle::symbol("b");
+        let env = HashMap::from([("a".to_string(), 0.25), ("b".to_string(), 0.5)]);
+        assert_eq!(expr.bind(&env).to_f64(), Ok(-1.0));
+
+        let partial = expr.bind(&HashMap::from([("a".to_string(), 0.25)]));
+        assert_eq!(partial.to_f64(), Err(UnboundSymbol("b".to_string())));
+    }
+}
@@ -1,10 +1,14 @@
 use cgmath::num_traits::ToPrimitive;
 use num_rational::Rational64;
+#[cfg(feature = "pyo3")]
+use pyo3::prelude::*;
 use std::{
     cmp::max,
     ops::{Add, Div, Mul, Neg, Sub},
 };
 
+use crate::component::operation::circuit::approx_eq;
+
 #[cfg_attr(feature = "pyo3", pyclass)]
 #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
 #[non_exhaustive]
@@ -21,6 +25,28 @@ pub enum WireType {
     SideEffects,
 }
 
+impl WireType {
+    /// Returns `true` for wire types whose values cannot be copied.
+    pub fn is_linear(&self) -> bool {
+        matches!(self, Self::Qubit | Self::LinearBit)
+    }
+
+    /// Returns `true` if values of this type can be converted to `to` by a
+    /// `Cast` operation.
+    ///
+    /// The legal conversions are `Bool` to `I64`, `I64` to `F64` or `Angle`,
+    /// and between `F64` and `Angle`, which is measured in half-turns.
+    pub fn can_cast_to(&self, to: WireType) -> bool {
+        matches!(
+            (self, to),
+            (Self::Bool, Self::I64)
+                | (Self::I64, Self::F64 | Self::Angle)
+                | (Self::F64, Self::Angle)
+                | (Self::Angle, Self::F64)
+        )
+    }
+}
+
 impl Default for WireType {
     fn default() -> Self {
         Self::Qubit
@@ -28,7 +54,7 @@ impl Default for WireType {
 }
 
 #[cfg_attr(feature = "pyo3", pyclass)]
-#[derive(Clone, Default)]
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
 pub struct Signature {
     pub linear: Vec<WireType>,
     pub nonlinear: [Vec<WireType>; 2],
@@ -67,6 +93,11 @@ impl Signature {
 }
 
 impl Signature {
+    /// Returns a builder for a signature, starting with no ports.
+    pub fn builder() -> SignatureBuilder {
+        SignatureBuilder::default()
+    }
+
     pub fn new(linear: Vec<WireType>, nonlinear: [Vec<WireType>; 2]) -> Self {
         Self { linear, nonlinear }
     }
@@ -92,17 +123,140 @@ impl Signature {
     pub fn outputs(&self) -> impl Iterator<Item = &WireType> {
         self.linear.iter().chain(self.nonlinear[1].iter())
     }
+
+    /// Returns the signature of two operations applied side by side, with the
+    /// wires of `self` before those of `other` in each group.
+    pub fn tensor(&self, other: &Signature) -> Signature {
+        let concat = |a: &[WireType], b: &[WireType]| [a, b].concat();
+        Signature {
+            linear: concat(&self.linear, &other.linear),
+            nonlinear: [
+                concat(&self.nonlinear[0], &other.nonlinear[0]),
+                concat(&self.nonlinear[1], &other.nonlinear[1]),
+            ],
+        }
+    }
+
+    /// Checks whether two signatures match position by position, where wire
+    /// types that differ are accepted if `coerce(self_type, other_type)` holds.
+    pub fn compatible_with(
+        &self,
+        other: &Signature,
+        coerce: impl Fn(WireType, WireType) -> bool,
+    ) -> bool {
+        let compatible = |a: &[WireType], b: &[WireType]| {
+            a.len() == b.len() && a.iter().zip(b).all(|(&x, &y)| x == y || coerce(x, y))
+        };
+        compatible(&self.linear, &other.linear)
+            && compatible(&self.nonlinear[0], &other.nonlinear[0])
+            && compatible(&self.nonlinear[1], &other.nonlinear[1])
+    }
+
+    /// Returns the type of the input port at offset `i`.
+    ///
+    /// Input ports are ordered with the linear wires first, followed by the
+    /// non-linear inputs, as in [`Signature::inputs`].
+    pub fn input_type(&self, i: usize) -> Option<WireType> {
+        self.inputs().nth(i).copied()
+    }
+
+    /// Returns the type of the output port at offset `i`.
+    ///
+    /// Output ports are ordered with the linear wires first, followed by the
+    /// non-linear outputs, as in [`Signature::outputs`].
+    pub fn output_type(&self, i: usize) -> Option<WireType> {
+        self.outputs().nth(i).copied()
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "pyo3", pyclass(name = "Rational"))]
 pub struct Rational(pub Rational64);
 
+impl Rational {
+    /// Reduces the angle modulo 2 half-turns into the range `[0, 2)`, exactly.
+    pub fn reduce_mod2(&self) -> Rational {
+        let two = Rational64::from_integer(2);
+        let reduced = self.0 - (self.0 / two).floor() * two;
+        Rational(reduced)
+    }
+
+    /// Checks whether two rational angles are equal modulo 2 half-turns,
+    /// without rounding.
+    pub fn angle_eq(&self, other: &Rational) -> bool {
+        self.reduce_mod2() == other.reduce_mod2()
+    }
+}
+
 impl From<Rational64> for Rational {
     fn from(r: Rational64) -> Self {
         Self(r)
     }
 }
+
+/// A floating point angle in half-turns, the Python class of
+/// [`AngleValue::F64`]. It keeps angles apart from plain `float` constants,
+/// which are read as [`ConstValue::F64`].
+#[cfg(feature = "pyo3")]
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[pyclass(name = "Angle")]
+pub struct PyAngle(pub f64);
+
+#[cfg(feature = "pyo3")]
+#[pymethods]
+impl PyAngle {
+    #[new]
+    fn new(half_turns: f64) -> Self {
+        Self(half_turns)
+    }
+
+    #[getter]
+    fn half_turns(&self) -> f64 {
+        self.0
+    }
+}
+/// A unit for displaying angles with [`AngleValue::format`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AngleUnit {
+    /// Multiples of π, such as `π/2`.
+    Pi,
+    /// Radians, with four decimal places.
+    Radians,
+    /// Degrees.
+    Degrees,
+}
+
+/// Incrementally builds a [`Signature`], one port at a time.
+#[derive(Clone, Default, Debug)]
+pub struct SignatureBuilder {
+    signature: Signature,
+}
+
+impl SignatureBuilder {
+    /// Appends a linear wire, present both as an input and as an output.
+    pub fn linear(mut self, typ: WireType) -> Self {
+        self.signature.linear.push(typ);
+        self
+    }
+
+    /// Appends a non-linear input.
+    pub fn input(mut self, typ: WireType) -> Self {
+        self.signature.nonlinear[0].push(typ);
+        self
+    }
+
+    /// Appends a non-linear output.
+    pub fn output(mut self, typ: WireType) -> Self {
+        self.signature.nonlinear[1].push(typ);
+        self
+    }
+
+    /// Returns the built signature.
+    pub fn build(self) -> Signature {
+        self.signature
+    }
+}
+
 // angle is contained value * pi in radians
 #[derive(Clone, PartialEq, Debug, Copy)]
 pub enum AngleValue {
@@ -110,6 +264,20 @@ pub enum AngleValue {
     Rational(Rational),
 }
 
+/// Reads an `Angle` or `Rational` object, or a plain number of half-turns.
+#[cfg(feature = "pyo3")]
+impl<'source> FromPyObject<'source> for AngleValue {
+    fn extract(ob: &'source PyAny) -> PyResult<Self> {
+        if let Ok(angle) = ob.extract::<PyAngle>() {
+            return Ok(Self::F64(angle.0));
+        }
+        if let Ok(r) = ob.extract::<Rational>() {
+            return Ok(Self::Rational(r));
+        }
+        Ok(Self::F64(ob.extract()?))
+    }
+}
+
 impl AngleValue {
     fn binary_op<F: FnOnce(f64, f64) -> f64, G: FnOnce(Rational64, Rational64) -> Rational64>(
         self,
@@ -147,9 +315,107 @@ impl AngleValue {
         }
     }
 
+    /// Reduces the angle modulo 2 half-turns into the range `[0, 2)`.
+    ///
+    /// Rational angles are reduced exactly.
+    pub fn normalize(&self) -> AngleValue {
+        match self {
+            AngleValue::F64(x) => AngleValue::F64(x.rem_euclid(2.0)),
+            AngleValue::Rational(r) => AngleValue::Rational(r.reduce_mod2()),
+        }
+    }
+
+    /// Checks whether two angles are equal modulo 2 half-turns.
+    ///
+    /// Two rational angles are compared exactly, otherwise the angles are
+    /// compared as floats within `tol`.
+    pub fn angle_eq(&self, other: &AngleValue, tol: f64) -> bool {
+        match (self, other) {
+            (AngleValue::Rational(x), AngleValue::Rational(y)) => x.angle_eq(y),
+            _ => approx_eq(self.to_f64(), other.to_f64(), 2, tol),
+        }
+    }
+
+    /// Creates an angle from a number of half-turns, the unit in which angles
+    /// are stored.
+    pub fn from_half_turns(half_turns: f64) -> Self {
+        AngleValue::F64(half_turns)
+    }
+
+    /// Creates an angle from a number of full turns of 2π.
+    pub fn from_turns(turns: f64) -> Self {
+        AngleValue::F64(turns * 2.0)
+    }
+
+    /// Returns the angle in half-turns, that is as a multiple of π. This is
+    /// the stored value.
+    pub fn half_turns(&self) -> f64 {
+        self.to_f64()
+    }
+
+    /// Returns the angle in full turns of 2π.
+    pub fn turns(&self) -> f64 {
+        self.to_f64() / 2.0
+    }
+
+    /// Returns the angle in radians.
     pub fn radians(&self) -> f64 {
         self.to_f64() * std::f64::consts::PI
     }
+
+    /// Formats the angle in the given unit.
+    ///
+    /// Rational angles are shown as exact fractions of π in the
+    /// [`AngleUnit::Pi`] unit.
+    pub fn format(&self, unit: AngleUnit) -> String {
+        match (unit, self) {
+            (AngleUnit::Pi, AngleValue::Rational(Rational(r))) => {
+                let numer = match *r.numer() {
+                    0 => return "0".to_string(),
+                    1 => "π".to_string(),
+                    -1 => "-π".to_string(),
+                    n => format!("{n}π"),
+                };
+                match *r.denom() {
+                    1 => numer,
+                    d => format!("{numer}/{d}"),
+                }
+            }
+            (AngleUnit::Pi, AngleValue::F64(x)) => format!("{x}π"),
+            (AngleUnit::Radians, _) => format!("{:.4} rad", self.radians()),
+            (AngleUnit::Degrees, _) => {
+                let degrees = (self.to_f64() * 180.0 * 1e4).round() / 1e4;
+                format!("{degrees}°")
+            }
+        }
+    }
+
+    /// Finds the rational multiple of π closest to this angle, with the
+    /// smallest denominator not exceeding `max_denominator` that lies within
+    /// `tol` of it.
+    ///
+    /// Returns `None` if no such rational exists. Rational angles are returned
+    /// unchanged.
+    pub fn to_rational(&self, tol: f64, max_denominator: i64) -> Option<Rational> {
+        let x = match self {
+            AngleValue::Rational(r) => return Some(*r),
+            AngleValue::F64(x) => *x,
+        };
+        if !x.is_finite() {
+            return None;
+        }
+        (1..=max_denominator).find_map(|denom| {
+            let numer = (x * denom as f64).round();
+            ((x - numer / denom as f64).abs() <= tol)
+                .then(|| Rational(Rational64::new(numer as i64, denom)))
+        })
+    }
+}
+
+impl Default for AngleValue {
+    fn default() -> Self {
+        AngleValue::Rational(Rational(Rational64::from_integer(0)))
+    }
 }
 
 impl Add for AngleValue {
@@ -242,6 +508,32 @@ impl From<cgmath::Quaternion<f64>> for Quat {
     }
 }
 
+impl Quat {
+    /// If the quaternion is a rotation about the X axis, returns its angle in
+    /// half-turns.
+    pub fn x_rotation(&self, tol: f64) -> Option<f64> {
+        let v = self.0.v;
+        self.axis_rotation(v.x, [v.y, v.z], tol)
+    }
+
+    /// If the quaternion is a rotation about the Z axis, returns its angle in
+    /// half-turns.
+    pub fn z_rotation(&self, tol: f64) -> Option<f64> {
+        let v = self.0.v;
+        self.axis_rotation(v.z, [v.x, v.y], tol)
+    }
+
+    /// Returns the signed rotation angle about an axis with vector component
+    /// `along`, if the `other` components vanish relative to the norm.
+    fn axis_rotation(&self, along: f64, other: [f64; 2], tol: f64) -> Option<f64> {
+        let norm = (self.0.s * self.0.s + along * along).sqrt();
+        if norm == 0.0 || other.iter().any(|c| c.abs() > tol * norm) {
+            return None;
+        }
+        Some(2.0 * along.atan2(self.0.s) / std::f64::consts::PI)
+    }
+}
+
 #[cfg_attr(feature = "pyo3", derive(FromPyObject))]
 #[derive(Clone, PartialEq, Debug)]
 pub enum ConstValue {
@@ -266,4 +558,173 @@ impl ConstValue {
     pub fn f64_angle(val: f64) -> Self {
         Self::Angle(AngleValue::F64(val))
     }
+
+    /// Converts the value to another type, following
+    /// [`WireType::can_cast_to`]. Integers become exact rational angles.
+    pub fn cast(&self, to: WireType) -> Option<Self> {
+        Some(match (self, to) {
+            (Self::Bool(b), WireType::I64) => Self::I64(i64::from(*b)),
+            (Self::I64(i), WireType::F64) => Self::F64(*i as f64),
+            (Self::I64(i), WireType::Angle) => {
+                Self::Angle(AngleValue::Rational(Rational(Rational64::from_integer(*i))))
+            }
+            (Self::F64(x), WireType::Angle) => Self::f64_angle(*x),
+            (Self::Angle(angle), WireType::F64) => Self::F64(angle.to_f64()),
+            _ => return None,
+        })
+    }
+}
+
+/// Converts constants to their natural Python representation.
+///
+/// Angles and quaternions are converted to their `Angle`, `Rational` and
+/// `Quaternion` classes, so that every value is read back unchanged.
+#[cfg(feature = "pyo3")]
+impl IntoPy<PyObject> for ConstValue {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        match self {
+            Self::Bool(b) => b.into_py(py),
+            Self::I64(i) => i.into_py(py),
+            Self::F64(x) => x.into_py(py),
+            Self::Angle(AngleValue::F64(x)) => PyAngle(x).into_py(py),
+            Self::Angle(AngleValue::Rational(r)) => r.into_py(py),
+            Self::Quat64(q) => q.into_py(py),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::operation::circuit;
+
+    #[test]
+    fn signature_builder() {
+        let measure = Signature::builder()
+            .linear(WireType::Qubit)
+            .linear(WireType::LinearBit)
+            .build();
+        assert_eq!(measure, circuit::Op::Measure.signature());
+
+        let copy = Signature::builder()
+            .input(WireType::Bool)
+            .output(WireType::Bool)
+            .output(WireType::Bool)
+            .build();
+        assert_eq!(
+            copy,
+            Signature::new_nonlinear(vec![WireType::Bool], vec![WireType::Bool; 2])
+        );
+    }
+
+    #[test]
+    fn format_angles() {
+        let half = AngleValue::Rational(Rational(Rational64::new(1, 2)));
+        assert_eq!(half.format(AngleUnit::Pi), "π/2");
+        assert_eq!(half.format(AngleUnit::Radians), "1.5708 rad");
+        assert_eq!(half.format(AngleUnit::Degrees), "90°");
+
+        let three_quarters = AngleValue::Rational(Rational(Rational64::new(-3, 4)));
+        assert_eq!(three_quarters.format(AngleUnit::Pi), "-3π/4");
+        let one = AngleValue::Rational(Rational(Rational64::from_integer(1)));
+        assert_eq!(one.format(AngleUnit::Pi), "π");
+        assert_eq!(AngleValue::F64(0.25).format(AngleUnit::Pi), "0.25π");
+        assert_eq!(AngleValue::F64(0.25).format(AngleUnit::Degrees), "45°");
+    }
+
+    #[test]
+    fn angle_units() {
+        use std::f64::consts::FRAC_PI_2;
+
+        assert!((AngleValue::from_turns(0.25).radians() - FRAC_PI_2).abs() < 1e-12);
+        assert!((AngleValue::from_half_turns(0.5).radians() - FRAC_PI_2).abs() < 1e-12);
+        assert_eq!(AngleValue::from_turns(0.25), AngleValue::from_half_turns(0.5));
+
+        let quarter = AngleValue::Rational(Rational(Rational64::new(1, 4)));
+        assert_eq!(quarter.half_turns(), 0.25);
+        assert_eq!(quarter.turns(), 0.125);
+        assert_eq!(AngleValue::from_turns(quarter.turns()).half_turns(), 0.25);
+    }
+
+    #[test]
+    fn exact_reduction() {
+        let r = |n, d| Rational(Rational64::new(n, d));
+        assert_eq!(r(7, 2).reduce_mod2(), r(3, 2));
+        assert_eq!(r(-1, 2).reduce_mod2(), r(3, 2));
+        assert_eq!(r(2, 1).reduce_mod2(), r(0, 1));
+        assert_eq!(r(1, 3).reduce_mod2(), r(1, 3));
+        assert_eq!(r(-13, 3).reduce_mod2(), r(5, 3));
+
+        let angle = AngleValue::Rational(r(9, 4));
+        assert_eq!(angle.normalize(), AngleValue::Rational(r(1, 4)));
+        assert_eq!(AngleValue::F64(-0.5).normalize(), AngleValue::F64(1.5));
+    }
+
+    #[test]
+    fn exact_angle_equality() {
+        let r = |n, d| Rational(Rational64::new(n, d));
+        assert!(r(3, 2).angle_eq(&r(7, 2)));
+        assert!(r(3, 2).angle_eq(&r(-1, 2)));
+        assert!(!r(3, 2).angle_eq(&r(1, 2)));
+
+        let a = AngleValue::Rational(r(3, 2));
+        assert!(a.angle_eq(&AngleValue::Rational(r(7, 2)), 0.0));
+        assert!(a.angle_eq(&AngleValue::F64(-0.5), 1e-10));
+        assert!(!a.angle_eq(&AngleValue::F64(0.5), 1e-10));
+    }
+
+    #[test]
+    fn port_types() {
+        let measure = circuit::Op::Measure.signature();
+        assert_eq!(measure.input_type(0), Some(WireType::Qubit));
+        assert_eq!(measure.input_type(1), Some(WireType::LinearBit));
+        assert_eq!(measure.input_type(2), None);
+        assert_eq!(measure.output_type(1), Some(WireType::LinearBit));
+
+        let rz = circuit::Op::RzF64.signature();
+        assert_eq!(rz.input_type(1), Some(WireType::Angle));
+        assert_eq!(rz.output_type(0), Some(WireType::Qubit));
+        assert_eq!(rz.output_type(1), None);
+    }
+
+    #[test]
+    fn compatible_signatures() {
+        let angle = Signature::new(vec![WireType::Qubit], [vec![WireType::Angle], vec![]]);
+        let float = Signature::new(vec![WireType::Qubit], [vec![WireType::F64], vec![]]);
+        let exact = |_, _| false;
+        let numeric = |a, b| {
+            matches!(
+                (a, b),
+                (WireType::F64, WireType::Angle) | (WireType::Angle, WireType::F64)
+            )
+        };
+
+        assert!(angle.compatible_with(&angle, exact));
+        assert!(!angle.compatible_with(&float, exact));
+        assert!(angle.compatible_with(&float, numeric));
+        assert!(float.compatible_with(&angle, numeric));
+
+        let measure = circuit::Op::Measure.signature();
+        assert!(!angle.compatible_with(&measure, numeric));
+    }
+
+    #[cfg(feature = "pyo3")]
+    #[test]
+    fn const_value_py_round_trip() {
+        pyo3::prepare_freethreaded_python();
+        let values = [
+            ConstValue::Bool(true),
+            ConstValue::I64(-3),
+            ConstValue::F64(0.25),
+            ConstValue::f64_angle(0.25),
+            ConstValue::Angle(AngleValue::Rational(Rational(Rational64::new(1, 3)))),
+            ConstValue::Quat64(Quat(cgmath::Quaternion::new(1.0, 0.0, 0.5, 0.0))),
+        ];
+        Python::with_gil(|py| {
+            for value in values {
+                let obj = value.clone().into_py(py);
+                assert_eq!(obj.extract::<ConstValue>(py).unwrap(), value);
+            }
+        });
+    }
 }
@@ -0,0 +1,93 @@
+//! Support for operations defined outside this crate.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::component::operation::CustomOp;
+
+/// A function constructing a custom operation from a serialized payload.
+pub type OpFactory = Box<dyn Fn(&str) -> Option<Box<dyn CustomOp>> + Send + Sync>;
+
+/// A registry of extension operations, used to reconstruct opaque operations
+/// from their name when deserializing or importing circuits, see
+/// [`Gug::from_text_with_registry`](crate::Gug::from_text_with_registry) and
+/// [`from_qasm2_with_registry`](crate::qasm::from_qasm2_with_registry).
+///
+/// The payload passed to a factory is the one returned by
+/// [`CustomOp::payload`] when serializing, or the parameter text of a QASM
+/// gate.
+#[derive(Default)]
+pub struct OpRegistry {
+    factories: HashMap<String, OpFactory>,
+}
+
+impl OpRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a factory for the operation with the given name, replacing
+    /// any previous registration.
+    pub fn register<F>(&mut self, name: impl Into<String>, factory: F)
+    where
+        F: Fn(&str) -> Option<Box<dyn CustomOp>> + Send + Sync + 'static,
+    {
+        self.factories.insert(name.into(), Box::new(factory));
+    }
+
+    /// Returns `true` if an operation with the given name has been registered.
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    /// Builds the operation with the given name from its serialized payload.
+    ///
+    /// Returns `None` if the name is unknown or the factory rejects the payload.
+    pub fn build(&self, name: &str, payload: &str) -> Option<Box<dyn CustomOp>> {
+        let factory = self.factories.get(name)?;
+        factory(payload)
+    }
+}
+
+impl fmt::Debug for OpRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.factories.keys()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::wire_type::{Signature, WireType};
+
+    #[derive(Clone, Debug)]
+    struct Repeat(usize);
+
+    impl CustomOp for Repeat {
+        fn name(&self) -> &str {
+            "Repeat"
+        }
+
+        fn signature(&self) -> Signature {
+            Signature::new_linear(vec![WireType::Qubit])
+        }
+    }
+
+    #[test]
+    fn build_by_name() {
+        let mut registry = OpRegistry::new();
+        registry.register("Repeat", |payload| {
+            let n = payload.parse().ok()?;
+            Some(Box::new(Repeat(n)) as Box<dyn CustomOp>)
+        });
+        assert!(registry.contains("Repeat"));
+
+        let op = registry.build("Repeat", "3").unwrap();
+        assert_eq!(op.name(), "Repeat");
+        assert_eq!(op.downcast_ref::<Repeat>().unwrap().0, 3);
+
+        assert!(registry.build("Repeat", "three").is_none());
+        assert!(registry.build("Unknown", "3").is_none());
+    }
+}
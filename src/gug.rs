@@ -1,32 +1,105 @@
 use std::{
     any::{Any, TypeId},
-    collections::HashMap, fmt::Debug,
+    collections::{HashMap, HashSet},
+    fmt::Debug,
 };
 
 use downcast_rs::{impl_downcast, Downcast};
 use portgraph::{
-    substitute::RewriteError, Hierarchy, NodeIndex, PortGraph, PortIndex, SecondaryMap,
+    hierarchy::AttachError, substitute::RewriteError, Direction, Hierarchy, LinkError, NodeIndex,
+    PortGraph, PortIndex, PortOperation, SecondaryMap,
 };
+use thiserror::Error;
 
 use crate::{
     component::{
-        operation::Op,
-        wire_type::{Signature, WireType},
+        operation::{circuit, Op},
+        wire_type::{AngleValue, Signature, WireType},
     },
     macros::impl_box_clone,
     rewrite::GugRewrite,
 };
 
+mod analysis;
+mod ancilla;
+mod angles;
+mod ascii;
+mod builder;
+mod canonical;
+mod compose;
+mod control_flow;
+mod debug_json;
+mod diff;
+#[cfg(feature = "fingerprint")]
+mod fingerprint;
+mod inference;
+mod measure;
+mod op_table;
+#[cfg(feature = "rand")]
+mod random;
+mod registers;
+mod routing;
+mod schedule;
+mod snapshot;
+mod stats;
+mod subcircuit;
+mod text;
+mod traversal;
+#[cfg(feature = "matrix")]
+mod unitary;
+mod validate;
+mod visitor;
+
+use op_table::OpTable;
+
+pub use analysis::HybridPartition;
+pub use ancilla::AncillaError;
+pub use angles::ParamError;
+pub use builder::BuildError;
+pub(crate) use builder::CircuitBuilder;
+pub use compose::{ComposeError, InverseError};
+pub use control_flow::UnrollError;
+pub use diff::{DiffEdge, GugDiff};
+pub use inference::{InferError, RetypeError};
+#[cfg(feature = "rand")]
+pub use random::GateSet;
+pub(crate) use registers::index_after_removal;
+pub use registers::QubitNameError;
+pub use routing::PermError;
+pub use schedule::ScheduleStrategy;
+pub use snapshot::GugSnapshot;
+pub use stats::{CircuitStats, CostModel, GateErrorModel, ROTATION_T_COST};
+pub use subcircuit::{BoxError, BoxedCircuit, Subcircuit};
+pub use text::{DeserializeError, ParseError, SerializeError, TEXT_FORMAT_VERSION};
+#[cfg(feature = "matrix")]
+pub use unitary::{UnitaryError, MAX_UNITARY_QUBITS};
+pub use validate::{LinearityError, ValidationError};
+pub use visitor::{CountingVisitor, GugVisitor};
+
+/// The Gug Unified Graph.
+///
+/// `Gug` is `Send + Sync`: the graph itself is plain data, and the operation and
+/// metadata trait objects it stores are required to be `Send + Sync`. Read-only
+/// analyses can therefore run concurrently on shared references, and with the
+/// `rayon` feature some analyses are parallelised over the nodes of a single graph.
 #[derive(Clone, Default, Debug)]
 pub struct Gug {
     pub(crate) graph: PortGraph,
     hierarchy: Hierarchy,
 
-    op_types: SecondaryMap<NodeIndex, Op>,
-    port_types: SecondaryMap<PortIndex, WireType>,
+    op_types: OpTable,
+    /// The type of each port, or `None` if it was never set.
+    port_types: SecondaryMap<PortIndex, Option<WireType>>,
 
     node_metadata: HashMap<TypeId, SecondaryMap<NodeIndex, Box<dyn NodeMetadata>>>,
     port_metadata: HashMap<TypeId, SecondaryMap<PortIndex, Box<dyn PortMetadata>>>,
+    /// The default value of each registered metadata component, restored on
+    /// the nodes and ports that are removed.
+    node_metadata_defaults: HashMap<TypeId, Box<dyn NodeMetadata>>,
+    port_metadata_defaults: HashMap<TypeId, Box<dyn PortMetadata>>,
+
+    /// The global phase of the circuit, in half-turns.
+    global_phase: AngleValue,
 }
 
 impl Gug {
@@ -34,9 +107,33 @@ impl Gug {
         Self::default()
     }
 
+    /// Creates a graph with a single `Input` node producing wires of the
+    /// `inputs` types, and a single `Output` node consuming wires of the
+    /// `outputs` types. The boundary nodes are left disconnected.
+    pub fn with_boundary(inputs: Vec<WireType>, outputs: Vec<WireType>) -> Self {
+        let mut gug = Self::new();
+        gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Input),
+            Signature::new_nonlinear(vec![], inputs),
+        );
+        gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Output),
+            Signature::new_nonlinear(outputs, vec![]),
+        );
+        gug
+    }
+
+    /// Creates an empty graph with space reserved for `num_nodes` nodes and
+    /// `num_edges` edges, both in the graph and in the maps storing the
+    /// operation and port types.
+    ///
+    /// Metadata components are registered later, and are not pre-sized.
     pub fn with_capacity(num_nodes: usize, num_edges: usize) -> Self {
         Self {
             graph: PortGraph::with_capacity(num_nodes, num_edges),
+            op_types: OpTable::with_capacity(num_nodes),
+            // Each edge links two ports.
+            port_types: SecondaryMap::with_capacity(2 * num_edges),
             ..Default::default()
         }
     }
@@ -47,6 +144,9 @@ impl Gug {
         self.node_metadata.entry(TypeId::of::<T>()).or_insert(
             SecondaryMap::with_default(Box::<T>::default()),
         );
+        self.node_metadata_defaults
+            .entry(TypeId::of::<T>())
+            .or_insert(Box::<T>::default());
     }
 
     /// Initialize a new port metadata component.
@@ -55,17 +155,481 @@ impl Gug {
         self.port_metadata.entry(TypeId::of::<T>()).or_insert(
             SecondaryMap::with_default(Box::<T>::default()),
         );
+        self.port_metadata_defaults
+            .entry(TypeId::of::<T>())
+            .or_insert(Box::<T>::default());
+    }
+
+    /// Adds a new node to the graph, allocating its ports according to the
+    /// signature of the operation.
+    ///
+    /// Operations whose arity depends on their fields get ports to match, so
+    /// a `Copy { n_copies, typ }` has one input and `n_copies` outputs of type
+    /// `typ`.
+    pub fn add_node(&mut self, op: Op) -> NodeIndex {
+        let signature = op.signature();
+        self.add_node_with_signature(op, signature)
+    }
+
+    /// Adds a new node to the graph with an explicit port signature.
+    ///
+    /// This is required for operations whose ports are not determined by the
+    /// operation itself, such as the `Input` and `Output` boundary nodes.
+    pub fn add_node_with_signature(&mut self, op: Op, signature: Signature) -> NodeIndex {
+        let (num_inputs, num_outputs) = signature.num_ports();
+        let node = self.graph.add_node(num_inputs, num_outputs);
+        for (port, typ) in self.graph.inputs(node).zip(signature.inputs()) {
+            self.port_types[port] = Some(*typ);
+        }
+        for (port, typ) in self.graph.outputs(node).zip(signature.outputs()) {
+            self.port_types[port] = Some(*typ);
+        }
+        self.op_types.set(node, op);
+        node
+    }
+
+    /// Adds a new node to the graph with ports allocated according to the
+    /// signature of the operation, calling `port_init` with each new port
+    /// and its type, inputs first.
+    pub fn add_node_with<F: FnMut(PortIndex, WireType)>(
+        &mut self,
+        op: Op,
+        mut port_init: F,
+    ) -> NodeIndex {
+        let node = self.add_node(op);
+        for port in self.graph.inputs(node).chain(self.graph.outputs(node)) {
+            port_init(port, self.port_type(port));
+        }
+        node
+    }
+
+    /// Removes a node from the graph, disconnecting all its ports.
+    ///
+    /// The operation, port types and metadata of the node are reset, so a
+    /// node added later at the same index starts from the defaults.
+    pub fn remove_node(&mut self, node: NodeIndex) {
+        let ports: Vec<PortIndex> = self
+            .graph
+            .inputs(node)
+            .chain(self.graph.outputs(node))
+            .collect();
+        for port in ports {
+            self.port_types[port] = None;
+            self.clear_port_metadata(port);
+        }
+        self.graph.remove_node(node);
+        self.hierarchy.remove(node);
+        self.op_types.remove(node);
+        self.clear_node_metadata(node);
+    }
+
+    /// Resets every registered metadata component of a node to its default.
+    fn clear_node_metadata(&mut self, node: NodeIndex) {
+        for (type_id, meta) in self.node_metadata.iter_mut() {
+            meta[node] = self.node_metadata_defaults[type_id].clone();
+        }
+    }
+
+    /// Resets every registered metadata component of a port to its default.
+    fn clear_port_metadata(&mut self, port: PortIndex) {
+        for (type_id, meta) in self.port_metadata.iter_mut() {
+            meta[port] = self.port_metadata_defaults[type_id].clone();
+        }
+    }
+
+    /// Removes a node, connecting the source of each of its linear input wires
+    /// directly to the target of the corresponding linear output wire.
+    pub fn remove_and_reconnect(&mut self, node: NodeIndex) {
+        let num_linear = self.signature(node).linear.len();
+        let links: Vec<_> = (0..num_linear)
+            .map(|i| {
+                let source = self.input_port(node, i).and_then(|p| self.port_link(p));
+                let target = self.output_port(node, i).and_then(|p| self.port_link(p));
+                (source, target)
+            })
+            .collect();
+        self.remove_node(node);
+        for (source, target) in links {
+            if let (Some(source), Some(target)) = (source, target) {
+                self.link_ports(source, target)
+                    .expect("Ports freed by the removed node must be unlinked");
+            }
+        }
+    }
+
+    /// Inserts a single-wire operation on the wire leaving output `offset` of
+    /// `node`, between it and the port it was linked to.
+    ///
+    /// Returns the new node.
+    pub fn insert_after(&mut self, node: NodeIndex, offset: usize, op: Op) -> NodeIndex {
+        let port = self.output_port(node, offset).unwrap();
+        let target = self.unlink_port(port);
+        let new = self.add_node(op);
+        self.connect(node, offset, new, 0).unwrap();
+        if let Some(target) = target {
+            let new_output = self.output_port(new, 0).unwrap();
+            self.link_ports(new_output, target).unwrap();
+        }
+        new
+    }
+
+    /// Inserts a `Copy` node on the wire leaving the output port `source`,
+    /// returning the `n` output ports of the copy for consumers to connect.
+    ///
+    /// If `source` was already linked, its consumer is moved to the first of
+    /// the returned ports. Linear wires cannot be copied.
+    pub fn broadcast(
+        &mut self,
+        source: PortIndex,
+        n: usize,
+    ) -> Result<Vec<PortIndex>, BroadcastError> {
+        let node = self.port_node(source).ok_or(BroadcastError::NotAnOutput(source))?;
+        if !self.outputs(node).any(|p| p == source) {
+            return Err(BroadcastError::NotAnOutput(source));
+        }
+        let typ = self.port_type(source);
+        if typ.is_linear() {
+            return Err(BroadcastError::Linear(typ));
+        }
+
+        let target = self.unlink_port(source);
+        let copy = self.add_node(Op::Circuit(circuit::Op::Copy {
+            n_copies: n as u32,
+            typ,
+        }));
+        self.link_ports(source, self.input_port(copy, 0).unwrap())?;
+        let outputs: Vec<PortIndex> = self.outputs(copy).collect();
+        if let (Some(target), Some(&first)) = (target, outputs.first()) {
+            self.link_ports(first, target)?;
+        }
+        Ok(outputs)
+    }
+
+    /// Links an output port to an input port.
+    pub fn link_ports(&mut self, from: PortIndex, to: PortIndex) -> Result<(), LinkError> {
+        self.graph.link_ports(from, to)
+    }
+
+    /// Unlinks a port, returning the port it was linked to.
+    pub fn unlink_port(&mut self, port: PortIndex) -> Option<PortIndex> {
+        self.graph.unlink_port(port)
+    }
+
+    /// Connects the output port at `from_offset` of node `from` to the input
+    /// port at `to_offset` of node `to`.
+    pub fn connect(
+        &mut self,
+        from: NodeIndex,
+        from_offset: usize,
+        to: NodeIndex,
+        to_offset: usize,
+    ) -> Result<(), ConnectError> {
+        let from_port = self
+            .graph
+            .output(from, from_offset)
+            .ok_or(ConnectError::UnknownPort(from, from_offset, Direction::Outgoing))?;
+        let to_port = self
+            .graph
+            .input(to, to_offset)
+            .ok_or(ConnectError::UnknownPort(to, to_offset, Direction::Incoming))?;
+        self.graph.link_ports(from_port, to_port)?;
+        Ok(())
+    }
+
+    /// Returns the number of nodes in the graph.
+    pub fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    /// Iterates over the nodes in the graph.
+    pub fn nodes(&self) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.graph.nodes_iter()
+    }
+
+    /// Returns the underlying port graph, for running graph algorithms that
+    /// are not wrapped by `Gug`.
+    ///
+    /// Only shared access is offered: keeping the graph consistent with the
+    /// node operations, port types and metadata is the responsibility of
+    /// `Gug`, so all mutation goes through its own methods.
+    pub fn graph(&self) -> &PortGraph {
+        &self.graph
+    }
+
+    /// Returns the node a port belongs to.
+    pub fn port_node(&self, port: PortIndex) -> Option<NodeIndex> {
+        self.graph.port_node(port)
+    }
+
+    /// Returns the offset of a port in its node's input or output list.
+    pub fn port_offset(&self, port: PortIndex) -> Option<usize> {
+        self.graph.port_offset(port)
+    }
+
+    /// Returns the port linked to the given port, if any.
+    pub fn port_link(&self, port: PortIndex) -> Option<PortIndex> {
+        self.graph.port_link(port)
+    }
+
+    /// Returns the input port of a node at the given offset.
+    pub fn input_port(&self, node: NodeIndex, offset: usize) -> Option<PortIndex> {
+        self.graph.input(node, offset)
+    }
+
+    /// Returns the output port of a node at the given offset.
+    pub fn output_port(&self, node: NodeIndex, offset: usize) -> Option<PortIndex> {
+        self.graph.output(node, offset)
+    }
+
+    /// Returns the number of input ports allocated to a node in the graph.
+    pub fn num_inputs(&self, node: NodeIndex) -> usize {
+        self.graph.num_inputs(node)
+    }
+
+    /// Returns the number of output ports allocated to a node in the graph.
+    pub fn num_outputs(&self, node: NodeIndex) -> usize {
+        self.graph.num_outputs(node)
+    }
+
+    /// Iterates over the input ports of a node, in the order of the inputs of
+    /// its signature: linear wires first, then the non-linear inputs.
+    pub fn inputs(&self, node: NodeIndex) -> impl Iterator<Item = PortIndex> + '_ {
+        self.graph.inputs(node)
+    }
+
+    /// Iterates over the output ports of a node, in the order of the outputs
+    /// of its signature: linear wires first, then the non-linear outputs.
+    pub fn outputs(&self, node: NodeIndex) -> impl Iterator<Item = PortIndex> + '_ {
+        self.graph.outputs(node)
+    }
+
+    /// Iterates over the ports of a node in the given direction, in offset order.
+    pub(crate) fn node_ports(
+        &self,
+        node: NodeIndex,
+        direction: Direction,
+    ) -> impl Iterator<Item = PortIndex> + '_ {
+        self.graph.ports(node, direction)
+    }
+
+    /// Places `child` in the region defined by `parent`, as its last child.
+    pub fn push_child(&mut self, child: NodeIndex, parent: NodeIndex) -> Result<(), AttachError> {
+        self.hierarchy.push_child(child, parent)
+    }
+
+    /// Returns the node defining the region `node` belongs to, if any.
+    pub fn parent(&self, node: NodeIndex) -> Option<NodeIndex> {
+        self.hierarchy.parent(node)
+    }
+
+    /// Iterates over the nodes in the region defined by `node`.
+    pub fn children(&self, node: NodeIndex) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.hierarchy.children(node)
+    }
+
+    /// Returns the wire type of a port, or the default [`WireType`] if it
+    /// was never set.
+    pub fn port_type(&self, port: PortIndex) -> WireType {
+        self.port_types[port].unwrap_or_default()
+    }
+
+    /// Returns the first node with the circuit `Input` operation.
+    pub fn input_node(&self) -> Option<NodeIndex> {
+        self.nodes()
+            .find(|&n| matches!(self.optype(n), Op::Circuit(circuit::Op::Input)))
+    }
+
+    /// Returns the first node with the circuit `Output` operation.
+    pub fn output_node(&self) -> Option<NodeIndex> {
+        self.nodes()
+            .find(|&n| matches!(self.optype(n), Op::Circuit(circuit::Op::Output)))
+    }
+
+    /// Returns the types of the wires leaving the `Input` node, in port order.
+    ///
+    /// These are the inputs of the circuit's signature, as exposed by
+    /// [`BoxedCircuit`].
+    pub fn input_wires(&self) -> Result<Vec<WireType>, BoundaryError> {
+        let (input, _) = self.boundary()?;
+        Ok(self.boundary_wires(input, Direction::Outgoing))
+    }
+
+    /// Returns the types of the wires entering the `Output` node, in port
+    /// order.
+    pub fn output_wires(&self) -> Result<Vec<WireType>, BoundaryError> {
+        let (_, output) = self.boundary()?;
+        Ok(self.boundary_wires(output, Direction::Incoming))
+    }
+
+    fn boundary_wires(&self, node: NodeIndex, direction: Direction) -> Vec<WireType> {
+        self.node_ports(node, direction)
+            .map(|p| self.port_type(p))
+            .collect()
+    }
+
+    /// Returns the `Input` and `Output` nodes of the circuit, checking that
+    /// there is exactly one of each.
+    ///
+    /// Only top-level nodes are considered, so the boundaries of control flow
+    /// regions are ignored.
+    pub fn boundary(&self) -> Result<(NodeIndex, NodeIndex), BoundaryError> {
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        for node in self.nodes().filter(|&n| self.parent(n).is_none()) {
+            match self.optype(node) {
+                Op::Circuit(circuit::Op::Input) => inputs.push(node),
+                Op::Circuit(circuit::Op::Output) => outputs.push(node),
+                _ => {}
+            }
+        }
+        match (&inputs[..], &outputs[..]) {
+            ([input], [output]) => Ok((*input, *output)),
+            ([], _) => Err(BoundaryError::MissingInput),
+            (_, []) => Err(BoundaryError::MissingOutput),
+            ([_], _) => Err(BoundaryError::MultipleOutputs(outputs)),
+            _ => Err(BoundaryError::MultipleInputs(inputs)),
+        }
     }
 
     pub fn optype(&self, node: NodeIndex) -> &Op {
-        &self.op_types[node]
+        self.op_types.get(node)
     }
 
+    /// Sets the operation of a node, resizing its ports to match the new
+    /// operation's signature.
+    ///
+    /// Ports at offsets present in both signatures keep their links, surplus
+    /// ports are unlinked and removed, and new ports are left unconnected.
+    /// Boundary nodes keep their ports, since those are not determined by the
+    /// operation.
     pub fn set_optype(&mut self, node: NodeIndex, op: Op) {
-        let (_input_ports, _output_ports) = op.signature().num_ports();
-        // TODO resize number of ports according to signature
-        // self.graph.reallocate_ports(node, input_ports, output_ports);
-        self.op_types[node] = op;
+        if !op.is_boundary() {
+            let signature = op.signature();
+            let (input_ports, output_ports) = signature.num_ports();
+            self.resize_ports(node, input_ports, output_ports);
+        }
+        self.op_types.set(node, op);
+        self.set_port_types_from_signature(node);
+    }
+
+    /// Rewrites the types of all the ports of a node from the signature of
+    /// its current operation, in a single pass over the ports.
+    ///
+    /// Ports beyond the signature keep their type, so the node should already
+    /// have the matching number of ports. Boundary nodes are left unchanged,
+    /// since their ports are not determined by the operation.
+    pub fn set_port_types_from_signature(&mut self, node: NodeIndex) {
+        let op = self.op_types.get(node);
+        if op.is_boundary() {
+            return;
+        }
+        let signature = op.signature();
+        for (port, typ) in self.graph.inputs(node).zip(signature.inputs()) {
+            self.port_types[port] = Some(*typ);
+        }
+        for (port, typ) in self.graph.outputs(node).zip(signature.outputs()) {
+            self.port_types[port] = Some(*typ);
+        }
+    }
+
+    /// Replaces the operation of a node like [`Gug::set_optype`], resizing its
+    /// ports if the arity changes, and guarantees that every registered node
+    /// metadata entry of the node, such as its [`crate::DebugData`] label or
+    /// [`crate::SourceSpan`], is kept.
+    pub fn replace_op_keep_meta(&mut self, node: NodeIndex, op: Op) {
+        let saved: Vec<(TypeId, Box<dyn NodeMetadata>)> = self
+            .node_metadata
+            .iter()
+            .map(|(&id, meta)| (id, meta[node].clone()))
+            .collect();
+        self.set_optype(node, op);
+        for (id, meta) in saved {
+            self.node_metadata.get_mut(&id).unwrap()[node] = meta;
+        }
+    }
+
+    /// Changes the number of ports of a node, keeping the types, metadata and
+    /// links of the ports at offsets present before and after the change.
+    /// New ports are left unconnected and untyped.
+    pub(crate) fn resize_ports(&mut self, node: NodeIndex, inputs: usize, outputs: usize) {
+        if (self.graph.num_inputs(node), self.graph.num_outputs(node)) == (inputs, outputs) {
+            return;
+        }
+        let port_types = &mut self.port_types;
+        let port_metadata = &mut self.port_metadata;
+        self.graph
+            .set_num_ports(node, inputs, outputs, |old, operation| {
+                if let PortOperation::Moved { new_index } = operation {
+                    port_types[new_index] = port_types[old];
+                    for meta in port_metadata.values_mut() {
+                        meta[new_index] = meta[old].clone();
+                    }
+                }
+            });
+    }
+
+    /// Removes the port of a node at `offset` in the given direction, moving
+    /// the following ports down by one together with their links, types and
+    /// metadata.
+    pub(crate) fn remove_port(&mut self, node: NodeIndex, direction: Direction, offset: usize) {
+        let ports: Vec<PortIndex> = self.graph.ports(node, direction).collect();
+        if offset >= ports.len() {
+            return;
+        }
+        self.graph.unlink_port(ports[offset]);
+        for window in ports[offset..].windows(2) {
+            let (to, from) = (window[0], window[1]);
+            let link = self.graph.unlink_port(from);
+            self.port_types[to] = self.port_types[from];
+            for meta in self.port_metadata.values_mut() {
+                meta[to] = meta[from].clone();
+            }
+            if let Some(link) = link {
+                let result = match direction {
+                    Direction::Incoming => self.graph.link_ports(link, to),
+                    Direction::Outgoing => self.graph.link_ports(to, link),
+                };
+                result.expect("Relinking a freshly unlinked port cannot fail");
+            }
+        }
+        let mut inputs = self.graph.num_inputs(node);
+        let mut outputs = self.graph.num_outputs(node);
+        match direction {
+            Direction::Incoming => inputs -= 1,
+            Direction::Outgoing => outputs -= 1,
+        }
+        self.resize_ports(node, inputs, outputs);
+    }
+
+    /// Applies `f` to the operation of every node, replacing it with the
+    /// returned operation when it is `Some`.
+    ///
+    /// Operations are replaced with [`Gug::set_optype`], so ports are resized
+    /// to the new signature and any new ports are left unconnected. Returns
+    /// the number of replaced operations.
+    pub fn map_ops(&mut self, mut f: impl FnMut(NodeIndex, &Op) -> Option<Op>) -> usize {
+        let nodes: Vec<NodeIndex> = self.nodes().collect();
+        let mut changed = 0;
+        for node in nodes {
+            if let Some(op) = f(node, self.optype(node)) {
+                self.set_optype(node, op);
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// Returns the global phase of the circuit, in half-turns.
+    pub fn global_phase(&self) -> AngleValue {
+        self.global_phase
+    }
+
+    /// Adds to the global phase of the circuit, in half-turns.
+    ///
+    /// Passes removing gates that are the identity only up to a phase record
+    /// the discarded phase here.
+    pub fn add_global_phase(&mut self, phase: AngleValue) {
+        self.global_phase = self.global_phase + phase;
     }
 
     pub fn signature(&self, node: NodeIndex) -> Signature {
@@ -101,35 +665,122 @@ impl Gug {
     }
 
     /// Applies a rewrite to the graph.
-    pub fn apply_rewrite(mut self, rewrite: GugRewrite) -> Result<(), RewriteError> {
+    ///
+    /// Only the nodes of the replaced subgraph and their ports are
+    /// invalidated: every other `NodeIndex` and `PortIndex` keeps referring to
+    /// the same node or port, with the same operation, types and metadata.
+    /// The indices of removed nodes may be reused for the inserted ones, which
+    /// take the metadata of the replacement, or the default for components
+    /// the replacement does not register.
+    pub fn apply_rewrite(&mut self, rewrite: GugRewrite) -> Result<(), RewriteError> {
         // Get the open graph for the rewrites, and a gug with the additional components.
         let (rewrite, mut replacement) = rewrite.into_parts();
 
+        let mut removed_nodes = Vec::new();
+        let mut removed_ports = Vec::new();
+        let mut inserted_nodes = HashSet::new();
+        let mut inserted_ports = HashSet::new();
+        let node_defaults = &self.node_metadata_defaults;
+        let port_defaults = &self.port_metadata_defaults;
         let node_inserted = |old, new| {
-            std::mem::swap(&mut self.op_types[new], &mut replacement.op_types[old]);
-            for (type_id, replacement_meta) in replacement.node_metadata.iter_mut() {
-                if let Some(meta) = self.node_metadata.get_mut(type_id) {
-                    std::mem::swap(&mut meta[new], &mut replacement_meta[old]);
-                }
+            inserted_nodes.insert(new);
+            self.op_types.set(new, replacement.op_types.get(old).clone());
+            for (type_id, meta) in self.node_metadata.iter_mut() {
+                meta[new] = match replacement.node_metadata.get_mut(type_id) {
+                    Some(replacement_meta) => replacement_meta[old].clone(),
+                    None => node_defaults[type_id].clone(),
+                };
             }
         };
         let port_inserted = |old, new| {
+            inserted_ports.insert(new);
             std::mem::swap(&mut self.port_types[new], &mut replacement.port_types[old]);
-            for (type_id, replacement_meta) in replacement.port_metadata.iter_mut() {
-                if let Some(meta) = self.port_metadata.get_mut(type_id) {
-                    std::mem::swap(&mut meta[new], &mut replacement_meta[old]);
-                }
+            for (type_id, meta) in self.port_metadata.iter_mut() {
+                meta[new] = match replacement.port_metadata.get_mut(type_id) {
+                    Some(replacement_meta) => replacement_meta[old].clone(),
+                    None => port_defaults[type_id].clone(),
+                };
             }
         };
         rewrite.apply_with_callbacks(
             &mut self.graph,
-            |_| {},
-            |_| {},
+            |node| removed_nodes.push(node),
+            |port| removed_ports.push(port),
             node_inserted,
             port_inserted,
             |_, _| {},
-        )
+        )?;
+
+        // Clear the components of the removed nodes, unless their index was
+        // reused by an inserted node.
+        for node in removed_nodes {
+            if !inserted_nodes.contains(&node) {
+                self.hierarchy.remove(node);
+                self.op_types.remove(node);
+                self.clear_node_metadata(node);
+            }
+        }
+        for port in removed_ports {
+            if !inserted_ports.contains(&port) {
+                self.port_types[port] = None;
+                self.clear_port_metadata(port);
+            }
+        }
+        Ok(())
     }
+
+    /// Applies a batch of rewrites in order, rolling the graph back to its
+    /// previous state if any of them fails.
+    ///
+    /// Each rewrite must remain valid after applying the preceding ones. The
+    /// rollback uses a [`Gug::checkpoint`] taken before the first rewrite.
+    pub fn apply_rewrites(
+        &mut self,
+        rewrites: impl IntoIterator<Item = GugRewrite>,
+    ) -> Result<(), RewriteError> {
+        let snapshot = self.checkpoint();
+        for rewrite in rewrites {
+            if let Err(err) = self.apply_rewrite(rewrite) {
+                self.restore(snapshot);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Error returned when connecting two ports fails.
+#[derive(Debug, Error)]
+pub enum ConnectError {
+    #[error("node {0:?} has no {2:?} port at offset {1}")]
+    UnknownPort(NodeIndex, usize, Direction),
+    #[error("failed to link ports: {0:?}")]
+    Link(#[from] LinkError),
+}
+
+/// Error returned when a circuit does not have exactly one `Input` and one
+/// `Output` node.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BoundaryError {
+    #[error("the circuit has no Input node")]
+    MissingInput,
+    #[error("the circuit has no Output node")]
+    MissingOutput,
+    #[error("the circuit has multiple Input nodes: {0:?}")]
+    MultipleInputs(Vec<NodeIndex>),
+    #[error("the circuit has multiple Output nodes: {0:?}")]
+    MultipleOutputs(Vec<NodeIndex>),
+}
+
+/// Error returned when a wire cannot be broadcast with [`Gug::broadcast`].
+#[derive(Debug, Error)]
+pub enum BroadcastError {
+    #[error("port {0:?} is not an output port")]
+    NotAnOutput(PortIndex),
+    #[error("cannot copy a linear {0:?} wire")]
+    Linear(WireType),
+    #[error("failed to link ports: {0:?}")]
+    Link(#[from] LinkError),
 }
 
 pub trait NodeMetadata: Send + Sync + Debug + Any + Downcast + NodeMetadataBoxClone {}
@@ -140,4 +791,300 @@ impl_box_clone!(NodeMetadata, NodeMetadataBoxClone);
 pub trait PortMetadata: Send + Sync + Debug + Any + Downcast + PortMetadataBoxClone {}
 
 impl_downcast!(PortMetadata);
-impl_box_clone!(PortMetadata, PortMetadataBoxClone);
\ No newline at end of file
+impl_box_clone!(PortMetadata, PortMetadataBoxClone);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::operation::{ControlFlowOp, CustomOp};
+    use crate::component::wire_type::ConstValue;
+    use crate::test_utils::TestCircuit;
+    use crate::DebugData;
+
+    #[test]
+    fn init_qubit_ports() {
+        let mut gug = Gug::new();
+        let mut counter = 0u32;
+        let mut tags = Vec::new();
+        let cx = gug.add_node_with(Op::Circuit(circuit::Op::CX), |port, typ| {
+            if typ == WireType::Qubit {
+                tags.push((port, counter));
+                counter += 1;
+            }
+        });
+        assert_eq!(counter, 4);
+        for (port, tag) in tags {
+            gug.set_physical_qubit(port, tag);
+        }
+        let tagged: Vec<_> = (0..2)
+            .map(|i| gug.physical_qubit(gug.output_port(cx, i).unwrap()))
+            .collect();
+        assert_eq!(tagged, vec![Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn graph_access() {
+        let mut circ = TestCircuit::qubits(1);
+        circ.gate(circuit::Op::H, &[0]);
+        let gug = circ.finish();
+        assert_eq!(gug.graph().node_count(), 3);
+        assert_eq!(gug.graph().node_count(), gug.node_count());
+    }
+
+    #[test]
+    fn port_counts() {
+        let mut circ = TestCircuit::qubits(1);
+        let angle = circ.constant(ConstValue::f64_angle(0.5));
+        let rz = circ.gate_with_args(circuit::Op::RzF64, &[0], &[(angle, 0)]);
+        let gug = circ.finish();
+
+        let num_ports = circuit::Op::RzF64.signature().num_ports();
+        assert_eq!((gug.num_inputs(rz), gug.num_outputs(rz)), num_ports);
+        assert_eq!((gug.num_inputs(rz), gug.num_outputs(rz)), (2, 1));
+        assert_eq!((gug.num_inputs(angle), gug.num_outputs(angle)), (0, 1));
+    }
+
+    #[test]
+    fn large_with_capacity() {
+        let build = |mut gug: Gug| {
+            let input = gug.add_node_with_signature(
+                Op::Circuit(circuit::Op::Input),
+                Signature::new_nonlinear(vec![], vec![WireType::Qubit; 2]),
+            );
+            let mut wires = [(input, 0), (input, 1)];
+            for i in 0..10_000 {
+                let op = if i % 3 == 0 { circuit::Op::CX } else { circuit::Op::H };
+                let node = gug.add_node(Op::Circuit(op.clone()));
+                let targets = if op == circuit::Op::CX { &[0, 1][..] } else { &[i % 2][..] };
+                for (offset, &qubit) in targets.iter().enumerate() {
+                    let (src, src_offset) = wires[qubit];
+                    gug.connect(src, src_offset, node, offset).unwrap();
+                    wires[qubit] = (node, offset);
+                }
+            }
+            gug
+        };
+        let reserved = build(Gug::with_capacity(10_001, 13_334));
+        let grown = build(Gug::new());
+        assert_eq!(reserved.node_count(), 10_001);
+        assert_eq!(reserved.to_text().unwrap(), grown.to_text().unwrap());
+        assert_eq!(reserved.gate_counts(), grown.gate_counts());
+    }
+
+    #[test]
+    fn boundary_constructor() {
+        let qubits = vec![WireType::Qubit; 2];
+        let gug = Gug::with_boundary(qubits.clone(), qubits);
+        let (input, output) = gug.boundary().unwrap();
+        assert_eq!(gug.node_count(), 2);
+        assert_eq!((gug.num_inputs(input), gug.num_outputs(input)), (0, 2));
+        assert_eq!((gug.num_inputs(output), gug.num_outputs(output)), (2, 0));
+        for port in gug.outputs(input).chain(gug.inputs(output)) {
+            assert_eq!(gug.port_type(port), WireType::Qubit);
+            assert_eq!(gug.port_link(port), None);
+        }
+
+        let gug = Gug::with_boundary(vec![WireType::Qubit], vec![WireType::Qubit, WireType::Bool]);
+        let output = gug.output_node().unwrap();
+        let types: Vec<_> = gug.inputs(output).map(|p| gug.port_type(p)).collect();
+        assert_eq!(types, vec![WireType::Qubit, WireType::Bool]);
+    }
+
+    #[test]
+    fn copy_ports() {
+        let mut gug = Gug::new();
+        let copy = gug.add_node(Op::Circuit(circuit::Op::Copy {
+            n_copies: 3,
+            typ: WireType::Angle,
+        }));
+        assert_eq!((gug.num_inputs(copy), gug.num_outputs(copy)), (1, 3));
+        for port in gug.inputs(copy).chain(gug.outputs(copy)) {
+            assert_eq!(gug.port_type(port), WireType::Angle);
+        }
+    }
+
+    #[test]
+    fn boundary_wires() {
+        let types = [WireType::Qubit, WireType::Qubit, WireType::LinearBit];
+        let mut circ = TestCircuit::new(&types);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::Measure, &[1, 2]);
+        let gug = circ.finish();
+
+        assert_eq!(gug.input_wires(), Ok(types.to_vec()));
+        assert_eq!(gug.output_wires(), Ok(types.to_vec()));
+        let boxed = BoxedCircuit::new("circ".into(), gug).unwrap();
+        let signature = boxed.signature();
+        assert_eq!(signature.linear, types.to_vec());
+        assert_eq!(signature.nonlinear, [vec![], vec![]]);
+        assert_eq!(Gug::new().input_wires(), Err(BoundaryError::MissingInput));
+    }
+
+    #[test]
+    fn port_types_from_signature() {
+        let mut gug = Gug::new();
+        let copy = gug.add_node(Op::Circuit(circuit::Op::Copy {
+            n_copies: 2,
+            typ: WireType::Bool,
+        }));
+        gug.op_types.set(copy, Op::Circuit(circuit::Op::Copy {
+            n_copies: 2,
+            typ: WireType::I64,
+        }));
+        gug.set_port_types_from_signature(copy);
+        for port in gug.inputs(copy).chain(gug.outputs(copy)) {
+            assert_eq!(gug.port_type(port), WireType::I64);
+        }
+
+        gug.set_optype(copy, Op::Circuit(circuit::Op::Measure));
+        let signature = circuit::Op::Measure.signature();
+        let types: Vec<WireType> = gug.inputs(copy).map(|p| gug.port_type(p)).collect();
+        assert_eq!(types, signature.inputs().copied().collect::<Vec<_>>());
+        let types: Vec<WireType> = gug.outputs(copy).map(|p| gug.port_type(p)).collect();
+        assert_eq!(types, signature.outputs().copied().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn ports_in_order() {
+        let mut circ = TestCircuit::qubits(2);
+        let cx = circ.gate(circuit::Op::CX, &[0, 1]);
+        let gug = circ.finish();
+
+        let inputs: Vec<_> = gug.inputs(cx).collect();
+        let outputs: Vec<_> = gug.outputs(cx).collect();
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(outputs.len(), 2);
+        for i in 0..2 {
+            assert_eq!(Some(inputs[i]), gug.input_port(cx, i));
+            assert_eq!(Some(outputs[i]), gug.output_port(cx, i));
+            assert_eq!(gug.port_offset(inputs[i]), Some(i));
+            assert_eq!(gug.port_offset(outputs[i]), Some(i));
+        }
+        let input = gug.input_node().unwrap();
+        assert_eq!(gug.port_link(inputs[1]), gug.output_port(input, 1));
+    }
+
+    #[test]
+    fn unique_boundary() {
+        let mut circ = TestCircuit::qubits(1);
+        circ.gate(circuit::Op::H, &[0]);
+        let mut gug = circ.finish();
+        let (input, output) = gug.boundary().unwrap();
+        assert_eq!(Some(input), gug.input_node());
+        assert_eq!(Some(output), gug.output_node());
+
+        let extra = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Output),
+            Signature::new_nonlinear(vec![WireType::Qubit], vec![]),
+        );
+        let ambiguous = BoundaryError::MultipleOutputs(vec![output, extra]);
+        assert_eq!(gug.boundary(), Err(ambiguous.clone()));
+        assert_eq!(gug.output_wires(), Err(ambiguous.clone()));
+        #[cfg(feature = "matrix")]
+        assert_eq!(
+            gug.to_unitary(0.0),
+            Err(UnitaryError::Boundary(ambiguous.clone()))
+        );
+        assert_eq!(gug.wire_type_histogram(), Err(ambiguous));
+        // The boundary of a control flow region is not the circuit's.
+        let region = gug.add_node_with_signature(
+            Op::ControlFlow(ControlFlowOp::Conditional),
+            Signature::default(),
+        );
+        gug.push_child(extra, region).unwrap();
+        assert_eq!(gug.boundary(), Ok((input, output)));
+
+        gug.remove_node(input);
+        assert_eq!(gug.boundary(), Err(BoundaryError::MissingInput));
+        assert_eq!(Gug::new().boundary(), Err(BoundaryError::MissingInput));
+    }
+
+    #[test]
+    fn replace_op_keeps_label() {
+        let mut circ = TestCircuit::qubits(1);
+        let z = circ.gate(circuit::Op::Z, &[0]);
+        let mut gug = circ.finish();
+        gug.register_node_metadata::<DebugData>();
+        *gug.node_metadata_mut::<DebugData>(z).unwrap() = DebugData::with_label("phase");
+
+        gug.replace_op_keep_meta(z, Op::Circuit(circuit::Op::RzF64));
+        assert_eq!(gug.optype(z), &Op::Circuit(circuit::Op::RzF64));
+        assert_eq!((gug.num_inputs(z), gug.num_outputs(z)), (2, 1));
+        let label = gug.node_metadata::<DebugData>(z).unwrap().label.clone();
+        assert_eq!(label.as_deref(), Some("phase"));
+    }
+
+    #[test]
+    fn removed_node_metadata() {
+        let mut circ = TestCircuit::qubits(1);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let mut gug = circ.finish();
+        gug.register_node_metadata::<DebugData>();
+        *gug.node_metadata_mut::<DebugData>(h).unwrap() = DebugData::with_label("old");
+        gug.set_physical_qubit(gug.output_port(h, 0).unwrap(), 3);
+
+        gug.remove_node(h);
+        let x = gug.add_node(Op::Circuit(circuit::Op::X));
+        assert_eq!(x, h);
+        assert_eq!(gug.node_metadata::<DebugData>(x).unwrap().label, None);
+        assert_eq!(gug.physical_qubit(gug.output_port(x, 0).unwrap()), None);
+    }
+
+    #[test]
+    fn broadcast_bool() {
+        let mut gug = Gug::new();
+        let flag = gug.add_node(Op::Circuit(circuit::Op::Const(ConstValue::Bool(true))));
+        let source = gug.output_port(flag, 0).unwrap();
+        let ports = gug.broadcast(source, 3).unwrap();
+        assert_eq!(ports.len(), 3);
+
+        let nots: Vec<_> = ports
+            .iter()
+            .map(|&port| {
+                let not = gug.add_node(Op::Circuit(circuit::Op::Not));
+                gug.link_ports(port, gug.input_port(not, 0).unwrap()).unwrap();
+                not
+            })
+            .collect();
+        let (copy, _) = gug.output_target(flag, 0).unwrap();
+        assert_eq!(gug.optype(copy).name(), "Copy");
+        for (i, &not) in nots.iter().enumerate() {
+            assert_eq!(gug.input_source(not, 0), Some((copy, i)));
+            assert_eq!(gug.port_type(ports[i]), WireType::Bool);
+        }
+
+        let mut circ = TestCircuit::qubits(1);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let mut gug = circ.finish();
+        let qubit = gug.output_port(h, 0).unwrap();
+        assert!(matches!(
+            gug.broadcast(qubit, 2),
+            Err(BroadcastError::Linear(WireType::Qubit))
+        ));
+        assert_eq!(gug.node_count(), 3);
+    }
+
+    #[test]
+    fn map_z_to_rz() {
+        let mut circ = TestCircuit::qubits(2);
+        let z0 = circ.gate(circuit::Op::Z, &[0]);
+        circ.gate(circuit::Op::H, &[1]);
+        let z1 = circ.gate(circuit::Op::Z, &[1]);
+        let mut gug = circ.finish();
+
+        let changed = gug.map_ops(|_, op| {
+            (op == &Op::Circuit(circuit::Op::Z)).then_some(Op::Circuit(circuit::Op::RzF64))
+        });
+        assert_eq!(changed, 2);
+        assert_eq!(gug.gate_counts()["RzF64"], 2);
+        assert!(!gug.gate_counts().contains_key("Z"));
+
+        // The qubit wire is kept, and the new angle input can be fed a constant.
+        let input = gug.input_node().unwrap();
+        assert_eq!(gug.input_source(z0, 0), Some((input, 0)));
+        let angle_port = gug.input_port(z1, 1).unwrap();
+        assert_eq!(gug.port_type(angle_port), WireType::Angle);
+        let pi = gug.add_node(Op::Circuit(circuit::Op::Const(ConstValue::f64_angle(1.0))));
+        gug.connect(pi, 0, z1, 1).unwrap();
+    }
+}
@@ -0,0 +1,534 @@
+//! Read-only analyses over the operations of a graph.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use portgraph::{Direction, NodeIndex};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::WireType;
+
+use super::{BoundaryError, Gug};
+
+/// The nodes of a circuit split by the kind of data they act on, as computed
+/// by [`Gug::partition_classical_quantum`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HybridPartition {
+    /// Nodes with no qubit ports, such as `Const` and `Xor`.
+    pub classical: Vec<NodeIndex>,
+    /// Nodes with only qubit ports.
+    pub quantum: Vec<NodeIndex>,
+    /// Nodes with both qubit and classical ports, bridging the two parts,
+    /// such as `Measure` or gates with an angle input.
+    pub bridge: Vec<NodeIndex>,
+}
+
+impl Gug {
+    /// Counts the number of operations of each kind in the graph, indexed by
+    /// operation name. Boundary nodes are not counted.
+    pub fn gate_counts(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for node in self.nodes() {
+            let op = self.optype(node);
+            if !op.is_boundary() {
+                *counts.entry(op.display_name().into_owned()).or_default() += 1;
+            }
+        }
+        counts
+    }
+
+    /// Counts the boundary wires of each type in the circuit.
+    ///
+    /// Each type is counted on both the `Input` and `Output` nodes and the
+    /// larger count is kept, so that wires passing through the circuit are
+    /// counted once while wires created or consumed inside it are still
+    /// included.
+    pub fn wire_type_histogram(&self) -> Result<HashMap<WireType, usize>, BoundaryError> {
+        let (input, output) = self.boundary()?;
+        let boundary_counts = |node, direction| {
+            let mut counts: HashMap<WireType, usize> = HashMap::new();
+            for port in self.node_ports(node, direction) {
+                *counts.entry(self.port_type(port)).or_default() += 1;
+            }
+            counts
+        };
+        let mut histogram = boundary_counts(input, Direction::Outgoing);
+        for (ty, count) in boundary_counts(output, Direction::Incoming) {
+            let entry = histogram.entry(ty).or_default();
+            *entry = (*entry).max(count);
+        }
+        Ok(histogram)
+    }
+
+    /// Returns `true` if the node's operation acts on exactly two qubits.
+    pub(crate) fn is_two_qubit_gate(&self, node: NodeIndex) -> bool {
+        let op = self.optype(node);
+        !op.is_boundary()
+            && op
+                .signature()
+                .linear
+                .iter()
+                .filter(|&&t| t == WireType::Qubit)
+                .count()
+                == 2
+    }
+
+    /// Returns `true` if the circuit only applies unitary gates, with no
+    /// measurements, resets, barriers, classically controlled gates or
+    /// classical results, and no `SideEffects` wires.
+    ///
+    /// Classical operations are allowed when they only compute gate
+    /// parameters, see [`Gug::parameter_nodes`].
+    /// Opaque operations are unitary when they declare so through
+    /// [`CustomOp::is_unitary`](crate::component::operation::CustomOp::is_unitary)
+    /// and are not classical. Control flow operations are not considered
+    /// unitary.
+    pub fn is_unitary(&self) -> bool {
+        let parameters = self.parameter_nodes();
+        self.nodes().all(|node| {
+            let allowed = match self.optype(node) {
+                Op::Circuit(
+                    circuit::Op::Measure
+                    | circuit::Op::MeasureReset
+                    | circuit::Op::Reset
+                    | circuit::Op::Barrier
+                    | circuit::Op::ClassicallyControlled(_),
+                ) => false,
+                Op::Circuit(circuit::Op::Input | circuit::Op::Output) => true,
+                Op::Circuit(op) => !op.is_pure_classical() || parameters.contains(&node),
+                Op::Opaque(op) => op.is_unitary() && !op.is_pure_classical(),
+                _ => false,
+            };
+            allowed
+                && self
+                    .inputs(node)
+                    .chain(self.outputs(node))
+                    .all(|p| self.port_type(p) != WireType::SideEffects)
+        })
+    }
+
+    /// Returns the classical circuit operations that only compute gate
+    /// parameters, such as `Const` or `AngleAdd` nodes feeding an `RzF64`.
+    ///
+    /// These have no qubit or linear ports, and their outputs only reach the
+    /// classical inputs of quantum operations, possibly through other such
+    /// nodes.
+    fn parameter_nodes(&self) -> HashSet<NodeIndex> {
+        let mut parameters: HashSet<NodeIndex> = self
+            .nodes()
+            .filter(|&node| match self.optype(node) {
+                Op::Circuit(_) if !self.optype(node).is_boundary() => {
+                    self.signature(node).linear.is_empty()
+                        && self
+                            .inputs(node)
+                            .chain(self.outputs(node))
+                            .all(|p| !self.port_type(p).is_linear())
+                }
+                _ => false,
+            })
+            .collect();
+        // Drop the nodes reaching anything else, until only parameters remain.
+        loop {
+            let invalid: Vec<NodeIndex> = parameters
+                .iter()
+                .copied()
+                .filter(|&node| {
+                    self.outputs(node).any(|port| {
+                        let Some(target) = self.port_link(port).and_then(|p| self.port_node(p))
+                        else {
+                            return false;
+                        };
+                        !parameters.contains(&target)
+                            && (self.optype(target).is_boundary()
+                                || self.signature(target).purely_classical())
+                    })
+                })
+                .collect();
+            if invalid.is_empty() {
+                return parameters;
+            }
+            for node in invalid {
+                parameters.remove(&node);
+            }
+        }
+    }
+
+    /// Returns `true` if every quantum gate of the circuit is named in
+    /// `gates`. See [`Gug::non_native_gates`] for the operations checked.
+    pub fn uses_only(&self, gates: &[&str]) -> bool {
+        self.non_native_gates(gates).is_empty()
+    }
+
+    /// Returns the quantum gates whose name is not in `gates`.
+    ///
+    /// Quantum gates are the operations acting on a qubit other than
+    /// measurements, resets, barriers and `Noop`s, together with all opaque
+    /// operations. Classical and control flow operations are not checked.
+    pub fn non_native_gates(&self, gates: &[&str]) -> Vec<NodeIndex> {
+        self.nodes()
+            .filter(|&node| match self.optype(node) {
+                Op::Circuit(
+                    circuit::Op::Measure
+                    | circuit::Op::MeasureReset
+                    | circuit::Op::Reset
+                    | circuit::Op::Barrier
+                    | circuit::Op::Noop(_)
+                    | circuit::Op::Input
+                    | circuit::Op::Output,
+                ) => false,
+                Op::Circuit(op) => op.signature().linear.contains(&WireType::Qubit),
+                Op::Opaque(_) => true,
+                _ => false,
+            })
+            .filter(|&node| !gates.contains(&self.optype(node).name()))
+            .collect()
+    }
+
+    /// Splits the nodes into the classical computation, the quantum circuit,
+    /// and the nodes bridging the two, so that a runtime can offload the
+    /// classical part.
+    ///
+    /// Nodes are classified by the types of their ports, with `LinearBit`
+    /// wires counted as classical. Circuit operations in the classical part
+    /// are exactly those that are [pure classical](circuit::Op::is_pure_classical).
+    /// Opaque operations declaring themselves classical, through
+    /// [`CustomOp::is_pure_classical`](crate::component::operation::CustomOp::is_pure_classical),
+    /// are always put in the classical part.
+    /// Boundary nodes are not included in any part.
+    pub fn partition_classical_quantum(&self) -> HybridPartition {
+        let mut partition = HybridPartition::default();
+        for node in self.nodes() {
+            if self.optype(node).is_boundary() {
+                continue;
+            }
+            let (mut qubits, mut classical) = (false, false);
+            for port in self.inputs(node).chain(self.outputs(node)) {
+                match self.port_type(port) {
+                    WireType::Qubit => qubits = true,
+                    _ => classical = true,
+                }
+            }
+            if self.optype(node).as_opaque().is_some_and(|op| op.is_pure_classical()) {
+                partition.classical.push(node);
+                continue;
+            }
+            let part = match (qubits, classical) {
+                (true, true) => &mut partition.bridge,
+                (true, false) => &mut partition.quantum,
+                (false, _) => &mut partition.classical,
+            };
+            part.push(node);
+        }
+        partition
+    }
+
+    /// Iterates over pairs of two-qubit gates that follow each other directly
+    /// on a shared qubit wire, with nothing in between on that wire.
+    ///
+    /// Each pair is yielded once, even if the gates share both wires.
+    pub fn two_qubit_gate_pairs(&self) -> impl Iterator<Item = (NodeIndex, NodeIndex)> + '_ {
+        let mut seen = HashSet::new();
+        self.nodes()
+            .filter(|&n| self.is_two_qubit_gate(n))
+            .flat_map(move |first| {
+                let num_linear = self.signature(first).linear.len();
+                (0..num_linear).filter_map(move |offset| {
+                    let (second, in_offset) = self.output_target(first, offset)?;
+                    let linear = self.signature(second).linear;
+                    (self.is_two_qubit_gate(second)
+                        && linear.get(in_offset) == Some(&WireType::Qubit))
+                    .then_some((first, second))
+                })
+            })
+            .filter(move |&pair| seen.insert(pair))
+    }
+
+    /// Groups the gates by their support, the set of logical qubits they act
+    /// on, indexed by position among the qubits of the `Input` node.
+    ///
+    /// Qubits are attributed by following each wire from the `Input` node with
+    /// [`Gug::qubit_history`]. Nodes acting on no qubit are left out, and each
+    /// group lists its gates in topological order.
+    pub fn group_by_support(
+        &self,
+    ) -> Result<HashMap<BTreeSet<usize>, Vec<NodeIndex>>, BoundaryError> {
+        let (input, _) = self.boundary()?;
+        let num_qubits = self.qubit_ports(input, Direction::Outgoing).len();
+        let mut support: HashMap<NodeIndex, BTreeSet<usize>> = HashMap::new();
+        for qubit in 0..num_qubits {
+            for (node, _) in self.qubit_history(qubit) {
+                support.entry(node).or_default().insert(qubit);
+            }
+        }
+
+        let mut groups: HashMap<BTreeSet<usize>, Vec<NodeIndex>> = HashMap::new();
+        for node in self.toposort() {
+            if let Some(qubits) = support.remove(&node) {
+                groups.entry(qubits).or_default().push(node);
+            }
+        }
+        Ok(groups)
+    }
+
+    /// Returns a parallel iterator over the nodes of the graph.
+    ///
+    /// The node indices are collected up front, so the iterator can be split
+    /// evenly between threads.
+    #[cfg(feature = "rayon")]
+    pub fn par_iter_nodes(&self) -> impl ParallelIterator<Item = NodeIndex> {
+        self.nodes().collect::<Vec<_>>().into_par_iter()
+    }
+
+    /// Parallel version of [`Gug::gate_counts`].
+    #[cfg(feature = "rayon")]
+    pub fn par_gate_counts(&self) -> HashMap<String, usize> {
+        self.par_iter_nodes()
+            .fold(HashMap::new, |mut counts: HashMap<String, usize>, node| {
+                let op = self.optype(node);
+                if !op.is_boundary() {
+                    *counts.entry(op.display_name().into_owned()).or_default() += 1;
+                }
+                counts
+            })
+            .reduce(HashMap::new, |mut acc, counts| {
+                for (name, count) in counts {
+                    *acc.entry(name).or_default() += count;
+                }
+                acc
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::operation::{circuit, CustomOp};
+    use crate::component::wire_type::Signature;
+    use crate::test_utils::TestCircuit;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn gug_is_send_sync() {
+        assert_send_sync::<Gug>();
+    }
+
+    #[test]
+    fn classical_quantum_partition() {
+        use crate::component::operation::ControlFlowOp;
+        use crate::component::wire_type::ConstValue;
+
+        let mut gug = Gug::new();
+        let qb = vec![WireType::Qubit];
+        let input = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Input),
+            Signature::new_nonlinear(vec![], qb.clone()),
+        );
+        let h = gug.add_node(Op::Circuit(circuit::Op::H));
+        let a = gug.add_node(Op::Circuit(circuit::Op::Const(ConstValue::Bool(true))));
+        let b = gug.add_node(Op::Circuit(circuit::Op::Const(ConstValue::Bool(false))));
+        let xor = gug.add_node(Op::Circuit(circuit::Op::Xor));
+        let cond = gug.add_node_with_signature(
+            Op::ControlFlow(ControlFlowOp::Conditional),
+            Signature::new(qb.clone(), [vec![WireType::Bool], vec![]]),
+        );
+        let output = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Output),
+            Signature::new_nonlinear(qb, vec![]),
+        );
+        gug.connect(input, 0, h, 0).unwrap();
+        gug.connect(h, 0, cond, 0).unwrap();
+        gug.connect(a, 0, xor, 0).unwrap();
+        gug.connect(b, 0, xor, 1).unwrap();
+        gug.connect(xor, 0, cond, 1).unwrap();
+        gug.connect(cond, 0, output, 0).unwrap();
+
+        let mut partition = gug.partition_classical_quantum();
+        partition.classical.sort();
+        let mut classical = vec![a, b, xor];
+        classical.sort();
+        assert_eq!(partition.classical, classical);
+        assert_eq!(partition.quantum, vec![h]);
+        assert_eq!(partition.bridge, vec![cond]);
+        for node in partition.classical {
+            assert!(gug.optype(node).as_circuit().unwrap().is_pure_classical());
+        }
+    }
+
+    #[test]
+    fn unitary_circuit() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        let mut gug = circ.finish();
+        assert!(gug.is_unitary());
+
+        gug.measure_all();
+        assert!(!gug.is_unitary());
+    }
+
+    #[test]
+    fn computed_angles_are_unitary() {
+        use crate::component::wire_type::ConstValue;
+
+        let mut circ = TestCircuit::qubits(1);
+        let a = circ.constant(ConstValue::f64_angle(0.25));
+        let b = circ.constant(ConstValue::f64_angle(0.5));
+        let sum = circ.gug.add_node(Op::Circuit(circuit::Op::AngleAdd));
+        circ.gug.connect(a, 0, sum, 0).unwrap();
+        circ.gug.connect(b, 0, sum, 1).unwrap();
+        circ.gate_with_args(circuit::Op::RzF64, &[0], &[(sum, 0)]);
+        let gug = circ.finish();
+        assert_eq!(gug.validate(), Ok(()));
+        assert!(gug.is_unitary());
+
+        // A classical result leaving the circuit is not a gate parameter.
+        let mut circ = TestCircuit::new(&[WireType::Qubit, WireType::Angle]);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::AngleNeg, &[1]);
+        let gug = circ.finish();
+        assert_eq!(gug.validate(), Ok(()));
+        assert!(!gug.is_unitary());
+    }
+
+    #[derive(Clone, Debug)]
+    struct Collapse;
+
+    impl CustomOp for Collapse {
+        fn name(&self) -> &str {
+            "Collapse"
+        }
+
+        fn signature(&self) -> Signature {
+            Signature::new_linear(vec![WireType::Qubit])
+        }
+
+        fn is_unitary(&self) -> bool {
+            false
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct Phase;
+
+    impl CustomOp for Phase {
+        fn name(&self) -> &str {
+            "Phase"
+        }
+
+        fn signature(&self) -> Signature {
+            Signature::new_linear(vec![WireType::Qubit])
+        }
+    }
+
+    #[test]
+    fn opaque_unitarity() {
+        let mut circ = TestCircuit::qubits(1);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let mut gug = circ.finish();
+        let phase = gug.insert_after(h, 0, Op::Opaque(Box::new(Phase)));
+        assert!(gug.is_unitary());
+
+        gug.insert_after(phase, 0, Op::Opaque(Box::new(Collapse)));
+        assert!(!gug.is_unitary());
+    }
+
+    fn large_circuit() -> Gug {
+        let mut circ = TestCircuit::qubits(4);
+        for i in 0..1000 {
+            circ.gate(circuit::Op::H, &[i % 4]);
+            circ.gate(circuit::Op::CX, &[i % 4, (i + 1) % 4]);
+            if i % 3 == 0 {
+                circ.gate(circuit::Op::T, &[(i + 2) % 4]);
+            }
+        }
+        circ.finish()
+    }
+
+    #[test]
+    fn gate_counts() {
+        let counts = large_circuit().gate_counts();
+        assert_eq!(counts["H"], 1000);
+        assert_eq!(counts["CX"], 1000);
+        assert_eq!(counts["T"], 334);
+        assert!(!counts.contains_key("Input"));
+    }
+
+    #[test]
+    fn count_classically_controlled_gates() {
+        let types = [WireType::Qubit, WireType::LinearBit];
+        let mut circ = TestCircuit::new(&types);
+        let controlled = |op| circuit::Op::ClassicallyControlled(Box::new(op));
+        circ.gate(controlled(circuit::Op::X), &[1, 0]);
+        circ.gate(controlled(circuit::Op::Z), &[1, 0]);
+        circ.gate(controlled(circuit::Op::X), &[1, 0]);
+        let gug = circ.finish();
+
+        let counts = gug.gate_counts();
+        assert_eq!(counts["C[X]"], 2);
+        assert_eq!(counts["C[Z]"], 1);
+        assert_eq!(gug.stats().gate_counts["C[X]"], 2);
+    }
+
+    #[test]
+    fn adjacent_two_qubit_pairs() {
+        let mut circ = TestCircuit::qubits(3);
+        let cx1 = circ.gate(circuit::Op::CX, &[0, 1]);
+        let cx2 = circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::H, &[1]);
+        circ.gate(circuit::Op::CX, &[1, 2]);
+        let gug = circ.finish();
+
+        let pairs: Vec<_> = gug.two_qubit_gate_pairs().collect();
+        assert_eq!(pairs, vec![(cx1, cx2)]);
+    }
+
+    #[test]
+    fn group_by_support() {
+        let mut circ = TestCircuit::qubits(3);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let cx1 = circ.gate(circuit::Op::CX, &[0, 1]);
+        let t = circ.gate(circuit::Op::T, &[2]);
+        let cx2 = circ.gate(circuit::Op::CX, &[1, 0]);
+        let cx3 = circ.gate(circuit::Op::CX, &[1, 2]);
+        let x = circ.gate(circuit::Op::X, &[0]);
+        let gug = circ.finish();
+
+        let groups = gug.group_by_support().unwrap();
+        let support = |qubits: &[usize]| -> &Vec<NodeIndex> {
+            &groups[&qubits.iter().copied().collect::<BTreeSet<_>>()]
+        };
+        assert_eq!(groups.len(), 4);
+        assert_eq!(support(&[0]), &vec![h, x]);
+        assert_eq!(support(&[2]), &vec![t]);
+        assert_eq!(support(&[0, 1]), &vec![cx1, cx2]);
+        assert_eq!(support(&[1, 2]), &vec![cx3]);
+    }
+
+    #[test]
+    fn wire_type_histogram() {
+        let mut circ = TestCircuit::new(&[
+            WireType::Qubit,
+            WireType::Qubit,
+            WireType::LinearBit,
+            WireType::LinearBit,
+        ]);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::Measure, &[1, 3]);
+        let gug = circ.finish();
+
+        let histogram = gug.wire_type_histogram().unwrap();
+        assert_eq!(histogram.len(), 2);
+        assert_eq!(histogram[&WireType::Qubit], 2);
+        assert_eq!(histogram[&WireType::LinearBit], 2);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_gate_counts() {
+        let gug = large_circuit();
+        assert_eq!(gug.par_gate_counts(), gug.gate_counts());
+        assert_eq!(gug.par_iter_nodes().count(), gug.node_count());
+    }
+}
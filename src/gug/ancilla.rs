@@ -0,0 +1,162 @@
+//! Allocation of temporary ancilla qubits.
+
+use portgraph::{Direction, NodeIndex, PortIndex};
+use thiserror::Error;
+
+use crate::component::operation::{circuit, Op};
+use crate::component::register::Ancillas;
+use crate::component::wire_type::WireType;
+
+use super::registers::index_after_removal;
+use super::{BoundaryError, Gug};
+
+/// Error returned when an ancilla cannot be freed.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AncillaError {
+    #[error(transparent)]
+    Boundary(#[from] BoundaryError),
+    #[error("qubit {0} is not an allocated ancilla")]
+    NotAllocated(usize),
+    #[error("ancilla {0} does not end in a Reset")]
+    NotClean(usize),
+}
+
+impl Gug {
+    /// Allocates an ancilla qubit in the |0⟩ state, returning its index among
+    /// the qubits of the `Input` node.
+    ///
+    /// A line released by [`Gug::free_ancilla`] is reused if there is one,
+    /// since it already ends in a `Reset`. Otherwise a new qubit wire is added
+    /// from the `Input` to the `Output` node, initialized by a `Reset`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the circuit does not have a single `Input` and `Output` node.
+    pub fn alloc_ancilla(&mut self) -> usize {
+        let (input, output) = self.boundary().expect("allocating an ancilla requires a boundary");
+        self.register_node_metadata::<Ancillas>();
+        let ancillas = self.node_metadata_mut::<Ancillas>(input).unwrap();
+        if let Some(index) = ancillas.free.pop_first() {
+            ancillas.allocated.insert(index);
+            return index;
+        }
+
+        let source = self.num_outputs(input);
+        let target = self.num_inputs(output);
+        self.resize_ports(input, 0, source + 1);
+        self.resize_ports(output, target + 1, 0);
+        for port in [self.output_port(input, source), self.input_port(output, target)] {
+            self.port_types[port.unwrap()] = Some(WireType::Qubit);
+        }
+        let reset = self.add_node(Op::Circuit(circuit::Op::Reset));
+        self.connect(input, source, reset, 0).unwrap();
+        self.connect(reset, 0, output, target).unwrap();
+
+        let index = self.qubit_ports(input, Direction::Outgoing).len() - 1;
+        let ancillas = self.node_metadata_mut::<Ancillas>(input).unwrap();
+        ancillas.allocated.insert(index);
+        index
+    }
+
+    /// Releases an ancilla allocated with [`Gug::alloc_ancilla`], checking
+    /// that it is returned to |0⟩ by a `Reset` just before the `Output` node.
+    ///
+    /// If the ancilla was never used, that is its line only holds `Reset`
+    /// gates, the line and its boundary wire are removed, and the indices of
+    /// the following qubits move down by one. Otherwise the line is kept and
+    /// reused by the next allocation.
+    pub fn free_ancilla(&mut self, index: usize) -> Result<(), AncillaError> {
+        let (input, output) = self.boundary()?;
+        let allocated = self
+            .node_metadata::<Ancillas>(input)
+            .is_some_and(|ancillas| ancillas.allocated.contains(&index));
+        if !allocated {
+            return Err(AncillaError::NotAllocated(index));
+        }
+
+        let start = self.qubit_ports(input, Direction::Outgoing)[index];
+        let (line, end) = self.qubit_line(start);
+        let is_reset =
+            |node: &NodeIndex| matches!(self.optype(*node), Op::Circuit(circuit::Op::Reset));
+        if !line.last().is_some_and(is_reset) {
+            return Err(AncillaError::NotClean(index));
+        }
+
+        let unused = line.iter().all(is_reset);
+        let ancillas = self.node_metadata_mut::<Ancillas>(input).unwrap();
+        ancillas.allocated.remove(&index);
+        if !unused {
+            ancillas.free.insert(index);
+            return Ok(());
+        }
+
+        self.remap_qubit_indices(input, |i| index_after_removal(i, &[index]));
+
+        let source = self.port_offset(start).unwrap();
+        let target = self.port_offset(end).unwrap();
+        for node in line {
+            self.remove_node(node);
+        }
+        self.remove_port(input, Direction::Outgoing, source);
+        self.remove_port(output, Direction::Incoming, target);
+        Ok(())
+    }
+
+    /// Follows a qubit wire from an output port of the `Input` node, returning
+    /// the nodes along it and the `Output` node port it ends at.
+    fn qubit_line(&self, start: PortIndex) -> (Vec<NodeIndex>, PortIndex) {
+        let mut line = Vec::new();
+        let mut port = start;
+        loop {
+            let link = self.port_link(port).expect("qubit wires are connected");
+            let node = self.port_node(link).unwrap();
+            if self.optype(node).is_boundary() {
+                return (line, link);
+            }
+            line.push(node);
+            port = self.output_port(node, self.port_offset(link).unwrap()).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn alloc_use_and_free() {
+        let mut circ = TestCircuit::qubits(1);
+        circ.gate(circuit::Op::H, &[0]);
+        let mut gug = circ.finish();
+
+        let ancilla = gug.alloc_ancilla();
+        assert_eq!(ancilla, 1);
+        assert_eq!(gug.validate(), Ok(()));
+        let history = gug.qubit_history(ancilla);
+        assert_eq!(history.len(), 1);
+        let reset = history[0].0;
+
+        let x = gug.insert_after(reset, 0, Op::Circuit(circuit::Op::X));
+        assert_eq!(gug.free_ancilla(ancilla), Err(AncillaError::NotClean(ancilla)));
+        gug.insert_after(x, 0, Op::Circuit(circuit::Op::Reset));
+        assert_eq!(gug.free_ancilla(ancilla), Ok(()));
+        assert_eq!(gug.free_ancilla(ancilla), Err(AncillaError::NotAllocated(ancilla)));
+
+        // The used line is kept and handed out again.
+        let node_count = gug.node_count();
+        assert_eq!(gug.alloc_ancilla(), ancilla);
+        assert_eq!(gug.node_count(), node_count);
+
+        // An unused ancilla is removed with its boundary wire.
+        let unused = gug.alloc_ancilla();
+        assert_eq!(unused, 2);
+        assert_eq!(gug.node_count(), node_count + 1);
+        assert_eq!(gug.free_ancilla(unused), Ok(()));
+        assert_eq!(gug.node_count(), node_count);
+        let (input, output) = gug.boundary().unwrap();
+        assert_eq!((gug.num_outputs(input), gug.num_inputs(output)), (2, 2));
+        assert_eq!(gug.free_ancilla(ancilla), Ok(()));
+        assert_eq!(gug.validate(), Ok(()));
+    }
+}
@@ -0,0 +1,227 @@
+//! Transformations of the angle parameters in a graph.
+
+use std::collections::HashMap;
+
+use portgraph::NodeIndex;
+use thiserror::Error;
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::{AngleValue, ConstValue};
+
+use super::Gug;
+
+/// Error returned when writing back the parameters of a circuit.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ParamError {
+    #[error("expected {expected} parameters, got {got}")]
+    LengthMismatch { expected: usize, got: usize },
+}
+
+impl Gug {
+    /// Lists the angle of every parametrized gate, in topological order.
+    ///
+    /// Parametrized gates are the `Rx` and `Rz` rotations with an inlined
+    /// angle, and the `RxF64` and `RzF64` rotations fed by a `Const` angle.
+    pub fn collect_parameters(&self) -> Vec<(NodeIndex, AngleValue)> {
+        self.parameter_holders()
+            .into_iter()
+            .filter_map(|(gate, holder)| Some((gate, *self.stored_angle(holder)?)))
+            .collect()
+    }
+
+    /// Writes back the angles of the parametrized gates, in the order of
+    /// [`Gug::collect_parameters`].
+    ///
+    /// Gates sharing the same `Const` angle node all take the last value
+    /// written to it.
+    pub fn set_parameters(&mut self, values: &[AngleValue]) -> Result<(), ParamError> {
+        let holders = self.parameter_holders();
+        if holders.len() != values.len() {
+            return Err(ParamError::LengthMismatch {
+                expected: holders.len(),
+                got: values.len(),
+            });
+        }
+        for ((_, holder), value) in holders.into_iter().zip(values) {
+            self.set_stored_angle(holder, *value);
+        }
+        Ok(())
+    }
+
+    /// Returns the parametrized gates in topological order, each with the
+    /// node storing its angle: the gate itself for inlined angles, or the
+    /// `Const` node feeding it.
+    fn parameter_holders(&self) -> Vec<(NodeIndex, NodeIndex)> {
+        self.toposort()
+            .into_iter()
+            .filter_map(|node| match self.optype(node) {
+                Op::Circuit(circuit::Op::Rx(_) | circuit::Op::Rz(_)) => Some((node, node)),
+                Op::Circuit(circuit::Op::RxF64 | circuit::Op::RzF64) => {
+                    let (source, _) = self.input_source(node, 1)?;
+                    self.stored_angle(source)?;
+                    Some((node, source))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the angle stored in an inlined rotation or a `Const` node.
+    fn stored_angle(&self, node: NodeIndex) -> Option<&AngleValue> {
+        match self.optype(node) {
+            Op::Circuit(circuit::Op::Const(ConstValue::Angle(angle)))
+            | Op::Circuit(circuit::Op::Rx(angle))
+            | Op::Circuit(circuit::Op::Rz(angle)) => Some(angle),
+            _ => None,
+        }
+    }
+
+    /// Replaces the angle stored in an inlined rotation or a `Const` node.
+    fn set_stored_angle(&mut self, node: NodeIndex, value: AngleValue) {
+        let mut op = self.optype(node).clone();
+        match &mut op {
+            Op::Circuit(circuit::Op::Const(ConstValue::Angle(angle)))
+            | Op::Circuit(circuit::Op::Rx(angle))
+            | Op::Circuit(circuit::Op::Rz(angle)) => *angle = value,
+            _ => return,
+        }
+        self.op_types.set(node, op);
+    }
+
+    /// Substitutes the values bound in `env` into the `Symbolic` angle nodes.
+    ///
+    /// Nodes left without free parameters become `Const` angles, which can
+    /// then be folded into the gates they feed. Returns the number of such
+    /// nodes.
+    pub fn bind_symbols(&mut self, env: &HashMap<String, f64>) -> usize {
+        let nodes: Vec<_> = self.nodes().collect();
+        let mut bound = 0;
+        for node in nodes {
+            let Op::Circuit(circuit::Op::Symbolic(expr)) = self.optype(node) else { continue };
+            let expr = expr.bind(env);
+            let op = match expr.value() {
+                Ok(angle) => {
+                    bound += 1;
+                    circuit::Op::Const(ConstValue::Angle(angle))
+                }
+                Err(_) => circuit::Op::Symbolic(expr),
+            };
+            self.op_types.set(node, Op::Circuit(op));
+        }
+        bound
+    }
+
+    /// Replaces floating point angle constants and inlined gate angles with
+    /// exact rational multiples of π, where the float lies within `tol` of a
+    /// rational with denominator at most `max_denominator`.
+    ///
+    /// Returns the number of converted angles.
+    pub fn rationalize_angles(&mut self, tol: f64, max_denominator: i64) -> usize {
+        let nodes: Vec<_> = self.nodes().collect();
+        let mut converted = 0;
+        for node in nodes {
+            let Some(&angle @ AngleValue::F64(_)) = self.stored_angle(node) else { continue };
+            if let Some(r) = angle.to_rational(tol, max_denominator) {
+                self.set_stored_angle(node, AngleValue::Rational(r));
+                converted += 1;
+            }
+        }
+        converted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_rational::Rational64;
+
+    use super::*;
+    use crate::component::symbolic::SymbolicAngle;
+    use crate::component::wire_type::Rational;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn parameters_round_trip() {
+        let mut circ = TestCircuit::qubits(2);
+        let rz0 = circ.gate(circuit::Op::Rz(AngleValue::F64(0.25)), &[0]);
+        let angle = circ.constant(ConstValue::f64_angle(0.5));
+        let rz1 = circ.gate_with_args(circuit::Op::RzF64, &[1], &[(angle, 0)]);
+        let rz2 = circ.gate(circuit::Op::Rz(AngleValue::F64(0.75)), &[0]);
+        let mut gug = circ.finish();
+
+        let params = gug.collect_parameters();
+        let nodes: Vec<_> = params.iter().map(|&(n, _)| n).collect();
+        assert_eq!(params.len(), 3);
+        assert!(nodes.contains(&rz1));
+        let pos = |n| nodes.iter().position(|&m| m == n).unwrap();
+        assert!(pos(rz0) < pos(rz2));
+
+        let doubled: Vec<_> = params.iter().map(|&(_, a)| a + a).collect();
+        gug.set_parameters(&doubled).unwrap();
+        let updated: Vec<_> = gug.collect_parameters().into_iter().map(|(_, a)| a).collect();
+        assert_eq!(updated, doubled);
+        assert_eq!(
+            gug.optype(angle),
+            &Op::Circuit(circuit::Op::Const(ConstValue::f64_angle(1.0)))
+        );
+
+        assert_eq!(
+            gug.set_parameters(&doubled[..2]),
+            Err(ParamError::LengthMismatch {
+                expected: 3,
+                got: 2
+            })
+        );
+    }
+
+    #[test]
+    fn bind_symbolic_angles() {
+        let half = AngleValue::Rational(Rational(Rational64::new(1, 2)));
+        let mut circ = TestCircuit::qubits(2);
+        let shifted = circ.gug.add_node(Op::Circuit(circuit::Op::Symbolic(
+            SymbolicAngle::symbol("theta") + half,
+        )));
+        let free = circ.gug.add_node(Op::Circuit(circuit::Op::Symbolic(
+            SymbolicAngle::symbol("theta") - SymbolicAngle::symbol("phi"),
+        )));
+        circ.gate_with_args(circuit::Op::RzF64, &[0], &[(shifted, 0)]);
+        circ.gate_with_args(circuit::Op::RxF64, &[1], &[(free, 0)]);
+        let mut gug = circ.finish();
+        assert_eq!(gug.validate(), Ok(()));
+        assert!(gug.is_unitary());
+
+        let env = HashMap::from([("theta".to_string(), 0.25)]);
+        assert_eq!(gug.bind_symbols(&env), 1);
+        assert_eq!(
+            gug.optype(shifted),
+            &Op::Circuit(circuit::Op::Const(ConstValue::f64_angle(0.75)))
+        );
+        let expected = SymbolicAngle::Value(AngleValue::F64(0.25)) - SymbolicAngle::symbol("phi");
+        assert_eq!(
+            gug.optype(free),
+            &Op::Circuit(circuit::Op::Symbolic(expected))
+        );
+        assert_eq!(gug.validate(), Ok(()));
+    }
+
+    #[test]
+    fn rationalize() {
+        let mut circ = TestCircuit::qubits(1);
+        let half = circ.constant(ConstValue::f64_angle(0.5000001));
+        let irrational = circ.constant(ConstValue::f64_angle(std::f64::consts::FRAC_1_SQRT_2));
+        let mut gug = circ.finish();
+
+        assert_eq!(gug.rationalize_angles(1e-6, 16), 1);
+        assert_eq!(
+            gug.optype(half),
+            &Op::Circuit(circuit::Op::Const(ConstValue::Angle(AngleValue::Rational(
+                Rational(Rational64::new(1, 2))
+            ))))
+        );
+        assert_eq!(
+            gug.optype(irrational),
+            &Op::Circuit(circuit::Op::Const(ConstValue::f64_angle(
+                std::f64::consts::FRAC_1_SQRT_2
+            )))
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Rendering of circuits as text diagrams.
+
+use std::collections::HashMap;
+
+use portgraph::{Direction, NodeIndex};
+
+use crate::component::wire_type::WireType;
+
+use super::{Gug, ScheduleStrategy};
+
+impl Gug {
+    /// Renders the circuit as a text diagram, with one row per linear wire of
+    /// the `Input` node and one column per time slice of the ASAP schedule.
+    ///
+    /// Qubit wires are drawn with `-` and classical bit wires with `=`. Gates
+    /// are labelled with their name on every wire they act on, and gates
+    /// acting on several wires are joined by `|` connectors. Classical
+    /// computations on non-linear wires are not drawn.
+    pub fn to_ascii(&self) -> String {
+        let Ok((input, _)) = self.boundary() else {
+            return String::new();
+        };
+        let (wires, lines) = self.linear_lines(input);
+        if wires.is_empty() {
+            return String::new();
+        }
+
+        let (mut qubits, mut bits) = (0, 0);
+        let mut names = Vec::with_capacity(wires.len());
+        for typ in &wires {
+            let (prefix, count) = match typ {
+                WireType::Qubit => ('q', &mut qubits),
+                _ => ('c', &mut bits),
+            };
+            names.push(format!("{prefix}{count}"));
+            *count += 1;
+        }
+        let name_width = names.iter().map(String::len).max().unwrap_or(0);
+
+        // Even rows draw the wires, odd rows the connectors between them.
+        let mut rows: Vec<String> = (0..2 * wires.len() - 1)
+            .map(|row| match row % 2 {
+                0 => format!("{:>name_width$}: ", names[row / 2]),
+                _ => " ".repeat(name_width + 2),
+            })
+            .collect();
+        for slice in self.schedule(ScheduleStrategy::Asap) {
+            let mut labels: Vec<Option<&str>> = vec![None; wires.len()];
+            let mut connected = vec![false; rows.len()];
+            for node in slice {
+                let num_linear = self.signature(node).linear.len();
+                let touched: Vec<usize> = (0..num_linear)
+                    .filter_map(|offset| lines.get(&(node, offset)).copied())
+                    .collect();
+                let (Some(&first), Some(&last)) = (touched.iter().min(), touched.iter().max())
+                else {
+                    continue;
+                };
+                for &line in &touched {
+                    labels[line] = Some(self.optype(node).name());
+                }
+                connected[2 * first..=2 * last].fill(true);
+            }
+
+            let width = labels.iter().flatten().map(|l| l.len()).max().unwrap_or(1);
+            for (row, text) in rows.iter_mut().enumerate() {
+                if row % 2 == 1 {
+                    let mark = if connected[row] { '|' } else { ' ' };
+                    text.push_str(&format!(" {mark:<width$} "));
+                    continue;
+                }
+                let line = row / 2;
+                let wire = match wires[line] {
+                    WireType::Qubit => '-',
+                    _ => '=',
+                };
+                let label = match labels[line] {
+                    Some(label) => label.to_string(),
+                    None if connected[row] => "|".to_string(),
+                    None => String::new(),
+                };
+                text.push(wire);
+                text.push_str(&label);
+                text.extend(std::iter::repeat(wire).take(width - label.len() + 1));
+            }
+        }
+
+        let rows: Vec<&str> = rows.iter().map(|row| row.trim_end()).collect();
+        rows.join("\n") + "\n"
+    }
+
+    /// Returns the types of the linear wires leaving the `Input` node, and a
+    /// map from each linear input port along them, as a node and offset, to
+    /// the index of its wire.
+    #[allow(clippy::type_complexity)]
+    fn linear_lines(
+        &self,
+        input: NodeIndex,
+    ) -> (Vec<WireType>, HashMap<(NodeIndex, usize), usize>) {
+        let mut wires = Vec::new();
+        let mut lines = HashMap::new();
+        for port in self.node_ports(input, Direction::Outgoing) {
+            let typ = self.port_type(port);
+            if !typ.is_linear() {
+                continue;
+            }
+            let line = wires.len();
+            wires.push(typ);
+            let mut port = Some(port);
+            while let Some(link) = port.and_then(|p| self.port_link(p)) {
+                let (Some(node), Some(offset)) = (self.port_node(link), self.port_offset(link))
+                else {
+                    break;
+                };
+                if offset >= self.signature(node).linear.len() || self.optype(node).is_boundary() {
+                    break;
+                }
+                lines.insert((node, offset), line);
+                port = self.output_port(node, offset);
+            }
+        }
+        (wires, lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::operation::circuit;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn bell_state() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        let gug = circ.finish();
+
+        let diagram = gug.to_ascii();
+        let rows: Vec<&str> = diagram.lines().collect();
+        assert_eq!(rows, vec!["q0: -H--CX-", "       |", "q1: ---CX-"]);
+        assert!(rows[0].contains('H') && !rows[2].contains('H'));
+        assert_eq!(rows[0].find("CX"), rows[1].find('|'));
+        assert_eq!(rows[2].find("CX"), rows[1].find('|'));
+    }
+
+    #[test]
+    fn classical_wire() {
+        let mut circ = TestCircuit::new(&[WireType::Qubit, WireType::LinearBit]);
+        circ.gate(circuit::Op::Measure, &[0, 1]);
+        let gug = circ.finish();
+
+        let diagram = gug.to_ascii();
+        let rows: Vec<&str> = diagram.lines().collect();
+        assert_eq!(rows, vec!["q0: -Measure-", "     |", "c0: =Measure="]);
+    }
+}
@@ -0,0 +1,333 @@
+//! Validated construction of circuits over a fixed set of wires.
+//!
+//! [`CircuitBuilder`] is shared by the importers, the decomposition templates
+//! and the test helpers, so that all of them reject the same malformed gates
+//! before touching the graph.
+
+use std::collections::BTreeMap;
+
+use portgraph::NodeIndex;
+use thiserror::Error;
+
+use crate::component::operation::{circuit, Op};
+use crate::component::register::QubitNames;
+use crate::component::wire_type::{Signature, WireType};
+
+use super::Gug;
+
+/// Error returned when appending an invalid operation to a circuit.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BuildError {
+    #[error("wire {0} is out of range")]
+    WireOutOfRange(usize),
+    #[error("wire {0} is used more than once")]
+    RepeatedWire(usize),
+    #[error("operation acts on {expected} wires, found {found}")]
+    WrongArity { expected: usize, found: usize },
+    #[error("wire {wire} has type {found:?}, but the operation expects {expected:?}")]
+    WireType {
+        wire: usize,
+        expected: WireType,
+        found: WireType,
+    },
+    #[error("operation takes at most {expected} extra inputs, found {found}")]
+    TooManyArgs { expected: usize, found: usize },
+    #[error("no wire is named {0:?}")]
+    UnknownName(String),
+}
+
+/// Appends operations to a circuit, keeping track of the open end of each
+/// wire.
+///
+/// The circuit itself is passed to each call, so that callers keep direct
+/// access to it between operations.
+pub(crate) struct CircuitBuilder {
+    input: NodeIndex,
+    types: Vec<WireType>,
+    /// The node and output offset currently terminating each wire.
+    wires: Vec<(NodeIndex, usize)>,
+    /// The qubit wires named with [`CircuitBuilder::name_wire`].
+    names: BTreeMap<String, usize>,
+}
+
+impl CircuitBuilder {
+    /// Adds an `Input` node producing wires of the given types.
+    pub fn new(gug: &mut Gug, types: &[WireType]) -> Self {
+        let input = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Input),
+            Signature::new_nonlinear(vec![], types.to_vec()),
+        );
+        Self {
+            input,
+            types: types.to_vec(),
+            wires: (0..types.len()).map(|i| (input, i)).collect(),
+            names: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the types of the wires.
+    pub fn types(&self) -> &[WireType] {
+        &self.types
+    }
+
+    /// Names a qubit wire, so that operations can be appended on it with
+    /// [`CircuitBuilder::append_named`]. Reusing a name moves it to the new
+    /// wire.
+    ///
+    /// The names are recorded on the `Input` node by
+    /// [`CircuitBuilder::finish`], as with [`Gug::name_qubit`].
+    pub fn name_wire(&mut self, wire: usize, name: &str) -> Result<(), BuildError> {
+        match self.types.get(wire) {
+            None => Err(BuildError::WireOutOfRange(wire)),
+            Some(&WireType::Qubit) => {
+                self.names.insert(name.to_string(), wire);
+                Ok(())
+            }
+            Some(&found) => Err(BuildError::WireType {
+                wire,
+                expected: WireType::Qubit,
+                found,
+            }),
+        }
+    }
+
+    /// Appends an operation on the wires with the given names, see
+    /// [`CircuitBuilder::append`].
+    pub fn append_named(
+        &mut self,
+        gug: &mut Gug,
+        op: circuit::Op,
+        names: &[&str],
+        args: &[(NodeIndex, usize)],
+    ) -> Result<NodeIndex, BuildError> {
+        let wires = names
+            .iter()
+            .map(|&name| {
+                self.names
+                    .get(name)
+                    .copied()
+                    .ok_or_else(|| BuildError::UnknownName(name.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.append(gug, op, &wires, args)
+    }
+
+    /// Appends an operation, see [`CircuitBuilder::append_with_signature`].
+    pub fn append(
+        &mut self,
+        gug: &mut Gug,
+        op: circuit::Op,
+        wires: &[usize],
+        args: &[(NodeIndex, usize)],
+    ) -> Result<NodeIndex, BuildError> {
+        let signature = op.signature();
+        self.append_with_signature(gug, Op::Circuit(op), signature, wires, args)
+    }
+
+    /// Appends an operation with the given signature.
+    ///
+    /// The wires are connected to its ports in order, and must cover at least
+    /// its linear ports, with the types of the ports at the same offsets. The
+    /// following inputs are fed by the `(node, output offset)` sources in
+    /// `args`, and any inputs left over stay disconnected.
+    ///
+    /// Nothing is added to the circuit if the operation is rejected.
+    pub fn append_with_signature(
+        &mut self,
+        gug: &mut Gug,
+        op: Op,
+        signature: Signature,
+        wires: &[usize],
+        args: &[(NodeIndex, usize)],
+    ) -> Result<NodeIndex, BuildError> {
+        self.check(&signature, wires, args)?;
+        let node = gug.add_node_with_signature(op, signature);
+        for (offset, &wire) in wires.iter().enumerate() {
+            let (src, src_offset) = self.wires[wire];
+            gug.connect(src, src_offset, node, offset).unwrap();
+            self.wires[wire] = (node, offset);
+        }
+        for (i, &(src, src_offset)) in args.iter().enumerate() {
+            gug.connect(src, src_offset, node, wires.len() + i).unwrap();
+        }
+        Ok(node)
+    }
+
+    fn check(
+        &self,
+        signature: &Signature,
+        wires: &[usize],
+        args: &[(NodeIndex, usize)],
+    ) -> Result<(), BuildError> {
+        let inputs: Vec<WireType> = signature.inputs().copied().collect();
+        let outputs: Vec<WireType> = signature.outputs().copied().collect();
+        let expected = wires
+            .len()
+            .clamp(signature.linear.len(), inputs.len().min(outputs.len()));
+        if wires.len() != expected {
+            return Err(BuildError::WrongArity {
+                expected,
+                found: wires.len(),
+            });
+        }
+        for (offset, &wire) in wires.iter().enumerate() {
+            let Some(&found) = self.types.get(wire) else {
+                return Err(BuildError::WireOutOfRange(wire));
+            };
+            if wires[..offset].contains(&wire) {
+                return Err(BuildError::RepeatedWire(wire));
+            }
+            if let Some(&expected) = [inputs[offset], outputs[offset]]
+                .iter()
+                .find(|&&typ| typ != found)
+            {
+                return Err(BuildError::WireType {
+                    wire,
+                    expected,
+                    found,
+                });
+            }
+        }
+        let expected = inputs.len() - wires.len();
+        if args.len() > expected {
+            return Err(BuildError::TooManyArgs {
+                expected,
+                found: args.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Caps all wires with an `Output` node and returns it, recording the
+    /// wire names on the `Input` node.
+    pub fn finish(self, gug: &mut Gug) -> NodeIndex {
+        if !self.names.is_empty() {
+            // Names are indexed among the qubits of the `Input` node.
+            let qubit_index = |wire: usize| {
+                self.types[..wire]
+                    .iter()
+                    .filter(|&&t| t == WireType::Qubit)
+                    .count()
+            };
+            let names = self
+                .names
+                .iter()
+                .map(|(name, &wire)| (name.clone(), qubit_index(wire)))
+                .collect();
+            gug.register_node_metadata::<QubitNames>();
+            *gug.node_metadata_mut::<QubitNames>(self.input).unwrap() = QubitNames(names);
+        }
+        let output = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Output),
+            Signature::new_nonlinear(self.types, vec![]),
+        );
+        for (i, &(src, src_offset)) in self.wires.iter().enumerate() {
+            gug.connect(src, src_offset, output, i).unwrap();
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::wire_type::ConstValue;
+
+    #[test]
+    fn rejects_malformed_gates() {
+        let mut gug = Gug::new();
+        let types = [WireType::Qubit, WireType::Qubit, WireType::LinearBit];
+        let mut builder = CircuitBuilder::new(&mut gug, &types);
+        let nodes = gug.node_count();
+
+        let mut append = |op, wires: &[usize]| builder.append(&mut gug, op, wires, &[]);
+        assert_eq!(
+            append(circuit::Op::H, &[3]),
+            Err(BuildError::WireOutOfRange(3))
+        );
+        assert_eq!(
+            append(circuit::Op::CX, &[1, 1]),
+            Err(BuildError::RepeatedWire(1))
+        );
+        assert_eq!(
+            append(circuit::Op::CX, &[0]),
+            Err(BuildError::WrongArity {
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            append(circuit::Op::CX, &[0, 2]),
+            Err(BuildError::WireType {
+                wire: 2,
+                expected: WireType::Qubit,
+                found: WireType::LinearBit
+            })
+        );
+        assert_eq!(
+            append(circuit::Op::Measure, &[2, 0]),
+            Err(BuildError::WireType {
+                wire: 2,
+                expected: WireType::Qubit,
+                found: WireType::LinearBit
+            })
+        );
+        assert!(append(circuit::Op::Measure, &[0, 2]).is_ok());
+        assert_eq!(gug.node_count(), nodes + 1);
+
+        let constant = gug.add_node(Op::Circuit(circuit::Op::Const(ConstValue::f64_angle(0.5))));
+        assert_eq!(
+            builder.append(
+                &mut gug,
+                circuit::Op::RzF64,
+                &[1],
+                &[(constant, 0), (constant, 0)]
+            ),
+            Err(BuildError::TooManyArgs {
+                expected: 1,
+                found: 2
+            })
+        );
+        builder
+            .append(&mut gug, circuit::Op::RzF64, &[1], &[(constant, 0)])
+            .unwrap();
+        builder.finish(&mut gug);
+        assert_eq!(gug.validate(), Ok(()));
+    }
+
+    #[test]
+    fn named_wires() {
+        let mut gug = Gug::new();
+        let types = [WireType::LinearBit, WireType::Qubit, WireType::Qubit];
+        let mut builder = CircuitBuilder::new(&mut gug, &types);
+        builder.name_wire(1, "data").unwrap();
+        builder.name_wire(2, "ancilla").unwrap();
+        assert_eq!(
+            builder.name_wire(0, "bit"),
+            Err(BuildError::WireType {
+                wire: 0,
+                expected: WireType::Qubit,
+                found: WireType::LinearBit
+            })
+        );
+        assert_eq!(
+            builder.name_wire(3, "flag"),
+            Err(BuildError::WireOutOfRange(3))
+        );
+
+        let cx = builder
+            .append_named(&mut gug, circuit::Op::CX, &["ancilla", "data"], &[])
+            .unwrap();
+        assert_eq!(
+            builder.append_named(&mut gug, circuit::Op::H, &["flag"], &[]),
+            Err(BuildError::UnknownName("flag".to_string()))
+        );
+        builder.finish(&mut gug);
+
+        let (input, _) = gug.boundary().unwrap();
+        assert_eq!(gug.input_source(cx, 0), Some((input, 2)));
+        assert_eq!(gug.qubit_by_name("data"), Some(0));
+        assert_eq!(gug.qubit_by_name("ancilla"), Some(1));
+        assert_eq!(gug.validate(), Ok(()));
+    }
+}
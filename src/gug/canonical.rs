@@ -0,0 +1,199 @@
+//! Canonical port ordering and node numbering.
+
+use std::collections::{HashMap, HashSet};
+
+use portgraph::{Direction, Hierarchy, NodeIndex, PortGraph, PortIndex, SecondaryMap};
+
+use crate::component::wire_type::WireType;
+
+use super::{Gug, OpTable};
+
+impl Gug {
+    /// Reorders the ports of every node to match the order of its operation's
+    /// signature: linear wires first, followed by the non-linear ports.
+    ///
+    /// Ports are matched to signature positions by wire type, keeping the
+    /// relative order of ports with the same type, and links and port
+    /// metadata move with their ports. Nodes whose port types are not a permutation of their signature,
+    /// such as boundary nodes, are left untouched.
+    pub fn canonicalize_ports(&mut self) {
+        let nodes: Vec<NodeIndex> = self.nodes().collect();
+        for node in nodes {
+            let signature = self.signature(node);
+            let inputs: Vec<WireType> = signature.inputs().copied().collect();
+            let outputs: Vec<WireType> = signature.outputs().copied().collect();
+            self.canonicalize_direction(node, Direction::Incoming, &inputs);
+            self.canonicalize_direction(node, Direction::Outgoing, &outputs);
+        }
+    }
+
+    /// Renumbers the nodes into the dense range `0..node_count`, following
+    /// the topological order of the graph, and returns the map from old to
+    /// new node indices.
+    ///
+    /// Operations, port types, metadata and the hierarchy are carried over to
+    /// the new indices. Nodes in cycles are numbered last, in their previous
+    /// order.
+    pub fn compact(&mut self) -> HashMap<NodeIndex, NodeIndex> {
+        let mut order = self.toposort();
+        let sorted: HashSet<NodeIndex> = order.iter().copied().collect();
+        order.extend(self.nodes().filter(|n| !sorted.contains(n)));
+
+        let mut graph = PortGraph::with_capacity(order.len(), self.graph.port_count());
+        let mut op_types = OpTable::with_capacity(order.len());
+        let mut port_types = SecondaryMap::new();
+        let mut node_map = HashMap::new();
+        let mut port_map = HashMap::new();
+        for &node in &order {
+            let new = graph.add_node(self.graph.num_inputs(node), self.graph.num_outputs(node));
+            op_types.set(new, self.optype(node).clone());
+            let old_ports = self.graph.inputs(node).chain(self.graph.outputs(node));
+            for (old, port) in old_ports.zip(graph.inputs(new).chain(graph.outputs(new))) {
+                port_types[port] = self.port_types[old];
+                port_map.insert(old, port);
+            }
+            node_map.insert(node, new);
+        }
+        for &node in &order {
+            for port in self.graph.outputs(node) {
+                if let Some(link) = self.port_link(port) {
+                    graph.link_ports(port_map[&port], port_map[&link]).unwrap();
+                }
+            }
+        }
+
+        let mut hierarchy = Hierarchy::new();
+        for &node in &order {
+            for child in self.children(node) {
+                hierarchy.push_child(node_map[&child], node_map[&node]).unwrap();
+            }
+        }
+        for meta in self.node_metadata.values_mut() {
+            let old = meta.clone();
+            for (&old_node, &new_node) in &node_map {
+                meta[new_node] = old[old_node].clone();
+            }
+        }
+        for meta in self.port_metadata.values_mut() {
+            let old = meta.clone();
+            for (&old_port, &new_port) in &port_map {
+                meta[new_port] = old[old_port].clone();
+            }
+        }
+
+        self.graph = graph;
+        self.hierarchy = hierarchy;
+        self.op_types = op_types;
+        self.port_types = port_types;
+        node_map
+    }
+
+    fn canonicalize_direction(&mut self, node: NodeIndex, direction: Direction, types: &[WireType]) {
+        let ports: Vec<PortIndex> = self.node_ports(node, direction).collect();
+        if ports.len() != types.len() {
+            return;
+        }
+
+        // For each canonical position, the current position of the port moving there.
+        let mut used = vec![false; ports.len()];
+        let mut assignment = Vec::with_capacity(ports.len());
+        for &typ in types {
+            let Some(current) = (0..ports.len())
+                .find(|&i| !used[i] && self.port_type(ports[i]) == typ)
+            else {
+                return;
+            };
+            used[current] = true;
+            assignment.push(current);
+        }
+        if assignment.iter().enumerate().all(|(i, &j)| i == j) {
+            return;
+        }
+
+        let links: Vec<Option<PortIndex>> =
+            ports.iter().map(|&port| self.unlink_port(port)).collect();
+        for (position, &current) in assignment.iter().enumerate() {
+            let port = ports[position];
+            self.port_types[port] = Some(types[position]);
+            if let Some(link) = links[current] {
+                let result = match direction {
+                    Direction::Incoming => self.link_ports(link, port),
+                    Direction::Outgoing => self.link_ports(port, link),
+                };
+                result.expect("Relinking a freshly unlinked port cannot fail");
+            }
+        }
+        for meta in self.port_metadata.values_mut() {
+            let old: Vec<_> = ports.iter().map(|&port| meta[port].clone()).collect();
+            for (position, &current) in assignment.iter().enumerate() {
+                meta[ports[position]] = old[current].clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::component::operation::{circuit, Op};
+    use crate::DebugData;
+    use crate::component::wire_type::{ConstValue, Signature, WireType};
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn reorder_scrambled_ports() {
+        let mut circ = TestCircuit::qubits(1);
+        let angle = circ.constant(ConstValue::f64_angle(0.5));
+        let rz = circ.gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::RzF64),
+            Signature::new(vec![], [vec![WireType::Angle, WireType::Qubit], vec![WireType::Qubit]]),
+        );
+        let input = circ.gug.input_node().unwrap();
+        circ.gug.connect(angle, 0, rz, 0).unwrap();
+        circ.gug.connect(input, 0, rz, 1).unwrap();
+        let output = circ.gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Output),
+            Signature::new_nonlinear(vec![WireType::Qubit], vec![]),
+        );
+        circ.gug.connect(rz, 0, output, 0).unwrap();
+        let mut gug = circ.gug;
+        let qubit_port = gug.input_port(rz, 1).unwrap();
+        gug.set_physical_qubit(qubit_port, 3);
+
+        gug.canonicalize_ports();
+
+        let port_types: Vec<_> = (0..2)
+            .map(|i| gug.port_type(gug.input_port(rz, i).unwrap()))
+            .collect();
+        assert_eq!(port_types, vec![WireType::Qubit, WireType::Angle]);
+        assert_eq!(gug.input_source(rz, 0), Some((input, 0)));
+        assert_eq!(gug.input_source(rz, 1), Some((angle, 0)));
+        assert_eq!(gug.output_target(rz, 0), Some((output, 0)));
+        let qubit_port = gug.input_port(rz, 0).unwrap();
+        assert_eq!(gug.physical_qubit(qubit_port), Some(3));
+        assert_eq!(gug.physical_qubit(gug.input_port(rz, 1).unwrap()), None);
+    }
+
+    #[test]
+    fn compact_after_removals() {
+        let mut circ = TestCircuit::qubits(2);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::X, &[1]);
+        let cx = circ.gate(circuit::Op::CX, &[0, 1]);
+        let z = circ.gate(circuit::Op::Z, &[1]);
+        let mut gug = circ.finish();
+        gug.register_node_metadata::<DebugData>();
+        *gug.node_metadata_mut::<DebugData>(cx).unwrap() = DebugData::with_label("cx");
+        gug.remove_and_reconnect(h);
+        gug.remove_and_reconnect(z);
+
+        let map = gug.compact();
+        assert_eq!(map.len(), gug.node_count());
+        let indices: Vec<usize> = gug.toposort().iter().map(|n| n.index()).collect();
+        assert_eq!(indices, (0..gug.node_count()).collect::<Vec<_>>());
+        let cx = map[&cx];
+        assert_eq!(gug.optype(cx), &Op::Circuit(circuit::Op::CX));
+        let label = gug.node_metadata::<DebugData>(cx).unwrap().label.clone();
+        assert_eq!(label.as_deref(), Some("cx"));
+        assert_eq!(gug.depth(), 2);
+    }
+}
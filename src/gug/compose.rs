@@ -0,0 +1,292 @@
+//! Sequential and parallel composition of circuits.
+
+use std::collections::HashMap;
+
+use portgraph::{Direction, NodeIndex, PortIndex};
+use thiserror::Error;
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::{ConstValue, Signature, WireType};
+
+use super::{BoundaryError, Gug};
+
+/// Error returned when two circuits cannot be composed.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ComposeError {
+    #[error(transparent)]
+    Boundary(#[from] BoundaryError),
+    #[error("the outputs {outputs:?} do not match the inputs {inputs:?}")]
+    SignatureMismatch {
+        outputs: Vec<WireType>,
+        inputs: Vec<WireType>,
+    },
+}
+
+/// Error returned when a circuit cannot be inverted.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum InverseError {
+    #[error(transparent)]
+    Boundary(#[from] BoundaryError),
+    #[error("node {0:?} has no inverse")]
+    NotInvertible(NodeIndex),
+}
+
+impl Gug {
+    /// Composes `other` after this circuit, feeding the wires of this
+    /// circuit's `Output` node into the wires of `other`'s `Input` node.
+    ///
+    /// The two boundary nodes are removed and replaced by direct links, and
+    /// `other`'s `Output` node becomes the output of the combined circuit. The
+    /// boundaries must have the same number and types of wires.
+    pub fn append(&mut self, other: &Gug) -> Result<(), ComposeError> {
+        let (_, output) = self.boundary()?;
+        let (other_input, other_output) = other.boundary()?;
+        let outputs: Vec<WireType> = self
+            .node_ports(output, Direction::Incoming)
+            .map(|p| self.port_type(p))
+            .collect();
+        let inputs: Vec<WireType> = other
+            .node_ports(other_input, Direction::Outgoing)
+            .map(|p| other.port_type(p))
+            .collect();
+        if outputs != inputs {
+            return Err(ComposeError::SignatureMismatch { outputs, inputs });
+        }
+
+        let ports: Vec<PortIndex> = self.node_ports(output, Direction::Incoming).collect();
+        let sources: Vec<Option<PortIndex>> =
+            ports.into_iter().map(|p| self.unlink_port(p)).collect();
+        self.remove_node(output);
+
+        let (_, ends) = self.splice(other, &sources);
+        let new_output = self.copy_node_from(other, other_output);
+        for (offset, end) in ends.into_iter().enumerate() {
+            if let (Some(end), Some(port)) = (end, self.input_port(new_output, offset)) {
+                self.link_ports(end, port).unwrap();
+            }
+        }
+        self.add_global_phase(other.global_phase());
+        Ok(())
+    }
+
+    /// Places two circuits side by side, with the wires of `self` before
+    /// those of `other` on the new `Input` and `Output` nodes.
+    pub fn tensor(&self, other: &Gug) -> Gug {
+        let boundary = |gug: &Gug| {
+            let (input, output) = gug.boundary().ok().unzip();
+            let types = |node, direction| -> Vec<WireType> {
+                node.into_iter()
+                    .flat_map(|n| gug.node_ports(n, direction))
+                    .map(|p| gug.port_type(p))
+                    .collect()
+            };
+            (
+                Signature::new_nonlinear(vec![], types(input, Direction::Outgoing)),
+                Signature::new_nonlinear(types(output, Direction::Incoming), vec![]),
+            )
+        };
+        let (self_input, self_output) = boundary(self);
+        let (other_input, other_output) = boundary(other);
+
+        let mut result = Gug::new();
+        let input = result.add_node_with_signature(
+            Op::Circuit(circuit::Op::Input),
+            self_input.tensor(&other_input),
+        );
+        let sources: Vec<Option<PortIndex>> = result
+            .node_ports(input, Direction::Outgoing)
+            .map(Some)
+            .collect();
+        let split = self_input.nonlinear[1].len();
+        let (_, mut ends) = result.splice(self, &sources[..split]);
+        let (_, other_ends) = result.splice(other, &sources[split..]);
+        ends.extend(other_ends);
+
+        let output = result.add_node_with_signature(
+            Op::Circuit(circuit::Op::Output),
+            self_output.tensor(&other_output),
+        );
+        for (offset, end) in ends.into_iter().enumerate() {
+            if let (Some(end), Some(port)) = (end, result.input_port(output, offset)) {
+                result.link_ports(end, port).unwrap();
+            }
+        }
+        result.add_global_phase(self.global_phase() + other.global_phase());
+        result
+    }
+
+    /// Returns the inverse of the circuit, applying the inverse of each gate
+    /// in reverse order.
+    ///
+    /// The `Input` and `Output` nodes swap roles. Gates must have a
+    /// parameterless inverse, or be `RzF64`/`RxF64` rotations with an angle
+    /// fed by a `Const` node, which are inverted by negating the angle.
+    /// Other `Const` nodes are dropped.
+    pub fn inverse(&self) -> Result<Gug, InverseError> {
+        let (input, output) = self.boundary()?;
+        let types = |node, direction| -> Vec<WireType> {
+            self.node_ports(node, direction).map(|p| self.port_type(p)).collect()
+        };
+
+        let mut inverse = Gug::new();
+        let mut map = HashMap::new();
+        for node in self.nodes() {
+            let new = if node == input {
+                inverse.add_node_with_signature(
+                    Op::Circuit(circuit::Op::Output),
+                    Signature::new_nonlinear(types(node, Direction::Outgoing), vec![]),
+                )
+            } else if node == output {
+                inverse.add_node_with_signature(
+                    Op::Circuit(circuit::Op::Input),
+                    Signature::new_nonlinear(vec![], types(node, Direction::Incoming)),
+                )
+            } else {
+                match self.optype(node) {
+                    Op::Circuit(circuit::Op::Const(_)) => continue,
+                    Op::Circuit(op @ (circuit::Op::RzF64 | circuit::Op::RxF64)) => {
+                        let angle = self
+                            .input_source(node, 1)
+                            .and_then(|(source, _)| match self.optype(source) {
+                                Op::Circuit(circuit::Op::Const(ConstValue::Angle(a))) => Some(*a),
+                                _ => None,
+                            })
+                            .ok_or(InverseError::NotInvertible(node))?;
+                        let gate = inverse.add_node(Op::Circuit(op.clone()));
+                        let negated = ConstValue::Angle(-angle);
+                        let constant = inverse.add_node(Op::Circuit(circuit::Op::Const(negated)));
+                        inverse.connect(constant, 0, gate, 1).unwrap();
+                        gate
+                    }
+                    Op::Circuit(op) => {
+                        let op = op.inverse().ok_or(InverseError::NotInvertible(node))?;
+                        inverse.add_node(Op::Circuit(op))
+                    }
+                    _ => return Err(InverseError::NotInvertible(node)),
+                }
+            };
+            map.insert(node, new);
+        }
+
+        for (&node, &new) in &map {
+            for offset in 0..self.node_ports(node, Direction::Outgoing).count() {
+                let Some((target, target_offset)) = self.output_target(node, offset) else {
+                    continue;
+                };
+                if let Some(&new_target) = map.get(&target) {
+                    inverse.connect(new_target, target_offset, new, offset).unwrap();
+                }
+            }
+        }
+        inverse.add_global_phase(-self.global_phase());
+        Ok(inverse)
+    }
+
+    /// Returns the circuit followed by its inverse, which implements the
+    /// identity on its unitary part. See [`Gug::inverse`] for the supported
+    /// operations.
+    pub fn mirror(&self) -> Result<Gug, InverseError> {
+        let mut mirrored = self.clone();
+        mirrored
+            .append(&self.inverse()?)
+            .expect("the inverse takes the outputs of the circuit as inputs");
+        Ok(mirrored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn append_in_sequence() {
+        let mut first = TestCircuit::qubits(1);
+        first.gate(circuit::Op::H, &[0]);
+        let mut gug = first.finish();
+        let mut second = TestCircuit::qubits(1);
+        second.gate(circuit::Op::X, &[0]);
+        let second = second.finish();
+
+        gug.append(&second).unwrap();
+        assert_eq!(gug.validate(), Ok(()));
+        assert_eq!(gug.node_count(), 4);
+        let input = gug.input_node().unwrap();
+        let (h, _) = gug.output_target(input, 0).unwrap();
+        assert_eq!(gug.optype(h), &Op::Circuit(circuit::Op::H));
+        let (x, _) = gug.output_target(h, 0).unwrap();
+        assert_eq!(gug.optype(x), &Op::Circuit(circuit::Op::X));
+        let (output, _) = gug.output_target(x, 0).unwrap();
+        assert_eq!(Some(output), gug.output_node());
+    }
+
+    #[test]
+    fn append_mismatch() {
+        let mut gug = TestCircuit::qubits(1).finish();
+        let other = TestCircuit::qubits(2).finish();
+        assert_eq!(
+            gug.append(&other),
+            Err(ComposeError::SignatureMismatch {
+                outputs: vec![WireType::Qubit],
+                inputs: vec![WireType::Qubit; 2],
+            })
+        );
+    }
+
+    #[test]
+    fn tensor_side_by_side() {
+        let mut first = TestCircuit::qubits(1);
+        first.gate(circuit::Op::H, &[0]);
+        let mut second = TestCircuit::qubits(1);
+        second.gate(circuit::Op::X, &[0]);
+
+        let gug = first.finish().tensor(&second.finish());
+        assert_eq!(gug.validate(), Ok(()));
+        let input = gug.input_node().unwrap();
+        let output = gug.output_node().unwrap();
+        assert_eq!(gug.node_ports(input, Direction::Outgoing).count(), 2);
+        assert_eq!(gug.node_ports(output, Direction::Incoming).count(), 2);
+        let (h, _) = gug.output_target(input, 0).unwrap();
+        let (x, _) = gug.output_target(input, 1).unwrap();
+        assert_eq!(gug.optype(h), &Op::Circuit(circuit::Op::H));
+        assert_eq!(gug.optype(x), &Op::Circuit(circuit::Op::X));
+        assert_eq!(gug.output_target(x, 0), Some((output, 1)));
+    }
+
+    #[test]
+    fn mirror_is_identity() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::H, &[0]);
+        let angle = circ.constant(ConstValue::f64_angle(0.25));
+        circ.gate_with_args(circuit::Op::RzF64, &[0], &[(angle, 0)]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        let gug = circ.finish();
+
+        let mirrored = gug.mirror().unwrap();
+        assert_eq!(mirrored.validate(), Ok(()));
+        let counts = mirrored.gate_counts();
+        assert_eq!(counts["H"], 2);
+        assert_eq!(counts["CX"], 2);
+        assert_eq!(counts["RzF64"], 2);
+
+        #[cfg(feature = "matrix")]
+        {
+            let unitary = crate::test_utils::circuit_unitary(&mirrored);
+            assert!(crate::component::operation::matrix::equal_up_to_phase(
+                &unitary,
+                &ndarray::Array2::eye(4),
+                1e-10
+            ));
+        }
+    }
+
+    #[test]
+    fn measure_not_invertible() {
+        let mut circ = TestCircuit::new(&[WireType::Qubit, WireType::LinearBit]);
+        let measure = circ.gate(circuit::Op::Measure, &[0, 1]);
+        assert_eq!(
+            circ.finish().mirror().map(|_| ()),
+            Err(InverseError::NotInvertible(measure))
+        );
+    }
+}
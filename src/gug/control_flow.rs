@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use portgraph::{Direction, NodeIndex, PortIndex};
+use thiserror::Error;
+
+use crate::component::operation::{ControlFlowOp, Op};
+use crate::component::wire_type::WireType;
+
+use super::Gug;
+
+/// Error returned when a loop cannot be unrolled.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum UnrollError {
+    #[error("node {0:?} is not a loop")]
+    NotALoop(NodeIndex),
+    #[error("loop {0:?} has no body with input and output nodes")]
+    MissingBody(NodeIndex),
+    #[error("the carried types of loop {0:?} do not match between iterations")]
+    CarriedTypeMismatch(NodeIndex),
+    #[error("loop {node:?} runs {expected} iterations, cannot unroll it {found} times")]
+    IterationMismatch {
+        node: NodeIndex,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Gug {
+    /// Replaces a `Loop` node with `times` sequential copies of its body,
+    /// feeding the outputs of each iteration into the inputs of the next.
+    ///
+    /// The body is read from the children of the loop node, and must start at
+    /// an `Input` node and end at an `Output` node carrying the same types as
+    /// the loop node itself. Regions nested in the body are copied into every
+    /// iteration. A loop with a static iteration count can only be unrolled
+    /// that many times.
+    pub fn unroll_loop(&mut self, node: NodeIndex, times: usize) -> Result<(), UnrollError> {
+        let Op::ControlFlow(ControlFlowOp::Loop { iterations }) = self.optype(node) else {
+            return Err(UnrollError::NotALoop(node));
+        };
+        if let Some(expected) = *iterations {
+            if expected != times {
+                return Err(UnrollError::IterationMismatch {
+                    node,
+                    expected,
+                    found: times,
+                });
+            }
+        }
+        let descendants = self.descendants(node);
+        let body = self.loop_body(node, &descendants);
+        let Ok((body_input, body_output)) = body.boundary() else {
+            return Err(UnrollError::MissingBody(node));
+        };
+
+        let port_types = |gug: &Gug, n: NodeIndex, direction| -> Vec<WireType> {
+            gug.node_ports(n, direction).map(|p| gug.port_type(p)).collect()
+        };
+        let carried = port_types(&body, body_input, Direction::Outgoing);
+        if carried != port_types(&body, body_output, Direction::Incoming)
+            || carried != port_types(self, node, Direction::Incoming)
+            || carried != port_types(self, node, Direction::Outgoing)
+        {
+            return Err(UnrollError::CarriedTypeMismatch(node));
+        }
+
+        // The loop is detached rather than removed, so that it is only removed
+        // once the unrolled body is in place.
+        let ports: Vec<PortIndex> = self.node_ports(node, Direction::Incoming).collect();
+        let mut sources: Vec<Option<PortIndex>> =
+            ports.into_iter().map(|p| self.unlink_port(p)).collect();
+        let ports: Vec<PortIndex> = self.node_ports(node, Direction::Outgoing).collect();
+        let targets: Vec<Option<PortIndex>> =
+            ports.into_iter().map(|p| self.unlink_port(p)).collect();
+        let parent = self.parent(node);
+
+        let mut inserted = Vec::new();
+        for _ in 0..times {
+            let (nodes, outputs) = self.splice(&body, &sources);
+            inserted.extend(nodes);
+            sources = outputs;
+        }
+        for (source, target) in sources.into_iter().zip(targets) {
+            if let (Some(source), Some(target)) = (source, target) {
+                self.link_ports(source, target).unwrap();
+            }
+        }
+        if let Some(parent) = parent {
+            for n in inserted {
+                if self.parent(n).is_none() {
+                    self.push_child(n, parent).unwrap();
+                }
+            }
+        }
+        for n in descendants {
+            self.remove_node(n);
+        }
+        self.remove_node(node);
+        Ok(())
+    }
+
+    /// Returns the nodes in the region of `node` and in the regions nested in
+    /// it, parents before their children.
+    fn descendants(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        let mut nodes: Vec<NodeIndex> = self.children(node).collect();
+        let mut i = 0;
+        while i < nodes.len() {
+            nodes.extend(self.children(nodes[i]));
+            i += 1;
+        }
+        nodes
+    }
+
+    /// Copies the descendants of `node` and the edges between them into a new
+    /// circuit, with the children of `node` at the top level and the nested
+    /// regions preserved.
+    fn loop_body(&self, node: NodeIndex, nodes: &[NodeIndex]) -> Gug {
+        let mut body = Gug::new();
+        let map: HashMap<NodeIndex, NodeIndex> = nodes
+            .iter()
+            .map(|&n| (n, body.copy_node_from(self, n)))
+            .collect();
+        for &n in nodes {
+            let parent = self.parent(n).filter(|&p| p != node);
+            if let Some(parent) = parent {
+                body.push_child(map[&n], map[&parent]).unwrap();
+            }
+        }
+        for (&old, &new) in &map {
+            let num_ports = self.node_ports(old, Direction::Outgoing).count();
+            for offset in 0..num_ports {
+                let Some((target, target_offset)) = self.output_target(old, offset) else {
+                    continue;
+                };
+                if let Some(&new_target) = map.get(&target) {
+                    let src = body.output_port(new, offset).unwrap();
+                    let dst = body.input_port(new_target, target_offset).unwrap();
+                    body.link_ports(src, dst).unwrap();
+                }
+            }
+        }
+        body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::operation::circuit;
+    use crate::component::wire_type::Signature;
+
+    #[test]
+    fn unroll_x_loop() {
+        let mut gug = Gug::new();
+        let qb = vec![WireType::Qubit];
+        let input = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Input),
+            Signature::new_nonlinear(vec![], qb.clone()),
+        );
+        let lp = gug.add_node_with_signature(
+            Op::ControlFlow(ControlFlowOp::Loop {
+                iterations: Some(3),
+            }),
+            Signature::new_nonlinear(qb.clone(), qb.clone()),
+        );
+        let output = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Output),
+            Signature::new_nonlinear(qb.clone(), vec![]),
+        );
+        gug.connect(input, 0, lp, 0).unwrap();
+        gug.connect(lp, 0, output, 0).unwrap();
+
+        let body_in = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Input),
+            Signature::new_nonlinear(vec![], qb.clone()),
+        );
+        let x = gug.add_node(Op::Circuit(circuit::Op::X));
+        let body_out = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Output),
+            Signature::new_nonlinear(qb, vec![]),
+        );
+        gug.connect(body_in, 0, x, 0).unwrap();
+        gug.connect(x, 0, body_out, 0).unwrap();
+        for n in [body_in, x, body_out] {
+            gug.push_child(n, lp).unwrap();
+        }
+
+        gug.unroll_loop(lp, 3).unwrap();
+
+        assert_eq!(gug.node_count(), 5);
+        let mut node = input;
+        for _ in 0..3 {
+            node = gug.output_target(node, 0).unwrap().0;
+            assert_eq!(gug.optype(node), &Op::Circuit(circuit::Op::X));
+        }
+        assert_eq!(gug.output_target(node, 0), Some((output, 0)));
+    }
+
+    #[test]
+    fn unroll_not_a_loop() {
+        let mut gug = Gug::new();
+        let x = gug.add_node(Op::Circuit(circuit::Op::X));
+        assert_eq!(gug.unroll_loop(x, 2), Err(UnrollError::NotALoop(x)));
+    }
+
+    /// Adds a region of `Input`, `X` and `Output` nodes on a qubit to `parent`.
+    fn x_region(gug: &mut Gug, parent: NodeIndex) {
+        let qb = vec![WireType::Qubit];
+        let input = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Input),
+            Signature::new_nonlinear(vec![], qb.clone()),
+        );
+        let x = gug.add_node(Op::Circuit(circuit::Op::X));
+        let output = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Output),
+            Signature::new_nonlinear(qb, vec![]),
+        );
+        gug.connect(input, 0, x, 0).unwrap();
+        gug.connect(x, 0, output, 0).unwrap();
+        for n in [input, x, output] {
+            gug.push_child(n, parent).unwrap();
+        }
+    }
+
+    #[test]
+    fn unroll_nested_regions() {
+        let mut gug = Gug::with_boundary(vec![WireType::Qubit], vec![WireType::Qubit]);
+        let (input, output) = gug.boundary().unwrap();
+        let qb = Signature::new_nonlinear(vec![WireType::Qubit], vec![WireType::Qubit]);
+        let lp = gug.add_node_with_signature(
+            Op::ControlFlow(ControlFlowOp::Loop {
+                iterations: Some(2),
+            }),
+            qb.clone(),
+        );
+        gug.connect(input, 0, lp, 0).unwrap();
+        gug.connect(lp, 0, output, 0).unwrap();
+
+        // The body applies a conditional region holding an X gate.
+        let body_in = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Input),
+            Signature::new_nonlinear(vec![], vec![WireType::Qubit]),
+        );
+        let cond = gug.add_node_with_signature(Op::ControlFlow(ControlFlowOp::Conditional), qb);
+        let body_out = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Output),
+            Signature::new_nonlinear(vec![WireType::Qubit], vec![]),
+        );
+        gug.connect(body_in, 0, cond, 0).unwrap();
+        gug.connect(cond, 0, body_out, 0).unwrap();
+        for n in [body_in, cond, body_out] {
+            gug.push_child(n, lp).unwrap();
+        }
+        x_region(&mut gug, cond);
+
+        assert_eq!(
+            gug.unroll_loop(lp, 3),
+            Err(UnrollError::IterationMismatch {
+                node: lp,
+                expected: 2,
+                found: 3
+            })
+        );
+        gug.unroll_loop(lp, 2).unwrap();
+
+        assert_eq!(gug.node_count(), 2 + 2 * 4);
+        let mut node = input;
+        for _ in 0..2 {
+            node = gug.output_target(node, 0).unwrap().0;
+            assert_eq!(gug.optype(node).name(), "Conditional");
+            assert_eq!(gug.parent(node), None);
+            let children: Vec<&str> = gug.children(node).map(|n| gug.optype(n).name()).collect();
+            assert_eq!(children, ["Input", "X", "Output"]);
+        }
+        assert_eq!(gug.output_target(node, 0), Some((output, 0)));
+        assert_eq!(gug.boundary(), Ok((input, output)));
+    }
+}
@@ -0,0 +1,105 @@
+//! Human-readable JSON dumps of graphs, for inspection in external tools.
+
+use portgraph::{Direction, NodeIndex};
+use serde_json::{json, Value};
+
+use crate::component::wire_type::WireType;
+
+use super::Gug;
+
+impl Gug {
+    /// Dumps the graph as a JSON value meant for inspecting and diffing in
+    /// external tools. It cannot be read back, use [`Gug::to_text`] for a
+    /// round-trippable serialization.
+    ///
+    /// The value holds the global phase and a list of nodes, each with its
+    /// index, operation name, signature, hierarchy parent, ports with their
+    /// types and links, and the `Debug` rendering of every registered node
+    /// metadata entry, sorted.
+    pub fn debug_json(&self) -> Value {
+        let nodes: Vec<Value> = self.nodes().map(|node| self.node_json(node)).collect();
+        json!({
+            "global_phase": self.global_phase.to_f64(),
+            "nodes": nodes,
+        })
+    }
+
+    fn node_json(&self, node: NodeIndex) -> Value {
+        let ports = |direction| -> Vec<Value> {
+            self.node_ports(node, direction)
+                .map(|port| {
+                    let link = self.port_link(port).and_then(|link| {
+                        Some(json!({
+                            "node": self.port_node(link)?.index(),
+                            "offset": self.port_offset(link)?,
+                        }))
+                    });
+                    json!({
+                        "type": format!("{:?}", self.port_type(port)),
+                        "link": link,
+                    })
+                })
+                .collect()
+        };
+        let signature = self.signature(node);
+        let mut metadata: Vec<String> = self
+            .node_metadata
+            .values()
+            .map(|meta| format!("{:?}", meta[node]))
+            .collect();
+        metadata.sort();
+
+        json!({
+            "index": node.index(),
+            "op": self.optype(node).name(),
+            "signature": {
+                "linear": type_names(&signature.linear),
+                "inputs": type_names(&signature.nonlinear[0]),
+                "outputs": type_names(&signature.nonlinear[1]),
+            },
+            "parent": self.parent(node).map(|parent| parent.index()),
+            "inputs": ports(Direction::Incoming),
+            "outputs": ports(Direction::Outgoing),
+            "metadata": metadata,
+        })
+    }
+}
+
+fn type_names(types: &[WireType]) -> Vec<String> {
+    types.iter().map(|t| format!("{t:?}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::component::operation::circuit;
+    use crate::test_utils::TestCircuit;
+    use crate::DebugData;
+
+    #[test]
+    fn dump_circuit() {
+        let mut circ = TestCircuit::qubits(2);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let cx = circ.gate(circuit::Op::CX, &[0, 1]);
+        let mut gug = circ.finish();
+        gug.register_node_metadata::<DebugData>();
+        *gug.node_metadata_mut::<DebugData>(cx).unwrap() = DebugData::with_label("entangle");
+
+        let dump = gug.debug_json();
+        let nodes = dump["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 4);
+        let node = |index: usize| nodes.iter().find(|n| n["index"] == index).unwrap();
+
+        let h = node(h.index());
+        assert_eq!(h["op"], "H");
+        assert_eq!(h["signature"]["linear"], serde_json::json!(["Qubit"]));
+        assert_eq!(h["outputs"][0]["type"], "Qubit");
+        assert_eq!(h["outputs"][0]["link"]["node"], cx.index());
+        assert_eq!(h["parent"], serde_json::Value::Null);
+
+        let cx = node(cx.index());
+        assert_eq!(cx["op"], "CX");
+        assert_eq!(cx["inputs"].as_array().unwrap().len(), 2);
+        let metadata = cx["metadata"][0].as_str().unwrap();
+        assert!(metadata.contains("entangle"));
+    }
+}
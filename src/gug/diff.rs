@@ -0,0 +1,212 @@
+//! Structural differences between two graphs.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use portgraph::{Direction, NodeIndex};
+
+use crate::component::operation::Op;
+
+use super::Gug;
+
+/// An edge between an output port of one node and an input port of another,
+/// identified by node indices and port offsets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DiffEdge {
+    pub source: NodeIndex,
+    pub source_offset: usize,
+    pub target: NodeIndex,
+    pub target_offset: usize,
+}
+
+/// The differences between two graphs sharing a common node index layout,
+/// as computed by [`Gug::diff`].
+#[derive(Clone, Debug, Default)]
+pub struct GugDiff {
+    /// Nodes only present in the new graph.
+    pub added_nodes: Vec<(NodeIndex, Op)>,
+    /// Nodes only present in the old graph.
+    pub removed_nodes: Vec<(NodeIndex, Op)>,
+    /// Nodes present in both graphs with a different operation, as `(node, old, new)`.
+    pub changed_ops: Vec<(NodeIndex, Op, Op)>,
+    /// Edges only present in the new graph.
+    pub added_edges: Vec<DiffEdge>,
+    /// Edges only present in the old graph.
+    pub removed_edges: Vec<DiffEdge>,
+}
+
+impl GugDiff {
+    /// Returns `true` if the graphs were identical.
+    pub fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.changed_ops.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+    }
+}
+
+impl Gug {
+    /// Computes the differences from `self` to `other`.
+    ///
+    /// Nodes are matched by index, so this is only meaningful when `other`
+    /// was derived from `self` (or both from a common ancestor), for example
+    /// by running a pass on a clone.
+    pub fn diff(&self, other: &Gug) -> GugDiff {
+        let old_nodes: HashSet<NodeIndex> = self.nodes().collect();
+        let new_nodes: HashSet<NodeIndex> = other.nodes().collect();
+        let mut diff = GugDiff::default();
+
+        for node in self.nodes() {
+            if !new_nodes.contains(&node) {
+                diff.removed_nodes.push((node, self.optype(node).clone()));
+            } else if self.optype(node) != other.optype(node) {
+                diff.changed_ops.push((
+                    node,
+                    self.optype(node).clone(),
+                    other.optype(node).clone(),
+                ));
+            }
+        }
+        for node in other.nodes() {
+            if !old_nodes.contains(&node) {
+                diff.added_nodes.push((node, other.optype(node).clone()));
+            }
+        }
+
+        let old_edges = self.diff_edges();
+        let new_edges = other.diff_edges();
+        diff.removed_edges = old_edges.difference(&new_edges).copied().collect();
+        diff.added_edges = new_edges.difference(&old_edges).copied().collect();
+        diff.removed_edges.sort_by_key(edge_key);
+        diff.added_edges.sort_by_key(edge_key);
+
+        diff
+    }
+
+    /// Checks whether two graphs have the same nodes, operations, port types
+    /// and links, ignoring any node or port metadata.
+    ///
+    /// Like [`Gug::diff`], nodes are matched by index. This is weaker than a
+    /// graph isomorphism check: equivalent graphs built in a different order
+    /// compare as different.
+    pub fn semantically_eq(&self, other: &Gug) -> bool {
+        let same_nodes = self.nodes().eq(other.nodes())
+            && self.nodes().all(|node| {
+                self.optype(node) == other.optype(node)
+                    && [Direction::Incoming, Direction::Outgoing]
+                        .into_iter()
+                        .all(|direction| {
+                            self.node_ports(node, direction)
+                                .map(|p| self.port_type(p))
+                                .eq(other.node_ports(node, direction).map(|p| other.port_type(p)))
+                        })
+            });
+        same_nodes && self.diff_edges() == other.diff_edges()
+    }
+
+    fn diff_edges(&self) -> HashSet<DiffEdge> {
+        let mut edges = HashSet::new();
+        for source in self.nodes() {
+            for (source_offset, port) in self.node_ports(source, Direction::Outgoing).enumerate() {
+                let Some(link) = self.port_link(port) else { continue };
+                edges.insert(DiffEdge {
+                    source,
+                    source_offset,
+                    target: self.port_node(link).unwrap(),
+                    target_offset: self.port_offset(link).unwrap(),
+                });
+            }
+        }
+        edges
+    }
+}
+
+fn edge_key(edge: &DiffEdge) -> (usize, usize, usize, usize) {
+    (
+        edge.source.index(),
+        edge.source_offset,
+        edge.target.index(),
+        edge.target_offset,
+    )
+}
+
+impl fmt::Display for DiffEdge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{} -> {}:{}",
+            self.source.index(),
+            self.source_offset,
+            self.target.index(),
+            self.target_offset
+        )
+    }
+}
+
+impl fmt::Display for GugDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (node, op) in &self.removed_nodes {
+            writeln!(f, "- node {}: {}", node.index(), op.name())?;
+        }
+        for (node, op) in &self.added_nodes {
+            writeln!(f, "+ node {}: {}", node.index(), op.name())?;
+        }
+        for (node, old, new) in &self.changed_ops {
+            writeln!(f, "~ node {}: {} -> {}", node.index(), old.name(), new.name())?;
+        }
+        for edge in &self.removed_edges {
+            writeln!(f, "- edge {edge}")?;
+        }
+        for edge in &self.added_edges {
+            writeln!(f, "+ edge {edge}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::operation::circuit;
+    use crate::test_utils::TestCircuit;
+    use crate::DebugData;
+
+    #[test]
+    fn diff_removed_gate() {
+        let mut circ = TestCircuit::qubits(1);
+        circ.gate(circuit::Op::H, &[0]);
+        let x = circ.gate(circuit::Op::X, &[0]);
+        let before = circ.finish();
+
+        let mut after = before.clone();
+        after.remove_node(x);
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.removed_nodes.len(), 1);
+        assert_eq!(diff.removed_nodes[0].0, x);
+        assert!(diff.added_nodes.is_empty());
+        assert!(diff.changed_ops.is_empty());
+        assert_eq!(diff.removed_edges.len(), 2);
+        assert!(diff.added_edges.is_empty());
+
+        let listing = diff.to_string();
+        assert!(listing.contains(&format!("- node {}: X", x.index())));
+        assert!(before.diff(&before).is_empty());
+    }
+
+    #[test]
+    fn semantic_eq_ignores_metadata() {
+        let mut circ = TestCircuit::qubits(1);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let mut a = circ.finish();
+        a.register_node_metadata::<DebugData>();
+        let mut b = a.clone();
+        *a.node_metadata_mut::<DebugData>(h).unwrap() = DebugData::with_label("first");
+        *b.node_metadata_mut::<DebugData>(h).unwrap() = DebugData::with_label("second");
+        assert!(a.semantically_eq(&b));
+
+        b.set_optype(h, Op::Circuit(circuit::Op::X));
+        assert!(!a.semantically_eq(&b));
+    }
+}
@@ -0,0 +1,45 @@
+//! Content hashes of circuits, stable across runs and platforms.
+
+use sha2::{Digest, Sha256};
+
+use super::Gug;
+
+impl Gug {
+    /// Computes a SHA-256 hash of the textual serialization of the graph.
+    ///
+    /// The hash only depends on the content written by [`Gug::to_text`], so
+    /// it is stable across process runs and architectures and can be used to
+    /// key persistent caches. Like the serialization, it depends on the node
+    /// index layout, see [`Gug::compact`]. Unlike it, the hash never fails:
+    /// metadata is left out, and opaque operations are hashed by name.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        Sha256::digest(self.write_text().as_bytes()).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::component::operation::{circuit, Op};
+    use crate::component::wire_type::AngleValue;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn equal_circuits_match() {
+        let build = || {
+            let mut circ = TestCircuit::qubits(2);
+            circ.gate(circuit::Op::H, &[0]);
+            let cx = circ.gate(circuit::Op::CX, &[0, 1]);
+            (circ.finish(), cx)
+        };
+        let (a, _) = build();
+        let (mut b, cx) = build();
+        assert_eq!(a.fingerprint(), b.fingerprint());
+
+        b.set_optype(cx, Op::Circuit(circuit::Op::CZ));
+        assert_ne!(a.fingerprint(), b.fingerprint());
+
+        let (mut c, _) = build();
+        c.add_global_phase(AngleValue::F64(0.5));
+        assert_ne!(a.fingerprint(), c.fingerprint());
+    }
+}
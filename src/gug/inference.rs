@@ -0,0 +1,236 @@
+use portgraph::{Direction, NodeIndex, PortIndex};
+use thiserror::Error;
+
+use crate::component::operation::Op;
+use crate::component::wire_type::WireType;
+
+use super::Gug;
+
+/// Error returned when port types cannot be inferred consistently.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum InferError {
+    #[error("port {port:?} has type {found:?}, but its operation expects {expected:?}")]
+    SignatureMismatch {
+        port: PortIndex,
+        expected: WireType,
+        found: WireType,
+    },
+    #[error("linked ports {0:?} and {1:?} have conflicting types")]
+    Conflict(PortIndex, PortIndex),
+}
+
+/// Error returned when a wire cannot be given a new type.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RetypeError {
+    #[error("port {port:?} of node {node:?} does not accept type {new_type:?}")]
+    Incompatible {
+        node: NodeIndex,
+        port: PortIndex,
+        new_type: WireType,
+    },
+}
+
+impl Gug {
+    /// Fills in the types of ports that were never set.
+    ///
+    /// Ports covered by their operation's signature take the type from it,
+    /// and ports that already have a type must match it. The remaining
+    /// untyped ports, such as those of boundary nodes, take the type of the
+    /// port they are linked to. Linked ports with different types are
+    /// reported as a conflict.
+    pub fn infer_port_types(&mut self) -> Result<(), InferError> {
+        let nodes: Vec<_> = self.nodes().collect();
+        for &node in &nodes {
+            let optype = self.optype(node);
+            if optype.is_boundary() {
+                continue;
+            }
+            let signature = optype.signature();
+            let inputs: Vec<WireType> = signature.inputs().copied().collect();
+            let outputs: Vec<WireType> = signature.outputs().copied().collect();
+            for (direction, expected) in [
+                (Direction::Incoming, inputs),
+                (Direction::Outgoing, outputs),
+            ] {
+                let node_ports: Vec<_> = self.node_ports(node, direction).collect();
+                for (port, expected) in node_ports.into_iter().zip(expected) {
+                    match self.port_types[port] {
+                        None => self.port_types[port] = Some(expected),
+                        Some(found) if found != expected => {
+                            return Err(InferError::SignatureMismatch {
+                                port,
+                                expected,
+                                found,
+                            });
+                        }
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+
+        for node in nodes {
+            let outputs: Vec<_> = self.node_ports(node, Direction::Outgoing).collect();
+            for source in outputs {
+                let Some(target) = self.port_link(source) else { continue };
+                match (self.port_types[source], self.port_types[target]) {
+                    (Some(typ), None) => self.port_types[target] = Some(typ),
+                    (None, Some(typ)) => self.port_types[source] = Some(typ),
+                    (Some(a), Some(b)) if a != b => {
+                        return Err(InferError::Conflict(source, target));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Changes the type of the wire starting at `start` to `new_type`.
+    ///
+    /// The wire is followed downstream through its links and through the
+    /// linear ports of the operations it passes, and every port along it is
+    /// retyped. Each of those operations must accept `new_type` on the
+    /// corresponding port according to its signature; boundary and control
+    /// flow nodes accept any type. Nothing is changed if the check fails.
+    pub fn retype_wire(&mut self, start: PortIndex, new_type: WireType) -> Result<(), RetypeError> {
+        let mut ports = Vec::new();
+        let mut next = Some(start);
+        while let Some(port) = next {
+            let node = self.port_node(port).unwrap();
+            let offset = self.port_offset(port).unwrap();
+            let incoming = self.input_port(node, offset) == Some(port);
+            let op = self.optype(node);
+            let signature = op.signature();
+            if !op.is_boundary() && !matches!(op, Op::ControlFlow(_)) {
+                let expected = if incoming {
+                    signature.inputs().nth(offset)
+                } else {
+                    signature.outputs().nth(offset)
+                };
+                if expected != Some(&new_type) {
+                    return Err(RetypeError::Incompatible {
+                        node,
+                        port,
+                        new_type,
+                    });
+                }
+            }
+            ports.push(port);
+            next = if !incoming {
+                self.port_link(port)
+            } else if !op.is_boundary() && offset < signature.linear.len() {
+                self.output_port(node, offset)
+            } else {
+                None
+            };
+        }
+        for port in ports {
+            self.port_types[port] = new_type;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::operation::circuit;
+    use crate::component::wire_type::Signature;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn infer_unset_types() {
+        let mut gug = Gug::new();
+        let input = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Input),
+            Signature::new_nonlinear(vec![], vec![]),
+        );
+        gug.resize_ports(input, 0, 2);
+        let rz = gug.add_node(Op::Circuit(circuit::Op::RzF64));
+        gug.connect(input, 0, rz, 0).unwrap();
+        gug.connect(input, 1, rz, 1).unwrap();
+        let output = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Output),
+            Signature::new_nonlinear(vec![], vec![]),
+        );
+        gug.resize_ports(output, 1, 0);
+        gug.connect(rz, 0, output, 0).unwrap();
+
+        // Forget the type of the angle input.
+        let angle = gug.input_port(rz, 1).unwrap();
+        gug.port_types[angle] = None;
+
+        gug.infer_port_types().unwrap();
+        assert_eq!(gug.port_type(angle), WireType::Angle);
+        let input_angle = gug.output_port(input, 1).unwrap();
+        assert_eq!(gug.port_type(input_angle), WireType::Angle);
+        let input_qubit = gug.output_port(input, 0).unwrap();
+        assert_eq!(gug.port_type(input_qubit), WireType::Qubit);
+    }
+
+    #[test]
+    fn infer_conflict() {
+        let mut gug = Gug::new();
+        let angle = gug.add_node(Op::Circuit(circuit::Op::AngleNeg));
+        let xor = gug.add_node(Op::Circuit(circuit::Op::Xor));
+        gug.connect(angle, 0, xor, 0).unwrap();
+        let source = gug.output_port(angle, 0).unwrap();
+        let target = gug.input_port(xor, 0).unwrap();
+        assert_eq!(
+            gug.infer_port_types(),
+            Err(InferError::Conflict(source, target))
+        );
+    }
+
+    #[test]
+    fn infer_conflict_on_qubit() {
+        // An explicit `Qubit` type is not mistaken for a missing one.
+        let mut gug = Gug::new();
+        let input = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Input),
+            Signature::new_nonlinear(vec![], vec![WireType::Qubit]),
+        );
+        let neg = gug.add_node(Op::Circuit(circuit::Op::AngleNeg));
+        gug.connect(input, 0, neg, 0).unwrap();
+        assert_eq!(
+            gug.infer_port_types(),
+            Err(InferError::Conflict(
+                gug.output_port(input, 0).unwrap(),
+                gug.input_port(neg, 0).unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn retype_classical_wire() {
+        let mut gug = TestCircuit::new(&[WireType::Bool]).finish();
+        let input = gug.input_node().unwrap();
+        let output = gug.output_node().unwrap();
+        let start = gug.output_port(input, 0).unwrap();
+
+        gug.retype_wire(start, WireType::LinearBit).unwrap();
+        assert_eq!(gug.port_type(start), WireType::LinearBit);
+        let end = gug.input_port(output, 0).unwrap();
+        assert_eq!(gug.port_type(end), WireType::LinearBit);
+        assert_eq!(gug.validate(), Ok(()));
+    }
+
+    #[test]
+    fn retype_forbidden_by_signature() {
+        let mut circ = TestCircuit::new(&[WireType::Bool]);
+        let not = circ.gate(circuit::Op::Not, &[0]);
+        let mut gug = circ.finish();
+        let start = gug.output_port(gug.input_node().unwrap(), 0).unwrap();
+
+        assert_eq!(
+            gug.retype_wire(start, WireType::LinearBit),
+            Err(RetypeError::Incompatible {
+                node: not,
+                port: gug.input_port(not, 0).unwrap(),
+                new_type: WireType::LinearBit,
+            })
+        );
+        assert_eq!(gug.port_type(start), WireType::Bool);
+    }
+}
@@ -0,0 +1,152 @@
+use portgraph::{Direction, NodeIndex, PortIndex};
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::WireType;
+
+use super::Gug;
+
+impl Gug {
+    /// Measures every qubit wire that is not already terminally measured,
+    /// inserting a `Measure` just before the `Output` node.
+    ///
+    /// A new classical bit is allocated for each measurement, as an extra
+    /// `LinearBit` wire from the `Input` node to the `Output` node.
+    pub fn measure_all(&mut self) {
+        let Ok((input, output)) = self.boundary() else {
+            return;
+        };
+        let live: Vec<usize> = self
+            .node_ports(output, Direction::Incoming)
+            .enumerate()
+            .filter(|&(offset, port)| {
+                self.port_type(port) == WireType::Qubit && !self.is_measured_output(output, offset)
+            })
+            .map(|(offset, _)| offset)
+            .collect();
+        if live.is_empty() {
+            return;
+        }
+
+        let first_input_bit = self.node_ports(input, Direction::Outgoing).count();
+        let first_output_bit = self.node_ports(output, Direction::Incoming).count();
+        self.resize_ports(input, 0, first_input_bit + live.len());
+        self.resize_ports(output, first_output_bit + live.len(), 0);
+
+        for (i, offset) in live.into_iter().enumerate() {
+            let input_bit = self.output_port(input, first_input_bit + i).unwrap();
+            let output_bit = self.input_port(output, first_output_bit + i).unwrap();
+            self.port_types[input_bit] = Some(WireType::LinearBit);
+            self.port_types[output_bit] = Some(WireType::LinearBit);
+
+            let (source, source_offset) = self.input_source(output, offset).unwrap();
+            let qubit = self.input_port(output, offset).unwrap();
+            self.unlink_port(qubit);
+            let measure = self.add_node(Op::Circuit(circuit::Op::Measure));
+            self.connect(source, source_offset, measure, 0).unwrap();
+            self.connect(input, first_input_bit + i, measure, 1).unwrap();
+            self.connect(measure, 0, output, offset).unwrap();
+            self.connect(measure, 1, output, first_output_bit + i).unwrap();
+        }
+    }
+
+    /// Returns `true` if every qubit wire reaching the `Output` node comes
+    /// directly from a `Measure`.
+    pub fn is_terminally_measured(&self) -> bool {
+        let Ok((_, output)) = self.boundary() else {
+            return true;
+        };
+        self.node_ports(output, Direction::Incoming)
+            .enumerate()
+            .filter(|&(_, port)| self.port_type(port) == WireType::Qubit)
+            .all(|(offset, _)| self.is_measured_output(output, offset))
+    }
+
+    /// Returns the ports of the `Output` node carrying classical `Bool` or
+    /// `LinearBit` wires, in order. These hold the measurement results read
+    /// out by a simulator.
+    pub fn output_bits(&self) -> Vec<PortIndex> {
+        self.output_ports_where(|typ| matches!(typ, WireType::Bool | WireType::LinearBit))
+    }
+
+    /// Returns the ports of the `Output` node carrying qubit wires, in order.
+    pub fn output_qubits(&self) -> Vec<PortIndex> {
+        self.output_ports_where(|typ| typ == WireType::Qubit)
+    }
+
+    fn output_ports_where(&self, pred: impl Fn(WireType) -> bool) -> Vec<PortIndex> {
+        let Ok((_, output)) = self.boundary() else {
+            return Vec::new();
+        };
+        self.node_ports(output, Direction::Incoming)
+            .filter(|&port| pred(self.port_type(port)))
+            .collect()
+    }
+
+    fn is_measured_output(&self, output: NodeIndex, offset: usize) -> bool {
+        matches!(
+            self.input_source(output, offset),
+            Some((source, _)) if self.optype(source) == &Op::Circuit(circuit::Op::Measure)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn measure_two_qubits() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        let mut gug = circ.finish();
+        assert!(!gug.is_terminally_measured());
+
+        gug.measure_all();
+        assert_eq!(gug.gate_counts()["Measure"], 2);
+        assert!(gug.is_terminally_measured());
+
+        let output = gug.output_node().unwrap();
+        let bits = gug
+            .node_ports(output, Direction::Incoming)
+            .filter(|&p| gug.port_type(p) == WireType::LinearBit)
+            .count();
+        assert_eq!(bits, 2);
+
+        let output_bits = gug.output_bits();
+        assert_eq!(output_bits.len(), 2);
+        for (i, &port) in output_bits.iter().enumerate() {
+            assert_eq!(port, gug.input_port(output, 2 + i).unwrap());
+            let (source, offset) = gug.input_source(output, 2 + i).unwrap();
+            assert_eq!((gug.optype(source).name(), offset), ("Measure", 1));
+        }
+        let output_qubits = gug.output_qubits();
+        assert_eq!(output_qubits.len(), 2);
+        assert_eq!(output_qubits[0], gug.input_port(output, 0).unwrap());
+
+        // Measuring again does not add more measurements.
+        gug.measure_all();
+        assert_eq!(gug.gate_counts()["Measure"], 2);
+    }
+
+    #[test]
+    fn classically_controlled_on_measurement() {
+        let mut circ = TestCircuit::new(&[WireType::Qubit, WireType::LinearBit, WireType::Qubit]);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::Measure, &[0, 1]);
+        let x = circ.gate(
+            circuit::Op::ClassicallyControlled(Box::new(circuit::Op::X)),
+            &[1, 2],
+        );
+        let gug = circ.finish();
+
+        assert_eq!(gug.validate(), Ok(()));
+        assert_eq!(gug.optype(x).name(), "ClassicallyControlled");
+        let types: Vec<WireType> = gug.inputs(x).map(|p| gug.port_type(p)).collect();
+        assert_eq!(types, vec![WireType::LinearBit, WireType::Qubit]);
+        let (measure, _) = gug.input_source(x, 0).unwrap();
+        assert_eq!(gug.optype(measure), &Op::Circuit(circuit::Op::Measure));
+        assert!(!gug.is_unitary());
+    }
+}
@@ -0,0 +1,178 @@
+//! Interned storage for the operations of the nodes.
+
+use std::collections::HashMap;
+
+use portgraph::{NodeIndex, SecondaryMap};
+
+use crate::component::operation::Op;
+
+/// The maximum number of distinct operations sharing a name that are
+/// interned. Further operations with that name, such as rotations by many
+/// different angles, get their own entry to keep lookups cheap.
+const MAX_INTERNED_PER_NAME: usize = 16;
+
+/// Stores the operation of each node as an index into a table of distinct
+/// operations, so that circuits dominated by a few kinds of gates only keep
+/// one copy of each.
+///
+/// Only circuit operations are shared, as the equality of control flow and
+/// opaque operations does not guarantee that they are interchangeable.
+/// Entries are reference counted, and their slots reused once unused. Entry
+/// `0` always holds the default operation, given to nodes without one.
+#[derive(Clone, Debug)]
+pub(super) struct OpTable {
+    ids: SecondaryMap<NodeIndex, u32>,
+    ops: Vec<Op>,
+    uses: Vec<usize>,
+    free: Vec<u32>,
+    /// Interned entries of circuit operations, by operation name.
+    by_name: HashMap<String, Vec<u32>>,
+}
+
+impl Default for OpTable {
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+impl OpTable {
+    pub(super) fn with_capacity(num_nodes: usize) -> Self {
+        Self {
+            ids: SecondaryMap::with_capacity(num_nodes),
+            ops: vec![Op::default()],
+            uses: vec![0],
+            free: Vec::new(),
+            by_name: HashMap::new(),
+        }
+    }
+
+    pub(super) fn get(&self, node: NodeIndex) -> &Op {
+        &self.ops[self.ids[node] as usize]
+    }
+
+    /// Sets the operation of a node, releasing its previous entry.
+    pub(super) fn set(&mut self, node: NodeIndex, op: Op) {
+        let id = self.intern(op);
+        let old = std::mem::replace(&mut self.ids[node], id);
+        self.release(old);
+    }
+
+    /// Resets a node to the default operation.
+    pub(super) fn remove(&mut self, node: NodeIndex) {
+        let old = std::mem::take(&mut self.ids[node]);
+        self.release(old);
+    }
+
+    /// Returns the number of entries in use, including the default one.
+    pub(super) fn num_entries(&self) -> usize {
+        self.ops.len() - self.free.len()
+    }
+
+    fn intern(&mut self, op: Op) -> u32 {
+        if op == Op::default() {
+            return 0;
+        }
+        let shared = matches!(op, Op::Circuit(_));
+        if shared {
+            if let Some(ids) = self.by_name.get(op.name()) {
+                if let Some(&id) = ids.iter().find(|&&id| self.ops[id as usize] == op) {
+                    self.uses[id as usize] += 1;
+                    return id;
+                }
+            }
+        }
+
+        let name = shared.then(|| op.name().to_string());
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.ops[id as usize] = op;
+                self.uses[id as usize] = 1;
+                id
+            }
+            None => {
+                self.ops.push(op);
+                self.uses.push(1);
+                (self.ops.len() - 1) as u32
+            }
+        };
+        if let Some(name) = name {
+            let ids = self.by_name.entry(name).or_default();
+            if ids.len() < MAX_INTERNED_PER_NAME {
+                ids.push(id);
+            }
+        }
+        id
+    }
+
+    fn release(&mut self, id: u32) {
+        if id == 0 {
+            return;
+        }
+        let uses = &mut self.uses[id as usize];
+        *uses -= 1;
+        if *uses > 0 {
+            return;
+        }
+        let op = std::mem::take(&mut self.ops[id as usize]);
+        if let Some(ids) = self.by_name.get_mut(op.name()) {
+            ids.retain(|&i| i != id);
+        }
+        self.free.push(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::size_of;
+
+    use super::*;
+    use crate::component::operation::circuit;
+    use crate::component::wire_type::AngleValue;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn shared_entries() {
+        let mut circ = TestCircuit::qubits(2);
+        let mut ts = Vec::new();
+        for i in 0..5000 {
+            circ.gate(circuit::Op::H, &[i % 2]);
+            circ.gate(circuit::Op::CX, &[0, 1]);
+            ts.push(circ.gate(circuit::Op::T, &[1]));
+        }
+        let mut gug = circ.finish();
+        // Input, Output, H, CX and T, besides the default entry.
+        assert_eq!(gug.op_types.num_entries(), 6);
+        let stored = gug.node_count() * size_of::<u32>() + 6 * size_of::<Op>();
+        assert!(stored * 4 < gug.node_count() * size_of::<Op>());
+
+        // Changing a node does not affect the others sharing its entry.
+        gug.set_optype(ts[0], Op::Circuit(circuit::Op::Tadj));
+        assert_eq!(gug.optype(ts[0]), &Op::Circuit(circuit::Op::Tadj));
+        assert_eq!(gug.optype(ts[1]), &Op::Circuit(circuit::Op::T));
+        assert_eq!(gug.gate_counts()["T"], 4999);
+        assert_eq!(gug.op_types.num_entries(), 7);
+
+        // Unused entries are released.
+        for &t in &ts[1..] {
+            gug.remove_and_reconnect(t);
+        }
+        assert_eq!(gug.op_types.num_entries(), 6);
+        assert_eq!(gug.gate_counts()["H"], 5000);
+        assert_eq!(gug.validate(), Ok(()));
+    }
+
+    #[test]
+    fn distinct_rotations() {
+        let mut circ = TestCircuit::qubits(1);
+        let angle = |i: usize| AngleValue::F64(i as f64 / 64.0);
+        let rotations: Vec<_> = (0..100)
+            .map(|i| circ.gate(circuit::Op::Rz(angle(i)), &[0]))
+            .collect();
+        let gug = circ.finish();
+
+        for (i, &rz) in rotations.iter().enumerate() {
+            assert_eq!(gug.optype(rz), &Op::Circuit(circuit::Op::Rz(angle(i))));
+        }
+        assert_eq!(gug.op_types.num_entries(), 103);
+    }
+}
@@ -0,0 +1,127 @@
+//! Generation of random circuits, for property tests.
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::{ConstValue, WireType};
+
+use super::Gug;
+
+/// The gates drawn by [`Gug::random`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct GateSet {
+    gates: Vec<circuit::Op>,
+}
+
+impl GateSet {
+    /// Creates a gate set from a list of gates.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a gate has linear wires other than qubits, or non-linear
+    /// wires other than angle inputs, which cannot be generated.
+    pub fn new(gates: impl IntoIterator<Item = circuit::Op>) -> Self {
+        let gates: Vec<circuit::Op> = gates.into_iter().collect();
+        for gate in &gates {
+            let signature = gate.signature();
+            assert!(
+                signature.linear.iter().all(|&t| t == WireType::Qubit)
+                    && signature.nonlinear[0].iter().all(|&t| t == WireType::Angle)
+                    && signature.nonlinear[1].is_empty(),
+                "cannot generate {} gates",
+                gate.name()
+            );
+        }
+        Self { gates }
+    }
+
+    /// The Clifford+T gate set, with the `CX` and `CZ` entangling gates.
+    pub fn clifford_t() -> Self {
+        use circuit::Op::{Sadj, Tadj, CX, CZ, H, S, T, X, Y, Z};
+        Self::new([H, S, Sadj, T, Tadj, X, Y, Z, CX, CZ])
+    }
+
+    /// Returns the gates of the set.
+    pub fn gates(&self) -> &[circuit::Op] {
+        &self.gates
+    }
+}
+
+impl Gug {
+    /// Generates a random circuit over `n_qubits` qubits with `depth` layers
+    /// of gates drawn uniformly from `gateset`.
+    ///
+    /// Each layer applies gates to every qubit, unless only gates on more
+    /// qubits than are left in the layer remain. The angle inputs of gates
+    /// are fed by `Const` nodes with random angles. The result always passes
+    /// [`Gug::validate`].
+    pub fn random(n_qubits: usize, depth: usize, gateset: &GateSet, rng: &mut impl Rng) -> Gug {
+        let qubits = vec![WireType::Qubit; n_qubits];
+        let mut gug = Gug::with_boundary(qubits.clone(), qubits);
+        let (input, output) = gug.boundary().unwrap();
+        let mut wires: Vec<_> = (0..n_qubits).map(|i| (input, i)).collect();
+
+        for _ in 0..depth {
+            let mut free: Vec<usize> = (0..n_qubits).collect();
+            free.shuffle(rng);
+            loop {
+                let fitting: Vec<&circuit::Op> = gateset
+                    .gates
+                    .iter()
+                    .filter(|gate| gate.num_qubits() <= free.len())
+                    .collect();
+                let Some(&gate) = fitting.choose(rng) else { break };
+                let node = gug.add_node(Op::Circuit(gate.clone()));
+                let num_qubits = gate.num_qubits();
+                for (offset, qubit) in free.drain(..num_qubits).enumerate() {
+                    let (src, src_offset) = wires[qubit];
+                    gug.connect(src, src_offset, node, offset).unwrap();
+                    wires[qubit] = (node, offset);
+                }
+                let num_angles = gate.signature().nonlinear[0].len();
+                for offset in num_qubits..num_qubits + num_angles {
+                    let angle = ConstValue::f64_angle(rng.gen_range(0.0..2.0));
+                    let constant = gug.add_node(Op::Circuit(circuit::Op::Const(angle)));
+                    gug.connect(constant, 0, node, offset).unwrap();
+                }
+            }
+        }
+        for (offset, (src, src_offset)) in wires.into_iter().enumerate() {
+            gug.connect(src, src_offset, output, offset).unwrap();
+        }
+        gug
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn random_circuits_validate() {
+        let mut rng = StdRng::seed_from_u64(0x6775_6721);
+        let gateset = GateSet::new(
+            GateSet::clifford_t()
+                .gates()
+                .iter()
+                .cloned()
+                .chain([circuit::Op::RzF64, circuit::Op::TK1, circuit::Op::CCX]),
+        );
+        for i in 0..100 {
+            let gug = Gug::random(1 + i % 4, 1 + i % 7, &gateset, &mut rng);
+            assert_eq!(gug.validate(), Ok(()));
+            assert!(gug.is_unitary());
+            assert_eq!(gug.boundary().map(|(input, _)| gug.num_outputs(input)), Ok(1 + i % 4));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn ungeneratable_gate() {
+        GateSet::new([circuit::Op::Measure]);
+    }
+}
@@ -0,0 +1,174 @@
+//! Register names for the wires of a circuit.
+
+use std::collections::BTreeSet;
+
+use portgraph::{Direction, NodeIndex, PortIndex};
+use thiserror::Error;
+
+use crate::component::register::{Ancillas, CregBit, QubitNames};
+
+use super::{BoundaryError, Gug};
+
+/// Error returned when naming a qubit line.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum QubitNameError {
+    #[error(transparent)]
+    Boundary(#[from] BoundaryError),
+    #[error("qubit {index} is out of range, the circuit has {qubits} qubits")]
+    OutOfRange { index: usize, qubits: usize },
+}
+
+impl Gug {
+    /// Records that the bit carried by `port` is stored at `index` in the
+    /// classical register `name`.
+    ///
+    /// This is usually set on the classical output port of a `Measure`.
+    pub fn set_creg(&mut self, port: PortIndex, name: impl Into<String>, index: usize) {
+        self.register_port_metadata::<CregBit>();
+        *self.port_metadata_mut::<CregBit>(port).unwrap() = CregBit(Some((name.into(), index)));
+    }
+
+    /// Returns the classical register name and index recorded for a port.
+    pub fn creg_of(&self, port: PortIndex) -> Option<(String, usize)> {
+        self.port_metadata::<CregBit>(port)?.0.clone()
+    }
+
+    /// Names the qubit line at `index` among the qubits of the `Input` node,
+    /// so that it can be found with [`Gug::qubit_by_name`]. Reusing a name
+    /// moves it to the new line.
+    ///
+    /// The names are stored as [`QubitNames`] metadata of the `Input` node,
+    /// and follow the lines when passes remove or reorder boundary qubits.
+    pub fn name_qubit(&mut self, index: usize, name: &str) -> Result<(), QubitNameError> {
+        let (input, _) = self.boundary()?;
+        let qubits = self.qubit_ports(input, Direction::Outgoing).len();
+        if index >= qubits {
+            return Err(QubitNameError::OutOfRange { index, qubits });
+        }
+        self.register_node_metadata::<QubitNames>();
+        let names = self.node_metadata_mut::<QubitNames>(input).unwrap();
+        names.0.insert(name.to_string(), index);
+        Ok(())
+    }
+
+    /// Returns the index of the qubit line given `name` with
+    /// [`Gug::name_qubit`].
+    pub fn qubit_by_name(&self, name: &str) -> Option<usize> {
+        let (input, _) = self.boundary().ok()?;
+        self.node_metadata::<QubitNames>(input)?.0.get(name).copied()
+    }
+
+    /// Updates the qubit indices recorded on the `Input` node, as
+    /// [`QubitNames`] and [`Ancillas`], after its qubit ports have been
+    /// removed or reordered. `remap` gives the new index of each old index,
+    /// or `None` if the qubit was removed, in which case its entries are
+    /// dropped.
+    pub(crate) fn remap_qubit_indices(
+        &mut self,
+        input: NodeIndex,
+        remap: impl Fn(usize) -> Option<usize>,
+    ) {
+        if let Some(names) = self.node_metadata_mut::<QubitNames>(input) {
+            names.0 = std::mem::take(&mut names.0)
+                .into_iter()
+                .filter_map(|(name, i)| Some((name, remap(i)?)))
+                .collect();
+        }
+        if let Some(ancillas) = self.node_metadata_mut::<Ancillas>(input) {
+            let remap_set = |indices: &BTreeSet<usize>| -> BTreeSet<usize> {
+                indices.iter().filter_map(|&i| remap(i)).collect()
+            };
+            ancillas.allocated = remap_set(&ancillas.allocated);
+            ancillas.free = remap_set(&ancillas.free);
+        }
+    }
+}
+
+/// Returns the index of qubit `index` after removing the qubits in `removed`,
+/// for [`Gug::remap_qubit_indices`].
+pub(crate) fn index_after_removal(index: usize, removed: &[usize]) -> Option<usize> {
+    if removed.contains(&index) {
+        return None;
+    }
+    Some(index - removed.iter().filter(|&&r| r < index).count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::operation::circuit;
+    use crate::component::wire_type::WireType;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn measurement_registers() {
+        let mut circ = TestCircuit::new(&[
+            WireType::Qubit,
+            WireType::Qubit,
+            WireType::LinearBit,
+            WireType::LinearBit,
+        ]);
+        let m0 = circ.gate(circuit::Op::Measure, &[0, 2]);
+        let m1 = circ.gate(circuit::Op::Measure, &[1, 3]);
+        let mut gug = circ.finish();
+
+        let bit0 = gug.output_port(m0, 1).unwrap();
+        let bit1 = gug.output_port(m1, 1).unwrap();
+        assert_eq!(gug.creg_of(bit0), None);
+
+        gug.set_creg(bit0, "c", 0);
+        gug.set_creg(bit1, "c", 1);
+        assert_eq!(gug.creg_of(bit0), Some(("c".to_string(), 0)));
+        assert_eq!(gug.creg_of(bit1), Some(("c".to_string(), 1)));
+        assert_eq!(gug.creg_of(gug.output_port(m0, 0).unwrap()), None);
+    }
+
+    #[test]
+    fn named_qubits() {
+        let mut gug = TestCircuit::qubits(3).finish();
+        assert_eq!(gug.qubit_by_name("ancilla"), None);
+
+        gug.name_qubit(2, "ancilla").unwrap();
+        gug.name_qubit(0, "data").unwrap();
+        assert_eq!(gug.qubit_by_name("ancilla"), Some(2));
+        assert_eq!(gug.qubit_by_name("data"), Some(0));
+        assert_eq!(gug.qubit_by_name("flag"), None);
+
+        gug.name_qubit(1, "ancilla").unwrap();
+        assert_eq!(gug.qubit_by_name("ancilla"), Some(1));
+
+        assert_eq!(
+            gug.name_qubit(3, "flag"),
+            Err(QubitNameError::OutOfRange {
+                index: 3,
+                qubits: 3
+            })
+        );
+        assert_eq!(
+            crate::Gug::new().name_qubit(0, "flag"),
+            Err(QubitNameError::Boundary(BoundaryError::MissingInput))
+        );
+    }
+
+    #[test]
+    fn names_follow_boundary_changes() {
+        let mut circ = TestCircuit::qubits(3);
+        circ.gate(circuit::Op::Noop(WireType::Qubit), &[0]);
+        circ.gate(circuit::Op::H, &[1]);
+        circ.gate(circuit::Op::X, &[2]);
+        let mut gug = circ.finish();
+        gug.name_qubit(0, "idle").unwrap();
+        gug.name_qubit(1, "data").unwrap();
+        gug.name_qubit(2, "flag").unwrap();
+
+        gug.permute_qubits(&[2, 0, 1]).unwrap();
+        assert_eq!(gug.qubit_by_name("idle"), Some(2));
+        assert_eq!(gug.qubit_by_name("data"), Some(0));
+        assert_eq!(gug.qubit_by_name("flag"), Some(1));
+
+        assert_eq!(gug.remove_idle_qubits(), Ok(1));
+        assert_eq!(gug.qubit_by_name("idle"), None);
+        assert_eq!(gug.qubit_by_name("data"), Some(0));
+        assert_eq!(gug.qubit_by_name("flag"), Some(1));
+    }
+}
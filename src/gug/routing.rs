@@ -0,0 +1,498 @@
+//! Qubit relabeling and routing primitives.
+
+use std::collections::{HashMap, HashSet};
+
+use portgraph::{Direction, NodeIndex, PortIndex};
+use thiserror::Error;
+
+use crate::component::operation::{circuit, Op};
+use crate::component::physical::PhysicalQubit;
+use crate::component::wire_type::WireType;
+
+use super::registers::index_after_removal;
+use super::{BoundaryError, Gug};
+
+/// Error returned when applying an invalid qubit permutation.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PermError {
+    #[error(transparent)]
+    Boundary(#[from] BoundaryError),
+    #[error("permutation has length {found}, but the circuit has {expected} qubits")]
+    WrongLength { expected: usize, found: usize },
+    #[error("{0:?} is not a permutation")]
+    NotAPermutation(Vec<usize>),
+}
+
+impl Gug {
+    /// Relabels the qubit lines of the circuit.
+    ///
+    /// The qubit wire at position `i` on the `Input` and `Output` boundary
+    /// nodes is moved to position `perm[i]`, together with the physical qubit
+    /// tag of its boundary port and its qubit names. Only the boundary ports
+    /// are relinked, the internal gates are left untouched.
+    pub fn permute_qubits(&mut self, perm: &[usize]) -> Result<(), PermError> {
+        let (input, output) = self.boundary()?;
+        let in_ports = self.qubit_ports(input, Direction::Outgoing);
+        let out_ports = self.qubit_ports(output, Direction::Incoming);
+        check_permutation(perm, in_ports.len())?;
+        check_permutation(perm, out_ports.len())?;
+
+        self.permute_links(&in_ports, perm, Direction::Outgoing);
+        self.permute_links(&out_ports, perm, Direction::Incoming);
+        self.remap_qubit_indices(input, |i| Some(perm[i]));
+        Ok(())
+    }
+
+    /// Tags a qubit port with the physical qubit its wire is mapped to.
+    pub fn set_physical_qubit(&mut self, port: PortIndex, qubit: u32) {
+        self.register_port_metadata::<PhysicalQubit>();
+        *self.port_metadata_mut::<PhysicalQubit>(port).unwrap() = PhysicalQubit(qubit);
+    }
+
+    /// Returns the physical qubit a port has been mapped to, if any.
+    pub fn physical_qubit(&self, port: PortIndex) -> Option<u32> {
+        self.port_metadata::<PhysicalQubit>(port)
+            .filter(|q| q.is_assigned())
+            .map(|q| q.0)
+    }
+
+    /// Realizes a qubit permutation by inserting `Swap` gates at the end of
+    /// the circuit, right before the `Output` node.
+    ///
+    /// The state carried by qubit line `i` is moved to line `perm[i]`. The
+    /// permutation is decomposed into transpositions, so a cycle of length `k`
+    /// uses `k - 1` swaps. Returns the inserted swap nodes in circuit order.
+    ///
+    /// If the qubit ports of the `Output` node are tagged with physical
+    /// qubits, the ports of each swap are tagged with the physical qubits of
+    /// the lines it acts on.
+    pub fn route_swaps(&mut self, perm: &[usize]) -> Result<Vec<NodeIndex>, PermError> {
+        let (_, output) = self.boundary()?;
+        let out_ports = self.qubit_ports(output, Direction::Incoming);
+        check_permutation(perm, out_ports.len())?;
+        let physical: Vec<Option<u32>> = out_ports
+            .iter()
+            .map(|&port| self.physical_qubit(port))
+            .collect();
+
+        // The output port currently terminating each qubit line.
+        let mut ends: Vec<Option<PortIndex>> = out_ports
+            .iter()
+            .map(|&port| self.graph.unlink_port(port))
+            .collect();
+
+        // `target[j]` is the line whose state must end up on line `j`, and
+        // `current[j]` the line whose state is on line `j` so far.
+        let mut target = vec![0; perm.len()];
+        for (i, &j) in perm.iter().enumerate() {
+            target[j] = i;
+        }
+        let mut current: Vec<usize> = (0..perm.len()).collect();
+
+        let mut swaps = Vec::new();
+        for j in 0..perm.len() {
+            if current[j] == target[j] {
+                continue;
+            }
+            let k = current.iter().position(|&c| c == target[j]).unwrap();
+            let swap = self.add_node(Op::Circuit(circuit::Op::Swap));
+            for (offset, line) in [j, k].into_iter().enumerate() {
+                let in_port = self.input_port(swap, offset).unwrap();
+                let out_port = self.output_port(swap, offset).unwrap();
+                if let Some(end) = ends[line] {
+                    self.graph.link_ports(end, in_port).unwrap();
+                }
+                if let Some(qubit) = physical[line] {
+                    self.set_physical_qubit(in_port, qubit);
+                    self.set_physical_qubit(out_port, qubit);
+                }
+                ends[line] = Some(out_port);
+            }
+            current.swap(j, k);
+            swaps.push(swap);
+        }
+
+        for (&port, end) in out_ports.iter().zip(ends) {
+            if let Some(end) = end {
+                self.graph.link_ports(end, port).unwrap();
+            }
+        }
+        Ok(swaps)
+    }
+
+    /// Checks that every two-qubit gate acts on qubits that are adjacent in
+    /// the undirected `coupling` graph, returning the violating gates.
+    ///
+    /// Qubits are identified by the physical qubit tagged on the gate's input
+    /// port, falling back to the index of the qubit line on the `Input` node.
+    /// Without a unique `Input` node, gates on untagged ports are reported.
+    pub fn check_connectivity(&self, coupling: &[(usize, usize)]) -> Result<(), Vec<NodeIndex>> {
+        let edges: HashSet<(usize, usize)> = coupling
+            .iter()
+            .flat_map(|&(a, b)| [(a, b), (b, a)])
+            .collect();
+        let lines = self.qubit_lines();
+        let qubit = |node, offset| {
+            let port = self.input_port(node, offset)?;
+            match self.physical_qubit(port) {
+                Some(physical) => Some(physical as usize),
+                None => lines.get(&(node, offset)).copied(),
+            }
+        };
+        let violations: Vec<NodeIndex> = self
+            .nodes()
+            .filter(|&node| self.is_two_qubit_gate(node))
+            .filter(|&node| match (qubit(node, 0), qubit(node, 1)) {
+                (Some(a), Some(b)) => !edges.contains(&(a, b)),
+                _ => true,
+            })
+            .collect();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Returns the indices of the qubit lines on the `Input` node that reach
+    /// the `Output` node with only `Noop` operations in between.
+    pub fn idle_qubits(&self) -> Result<Vec<usize>, BoundaryError> {
+        let (input, _) = self.boundary()?;
+        Ok(self
+            .qubit_ports(input, Direction::Outgoing)
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, port)| self.idle_wire(port).is_some())
+            .map(|(line, _)| line)
+            .collect())
+    }
+
+    /// Removes the idle qubits reported by [`Gug::idle_qubits`], together
+    /// with their `Noop` operations, narrowing the `Input` and `Output`
+    /// boundaries. Names and ancillas of the removed qubits are dropped.
+    ///
+    /// Returns the number of removed qubits.
+    pub fn remove_idle_qubits(&mut self) -> Result<usize, BoundaryError> {
+        let (input, output) = self.boundary()?;
+        let mut lines = Vec::new();
+        let mut input_offsets = Vec::new();
+        let mut output_offsets = Vec::new();
+        let mut noops = Vec::new();
+        for (line, port) in self.qubit_ports(input, Direction::Outgoing).into_iter().enumerate() {
+            let Some((wire_noops, end)) = self.idle_wire(port) else {
+                continue;
+            };
+            lines.push(line);
+            input_offsets.extend(self.port_offset(port));
+            output_offsets.extend(self.port_offset(end));
+            noops.extend(wire_noops);
+        }
+        for node in noops {
+            self.remove_node(node);
+        }
+        input_offsets.sort_unstable();
+        output_offsets.sort_unstable();
+        for &offset in input_offsets.iter().rev() {
+            self.remove_port(input, Direction::Outgoing, offset);
+        }
+        for &offset in output_offsets.iter().rev() {
+            self.remove_port(output, Direction::Incoming, offset);
+        }
+        self.remap_qubit_indices(input, |i| index_after_removal(i, &lines));
+        Ok(lines.len())
+    }
+
+    /// Follows the wire leaving an `Input` port through `Noop` operations,
+    /// returning them and the `Output` port it ends at, or `None` if the
+    /// wire reaches any other operation.
+    fn idle_wire(&self, port: PortIndex) -> Option<(Vec<NodeIndex>, PortIndex)> {
+        let mut noops = Vec::new();
+        let mut link = self.port_link(port)?;
+        loop {
+            let node = self.port_node(link)?;
+            match self.optype(node) {
+                Op::Circuit(circuit::Op::Output) => return Some((noops, link)),
+                Op::Circuit(circuit::Op::Noop(_)) => {
+                    noops.push(node);
+                    link = self.port_link(self.output_port(node, 0)?)?;
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    /// Returns the logical qubit a port on a qubit wire belongs to: the index
+    /// of the line it is traced back to among the qubits of the `Input` node.
+    ///
+    /// Each call walks the wire back to the `Input` node. To resolve many
+    /// ports, [`Gug::qubit_history`] traces every line once instead. Returns
+    /// `None` for ports of other types, or if the wire is broken before
+    /// reaching the `Input` node.
+    pub fn logical_qubit_of(&self, port: PortIndex) -> Option<usize> {
+        if self.port_type(port) != WireType::Qubit {
+            return None;
+        }
+        let mut port = port;
+        loop {
+            let node = self.port_node(port)?;
+            let offset = self.port_offset(port)?;
+            if self.input_port(node, offset) == Some(port) {
+                port = self.port_link(port)?;
+            } else if matches!(self.optype(node), Op::Circuit(circuit::Op::Input)) {
+                let qubits = self.qubit_ports(node, Direction::Outgoing);
+                return qubits.iter().position(|&p| p == port);
+            } else if offset < self.signature(node).linear.len() {
+                port = self.input_port(node, offset)?;
+            } else {
+                return None;
+            }
+        }
+    }
+
+    /// Maps each linear input port on a qubit line, as a node and offset, to
+    /// the index of the line on the `Input` node.
+    ///
+    /// The map is empty if the circuit does not have a unique boundary.
+    fn qubit_lines(&self) -> HashMap<(NodeIndex, usize), usize> {
+        let mut lines = HashMap::new();
+        let Ok((input, _)) = self.boundary() else {
+            return lines;
+        };
+        for (line, port) in self.qubit_ports(input, Direction::Outgoing).into_iter().enumerate() {
+            let mut port = Some(port);
+            while let Some(link) = port.and_then(|p| self.port_link(p)) {
+                let (Some(node), Some(offset)) = (self.port_node(link), self.port_offset(link))
+                else {
+                    break;
+                };
+                if offset >= self.signature(node).linear.len() || self.optype(node).is_boundary() {
+                    break;
+                }
+                lines.insert((node, offset), line);
+                port = self.output_port(node, offset);
+            }
+        }
+        lines
+    }
+
+    /// Returns the qubit-typed ports of a node in the given direction.
+    pub(crate) fn qubit_ports(&self, node: NodeIndex, direction: Direction) -> Vec<PortIndex> {
+        self.node_ports(node, direction)
+            .filter(|&p| self.port_type(p) == WireType::Qubit)
+            .collect()
+    }
+
+    /// Moves the link and the physical qubit tag of `ports[i]` to
+    /// `ports[perm[i]]`.
+    fn permute_links(&mut self, ports: &[PortIndex], perm: &[usize], direction: Direction) {
+        let tags: Vec<Option<PhysicalQubit>> = ports
+            .iter()
+            .map(|&port| self.port_metadata::<PhysicalQubit>(port).copied())
+            .collect();
+        for (i, tag) in tags.into_iter().enumerate() {
+            let slot = self.port_metadata_mut::<PhysicalQubit>(ports[perm[i]]);
+            if let (Some(tag), Some(slot)) = (tag, slot) {
+                *slot = tag;
+            }
+        }
+
+        let links: Vec<_> = ports
+            .iter()
+            .map(|&port| self.graph.unlink_port(port))
+            .collect();
+        for (i, link) in links.into_iter().enumerate() {
+            let Some(link) = link else { continue };
+            let port = ports[perm[i]];
+            let result = match direction {
+                Direction::Outgoing => self.graph.link_ports(port, link),
+                Direction::Incoming => self.graph.link_ports(link, port),
+            };
+            result.expect("Relinking a freshly unlinked port cannot fail");
+        }
+    }
+}
+
+/// Checks that `perm` is a permutation of `0..len`.
+pub(crate) fn check_permutation(perm: &[usize], len: usize) -> Result<(), PermError> {
+    if perm.len() != len {
+        return Err(PermError::WrongLength {
+            expected: len,
+            found: perm.len(),
+        });
+    }
+    let mut seen = vec![false; len];
+    for &i in perm {
+        if i >= len || std::mem::replace(&mut seen[i], true) {
+            return Err(PermError::NotAPermutation(perm.to_vec()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn linear_connectivity() {
+        let mut circ = TestCircuit::qubits(3);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::CX, &[2, 1]);
+        let near = circ.finish();
+        assert_eq!(near.check_connectivity(&[(0, 1), (1, 2)]), Ok(()));
+
+        let mut circ = TestCircuit::qubits(3);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        let far = circ.gate(circuit::Op::CX, &[0, 2]);
+        let mut gug = circ.finish();
+        assert_eq!(gug.check_connectivity(&[(0, 1), (1, 2)]), Err(vec![far]));
+
+        // Physical qubit tags take precedence over the line indices.
+        let port = gug.input_port(far, 0).unwrap();
+        gug.set_physical_qubit(port, 1);
+        assert_eq!(gug.check_connectivity(&[(0, 1), (1, 2)]), Ok(()));
+    }
+
+    #[test]
+    fn logical_qubits() {
+        let mut circ = TestCircuit::qubits(3);
+        let h = circ.gate(circuit::Op::H, &[1]);
+        let cx = circ.gate(circuit::Op::CX, &[2, 1]);
+        let gug = circ.finish();
+
+        let input = |offset| gug.input_port(cx, offset).unwrap();
+        assert_eq!(gug.logical_qubit_of(input(0)), Some(2));
+        assert_eq!(gug.logical_qubit_of(input(1)), Some(1));
+        let output = gug.output_port(cx, 1).unwrap();
+        assert_eq!(gug.logical_qubit_of(output), Some(1));
+        let h = gug.input_port(h, 0).unwrap();
+        assert_eq!(gug.logical_qubit_of(h), Some(1));
+        let (_, end) = gug.boundary().unwrap();
+        let end = gug.input_port(end, 0).unwrap();
+        assert_eq!(gug.logical_qubit_of(end), Some(0));
+    }
+
+    #[test]
+    fn remove_unused_qubit() {
+        let mut circ = TestCircuit::qubits(3);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::Noop(WireType::Qubit), &[1]);
+        circ.gate(circuit::Op::CX, &[0, 2]);
+        let mut gug = circ.finish();
+        assert_eq!(gug.idle_qubits(), Ok(vec![1]));
+
+        assert_eq!(gug.remove_idle_qubits(), Ok(1));
+        assert_eq!(gug.idle_qubits(), Ok(vec![]));
+        assert_eq!(gug.validate(), Ok(()));
+        assert_eq!(gug.node_count(), 4);
+        let (input, output) = gug.boundary().unwrap();
+        assert_eq!(gug.num_outputs(input), 2);
+        assert_eq!(gug.num_inputs(output), 2);
+        let (cx, offset) = gug.output_target(input, 1).unwrap();
+        assert_eq!((gug.optype(cx).name(), offset), ("CX", 1));
+        assert_eq!(gug.output_target(cx, 1), Some((output, 1)));
+    }
+
+    #[test]
+    fn permute_two_qubits() {
+        let mut circ = TestCircuit::qubits(2);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let x = circ.gate(circuit::Op::X, &[1]);
+        let mut gug = circ.finish();
+        let input = gug.input_node().unwrap();
+        let output = gug.output_node().unwrap();
+
+        let first_target = |gug: &Gug| {
+            let port = gug.output_port(input, 0).unwrap();
+            gug.port_node(gug.port_link(port).unwrap()).unwrap()
+        };
+        let first_source = |gug: &Gug| {
+            let port = gug.input_port(output, 0).unwrap();
+            gug.port_node(gug.port_link(port).unwrap()).unwrap()
+        };
+        assert_eq!(first_target(&gug), h);
+        assert_eq!(first_source(&gug), h);
+
+        gug.permute_qubits(&[1, 0]).unwrap();
+
+        assert_eq!(first_target(&gug), x);
+        assert_eq!(first_source(&gug), x);
+        assert_eq!(gug.optype(h), &Op::Circuit(circuit::Op::H));
+        assert_eq!(gug.optype(x), &Op::Circuit(circuit::Op::X));
+        assert_eq!(gug.node_count(), 4);
+    }
+
+    #[test]
+    fn invalid_permutations() {
+        let mut gug = TestCircuit::qubits(2).finish();
+        assert_eq!(
+            gug.permute_qubits(&[0]),
+            Err(PermError::WrongLength {
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            gug.permute_qubits(&[1, 1]),
+            Err(PermError::NotAPermutation(vec![1, 1]))
+        );
+    }
+
+    #[test]
+    fn route_three_cycle() {
+        let mut gug = TestCircuit::qubits(3).finish();
+        let input = gug.input_node().unwrap();
+        let output = gug.output_node().unwrap();
+        let perm = [1, 2, 0];
+
+        let swaps = gug.route_swaps(&perm).unwrap();
+        assert_eq!(swaps.len(), 2);
+        assert_eq!(gug.node_count(), 4);
+
+        // Follow the state of each input qubit through the swaps.
+        for (i, &expected) in perm.iter().enumerate() {
+            let mut port = gug.output_port(input, i).unwrap();
+            loop {
+                let next = gug.port_link(port).unwrap();
+                let node = gug.port_node(next).unwrap();
+                let offset = gug.port_offset(next).unwrap();
+                if node == output {
+                    assert_eq!(offset, expected);
+                    break;
+                }
+                assert_eq!(gug.optype(node), &Op::Circuit(circuit::Op::Swap));
+                port = gug.output_port(node, 1 - offset).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn physical_qubit_tags() {
+        let mut gug = TestCircuit::qubits(2).finish();
+        let input = gug.input_node().unwrap();
+        let output = gug.output_node().unwrap();
+        let in_ports = gug.qubit_ports(input, Direction::Outgoing);
+        let out_ports = gug.qubit_ports(output, Direction::Incoming);
+        assert_eq!(gug.physical_qubit(in_ports[0]), None);
+
+        for (i, (&p, &q)) in in_ports.iter().zip(&out_ports).enumerate() {
+            gug.set_physical_qubit(p, 10 + i as u32);
+            gug.set_physical_qubit(q, 10 + i as u32);
+        }
+        gug.permute_qubits(&[0, 1]).unwrap();
+        assert_eq!(gug.physical_qubit(in_ports[0]), Some(10));
+        assert_eq!(gug.physical_qubit(in_ports[1]), Some(11));
+
+        // The tags follow the wires to their new positions.
+        gug.permute_qubits(&[1, 0]).unwrap();
+        assert_eq!(gug.physical_qubit(in_ports[0]), Some(11));
+        assert_eq!(gug.physical_qubit(in_ports[1]), Some(10));
+        assert_eq!(gug.physical_qubit(out_ports[0]), Some(11));
+        assert_eq!(gug.physical_qubit(out_ports[1]), Some(10));
+
+        let swaps = gug.route_swaps(&[1, 0]).unwrap();
+        let swap_in = gug.input_port(swaps[0], 1).unwrap();
+        assert_eq!(gug.physical_qubit(swap_in), Some(10));
+    }
+}
@@ -0,0 +1,339 @@
+//! Partitioning of circuits into parallel time slices.
+
+use std::collections::{HashMap, HashSet};
+
+use portgraph::{Direction, NodeIndex};
+
+use crate::component::operation::{circuit, Op};
+use crate::component::physical::GateDuration;
+use crate::component::wire_type::{Signature, WireType};
+
+use super::Gug;
+
+/// Strategy used to assign gates to time slices by [`Gug::schedule`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ScheduleStrategy {
+    /// Place each gate in the earliest slice allowed by its dependencies.
+    Asap,
+    /// Place each gate in the latest slice allowed by its dependents.
+    Alap,
+}
+
+impl Gug {
+    /// Returns `true` if the node is a gate occupying a time slice, that is, a
+    /// non-boundary operation acting on a linear quantum or bit wire.
+    pub(crate) fn is_scheduled_gate(&self, node: NodeIndex) -> bool {
+        let op = self.optype(node);
+        !op.is_boundary()
+            && op
+                .signature()
+                .linear
+                .iter()
+                .any(|t| matches!(t, WireType::Qubit | WireType::LinearBit))
+    }
+
+    /// Returns the depth of the circuit, the number of time slices in its schedule.
+    pub fn depth(&self) -> usize {
+        self.schedule(ScheduleStrategy::Asap).len()
+    }
+
+    /// Partitions the gates of the circuit into time slices, where gates in
+    /// the same slice act on disjoint wires.
+    ///
+    /// Classical and boundary nodes are not part of the schedule, but their
+    /// dependencies are respected. The number of slices is the circuit depth.
+    pub fn schedule(&self, strategy: ScheduleStrategy) -> Vec<Vec<NodeIndex>> {
+        let order = self.toposort();
+        let weight = |node| usize::from(self.is_scheduled_gate(node));
+
+        let mut slices: Vec<Vec<NodeIndex>> = Vec::new();
+        let place = |slices: &mut Vec<Vec<NodeIndex>>, slice: usize, node| {
+            if slices.len() <= slice {
+                slices.resize_with(slice + 1, Vec::new);
+            }
+            slices[slice].push(node);
+        };
+
+        match strategy {
+            ScheduleStrategy::Asap => {
+                let start = self.asap_starts(&order);
+                for &node in &order {
+                    if self.is_scheduled_gate(node) {
+                        place(&mut slices, start[&node], node);
+                    }
+                }
+            }
+            ScheduleStrategy::Alap => {
+                // The number of slices needed after the start of each node.
+                let mut remaining: HashMap<NodeIndex, usize> = HashMap::new();
+                for &node in order.iter().rev() {
+                    let after = self
+                        .linked_nodes(node, Direction::Outgoing)
+                        .map(|succ| remaining[&succ])
+                        .max()
+                        .unwrap_or(0);
+                    remaining.insert(node, after + weight(node));
+                }
+                let depth = remaining.values().copied().max().unwrap_or(0);
+                for &node in &order {
+                    if self.is_scheduled_gate(node) {
+                        place(&mut slices, depth - remaining[&node], node);
+                    }
+                }
+            }
+        }
+        slices
+    }
+
+    /// Records the duration of an operation for [`Gug::schedule_with_durations`].
+    pub fn set_gate_duration(&mut self, node: NodeIndex, duration: f64) {
+        self.register_node_metadata::<GateDuration>();
+        *self.node_metadata_mut::<GateDuration>(node).unwrap() = GateDuration(duration);
+    }
+
+    /// Computes the earliest start time of each gate, given the durations
+    /// recorded as [`GateDuration`] metadata.
+    ///
+    /// Gates without a recorded duration take one time unit, while classical
+    /// and boundary nodes take no time. Returns the gates in topological order
+    /// with their start times.
+    pub fn schedule_with_durations(&self) -> Vec<(NodeIndex, f64)> {
+        let duration = |node| {
+            if !self.is_scheduled_gate(node) {
+                return 0.0;
+            }
+            self.node_metadata::<GateDuration>(node)
+                .copied()
+                .unwrap_or_default()
+                .0
+        };
+        let mut end: HashMap<NodeIndex, f64> = HashMap::new();
+        let mut starts = Vec::new();
+        for node in self.toposort() {
+            let start = self
+                .linked_nodes(node, Direction::Incoming)
+                .map(|pred| end[&pred])
+                .fold(0.0, f64::max);
+            end.insert(node, start + duration(node));
+            if self.is_scheduled_gate(node) {
+                starts.push((node, start));
+            }
+        }
+        starts
+    }
+
+    /// Returns the earliest slice each node can start in, given the nodes in
+    /// topological order.
+    fn asap_starts(&self, order: &[NodeIndex]) -> HashMap<NodeIndex, usize> {
+        let weight = |node| usize::from(self.is_scheduled_gate(node));
+        let mut start: HashMap<NodeIndex, usize> = HashMap::new();
+        for &node in order {
+            let slice = self
+                .linked_nodes(node, Direction::Incoming)
+                .map(|pred| start[&pred] + weight(pred))
+                .max()
+                .unwrap_or(0);
+            start.insert(node, slice);
+        }
+        start
+    }
+
+    /// Inserts `Noop` operations on the idle linear wires of the
+    /// [`ScheduleStrategy::Asap`] schedule, so that every slice has an
+    /// operation on each qubit and linear bit wire running through it.
+    ///
+    /// Wires ending at the `Output` node are padded up to the full depth of
+    /// the circuit, which is left unchanged. Returns the number of inserted
+    /// operations.
+    pub fn pad_with_noops(&mut self) -> usize {
+        let order = self.toposort();
+        let start = self.asap_starts(&order);
+        let depth = self.depth();
+        let end = |node| start[&node] + usize::from(self.is_scheduled_gate(node));
+
+        let mut gaps = Vec::new();
+        for &node in &order {
+            for (offset, port) in self.node_ports(node, Direction::Outgoing).enumerate() {
+                let typ = self.port_type(port);
+                if !matches!(typ, WireType::Qubit | WireType::LinearBit) {
+                    continue;
+                }
+                let Some((target, _)) = self.output_target(node, offset) else { continue };
+                let target_start = match self.optype(target) {
+                    Op::Circuit(circuit::Op::Output) => depth,
+                    _ => start[&target],
+                };
+                let gap = target_start.saturating_sub(end(node));
+                if gap > 0 {
+                    gaps.push((node, offset, typ, gap));
+                }
+            }
+        }
+
+        let mut inserted = 0;
+        for (node, offset, typ, gap) in gaps {
+            let (mut node, mut offset) = (node, offset);
+            for _ in 0..gap {
+                node = self.insert_after(node, offset, Op::Circuit(circuit::Op::Noop(typ)));
+                offset = 0;
+            }
+            inserted += gap;
+        }
+        inserted
+    }
+
+    /// Returns a new circuit with the gates in the first `up_to_depth` slices
+    /// of the [`ScheduleStrategy::Asap`] schedule.
+    ///
+    /// Classical nodes are kept when they feed a kept gate. Every wire leaving
+    /// the kept nodes towards a dropped node or the old `Output` is capped by
+    /// a fresh `Output` node, so linear wires cut mid-circuit end there.
+    pub fn prefix(&self, up_to_depth: usize) -> Gug {
+        let mut kept: HashSet<NodeIndex> = self
+            .schedule(ScheduleStrategy::Asap)
+            .into_iter()
+            .take(up_to_depth)
+            .flatten()
+            .collect();
+        kept.extend(self.input_node());
+        let order = self.toposort();
+        for &node in order.iter().rev() {
+            if !self.optype(node).is_boundary()
+                && !self.is_scheduled_gate(node)
+                && self.successors(node).any(|succ| kept.contains(&succ))
+            {
+                kept.insert(node);
+            }
+        }
+
+        let mut prefix = Gug::new();
+        let mut map = HashMap::new();
+        for &node in order.iter().filter(|n| kept.contains(n)) {
+            map.insert(node, prefix.copy_node_from(self, node));
+        }
+        let mut cut = Vec::new();
+        for &node in order.iter().filter(|n| kept.contains(n)) {
+            for (offset, port) in self.node_ports(node, Direction::Outgoing).enumerate() {
+                let Some((target, target_offset)) = self.output_target(node, offset) else {
+                    continue;
+                };
+                match map.get(&target) {
+                    Some(&new_target) => {
+                        prefix.connect(map[&node], offset, new_target, target_offset).unwrap()
+                    }
+                    None => cut.push((map[&node], offset, self.port_type(port))),
+                }
+            }
+        }
+
+        let types = cut.iter().map(|&(_, _, typ)| typ).collect();
+        let output = prefix.add_node_with_signature(
+            Op::Circuit(circuit::Op::Output),
+            Signature::new_nonlinear(types, vec![]),
+        );
+        for (i, (node, offset, _)) in cut.into_iter().enumerate() {
+            prefix.connect(node, offset, output, i).unwrap();
+        }
+        prefix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::operation::circuit;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn independent_gates_share_a_slice() {
+        let mut circ = TestCircuit::qubits(2);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let x = circ.gate(circuit::Op::X, &[1]);
+        let gug = circ.finish();
+
+        let slices = gug.schedule(ScheduleStrategy::Asap);
+        assert_eq!(slices.len(), 1);
+        assert_eq!(slices[0].len(), 2);
+        assert!(slices[0].contains(&h) && slices[0].contains(&x));
+    }
+
+    #[test]
+    fn chain_and_alap() {
+        let mut circ = TestCircuit::qubits(2);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let x = circ.gate(circuit::Op::X, &[0]);
+        let z = circ.gate(circuit::Op::Z, &[1]);
+        let gug = circ.finish();
+
+        let asap = gug.schedule(ScheduleStrategy::Asap);
+        assert_eq!(asap.len(), 2);
+        assert_eq!(asap[0].len(), 2);
+        assert!(asap[0].contains(&h) && asap[0].contains(&z));
+        assert_eq!(asap[1], vec![x]);
+
+        let alap = gug.schedule(ScheduleStrategy::Alap);
+        assert_eq!(alap.len(), 2);
+        assert_eq!(alap[0], vec![h]);
+        assert!(alap[1].contains(&x) && alap[1].contains(&z));
+    }
+
+    #[test]
+    fn prefix_cuts_wires() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::X, &[1]);
+        let gug = circ.finish();
+        assert_eq!(gug.depth(), 3);
+
+        let prefix = gug.prefix(2);
+        assert_eq!(prefix.depth(), 2);
+        assert_eq!(prefix.gate_counts().values().sum::<usize>(), 2);
+        assert!(!prefix.gate_counts().contains_key("X"));
+
+        let output = prefix.output_node().unwrap();
+        let open: Vec<_> = prefix.node_ports(output, Direction::Incoming).collect();
+        assert_eq!(open.len(), 2);
+        assert!(open.iter().all(|&p| prefix.port_link(p).is_some()));
+        assert!(open.iter().all(|&p| prefix.port_type(p) == WireType::Qubit));
+    }
+
+    #[test]
+    fn pad_idle_qubit() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::H, &[0]);
+        let cx = circ.gate(circuit::Op::CX, &[0, 1]);
+        let mut gug = circ.finish();
+
+        assert_eq!(gug.pad_with_noops(), 1);
+        let (noop, _) = gug.input_source(cx, 1).unwrap();
+        assert_eq!(
+            gug.optype(noop),
+            &Op::Circuit(circuit::Op::Noop(WireType::Qubit))
+        );
+        let slices = gug.schedule(ScheduleStrategy::Asap);
+        assert_eq!(slices.len(), 2);
+        assert_eq!(slices[0].len(), 2);
+        assert!(slices[0].contains(&noop));
+        assert_eq!(gug.pad_with_noops(), 0);
+    }
+
+    #[test]
+    fn durations_delay_gates() {
+        let mut circ = TestCircuit::qubits(3);
+        let cx = circ.gate(circuit::Op::CX, &[0, 1]);
+        let h = circ.gate(circuit::Op::H, &[2]);
+        let x = circ.gate(circuit::Op::X, &[1]);
+        let z = circ.gate(circuit::Op::Z, &[2]);
+        let mut gug = circ.finish();
+        gug.set_gate_duration(cx, 5.0);
+        gug.set_gate_duration(h, 0.5);
+
+        let starts: HashMap<NodeIndex, f64> = gug.schedule_with_durations().into_iter().collect();
+        assert_eq!(starts.len(), 4);
+        assert_eq!(starts[&cx], 0.0);
+        assert_eq!(starts[&h], 0.0);
+        assert_eq!(starts[&x], 5.0);
+        assert_eq!(starts[&z], 0.5);
+    }
+}
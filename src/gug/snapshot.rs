@@ -0,0 +1,65 @@
+//! Snapshots of a graph, for rolling back speculative changes.
+
+use super::Gug;
+
+/// A saved state of a graph, created by [`Gug::checkpoint`] and rolled back
+/// to with [`Gug::restore`].
+#[derive(Clone, Debug)]
+pub struct GugSnapshot {
+    gug: Gug,
+}
+
+impl GugSnapshot {
+    /// Returns the graph as it was when the snapshot was taken.
+    pub fn gug(&self) -> &Gug {
+        &self.gug
+    }
+}
+
+impl Gug {
+    /// Saves the current state of the graph, including operations, port
+    /// types, metadata and the global phase.
+    ///
+    /// The snapshot is currently a full clone of the graph, so taking one
+    /// costs time and memory linear in the size of the graph and its
+    /// metadata. Optimizers exploring many candidate changes should keep
+    /// the number of live snapshots small.
+    pub fn checkpoint(&self) -> GugSnapshot {
+        GugSnapshot { gug: self.clone() }
+    }
+
+    /// Rolls the graph back to a snapshot taken with [`Gug::checkpoint`].
+    ///
+    /// Node and port indices are restored along with the graph, so indices
+    /// obtained before the snapshot was taken are valid again, while indices
+    /// of nodes added since then are invalidated.
+    pub fn restore(&mut self, snapshot: GugSnapshot) {
+        *self = snapshot.gug;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::component::operation::{circuit, Op};
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn restore_checkpoint() {
+        let mut circ = TestCircuit::qubits(2);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let cx = circ.gate(circuit::Op::CX, &[0, 1]);
+        let mut gug = circ.finish();
+        let snapshot = gug.checkpoint();
+
+        gug.remove_and_reconnect(h);
+        gug.set_optype(cx, Op::Circuit(circuit::Op::CZ));
+        gug.insert_after(cx, 1, Op::Circuit(circuit::Op::T));
+        assert!(!gug.diff(snapshot.gug()).is_empty());
+
+        gug.restore(snapshot.clone());
+        assert!(gug.diff(snapshot.gug()).is_empty());
+        assert_eq!(gug.optype(h), &Op::Circuit(circuit::Op::H));
+        assert_eq!(gug.output_target(h, 0), Some((cx, 0)));
+        assert_eq!(gug.validate(), Ok(()));
+    }
+}
@@ -0,0 +1,300 @@
+//! Summary statistics of a circuit.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use portgraph::{Direction, NodeIndex};
+
+use crate::component::operation::circuit::{self, eighth_turns};
+use crate::component::operation::Op;
+use crate::component::wire_type::{ConstValue, WireType};
+
+use super::Gug;
+
+/// A summary of the resources used by a circuit, computed by [`Gug::stats`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CircuitStats {
+    /// Number of qubit wires on the `Input` boundary.
+    pub qubits: usize,
+    /// Number of classical bit wires on the `Input` boundary.
+    pub bits: usize,
+    /// Number of non-boundary operations.
+    pub gates: usize,
+    /// Number of operations acting on exactly two qubits.
+    pub two_qubit_gates: usize,
+    /// Number of time slices in the ASAP schedule of the circuit.
+    pub depth: usize,
+    /// Number of operations of each kind, indexed by name.
+    pub gate_counts: BTreeMap<String, usize>,
+}
+
+/// Estimated number of T gates needed to synthesize a rotation by an
+/// arbitrary angle, roughly `3 log2(1/ε)` for a precision `ε` around `1e-10`.
+pub const ROTATION_T_COST: usize = 100;
+
+/// Error rates of operations, indexed by operation name, used to estimate the
+/// fidelity of a circuit with [`Gug::estimated_fidelity`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GateErrorModel {
+    errors: HashMap<String, f64>,
+    default_error: f64,
+}
+
+impl GateErrorModel {
+    /// Creates a model in which every operation is error-free.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the error rate of the operations with the given name.
+    pub fn with_error(mut self, name: impl Into<String>, error: f64) -> Self {
+        self.errors.insert(name.into(), error);
+        self
+    }
+
+    /// Sets the error rate of operations without an explicit rate.
+    pub fn with_default_error(mut self, error: f64) -> Self {
+        self.default_error = error;
+        self
+    }
+
+    /// Returns the error rate of the operations with the given name.
+    pub fn error_rate(&self, name: &str) -> f64 {
+        self.errors.get(name).copied().unwrap_or(self.default_error)
+    }
+}
+
+/// Costs of operations, indexed by operation name, used to compare circuits
+/// with [`Gug::cost`] and to rank rewrites with
+/// [`crate::rewrite::GugRewrite::cost_delta`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CostModel {
+    costs: HashMap<String, f64>,
+    default_cost: f64,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            costs: HashMap::new(),
+            default_cost: 1.0,
+        }
+    }
+}
+
+impl CostModel {
+    /// Creates a model in which every operation costs one, so that the cost
+    /// of a circuit is its number of operations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the cost of the operations with the given name.
+    pub fn with_cost(mut self, name: impl Into<String>, cost: f64) -> Self {
+        self.costs.insert(name.into(), cost);
+        self
+    }
+
+    /// Sets the cost of operations without an explicit cost.
+    pub fn with_default_cost(mut self, cost: f64) -> Self {
+        self.default_cost = cost;
+        self
+    }
+
+    /// Returns the cost of an operation. Boundary operations are free.
+    pub fn op_cost(&self, op: &Op) -> f64 {
+        if op.is_boundary() {
+            return 0.0;
+        }
+        self.costs.get(op.name()).copied().unwrap_or(self.default_cost)
+    }
+}
+
+impl Gug {
+    /// Returns the total cost of the operations of the circuit.
+    pub fn cost(&self, model: &CostModel) -> f64 {
+        self.nodes().map(|node| model.op_cost(self.optype(node))).sum()
+    }
+
+    /// Estimates the probability that the circuit runs without error, as the
+    /// product of `1 - error` over all non-boundary operations.
+    pub fn estimated_fidelity(&self, model: &GateErrorModel) -> f64 {
+        self.nodes()
+            .map(|node| self.optype(node))
+            .filter(|op| !op.is_boundary())
+            .map(|op| 1.0 - model.error_rate(op.name()))
+            .product()
+    }
+
+    /// Estimates the number of T gates needed to run the circuit
+    /// fault-tolerantly.
+    ///
+    /// `T` and `Tadj` count as one. `Rx`/`Rz` rotations count as one when
+    /// their angle is an odd multiple of π/4, and as [`ROTATION_T_COST`] when
+    /// it is not a multiple of π/4 or is not a known constant. Clifford
+    /// rotations, see [`circuit::Op::is_clifford`], are free.
+    pub fn t_count(&self) -> usize {
+        self.nodes().map(|node| self.t_cost(node)).sum()
+    }
+
+    /// Returns the largest number of T-like gates, those counted by
+    /// [`Gug::t_count`], on any path through the circuit.
+    pub fn t_depth(&self) -> usize {
+        let mut depths: HashMap<NodeIndex, usize> = HashMap::new();
+        let mut max_depth = 0;
+        for node in self.toposort() {
+            let pred_depth = self
+                .linked_nodes(node, Direction::Incoming)
+                .filter_map(|pred| depths.get(&pred).copied())
+                .max()
+                .unwrap_or(0);
+            let depth = pred_depth + usize::from(self.t_cost(node) > 0);
+            max_depth = max_depth.max(depth);
+            depths.insert(node, depth);
+        }
+        max_depth
+    }
+
+    /// Returns the estimated T-cost of a single operation.
+    fn t_cost(&self, node: NodeIndex) -> usize {
+        let Op::Circuit(op) = self.optype(node) else {
+            return 0;
+        };
+        let angle = match op {
+            circuit::Op::T | circuit::Op::Tadj => return 1,
+            _ if op.is_clifford() => return 0,
+            circuit::Op::Rx(angle) | circuit::Op::Rz(angle) => Some(*angle),
+            circuit::Op::RxF64 | circuit::Op::RzF64 => self
+                .input_source(node, 1)
+                .and_then(|(source, _)| match self.optype(source) {
+                    Op::Circuit(circuit::Op::Const(ConstValue::Angle(a))) => Some(*a),
+                    _ => None,
+                }),
+            _ => return 0,
+        };
+        match angle.as_ref().and_then(eighth_turns) {
+            Some(n) if n % 2 == 0 => 0,
+            Some(_) => 1,
+            None => ROTATION_T_COST,
+        }
+    }
+
+    /// Computes the summary statistics of the circuit in a single traversal.
+    pub fn stats(&self) -> CircuitStats {
+        let mut stats = CircuitStats::default();
+        if let Ok((input, _)) = self.boundary() {
+            for port in self.node_ports(input, Direction::Outgoing) {
+                match self.port_type(port) {
+                    WireType::Qubit => stats.qubits += 1,
+                    WireType::LinearBit | WireType::Bool => stats.bits += 1,
+                    _ => {}
+                }
+            }
+        }
+
+        let mut end: HashMap<NodeIndex, usize> = HashMap::new();
+        for node in self.toposort() {
+            let start = self
+                .linked_nodes(node, Direction::Incoming)
+                .map(|pred| end[&pred])
+                .max()
+                .unwrap_or(0);
+            let gate = self.is_scheduled_gate(node);
+            end.insert(node, start + usize::from(gate));
+            stats.depth = stats.depth.max(end[&node]);
+
+            let op = self.optype(node);
+            if op.is_boundary() {
+                continue;
+            }
+            stats.gates += 1;
+            if self.is_two_qubit_gate(node) {
+                stats.two_qubit_gates += 1;
+            }
+            *stats.gate_counts.entry(op.display_name().into_owned()).or_default() += 1;
+        }
+        stats
+    }
+}
+
+impl fmt::Display for CircuitStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "qubits:          {}", self.qubits)?;
+        writeln!(f, "bits:            {}", self.bits)?;
+        writeln!(f, "gates:           {}", self.gates)?;
+        writeln!(f, "two-qubit gates: {}", self.two_qubit_gates)?;
+        writeln!(f, "depth:           {}", self.depth)?;
+        for (name, count) in &self.gate_counts {
+            writeln!(f, "  {name}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::wire_type::AngleValue;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn t_count() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::T, &[0]);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::T, &[1]);
+        let gug = circ.finish();
+        assert_eq!(gug.t_count(), 2);
+        assert_eq!(gug.t_depth(), 1);
+
+        let mut circ = TestCircuit::qubits(1);
+        circ.gate(circuit::Op::Tadj, &[0]);
+        circ.gate(circuit::Op::Rz(AngleValue::F64(0.75)), &[0]);
+        circ.gate(circuit::Op::Rz(AngleValue::F64(0.5)), &[0]);
+        let angle = circ.constant(ConstValue::f64_angle(0.1));
+        circ.gate_with_args(circuit::Op::RzF64, &[0], &[(angle, 0)]);
+        let gug = circ.finish();
+        assert_eq!(gug.t_count(), 2 + ROTATION_T_COST);
+        assert_eq!(gug.t_depth(), 3);
+    }
+
+    #[test]
+    fn stats() {
+        let mut circ =
+            TestCircuit::new(&[WireType::Qubit, WireType::Qubit, WireType::LinearBit]);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::H, &[1]);
+        circ.gate(circuit::Op::Measure, &[1, 2]);
+        let gug = circ.finish();
+
+        let stats = gug.stats();
+        assert_eq!(stats.qubits, 2);
+        assert_eq!(stats.bits, 1);
+        assert_eq!(stats.gates, 4);
+        assert_eq!(stats.two_qubit_gates, 1);
+        assert_eq!(stats.depth, 4);
+        assert_eq!(stats.depth, gug.depth());
+        assert_eq!(stats.gate_counts["H"], 2);
+        assert_eq!(stats.gate_counts["CX"], 1);
+        assert_eq!(stats.gate_counts["Measure"], 1);
+        assert!(stats.to_string().contains("two-qubit gates: 1"));
+    }
+
+    #[test]
+    fn estimated_fidelity() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::CX, &[1, 0]);
+        circ.gate(circuit::Op::T, &[1]);
+        let gug = circ.finish();
+
+        let model = GateErrorModel::new()
+            .with_error("CX", 0.01)
+            .with_error("H", 0.001);
+        let expected = 0.999 * 0.99 * 0.99;
+        assert!((gug.estimated_fidelity(&model) - expected).abs() < 1e-12);
+        assert_eq!(gug.estimated_fidelity(&GateErrorModel::new()), 1.0);
+    }
+}
@@ -0,0 +1,607 @@
+//! Extraction of subcircuits into standalone graphs, and their boxing into
+//! opaque operations.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use portgraph::{Direction, NodeIndex, PortIndex};
+use thiserror::Error;
+
+use crate::component::operation::{circuit, CustomOp, Op, ToGUGFail};
+use crate::component::wire_type::Signature;
+
+use super::{BoundaryError, Gug};
+
+/// Error returned when extracting, boxing, or inlining subcircuits.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum BoxError {
+    #[error("cannot extract an empty set of nodes")]
+    Empty,
+    #[error("the node set is not convex")]
+    NotConvex,
+    #[error("node {0:?} is not an opaque operation with a definition")]
+    NotInlinable(NodeIndex),
+    #[error("the definition of node {0:?} does not match its ports")]
+    SignatureMismatch(NodeIndex),
+}
+
+/// A subcircuit removed from a graph by [`Gug::extract_subcircuit`].
+#[derive(Clone, Debug)]
+pub struct Subcircuit {
+    /// The extracted nodes, between new `Input` and `Output` boundary nodes.
+    pub gug: Gug,
+    /// The output ports in the host graph that fed the subcircuit, in the
+    /// order of the `Input` node's ports. They are left unlinked.
+    pub inputs: Vec<PortIndex>,
+    /// The input ports in the host graph fed by the subcircuit, in the order
+    /// of the `Output` node's ports. They are left unlinked.
+    pub outputs: Vec<PortIndex>,
+}
+
+/// An opaque operation wrapping a subcircuit, created by [`Gug::box_subcircuit`].
+///
+/// The boundary wires are all exposed as non-linear ports of the operation.
+#[derive(Clone, Debug)]
+pub struct BoxedCircuit {
+    pub name: String,
+    pub circuit: Gug,
+    signature: Signature,
+}
+
+impl BoxedCircuit {
+    /// Wraps a circuit with unique `Input` and `Output` boundary nodes.
+    ///
+    /// The leading wires with the same linear type on both boundaries form the
+    /// linear part of the signature, keeping the order of the ports.
+    pub fn new(name: String, circuit: Gug) -> Result<Self, BoundaryError> {
+        let inputs = circuit.input_wires()?;
+        let outputs = circuit.output_wires()?;
+        let num_linear = inputs
+            .iter()
+            .zip(&outputs)
+            .take_while(|(input, output)| input == output && input.is_linear())
+            .count();
+        let signature = Signature::new(
+            inputs[..num_linear].to_vec(),
+            [
+                inputs[num_linear..].to_vec(),
+                outputs[num_linear..].to_vec(),
+            ],
+        );
+        Ok(Self {
+            name,
+            circuit,
+            signature,
+        })
+    }
+}
+
+impl CustomOp for BoxedCircuit {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn signature(&self) -> Signature {
+        self.signature.clone()
+    }
+
+    fn to_gug(&self) -> Result<Gug, ToGUGFail> {
+        Ok(self.circuit.clone())
+    }
+}
+
+impl Gug {
+    /// Adds a copy of a node from another graph, with the same operation and
+    /// port types. Metadata is not copied.
+    pub(crate) fn copy_node_from(&mut self, other: &Gug, node: NodeIndex) -> NodeIndex {
+        let types = |direction| {
+            other
+                .node_ports(node, direction)
+                .map(|p| other.port_type(p))
+                .collect()
+        };
+        let signature =
+            Signature::new_nonlinear(types(Direction::Incoming), types(Direction::Outgoing));
+        self.add_node_with_signature(other.optype(node).clone(), signature)
+    }
+
+    /// Removes a convex set of nodes from the graph, returning them as a
+    /// standalone circuit together with the host ports left dangling.
+    pub fn extract_subcircuit(&mut self, nodes: &[NodeIndex]) -> Result<Subcircuit, BoxError> {
+        if nodes.is_empty() {
+            return Err(BoxError::Empty);
+        }
+        if !self.is_convex(nodes) {
+            return Err(BoxError::NotConvex);
+        }
+
+        let (gug, inputs, outputs) = self.copy_region(nodes);
+        let inputs = inputs
+            .into_iter()
+            .map(|port| self.port_link(port).unwrap())
+            .collect();
+        let outputs = outputs
+            .into_iter()
+            .map(|port| self.port_link(port).unwrap())
+            .collect();
+        for &node in nodes {
+            self.remove_node(node);
+        }
+        Ok(Subcircuit {
+            gug,
+            inputs,
+            outputs,
+        })
+    }
+
+    /// Copies a convex set of nodes into a standalone circuit, between new
+    /// `Input` and `Output` boundary nodes, leaving this graph unchanged.
+    ///
+    /// Unlike [`Gug::extract_subcircuit`], convexity is not checked: copying
+    /// a non-convex set gives a circuit whose boundary does not respect the
+    /// ordering of the original.
+    pub fn subgraph_to_gug(&self, nodes: &[NodeIndex]) -> Gug {
+        self.copy_region(nodes).0
+    }
+
+    /// Splits the circuit into blocks at its `Barrier` nodes, copying each
+    /// block into a standalone circuit with its own boundary nodes.
+    ///
+    /// A barrier only separates the wires it spans, so gates on other wires
+    /// stay in the block before it unless they follow a gate placed after it.
+    /// Parameter sources such as `Const` nodes are placed in the first block
+    /// that uses them. Barriers are not copied, and empty blocks are skipped.
+    pub fn split_at_barriers(&self) -> Vec<Gug> {
+        let is_barrier = |node| matches!(self.optype(node), Op::Circuit(circuit::Op::Barrier));
+        let sources = |node| {
+            let num_inputs = self.node_ports(node, Direction::Incoming).count();
+            (0..num_inputs).filter_map(move |offset| self.input_source(node, offset))
+        };
+
+        let order = self.toposort();
+        let mut block: HashMap<NodeIndex, usize> = HashMap::new();
+        for &node in &order {
+            let start = sources(node)
+                .map(|(src, _)| block[&src] + usize::from(is_barrier(src)))
+                .max()
+                .unwrap_or(0);
+            block.insert(node, start);
+        }
+        for &node in order.iter().rev() {
+            if self.optype(node).is_boundary() || sources(node).next().is_some() {
+                continue;
+            }
+            let num_outputs = self.node_ports(node, Direction::Outgoing).count();
+            let first_use = (0..num_outputs)
+                .filter_map(|offset| self.output_target(node, offset))
+                .filter_map(|(target, _)| block.get(&target).copied())
+                .min();
+            if let Some(first_use) = first_use {
+                block.insert(node, first_use);
+            }
+        }
+
+        let mut blocks: Vec<Vec<NodeIndex>> = Vec::new();
+        for node in order {
+            if self.optype(node).is_boundary() || is_barrier(node) {
+                continue;
+            }
+            let index = block[&node];
+            if blocks.len() <= index {
+                blocks.resize_with(index + 1, Vec::new);
+            }
+            blocks[index].push(node);
+        }
+        blocks
+            .into_iter()
+            .filter(|nodes| !nodes.is_empty())
+            .map(|nodes| self.copy_region(&nodes).0)
+            .collect()
+    }
+
+    /// Groups structurally identical regions of the circuit with at least
+    /// `min_size` nodes, as candidates for boxing into a shared definition
+    /// with [`Gug::box_subcircuit`].
+    ///
+    /// The regions are the connected components left after removing the
+    /// `Input` and `Output` nodes, so they are always convex. Regions are
+    /// matched by a local structural hash, built from the operation of each
+    /// node and the hashes of the nodes feeding its inputs in port order.
+    /// Only groups of at least two regions are returned, ordered by their
+    /// first node, with the nodes of each region in topological order.
+    pub fn find_repeated_subcircuits(&self, min_size: usize) -> Vec<Vec<Vec<NodeIndex>>> {
+        let order = self.toposort();
+        let position: HashMap<NodeIndex, usize> =
+            order.iter().enumerate().map(|(i, &n)| (n, i)).collect();
+        let inner = |node: NodeIndex| {
+            position.contains_key(&node) && !self.optype(node).is_boundary()
+        };
+
+        let mut hashes: HashMap<NodeIndex, u64> = HashMap::new();
+        for &node in order.iter().filter(|&&n| inner(n)) {
+            let mut hasher = DefaultHasher::new();
+            format!("{:?}", self.optype(node)).hash(&mut hasher);
+            let num_inputs = self.node_ports(node, Direction::Incoming).count();
+            for offset in 0..num_inputs {
+                // Wires entering the region are hashed as open.
+                let source = self.input_source(node, offset).filter(|&(n, _)| inner(n));
+                source.map(|(n, offset)| (hashes[&n], offset)).hash(&mut hasher);
+            }
+            hashes.insert(node, hasher.finish());
+        }
+
+        let mut seen = HashSet::new();
+        let mut groups: Vec<(u64, Vec<Vec<NodeIndex>>)> = Vec::new();
+        for &start in &order {
+            if !inner(start) || !seen.insert(start) {
+                continue;
+            }
+            let mut region = vec![start];
+            let mut stack = vec![start];
+            while let Some(node) = stack.pop() {
+                let neighbours = self
+                    .linked_nodes(node, Direction::Incoming)
+                    .chain(self.linked_nodes(node, Direction::Outgoing));
+                for neighbour in neighbours {
+                    if inner(neighbour) && seen.insert(neighbour) {
+                        region.push(neighbour);
+                        stack.push(neighbour);
+                    }
+                }
+            }
+            if region.len() < min_size {
+                continue;
+            }
+            region.sort_by_key(|n| position[n]);
+
+            let mut node_hashes: Vec<u64> = region.iter().map(|n| hashes[n]).collect();
+            node_hashes.sort_unstable();
+            let mut hasher = DefaultHasher::new();
+            node_hashes.hash(&mut hasher);
+            let key = hasher.finish();
+            match groups.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, regions)) => regions.push(region),
+                None => groups.push((key, vec![region])),
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(_, regions)| regions)
+            .filter(|regions| regions.len() > 1)
+            .collect()
+    }
+
+    /// Copies a set of nodes into a new circuit with boundary nodes, returning
+    /// it together with the ports of `nodes` linked outside the set, in the
+    /// order of the new `Input` and `Output` node ports.
+    fn copy_region(&self, nodes: &[NodeIndex]) -> (Gug, Vec<PortIndex>, Vec<PortIndex>) {
+        let mut gug = Gug::new();
+        let map: HashMap<NodeIndex, NodeIndex> = nodes
+            .iter()
+            .map(|&n| (n, gug.copy_node_from(self, n)))
+            .collect();
+
+        // Classify the links of the extracted nodes.
+        let mut internal = Vec::new();
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        for &node in nodes {
+            for (offset, port) in self.node_ports(node, Direction::Incoming).enumerate() {
+                let Some((src, src_offset)) = self.input_source(node, offset) else { continue };
+                match map.get(&src) {
+                    Some(&new_src) => internal.push((new_src, src_offset, map[&node], offset)),
+                    None => inputs.push((port, map[&node], offset)),
+                }
+            }
+            for (offset, port) in self.node_ports(node, Direction::Outgoing).enumerate() {
+                match self.output_target(node, offset) {
+                    Some((target, _)) if !map.contains_key(&target) => {
+                        outputs.push((port, map[&node], offset))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let input_types = inputs.iter().map(|&(p, _, _)| self.port_type(p)).collect();
+        let output_types = outputs.iter().map(|&(p, _, _)| self.port_type(p)).collect();
+        let input = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Input),
+            Signature::new_nonlinear(vec![], input_types),
+        );
+        let output = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Output),
+            Signature::new_nonlinear(output_types, vec![]),
+        );
+        for (src, src_offset, dst, dst_offset) in internal {
+            gug.connect(src, src_offset, dst, dst_offset).unwrap();
+        }
+        for (i, &(_, dst, dst_offset)) in inputs.iter().enumerate() {
+            gug.connect(input, i, dst, dst_offset).unwrap();
+        }
+        for (i, &(_, src, src_offset)) in outputs.iter().enumerate() {
+            gug.connect(src, src_offset, output, i).unwrap();
+        }
+
+        let inputs = inputs.into_iter().map(|(port, _, _)| port).collect();
+        let outputs = outputs.into_iter().map(|(port, _, _)| port).collect();
+        (gug, inputs, outputs)
+    }
+
+    /// Replaces a convex set of nodes with a single opaque node wrapping them,
+    /// which can be expanded again with [`Gug::inline_custom`].
+    pub fn box_subcircuit(
+        &mut self,
+        nodes: &[NodeIndex],
+        name: String,
+    ) -> Result<NodeIndex, BoxError> {
+        let Subcircuit {
+            gug,
+            inputs,
+            outputs,
+        } = self.extract_subcircuit(nodes)?;
+        let boxed = BoxedCircuit::new(name, gug).expect("extracted subcircuits have a boundary");
+        let boxed = self.add_node(Op::Opaque(Box::new(boxed)));
+        for (i, src) in inputs.into_iter().enumerate() {
+            let port = self.input_port(boxed, i).unwrap();
+            self.link_ports(src, port).unwrap();
+        }
+        for (i, dst) in outputs.into_iter().enumerate() {
+            let port = self.output_port(boxed, i).unwrap();
+            self.link_ports(port, dst).unwrap();
+        }
+        Ok(boxed)
+    }
+
+    /// Replaces an opaque node with the circuit returned by its
+    /// [`CustomOp::to_gug`] definition.
+    ///
+    /// Returns the inserted nodes.
+    pub fn inline_custom(&mut self, node: NodeIndex) -> Result<Vec<NodeIndex>, BoxError> {
+        let Op::Opaque(op) = self.optype(node) else {
+            return Err(BoxError::NotInlinable(node));
+        };
+        let definition = op.to_gug().map_err(|_| BoxError::NotInlinable(node))?;
+        self.replace_with_circuit(node, &definition)
+    }
+
+    /// Replaces a node with a copy of a circuit whose `Input` and `Output`
+    /// boundary nodes match the node's inputs and outputs by offset.
+    ///
+    /// The global phase of the circuit is added to this one. Returns the
+    /// inserted nodes.
+    pub(crate) fn replace_with_circuit(
+        &mut self,
+        node: NodeIndex,
+        definition: &Gug,
+    ) -> Result<Vec<NodeIndex>, BoxError> {
+        let Ok((def_input, def_output)) = definition.boundary() else {
+            return Err(BoxError::SignatureMismatch(node));
+        };
+        let num_inputs = definition.node_ports(def_input, Direction::Outgoing).count();
+        let num_outputs = definition.node_ports(def_output, Direction::Incoming).count();
+        if num_inputs != self.node_ports(node, Direction::Incoming).count()
+            || num_outputs != self.node_ports(node, Direction::Outgoing).count()
+        {
+            return Err(BoxError::SignatureMismatch(node));
+        }
+
+        // The host ports connected to the node being replaced.
+        let sources: Vec<Option<PortIndex>> = (0..num_inputs)
+            .map(|i| self.port_link(self.input_port(node, i).unwrap()))
+            .collect();
+        let targets: Vec<Option<PortIndex>> = (0..num_outputs)
+            .map(|i| self.port_link(self.output_port(node, i).unwrap()))
+            .collect();
+        self.remove_node(node);
+
+        let (nodes, outputs) = self.splice(definition, &sources);
+        for (output, target) in outputs.into_iter().zip(targets) {
+            if let (Some(output), Some(target)) = (output, target) {
+                self.link_ports(output, target).unwrap();
+            }
+        }
+        self.add_global_phase(definition.global_phase());
+        Ok(nodes)
+    }
+
+    /// Copies the nodes of a circuit with `Input` and `Output` boundary nodes
+    /// into this graph, feeding the circuit's inputs from the `sources` ports.
+    ///
+    /// Regions nested in the circuit are copied along with their nodes.
+    ///
+    /// Returns the inserted nodes, and for each output of the circuit the
+    /// (unlinked) port producing it, which is one of the `sources` if the
+    /// input is wired directly to the output.
+    pub(crate) fn splice(
+        &mut self,
+        definition: &Gug,
+        sources: &[Option<PortIndex>],
+    ) -> (Vec<NodeIndex>, Vec<Option<PortIndex>>) {
+        let (def_input, def_output) = definition.boundary().ok().unzip();
+        let num_outputs = def_output
+            .map_or(0, |n| definition.node_ports(n, Direction::Incoming).count());
+
+        let mut map = HashMap::new();
+        for def_node in definition.nodes() {
+            if Some(def_node) != def_input && Some(def_node) != def_output {
+                map.insert(def_node, self.copy_node_from(definition, def_node));
+            }
+        }
+        for def_node in definition.nodes() {
+            for def_child in definition.children(def_node) {
+                if let (Some(&parent), Some(&child)) = (map.get(&def_node), map.get(&def_child)) {
+                    self.push_child(child, parent).unwrap();
+                }
+            }
+        }
+        let mut outputs = vec![None; num_outputs];
+        for def_node in definition.nodes() {
+            let num_ports = definition.node_ports(def_node, Direction::Outgoing).count();
+            for offset in 0..num_ports {
+                let Some((target, target_offset)) = definition.output_target(def_node, offset)
+                else {
+                    continue;
+                };
+                let src = match map.get(&def_node) {
+                    Some(&n) => self.output_port(n, offset),
+                    None if Some(def_node) == def_input => sources.get(offset).copied().flatten(),
+                    None => continue,
+                };
+                match map.get(&target) {
+                    Some(&n) => {
+                        if let (Some(src), Some(dst)) = (src, self.input_port(n, target_offset)) {
+                            self.link_ports(src, dst).unwrap();
+                        }
+                    }
+                    None if Some(target) == def_output => outputs[target_offset] = src,
+                    None => {}
+                }
+            }
+        }
+        (map.into_values().collect(), outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::wire_type::WireType;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn box_and_inline() {
+        let mut circ = TestCircuit::qubits(2);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let cx = circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::X, &[1]);
+        let mut gug = circ.finish();
+        let original = gug.to_text().unwrap();
+
+        let boxed = gug.box_subcircuit(&[h, cx], "HCX".to_string()).unwrap();
+        assert_eq!(gug.node_count(), 4);
+        assert_eq!(gug.optype(boxed).name(), "HCX");
+        assert_eq!(gug.signature(boxed).num_ports(), (2, 2));
+        let signature = gug.optype(boxed).signature();
+        assert_eq!(signature.linear, vec![WireType::Qubit; 2]);
+        assert!(signature.nonlinear.iter().all(Vec::is_empty));
+
+        let inserted = gug.inline_custom(boxed).unwrap();
+        assert_eq!(inserted.len(), 2);
+        assert_eq!(gug.node_count(), 5);
+        assert_eq!(gug.gate_counts(), Gug::from_text(&original).unwrap().gate_counts());
+
+        // The H feeds the control of the CX, which feeds the X on its target.
+        let input = gug.input_node().unwrap();
+        let (h, _) = gug.output_target(input, 0).unwrap();
+        assert_eq!(gug.optype(h).name(), "H");
+        let (cx, offset) = gug.output_target(h, 0).unwrap();
+        assert_eq!((gug.optype(cx).name(), offset), ("CX", 0));
+        let (x, _) = gug.output_target(cx, 1).unwrap();
+        assert_eq!(gug.optype(x).name(), "X");
+    }
+
+    #[test]
+    fn split_at_barriers() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.barrier(&[0, 1]);
+        circ.gate(circuit::Op::X, &[0]);
+        circ.gate(circuit::Op::CZ, &[0, 1]);
+        let gug = circ.finish();
+
+        let blocks = gug.split_at_barriers();
+        assert_eq!(blocks.len(), 2);
+        let names = |block: &Gug| {
+            let mut names: Vec<String> = block.gate_counts().into_keys().collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&blocks[0]), ["CX", "H"]);
+        assert_eq!(names(&blocks[1]), ["CZ", "X"]);
+        for block in &blocks {
+            assert_eq!(block.validate(), Ok(()));
+            let (input, output) = block.boundary().unwrap();
+            assert_eq!(block.num_outputs(input), 2);
+            assert_eq!(block.num_inputs(output), 2);
+        }
+    }
+
+    #[test]
+    fn repeated_subcircuits() {
+        let mut circ = TestCircuit::qubits(4);
+        let mut sequences = Vec::new();
+        for qubit in 0..3 {
+            let h = circ.gate(circuit::Op::H, &[qubit]);
+            let t = circ.gate(circuit::Op::T, &[qubit]);
+            sequences.push(vec![h, t]);
+        }
+        circ.gate(circuit::Op::H, &[3]);
+        circ.gate(circuit::Op::S, &[3]);
+        let gug = circ.finish();
+
+        let mut groups = gug.find_repeated_subcircuits(2);
+        assert_eq!(groups.len(), 1);
+        groups[0].sort();
+        assert_eq!(groups[0], sequences);
+        assert!(gug.find_repeated_subcircuits(3).is_empty());
+    }
+
+    #[test]
+    fn split_at_partial_barrier() {
+        let mut circ = TestCircuit::qubits(3);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.barrier(&[0, 1]);
+        circ.gate(circuit::Op::X, &[2]);
+        circ.gate(circuit::Op::H, &[1]);
+        circ.gate(circuit::Op::CX, &[1, 2]);
+        let gug = circ.finish();
+
+        // The X on the unspanned wire stays in the first block.
+        let blocks = gug.split_at_barriers();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].gate_counts()["H"], 1);
+        assert_eq!(blocks[0].gate_counts()["X"], 1);
+        assert_eq!(blocks[1].gate_counts()["H"], 1);
+        assert_eq!(blocks[1].gate_counts()["CX"], 1);
+    }
+
+    #[test]
+    fn copy_region() {
+        let mut circ = TestCircuit::qubits(2);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let cx = circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::X, &[1]);
+        let gug = circ.finish();
+        let original = gug.to_text().unwrap();
+
+        let copy = gug.subgraph_to_gug(&[h, cx]);
+        assert_eq!(copy.validate(), Ok(()));
+        assert_eq!(copy.node_count(), 4);
+        assert_eq!(copy.gate_counts()["H"], 1);
+        assert_eq!(copy.gate_counts()["CX"], 1);
+        let (input, output) = copy.boundary().unwrap();
+        assert_eq!(copy.num_outputs(input), 2);
+        assert_eq!(copy.num_inputs(output), 2);
+
+        assert_eq!(gug.to_text().unwrap(), original);
+        assert_eq!(gug.validate(), Ok(()));
+    }
+
+    #[test]
+    fn reject_non_convex() {
+        let mut circ = TestCircuit::qubits(2);
+        let cx1 = circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::X, &[1]);
+        let cx2 = circ.gate(circuit::Op::CX, &[0, 1]);
+        let mut gug = circ.finish();
+
+        assert_eq!(
+            gug.box_subcircuit(&[cx1, cx2], "box".to_string()).unwrap_err(),
+            BoxError::NotConvex
+        );
+        assert_eq!(gug.node_count(), 5);
+    }
+}
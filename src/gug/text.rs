@@ -0,0 +1,1007 @@
+//! A line-based textual serialization format for graphs.
+//!
+//! The text starts with a header giving the version of the format,
+//!
+//! ```text
+//! format <version>
+//! ```
+//!
+//! which must match [`TEXT_FORMAT_VERSION`]. Text without a header predates
+//! the versioning and is read as version 1. A non-zero
+//! [`Gug::global_phase`] follows as
+//!
+//! ```text
+//! phase <angle>
+//! ```
+//!
+//! with the angle in half-turns. Each node is then written on a line of the
+//! form
+//!
+//! ```text
+//! node <id> <op> <input types> <output types>
+//! ```
+//!
+//! where the port type lists are comma-separated (or `-` when empty), and each
+//! edge as
+//!
+//! ```text
+//! edge <node>:<output offset> <node>:<input offset>
+//! ```
+//!
+//! and each node placed in the region of a control flow node as
+//!
+//! ```text
+//! parent <child> <parent>
+//! ```
+//!
+//! in the order of the region's children. Metadata entries that differ from
+//! the default of their component follow as
+//!
+//! ```text
+//! meta <node> <component> <value>
+//! meta <node>:in<offset> <component> <value>
+//! meta <node>:out<offset> <component> <value>
+//! ```
+//!
+//! for node and port metadata respectively. The crate's own components are
+//! supported: `DebugData` labels, `SourceSpan`s, `GateDuration`s,
+//! `QubitNames` and `Ancillas` on nodes, and `PhysicalQubit`s and `CregBit`s
+//! on ports. Strings and collections in values are written as JSON.
+//!
+//! Angles are written in half-turns, as a float or a fraction such as `1/4`.
+//! A `Symbolic` angle is written as an expression over angles and `$name`
+//! parameters, built with `add(<a>,<b>)`, `neg(<a>)` and `mul(<a>,<angle>)`,
+//! where parameter names are made of alphanumeric characters and `_`.
+//!
+//! Node ids are assigned densely in iteration order. Blank lines and lines
+//! starting with `#` are ignored. An opaque operation with a
+//! [`CustomOp::payload`] is written as `Opaque(<name>:<payload>)`, and is
+//! rebuilt from an [`OpRegistry`] by [`Gug::from_text_with_registry`].
+//! Metadata of other components is type-erased and other opaque operations
+//! cannot be reconstructed, so [`Gug::to_text`] fails on graphs holding
+//! either.
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use num_rational::Rational64;
+use portgraph::{Direction, NodeIndex, PortIndex};
+use thiserror::Error;
+
+use crate::component::debug::{DebugData, SourceSpan};
+use crate::component::operation::{circuit, ControlFlowOp, CustomOp, Op};
+use crate::component::physical::{GateDuration, PhysicalQubit};
+use crate::component::register::{Ancillas, CregBit, QubitNames};
+use crate::component::symbolic::SymbolicAngle;
+use crate::component::wire_type::{AngleValue, ConstValue, Quat, Rational, Signature, WireType};
+use crate::extension::OpRegistry;
+
+use super::{Gug, NodeMetadata};
+
+/// The version of the textual format written by [`Gug::to_text`]. It is
+/// increased with every change to the format that older readers would
+/// misinterpret.
+pub const TEXT_FORMAT_VERSION: u32 = 1;
+
+/// Error returned when reading the textual representation of a graph.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum DeserializeError {
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error("unsupported format version {found}, expected {expected}")]
+    VersionMismatch { found: u32, expected: u32 },
+}
+
+/// Error returned when a graph cannot be written in the textual format.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum SerializeError {
+    #[error("node {0:?} holds the opaque operation {1}, which cannot be serialized")]
+    OpaqueOp(NodeIndex, String),
+    #[error("node {0:?} has metadata of an unknown component, which cannot be serialized")]
+    NodeMetadata(NodeIndex),
+    #[error("node {0:?} has a symbolic angle with a parameter name that cannot be serialized")]
+    SymbolName(NodeIndex),
+    #[error("port {0:?} has metadata of an unknown component, which cannot be serialized")]
+    PortMetadata(PortIndex),
+}
+
+/// Error returned when parsing the textual representation of a graph.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[error("line {line}: {message}")]
+pub struct ParseError {
+    /// The one-based line number of the error.
+    pub line: usize,
+    /// A description of the error.
+    pub message: String,
+}
+
+impl Gug {
+    /// Serializes the graph to its textual representation.
+    ///
+    /// See the module documentation for a description of the format. Fails
+    /// if a node holds an opaque operation without a whitespace-free payload
+    /// or a symbolic angle with an invalid parameter name, or if a node or
+    /// port has a metadata entry of a component unknown to the format, other
+    /// than the default of that component, since these could not be read
+    /// back.
+    pub fn to_text(&self) -> Result<String, SerializeError> {
+        for node in self.nodes() {
+            if let Op::Opaque(op) = self.optype(node) {
+                if opaque_to_text(op.as_ref()).is_none() {
+                    return Err(SerializeError::OpaqueOp(node, op.name().to_string()));
+                }
+            }
+            if let Op::Circuit(circuit::Op::Symbolic(expr)) = self.optype(node) {
+                if symbolic_to_text(expr).is_none() {
+                    return Err(SerializeError::SymbolName(node));
+                }
+            }
+            if self.has_unknown_node_metadata(node) {
+                return Err(SerializeError::NodeMetadata(node));
+            }
+            if let Some(port) = self
+                .inputs(node)
+                .chain(self.outputs(node))
+                .find(|&port| self.has_unknown_port_metadata(port))
+            {
+                return Err(SerializeError::PortMetadata(port));
+            }
+        }
+        let mut text = self.write_text();
+        self.write_metadata(&mut text);
+        Ok(text)
+    }
+
+    /// Checks whether a node has a metadata entry other than the default of
+    /// its component, for a component not written by [`Gug::write_metadata`].
+    /// Entries are type-erased, so they are compared through their `Debug`
+    /// rendering.
+    fn has_unknown_node_metadata(&self, node: NodeIndex) -> bool {
+        let known = [
+            TypeId::of::<DebugData>(),
+            TypeId::of::<SourceSpan>(),
+            TypeId::of::<GateDuration>(),
+            TypeId::of::<QubitNames>(),
+            TypeId::of::<Ancillas>(),
+        ];
+        self.node_metadata
+            .iter()
+            .filter(|(type_id, _)| !known.contains(type_id))
+            .any(|(type_id, meta)| {
+                format!("{:?}", meta[node])
+                    != format!("{:?}", self.node_metadata_defaults[type_id])
+            })
+    }
+
+    /// Checks whether a port has a metadata entry other than the default of
+    /// its component, for a component not written by [`Gug::write_metadata`],
+    /// like [`Gug::has_unknown_node_metadata`].
+    fn has_unknown_port_metadata(&self, port: PortIndex) -> bool {
+        let known = [TypeId::of::<PhysicalQubit>(), TypeId::of::<CregBit>()];
+        self.port_metadata
+            .iter()
+            .filter(|(type_id, _)| !known.contains(type_id))
+            .any(|(type_id, meta)| {
+                format!("{:?}", meta[port])
+                    != format!("{:?}", self.port_metadata_defaults[type_id])
+            })
+    }
+
+    /// Writes a `meta` line for each entry of the crate's own metadata
+    /// components that differs from the default of its component.
+    fn write_metadata(&self, text: &mut String) {
+        for (id, node) in self.nodes().enumerate() {
+            let mut meta = |target: &str, component: &str, value: String| {
+                writeln!(text, "meta {target} {component} {value}").unwrap();
+            };
+            let target = id.to_string();
+            if let Some(label) = self
+                .node_metadata::<DebugData>(node)
+                .and_then(|d| d.label.as_ref())
+            {
+                meta(&target, "DebugData", to_json(label));
+            }
+            if let Some(span) = self.source_span(node) {
+                meta(&target, "SourceSpan", span.to_string());
+            }
+            if let Some(&GateDuration(duration)) = self
+                .node_metadata::<GateDuration>(node)
+                .filter(|&&d| d != GateDuration::default())
+            {
+                meta(&target, "GateDuration", format!("{duration:?}"));
+            }
+            if let Some(names) = self
+                .node_metadata::<QubitNames>(node)
+                .filter(|n| !n.0.is_empty())
+            {
+                meta(&target, "QubitNames", to_json(&names.0));
+            }
+            if let Some(ancillas) = self
+                .node_metadata::<Ancillas>(node)
+                .filter(|&a| a != &Ancillas::default())
+            {
+                meta(&target, "Ancillas", to_json(&(&ancillas.allocated, &ancillas.free)));
+            }
+            for (direction, side) in [(Direction::Incoming, "in"), (Direction::Outgoing, "out")] {
+                for (offset, port) in self.node_ports(node, direction).enumerate() {
+                    let target = format!("{id}:{side}{offset}");
+                    if let Some(qubit) = self.physical_qubit(port) {
+                        meta(&target, "PhysicalQubit", qubit.to_string());
+                    }
+                    if let Some(creg) = self.creg_of(port) {
+                        meta(&target, "CregBit", to_json(&creg));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes the textual representation of the graph, leaving out metadata
+    /// and writing opaque operations without a payload by name.
+    pub(super) fn write_text(&self) -> String {
+        let ids: HashMap<NodeIndex, usize> =
+            self.nodes().enumerate().map(|(i, n)| (n, i)).collect();
+        let mut text = format!("format {TEXT_FORMAT_VERSION}\n");
+        if self.global_phase != AngleValue::default() {
+            writeln!(text, "phase {}", angle_to_text(&self.global_phase)).unwrap();
+        }
+        for node in self.nodes() {
+            let types = |direction| {
+                let types: Vec<String> = self
+                    .node_ports(node, direction)
+                    .map(|p| format!("{:?}", self.port_type(p)))
+                    .collect();
+                if types.is_empty() {
+                    "-".to_string()
+                } else {
+                    types.join(",")
+                }
+            };
+            writeln!(
+                text,
+                "node {} {} {} {}",
+                ids[&node],
+                op_to_text(self.optype(node)),
+                types(Direction::Incoming),
+                types(Direction::Outgoing)
+            )
+            .unwrap();
+        }
+        for node in self.nodes() {
+            for offset in 0..self.node_ports(node, Direction::Outgoing).count() {
+                if let Some((target, target_offset)) = self.output_target(node, offset) {
+                    writeln!(
+                        text,
+                        "edge {}:{} {}:{}",
+                        ids[&node], offset, ids[&target], target_offset
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        for node in self.nodes() {
+            for child in self.children(node) {
+                writeln!(text, "parent {} {}", ids[&child], ids[&node]).unwrap();
+            }
+        }
+        text
+    }
+
+    /// Parses a graph from its textual representation, as produced by
+    /// [`Gug::to_text`].
+    ///
+    /// Fails with [`DeserializeError::VersionMismatch`] if the text was written
+    /// with a different version of the format. Opaque operations are rejected,
+    /// see [`Gug::from_text_with_registry`].
+    pub fn from_text(text: &str) -> Result<Gug, DeserializeError> {
+        Self::from_text_with_registry(text, &OpRegistry::new())
+    }
+
+    /// Parses a graph from its textual representation, rebuilding opaque
+    /// operations with the factories of `registry`.
+    pub fn from_text_with_registry(
+        text: &str,
+        registry: &OpRegistry,
+    ) -> Result<Gug, DeserializeError> {
+        let mut gug = Gug::new();
+        let mut nodes: HashMap<usize, NodeIndex> = HashMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let err = |message: String| ParseError {
+                line: i + 1,
+                message,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // Metadata values may contain spaces, so only the first fields
+            // are split off.
+            if let Some(meta) = line.strip_prefix("meta ") {
+                let mut fields = meta.splitn(3, ' ');
+                let (Some(target), Some(component), Some(value)) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    return Err(err(format!("invalid metadata {meta:?}")).into());
+                };
+                metadata_from_text(&mut gug, &nodes, target, component, value)
+                    .ok_or_else(|| err(format!("invalid metadata {meta:?}")))?;
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                ["format", version] => {
+                    let found: u32 = version
+                        .parse()
+                        .map_err(|_| err(format!("invalid format version {version}")))?;
+                    if found != TEXT_FORMAT_VERSION {
+                        return Err(DeserializeError::VersionMismatch {
+                            found,
+                            expected: TEXT_FORMAT_VERSION,
+                        });
+                    }
+                }
+                ["phase", angle] => {
+                    let angle = angle_from_text(angle)
+                        .ok_or_else(|| err(format!("invalid angle {angle}")))?;
+                    gug.add_global_phase(angle);
+                }
+                ["node", id, op, inputs, outputs] => {
+                    let id: usize = id.parse().map_err(|_| err(format!("invalid id {id}")))?;
+                    let op = op_from_text(op, registry)
+                        .ok_or_else(|| err(format!("invalid op {op}")))?;
+                    let inputs = parse_types(inputs).ok_or_else(|| err("invalid types".into()))?;
+                    let outputs =
+                        parse_types(outputs).ok_or_else(|| err("invalid types".into()))?;
+                    let node =
+                        gug.add_node_with_signature(op, Signature::new_nonlinear(inputs, outputs));
+                    if nodes.insert(id, node).is_some() {
+                        return Err(err(format!("duplicate node id {id}")).into());
+                    }
+                }
+                ["edge", from, to] => {
+                    let endpoint = |s: &str| -> Option<(NodeIndex, usize)> {
+                        let (id, offset) = s.split_once(':')?;
+                        Some((*nodes.get(&id.parse().ok()?)?, offset.parse().ok()?))
+                    };
+                    let (from, from_offset) =
+                        endpoint(from).ok_or_else(|| err(format!("invalid endpoint {from}")))?;
+                    let (to, to_offset) =
+                        endpoint(to).ok_or_else(|| err(format!("invalid endpoint {to}")))?;
+                    gug.connect(from, from_offset, to, to_offset)
+                        .map_err(|e| err(e.to_string()))?;
+                }
+                ["parent", child, parent] => {
+                    let node =
+                        |id: &str| -> Option<NodeIndex> { nodes.get(&id.parse().ok()?).copied() };
+                    let child = node(child).ok_or_else(|| err(format!("invalid node {child}")))?;
+                    let parent =
+                        node(parent).ok_or_else(|| err(format!("invalid node {parent}")))?;
+                    gug.push_child(child, parent)
+                        .map_err(|e| err(format!("{e:?}")))?;
+                }
+                _ => return Err(err(format!("unrecognized line {line:?}")).into()),
+            }
+        }
+        Ok(gug)
+    }
+}
+
+fn to_json<T: serde::Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value).expect("metadata values serialize to JSON")
+}
+
+/// Sets a metadata entry read from a `meta` line, returning `None` if the
+/// target, the component or the value is invalid.
+fn metadata_from_text(
+    gug: &mut Gug,
+    nodes: &HashMap<usize, NodeIndex>,
+    target: &str,
+    component: &str,
+    value: &str,
+) -> Option<()> {
+    let (id, port) = match target.split_once(':') {
+        Some((id, port)) => (id, Some(port)),
+        None => (target, None),
+    };
+    let node = *nodes.get(&id.parse().ok()?)?;
+    let Some(port) = port else {
+        match component {
+            "DebugData" => {
+                let label: String = from_json(value)?;
+                set_node_metadata(gug, node, DebugData::with_label(label));
+            }
+            "SourceSpan" => {
+                let (start, end) = value.split_once("..")?;
+                let span = SourceSpan {
+                    start: start.parse().ok()?,
+                    end: end.parse().ok()?,
+                };
+                gug.set_source_span(node, span);
+            }
+            "GateDuration" => set_node_metadata(gug, node, GateDuration(value.parse().ok()?)),
+            "QubitNames" => set_node_metadata(gug, node, QubitNames(from_json(value)?)),
+            "Ancillas" => {
+                let (allocated, free) = from_json(value)?;
+                set_node_metadata(gug, node, Ancillas { allocated, free });
+            }
+            _ => return None,
+        }
+        return Some(());
+    };
+    let port = match port.strip_prefix("in") {
+        Some(offset) => gug.input_port(node, offset.parse().ok()?)?,
+        None => gug.output_port(node, port.strip_prefix("out")?.parse().ok()?)?,
+    };
+    match component {
+        "PhysicalQubit" => gug.set_physical_qubit(port, value.parse().ok()?),
+        "CregBit" => {
+            let (name, index): (String, usize) = from_json(value)?;
+            gug.set_creg(port, name, index);
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(value: &str) -> Option<T> {
+    serde_json::from_str(value).ok()
+}
+
+fn set_node_metadata<T: NodeMetadata + Default>(gug: &mut Gug, node: NodeIndex, value: T) {
+    gug.register_node_metadata::<T>();
+    *gug.node_metadata_mut::<T>(node).unwrap() = value;
+}
+
+fn parse_types(s: &str) -> Option<Vec<WireType>> {
+    if s == "-" {
+        return Some(vec![]);
+    }
+    s.split(',').map(parse_wire_type).collect()
+}
+
+fn parse_wire_type(s: &str) -> Option<WireType> {
+    Some(match s {
+        "Qubit" => WireType::Qubit,
+        "LinearBit" => WireType::LinearBit,
+        "Bool" => WireType::Bool,
+        "I64" => WireType::I64,
+        "F64" => WireType::F64,
+        "Quat64" => WireType::Quat64,
+        "Angle" => WireType::Angle,
+        "SideEffects" => WireType::SideEffects,
+        _ => return None,
+    })
+}
+
+/// Encodes an operation as a single whitespace-free token.
+fn op_to_text(op: &Op) -> String {
+    match op {
+        Op::Circuit(op) => circuit_op_to_text(op),
+        Op::ControlFlow(ControlFlowOp::Loop {
+            iterations: Some(n),
+        }) => format!("Loop({n})"),
+        Op::Opaque(op) => opaque_to_text(op.as_ref()).unwrap_or_else(|| op.name().to_string()),
+        op => op.name().to_string(),
+    }
+}
+
+/// Encodes an opaque operation with its payload, if both the name and the
+/// payload fit in a token.
+fn opaque_to_text(op: &dyn CustomOp) -> Option<String> {
+    let payload = op.payload()?;
+    let name = op.name();
+    if name.is_empty() || name.contains([':', '(']) || name.contains(char::is_whitespace) {
+        return None;
+    }
+    if payload.contains(char::is_whitespace) {
+        return None;
+    }
+    Some(format!("Opaque({name}:{payload})"))
+}
+
+fn circuit_op_to_text(op: &circuit::Op) -> String {
+    match op {
+        circuit::Op::Noop(typ) => format!("Noop({typ:?})"),
+        circuit::Op::Select(typ) => format!("Select({typ:?})"),
+        circuit::Op::Copy { n_copies, typ } => format!("Copy({n_copies},{typ:?})"),
+        circuit::Op::Cast { from, to } => format!("Cast({from:?},{to:?})"),
+        circuit::Op::Const(value) => format!("Const({})", const_to_text(value)),
+        circuit::Op::Symbolic(expr) => match symbolic_to_text(expr) {
+            Some(expr) => format!("Symbolic({expr})"),
+            None => op.name().to_string(),
+        },
+        circuit::Op::Controlled(op) => format!("Controlled({})", circuit_op_to_text(op)),
+        circuit::Op::ClassicallyControlled(op) => {
+            format!("ClassicallyControlled({})", circuit_op_to_text(op))
+        }
+        circuit::Op::Permute(perm) => {
+            let perm: Vec<String> = perm.iter().map(usize::to_string).collect();
+            format!("Permute({})", perm.join(","))
+        }
+        circuit::Op::Rx(angle) => format!("Rx({})", angle_to_text(angle)),
+        circuit::Op::Rz(angle) => format!("Rz({})", angle_to_text(angle)),
+        circuit::Op::U3 { theta, phi, lambda } => format!(
+            "U3({},{},{})",
+            angle_to_text(theta),
+            angle_to_text(phi),
+            angle_to_text(lambda)
+        ),
+        op => op.name().to_string(),
+    }
+}
+
+fn const_to_text(value: &ConstValue) -> String {
+    match value {
+        ConstValue::Bool(b) => format!("Bool:{b}"),
+        ConstValue::I64(i) => format!("I64:{i}"),
+        ConstValue::F64(x) => format!("F64:{x:?}"),
+        ConstValue::Angle(angle) => format!("Angle:{}", angle_to_text(angle)),
+        ConstValue::Quat64(q) => {
+            let q = q.0;
+            format!("Quat64:{:?},{:?},{:?},{:?}", q.s, q.v.x, q.v.y, q.v.z)
+        }
+    }
+}
+
+fn angle_to_text(angle: &AngleValue) -> String {
+    match angle {
+        AngleValue::F64(x) => format!("{x:?}"),
+        AngleValue::Rational(r) => format!("{}/{}", r.0.numer(), r.0.denom()),
+    }
+}
+
+fn angle_from_text(s: &str) -> Option<AngleValue> {
+    Some(match s.split_once('/') {
+        Some((numer, denom)) => {
+            let denom: i64 = denom.parse().ok()?;
+            if denom == 0 {
+                return None;
+            }
+            AngleValue::Rational(Rational(Rational64::new(numer.parse().ok()?, denom)))
+        }
+        None => AngleValue::F64(s.parse().ok()?),
+    })
+}
+
+/// Encodes a symbolic angle, if all its parameter names are valid.
+fn symbolic_to_text(expr: &SymbolicAngle) -> Option<String> {
+    Some(match expr {
+        SymbolicAngle::Value(angle) => angle_to_text(angle),
+        SymbolicAngle::Symbol(name) => {
+            if !is_symbol_name(name) {
+                return None;
+            }
+            format!("${name}")
+        }
+        SymbolicAngle::Add(a, b) => {
+            format!("add({},{})", symbolic_to_text(a)?, symbolic_to_text(b)?)
+        }
+        SymbolicAngle::Neg(a) => format!("neg({})", symbolic_to_text(a)?),
+        SymbolicAngle::Scale(a, c) => format!("mul({},{})", symbolic_to_text(a)?, angle_to_text(c)),
+    })
+}
+
+fn symbolic_from_text(s: &str) -> Option<SymbolicAngle> {
+    if let Some(name) = s.strip_prefix('$') {
+        return is_symbol_name(name).then(|| SymbolicAngle::symbol(name));
+    }
+    let Some((head, args)) = s.split_once('(') else {
+        return Some(SymbolicAngle::Value(angle_from_text(s)?));
+    };
+    // Split the arguments at the commas outside of nested parentheses.
+    let args = args.strip_suffix(')')?;
+    let mut depth = 0usize;
+    let split = args.char_indices().find(|&(_, c)| {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        c == ',' && depth == 0
+    });
+    let operands = match split {
+        Some((i, _)) => vec![&args[..i], &args[i + 1..]],
+        None => vec![args],
+    };
+    Some(match (head, &operands[..]) {
+        ("add", [a, b]) => SymbolicAngle::Add(
+            Box::new(symbolic_from_text(a)?),
+            Box::new(symbolic_from_text(b)?),
+        ),
+        ("neg", [a]) => SymbolicAngle::Neg(Box::new(symbolic_from_text(a)?)),
+        ("mul", [a, c]) => {
+            SymbolicAngle::Scale(Box::new(symbolic_from_text(a)?), angle_from_text(c)?)
+        }
+        _ => return None,
+    })
+}
+
+fn is_symbol_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn op_from_text(s: &str, registry: &OpRegistry) -> Option<Op> {
+    if let Some(op) = circuit_op_from_text(s) {
+        return Some(Op::Circuit(op));
+    }
+    if let Some(opaque) = s.strip_prefix("Opaque(") {
+        let (name, payload) = opaque.strip_suffix(')')?.split_once(':')?;
+        return registry.build(name, payload).map(Op::Opaque);
+    }
+    let op = match s {
+        "Conditional" => ControlFlowOp::Conditional,
+        "Loop" => ControlFlowOp::Loop { iterations: None },
+        _ => {
+            let n = s.strip_prefix("Loop(")?.strip_suffix(')')?;
+            ControlFlowOp::Loop {
+                iterations: Some(n.parse().ok()?),
+            }
+        }
+    };
+    Some(Op::ControlFlow(op))
+}
+
+fn circuit_op_from_text(s: &str) -> Option<circuit::Op> {
+    let (name, args) = match s.split_once('(') {
+        Some((name, rest)) => (name, Some(rest.strip_suffix(')')?)),
+        None => (s, None),
+    };
+    let op = match (name, args) {
+        ("Noop", Some(typ)) => circuit::Op::Noop(parse_wire_type(typ)?),
+        ("Select", Some(typ)) => circuit::Op::Select(parse_wire_type(typ)?),
+        ("Copy", Some(args)) => {
+            let (n_copies, typ) = args.split_once(',')?;
+            circuit::Op::Copy {
+                n_copies: n_copies.parse().ok()?,
+                typ: parse_wire_type(typ)?,
+            }
+        }
+        ("Cast", Some(args)) => {
+            let (from, to) = args.split_once(',')?;
+            circuit::Op::cast(parse_wire_type(from)?, parse_wire_type(to)?)?
+        }
+        ("Const", Some(value)) => circuit::Op::Const(const_from_text(value)?),
+        ("Symbolic", Some(expr)) => circuit::Op::Symbolic(symbolic_from_text(expr)?),
+        ("Controlled", Some(op)) => circuit::Op::Controlled(Box::new(circuit_op_from_text(op)?)),
+        ("ClassicallyControlled", Some(op)) => {
+            circuit::Op::ClassicallyControlled(Box::new(circuit_op_from_text(op)?))
+        }
+        ("Permute", Some("")) => circuit::Op::Permute(vec![]),
+        ("Permute", Some(perm)) => {
+            circuit::Op::Permute(perm.split(',').map(|i| i.parse().ok()).collect::<Option<_>>()?)
+        }
+        ("Rx", Some(angle)) => circuit::Op::Rx(angle_from_text(angle)?),
+        ("Rz", Some(angle)) => circuit::Op::Rz(angle_from_text(angle)?),
+        ("U3", Some(angles)) => {
+            let angles: Vec<AngleValue> =
+                angles.split(',').map(angle_from_text).collect::<Option<_>>()?;
+            let [theta, phi, lambda] = angles[..] else { return None };
+            circuit::Op::U3 { theta, phi, lambda }
+        }
+        (name, None) => circuit::Op::from_name(name)?,
+        _ => return None,
+    };
+    Some(op)
+}
+
+fn const_from_text(s: &str) -> Option<ConstValue> {
+    let (typ, value) = s.split_once(':')?;
+    Some(match typ {
+        "Bool" => ConstValue::Bool(value.parse().ok()?),
+        "I64" => ConstValue::I64(value.parse().ok()?),
+        "F64" => ConstValue::F64(value.parse().ok()?),
+        "Angle" => ConstValue::Angle(angle_from_text(value)?),
+        "Quat64" => {
+            let parts: Vec<f64> = value
+                .split(',')
+                .map(|x| x.parse().ok())
+                .collect::<Option<_>>()?;
+            let [s, x, y, z] = parts[..] else { return None };
+            ConstValue::Quat64(Quat(cgmath::Quaternion::new(s, x, y, z)))
+        }
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn round_trip() {
+        let mut circ = TestCircuit::qubits(2);
+        let quat = circ.constant(ConstValue::Quat64(Quat(cgmath::Quaternion::new(
+            0.5, 0.5, -0.5, 0.1,
+        ))));
+        let angle = circ.constant(ConstValue::Angle(AngleValue::Rational(Rational(
+            Rational64::new(3, 4),
+        ))));
+        circ.gate(circuit::Op::ZZMax, &[0, 1]);
+        circ.gate_with_args(circuit::Op::Rotation, &[0], &[(quat, 0)]);
+        circ.gate_with_args(circuit::Op::RzF64, &[1], &[(angle, 0)]);
+        circ.gate(circuit::Op::Rx(AngleValue::F64(0.25)), &[0]);
+        circ.gate(
+            circuit::Op::U3 {
+                theta: AngleValue::F64(0.5),
+                phi: AngleValue::Rational(Rational(Rational64::new(1, 4))),
+                lambda: AngleValue::F64(-1.0),
+            },
+            &[1],
+        );
+        circ.gate(circuit::Op::Permute(vec![1, 0]), &[0, 1]);
+        let gug = circ.finish();
+
+        let text = gug.to_text().unwrap();
+        assert!(text.contains("ZZMax"));
+        assert!(text.contains("Rx(0.25)"));
+        assert!(text.contains("U3(0.5,1/4,-1.0)"));
+        assert!(text.contains("Permute(1,0)"));
+        assert!(text.contains("Const(Angle:3/4)"));
+        let parsed = Gug::from_text(&text).unwrap();
+        assert_eq!(parsed.to_text().unwrap(), text);
+        assert_eq!(parsed.node_count(), gug.node_count());
+        assert_eq!(parsed.gate_counts(), gug.gate_counts());
+
+        let empty = Op::Circuit(circuit::Op::Permute(vec![]));
+        assert_eq!(op_to_text(&empty), "Permute()");
+        assert_eq!(op_from_text("Permute()", &OpRegistry::new()), Some(empty));
+    }
+
+    #[test]
+    fn round_trip_regions() {
+        let mut gug = Gug::new();
+        let qb = vec![WireType::Qubit];
+        let repeat = gug.add_node_with_signature(
+            Op::ControlFlow(ControlFlowOp::Loop {
+                iterations: Some(3),
+            }),
+            Signature::new_nonlinear(qb.clone(), qb.clone()),
+        );
+        gug.add_node_with_signature(
+            Op::ControlFlow(ControlFlowOp::Conditional),
+            Signature::new_nonlinear(vec![WireType::Bool, WireType::Qubit], qb),
+        );
+        for op in [circuit::Op::X, circuit::Op::H] {
+            let child = gug.add_node(Op::Circuit(op));
+            gug.push_child(child, repeat).unwrap();
+        }
+
+        let text = gug.to_text().unwrap();
+        assert!(text.contains("Loop(3)"));
+        assert!(text.contains("Conditional"));
+        let parsed = Gug::from_text(&text).unwrap();
+        assert_eq!(parsed.to_text().unwrap(), text);
+        let repeat = parsed
+            .nodes()
+            .find(|&n| parsed.optype(n).name() == "Loop")
+            .unwrap();
+        let children: Vec<&str> = parsed
+            .children(repeat)
+            .map(|n| parsed.optype(n).name())
+            .collect();
+        assert_eq!(children, ["X", "H"]);
+    }
+
+    #[test]
+    fn round_trip_symbolic() {
+        let half = AngleValue::Rational(Rational(Rational64::new(1, 2)));
+        let expr = -(SymbolicAngle::symbol("theta") * AngleValue::F64(2.0))
+            + (SymbolicAngle::symbol("phi_1") + half);
+        let mut circ = TestCircuit::qubits(1);
+        let angle = circ
+            .gug
+            .add_node(Op::Circuit(circuit::Op::Symbolic(expr.clone())));
+        circ.gate_with_args(circuit::Op::RzF64, &[0], &[(angle, 0)]);
+        let gug = circ.finish();
+
+        let text = gug.to_text().unwrap();
+        assert!(text.contains("Symbolic(add(neg(mul($theta,2.0)),add($phi_1,1/2)))"));
+        let parsed = Gug::from_text(&text).unwrap();
+        assert_eq!(parsed.to_text().unwrap(), text);
+        let symbolic = parsed
+            .nodes()
+            .find(|&n| parsed.optype(n).name() == "Symbolic")
+            .unwrap();
+        assert_eq!(
+            parsed.optype(symbolic),
+            &Op::Circuit(circuit::Op::Symbolic(expr))
+        );
+        assert!(Gug::from_text("node 0 Symbolic(add($a) - Angle").is_err());
+        assert!(Gug::from_text("node 0 Symbolic($a,b) - Angle").is_err());
+
+        let mut gug = Gug::new();
+        let bad = SymbolicAngle::symbol("two words");
+        let node = gug.add_node(Op::Circuit(circuit::Op::Symbolic(bad)));
+        assert_eq!(gug.to_text(), Err(SerializeError::SymbolName(node)));
+    }
+
+    #[test]
+    fn round_trip_phase() {
+        let mut gug = TestCircuit::qubits(1).finish();
+        let plain = gug.to_text().unwrap();
+        assert!(!plain.contains("phase"));
+
+        gug.add_global_phase(AngleValue::Rational(Rational(Rational64::new(1, 4))));
+        let text = gug.to_text().unwrap();
+        assert!(text.contains("phase 1/4\n"));
+        let parsed = Gug::from_text(&text).unwrap();
+        assert_eq!(parsed.global_phase(), gug.global_phase());
+        assert_eq!(parsed.to_text().unwrap(), text);
+        assert!(Gug::from_text("phase 1/0").is_err());
+    }
+
+    #[test]
+    fn round_trip_metadata() {
+        let mut circ = TestCircuit::qubits(2);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let mut gug = circ.finish();
+        let (input, _) = gug.boundary().unwrap();
+        set_node_metadata(&mut gug, h, DebugData::with_label("two words"));
+        set_node_metadata(&mut gug, h, GateDuration(1.5));
+        gug.set_source_span(h, SourceSpan { start: 3, end: 8 });
+        set_node_metadata(
+            &mut gug,
+            input,
+            QubitNames([("a".to_string(), 0), ("b".to_string(), 1)].into()),
+        );
+        set_node_metadata(
+            &mut gug,
+            input,
+            Ancillas {
+                allocated: [1].into(),
+                free: Default::default(),
+            },
+        );
+        let port = gug.output_port(input, 0).unwrap();
+        gug.set_physical_qubit(port, 4);
+        let port = gug.input_port(h, 0).unwrap();
+        gug.set_creg(port, "c", 2);
+
+        let text = gug.to_text().unwrap();
+        assert!(text.contains("DebugData \"two words\"\n"));
+        let parsed = Gug::from_text(&text).unwrap();
+        assert_eq!(parsed.to_text().unwrap(), text);
+        let h = parsed
+            .nodes()
+            .find(|&n| parsed.optype(n).name() == "H")
+            .unwrap();
+        let (input, _) = parsed.boundary().unwrap();
+        assert_eq!(
+            parsed.node_metadata::<DebugData>(h).unwrap().label.as_deref(),
+            Some("two words")
+        );
+        assert_eq!(parsed.node_metadata::<GateDuration>(h), Some(&GateDuration(1.5)));
+        assert_eq!(parsed.source_span(h), Some(SourceSpan { start: 3, end: 8 }));
+        assert_eq!(
+            parsed.node_metadata::<QubitNames>(input),
+            gug.node_metadata::<QubitNames>(gug.boundary().unwrap().0)
+        );
+        assert_eq!(
+            parsed.node_metadata::<Ancillas>(input).unwrap().allocated,
+            [1].into()
+        );
+        let port = parsed.output_port(input, 0).unwrap();
+        assert_eq!(parsed.physical_qubit(port), Some(4));
+        let port = parsed.input_port(h, 0).unwrap();
+        assert_eq!(parsed.creg_of(port), Some(("c".to_string(), 2)));
+
+        assert!(Gug::from_text(&format!("{text}meta 0 Frobnicate 1\n")).is_err());
+        assert!(Gug::from_text(&format!("{text}meta 0:in9 PhysicalQubit 1\n")).is_err());
+        assert!(Gug::from_text(&format!("{text}meta 0 SourceSpan 3\n")).is_err());
+    }
+
+    #[derive(Clone, Debug, Default)]
+    struct Note(Option<String>);
+
+    impl NodeMetadata for Note {}
+
+    impl crate::gug::PortMetadata for Note {}
+
+    #[test]
+    fn unserializable() {
+        let mut circ = TestCircuit::qubits(1);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let mut gug = circ.finish();
+        // Registering a component without setting any entry keeps the graph serializable.
+        gug.register_node_metadata::<Note>();
+        assert!(gug.to_text().is_ok());
+
+        *gug.node_metadata_mut::<Note>(h).unwrap() = Note(Some("h".to_string()));
+        assert_eq!(gug.to_text(), Err(SerializeError::NodeMetadata(h)));
+
+        let mut gug = TestCircuit::qubits(1).finish();
+        let port = gug.output_port(gug.input_node().unwrap(), 0).unwrap();
+        gug.register_port_metadata::<Note>();
+        *gug.port_metadata_mut::<Note>(port).unwrap() = Note(Some("q".to_string()));
+        assert_eq!(gug.to_text(), Err(SerializeError::PortMetadata(port)));
+
+        let mut circ = TestCircuit::qubits(1);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let mut gug = circ.finish();
+        let boxed = gug.box_subcircuit(&[h], "Boxed".to_string()).unwrap();
+        let err = SerializeError::OpaqueOp(boxed, "Boxed".to_string());
+        assert_eq!(gug.to_text(), Err(err));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let err = Gug::from_text("node 0 H Qubit Qubit\nnode 1 Frobnicate - -").unwrap_err();
+        let DeserializeError::Parse(err) = err else { panic!("unexpected error {err}") };
+        assert_eq!(err.line, 2);
+        assert!(Gug::from_text("edge 0:0 1:0").is_err());
+        assert!(Gug::from_text("node 0 Rz(1/0) Qubit Qubit").is_err());
+        assert!(Gug::from_text("node 0 Const(Angle:1/0) - Angle").is_err());
+        assert!(Gug::from_text("node 0 H Qubit Qubit\nparent 0 1").is_err());
+        assert!(Gug::from_text("node 0 Permute(0,) Qubit Qubit").is_err());
+    }
+
+    #[test]
+    fn format_version() {
+        let mut circ = TestCircuit::qubits(1);
+        circ.gate(circuit::Op::H, &[0]);
+        let text = circ.finish().to_text().unwrap();
+        assert!(text.starts_with(&format!("format {TEXT_FORMAT_VERSION}\n")));
+
+        let bumped = text.replacen(
+            &format!("format {TEXT_FORMAT_VERSION}"),
+            &format!("format {}", TEXT_FORMAT_VERSION + 1),
+            1,
+        );
+        assert_eq!(
+            Gug::from_text(&bumped).unwrap_err(),
+            DeserializeError::VersionMismatch {
+                found: TEXT_FORMAT_VERSION + 1,
+                expected: TEXT_FORMAT_VERSION,
+            }
+        );
+
+        // Text written before the header was introduced is still accepted.
+        let unversioned: String = text.lines().skip(1).map(|l| format!("{l}\n")).collect();
+        assert_eq!(
+            Gug::from_text(&unversioned).unwrap().to_text().unwrap(),
+            text
+        );
+    }
+
+    #[derive(Clone, Debug)]
+    struct Repeat(usize);
+
+    impl CustomOp for Repeat {
+        fn name(&self) -> &str {
+            "Repeat"
+        }
+
+        fn signature(&self) -> Signature {
+            Signature::new_linear(vec![WireType::Qubit])
+        }
+
+        fn payload(&self) -> Option<String> {
+            Some(self.0.to_string())
+        }
+    }
+
+    #[test]
+    fn round_trip_opaque() {
+        let mut gug = TestCircuit::qubits(1).finish();
+        let (input, _) = gug.boundary().unwrap();
+        gug.insert_after(input, 0, Op::Opaque(Box::new(Repeat(3))));
+        let text = gug.to_text().unwrap();
+        assert!(text.contains("Opaque(Repeat:3)"));
+        assert!(Gug::from_text(&text).is_err());
+
+        let mut registry = OpRegistry::new();
+        registry.register("Repeat", |payload| {
+            Some(Box::new(Repeat(payload.parse().ok()?)) as Box<dyn CustomOp>)
+        });
+        let parsed = Gug::from_text_with_registry(&text, &registry).unwrap();
+        assert_eq!(parsed.to_text().unwrap(), text);
+        let op = parsed
+            .nodes()
+            .find_map(|n| parsed.optype(n).as_opaque())
+            .unwrap();
+        assert_eq!(op.downcast_ref::<Repeat>().unwrap().0, 3);
+    }
+}
@@ -0,0 +1,513 @@
+//! Graph traversals.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use portgraph::{Direction, NodeIndex, PortIndex};
+
+use crate::component::operation::Op;
+use crate::component::wire_type::WireType;
+
+use super::{Gug, ValidationError};
+
+impl Gug {
+    /// Iterates over the nodes linked to `node` through its ports in the given
+    /// direction. A node linked through multiple wires is yielded once per wire.
+    pub(crate) fn linked_nodes(
+        &self,
+        node: NodeIndex,
+        direction: Direction,
+    ) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.node_ports(node, direction)
+            .filter_map(|port| self.port_link(port))
+            .filter_map(|link| self.port_node(link))
+    }
+
+    /// Iterates over the distinct nodes with an output linked to an input of `node`.
+    pub fn predecessors(&self, node: NodeIndex) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.distinct_linked_nodes(node, Direction::Incoming)
+    }
+
+    /// Iterates over the distinct nodes with an input linked to an output of `node`.
+    pub fn successors(&self, node: NodeIndex) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.distinct_linked_nodes(node, Direction::Outgoing)
+    }
+
+    /// Iterates over the distinct nodes with an input linked to an output of
+    /// `node` carrying a wire of the given type.
+    pub fn successors_on(
+        &self,
+        node: NodeIndex,
+        wire_type: WireType,
+    ) -> impl Iterator<Item = NodeIndex> + '_ {
+        let mut seen = HashSet::new();
+        self.outputs(node)
+            .filter(move |&port| self.port_type(port) == wire_type)
+            .filter_map(|port| self.port_link(port))
+            .filter_map(|link| self.port_node(link))
+            .filter(move |&n| seen.insert(n))
+    }
+
+    fn distinct_linked_nodes(
+        &self,
+        node: NodeIndex,
+        direction: Direction,
+    ) -> impl Iterator<Item = NodeIndex> + '_ {
+        let mut seen = HashSet::new();
+        self.linked_nodes(node, direction)
+            .filter(move |&n| seen.insert(n))
+    }
+
+    /// Returns the node and output offset feeding the input port of `node` at
+    /// `offset`, if it is connected.
+    pub fn input_source(&self, node: NodeIndex, offset: usize) -> Option<(NodeIndex, usize)> {
+        let link = self.port_link(self.input_port(node, offset)?)?;
+        Some((self.port_node(link)?, self.port_offset(link)?))
+    }
+
+    /// Returns the node and input offset consuming the output port of `node`
+    /// at `offset`, if it is connected.
+    pub fn output_target(&self, node: NodeIndex, offset: usize) -> Option<(NodeIndex, usize)> {
+        let link = self.port_link(self.output_port(node, offset)?)?;
+        Some((self.port_node(link)?, self.port_offset(link)?))
+    }
+
+    /// Iterates over the edges of the graph as pairs of linked output and
+    /// input ports, together with the type of the wire.
+    pub fn edges(&self) -> impl Iterator<Item = (PortIndex, PortIndex, WireType)> + '_ {
+        self.nodes()
+            .flat_map(|node| self.node_ports(node, Direction::Outgoing))
+            .filter_map(|port| {
+                let link = self.port_link(port)?;
+                Some((port, link, self.port_type(port)))
+            })
+    }
+
+    /// Returns the operations acting on a qubit line, in order from the
+    /// `Input` node to the `Output` node, excluding both.
+    ///
+    /// Qubits are numbered by the order of the qubit outputs of the `Input`
+    /// node. Multi-qubit gates appear in the history of each of their qubits.
+    pub fn qubit_history(&self, qubit: usize) -> Vec<(NodeIndex, &Op)> {
+        let mut history = Vec::new();
+        let Ok((input, _)) = self.boundary() else {
+            return history;
+        };
+        let mut port = self
+            .qubit_ports(input, Direction::Outgoing)
+            .get(qubit)
+            .copied();
+        while let Some(link) = port.and_then(|p| self.port_link(p)) {
+            let (Some(node), Some(offset)) = (self.port_node(link), self.port_offset(link)) else {
+                break;
+            };
+            let op = self.optype(node);
+            if op.is_boundary() || offset >= op.signature().linear.len() {
+                break;
+            }
+            history.push((node, op));
+            port = self.output_port(node, offset);
+        }
+        history
+    }
+
+    /// Partitions the nodes into weakly connected components, ignoring the
+    /// direction of the edges.
+    ///
+    /// Components are ordered by their first node, and list their nodes in
+    /// breadth-first order from it.
+    pub fn connected_components(&self) -> Vec<Vec<NodeIndex>> {
+        let mut seen = HashSet::new();
+        let mut components = Vec::new();
+        for start in self.nodes() {
+            if !seen.insert(start) {
+                continue;
+            }
+            let mut component = Vec::new();
+            let mut queue = VecDeque::from([start]);
+            while let Some(node) = queue.pop_front() {
+                component.push(node);
+                let neighbours = self
+                    .linked_nodes(node, Direction::Incoming)
+                    .chain(self.linked_nodes(node, Direction::Outgoing));
+                for neighbour in neighbours {
+                    if seen.insert(neighbour) {
+                        queue.push_back(neighbour);
+                    }
+                }
+            }
+            components.push(component);
+        }
+        components
+    }
+
+    /// Returns the forward light cone of `node`: every node reachable from it
+    /// through its outgoing wires, in breadth-first order. The node itself is
+    /// not included.
+    pub fn light_cone(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        self.breadth_first(node, Direction::Outgoing)
+    }
+
+    /// Returns the backward light cone of `node`: every node it can be reached
+    /// from through its incoming wires, in breadth-first order. The node
+    /// itself is not included.
+    pub fn back_light_cone(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        self.breadth_first(node, Direction::Incoming)
+    }
+
+    /// Enumerates the simple directed paths from `from` to `to` with at most
+    /// `max_len` edges, each given as the sequence of nodes it visits,
+    /// including both ends.
+    ///
+    /// Nodes linked through several wires count as a single edge, so each path
+    /// is returned once. The search is exhaustive within the length bound,
+    /// which should be kept small on large circuits.
+    pub fn paths_between(
+        &self,
+        from: NodeIndex,
+        to: NodeIndex,
+        max_len: usize,
+    ) -> Vec<Vec<NodeIndex>> {
+        let mut paths = Vec::new();
+        let mut path = vec![from];
+        self.extend_paths(to, max_len, &mut path, &mut paths);
+        paths
+    }
+
+    fn extend_paths(
+        &self,
+        to: NodeIndex,
+        max_len: usize,
+        path: &mut Vec<NodeIndex>,
+        paths: &mut Vec<Vec<NodeIndex>>,
+    ) {
+        let last = *path.last().unwrap();
+        if last == to {
+            paths.push(path.clone());
+            return;
+        }
+        if path.len() > max_len {
+            return;
+        }
+        for next in self.successors(last) {
+            if !path.contains(&next) {
+                path.push(next);
+                self.extend_paths(to, max_len, path, paths);
+                path.pop();
+            }
+        }
+    }
+
+    fn breadth_first(&self, start: NodeIndex, direction: Direction) -> Vec<NodeIndex> {
+        let mut seen = HashSet::from([start]);
+        let mut order = Vec::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some(node) = queue.pop_front() {
+            for next in self.linked_nodes(node, direction) {
+                if seen.insert(next) {
+                    order.push(next);
+                    queue.push_back(next);
+                }
+            }
+        }
+        order
+    }
+
+    /// Returns the nodes of the graph in a topological order, such that every
+    /// node appears after all of its predecessors.
+    ///
+    /// Nodes that are part of a cycle, or only reachable through one, are not
+    /// included in the result. Use [`Gug::try_toposort`] to detect cycles.
+    pub fn toposort(&self) -> Vec<NodeIndex> {
+        self.kahn_order().0
+    }
+
+    /// Returns the nodes of the graph in a topological order, or a
+    /// [`ValidationError::Cycle`] if the graph is not a DAG.
+    pub fn try_toposort(&self) -> Result<Vec<NodeIndex>, ValidationError> {
+        let (order, in_degree) = self.kahn_order();
+        match in_degree.keys().find(|&node| in_degree[node] > 0) {
+            Some(&start) => Err(ValidationError::Cycle(self.cycle_from(start, &in_degree))),
+            None => Ok(order),
+        }
+    }
+
+    /// Checks whether the graph has no directed cycles.
+    pub fn is_acyclic(&self) -> bool {
+        self.try_toposort().is_ok()
+    }
+
+    /// Runs Kahn's algorithm, returning the sorted nodes along with the
+    /// remaining in-degrees. Nodes left with a positive in-degree could not be
+    /// sorted because of a cycle.
+    fn kahn_order(&self) -> (Vec<NodeIndex>, HashMap<NodeIndex, usize>) {
+        let mut in_degree = HashMap::new();
+        let mut queue = VecDeque::new();
+        for node in self.nodes() {
+            let degree = self.linked_nodes(node, Direction::Incoming).count();
+            if degree == 0 {
+                queue.push_back(node);
+            } else {
+                in_degree.insert(node, degree);
+            }
+        }
+
+        let mut order = Vec::with_capacity(self.node_count());
+        while let Some(node) = queue.pop_front() {
+            order.push(node);
+            for succ in self.linked_nodes(node, Direction::Outgoing) {
+                let degree = in_degree.get_mut(&succ).unwrap();
+                *degree -= 1;
+                if *degree == 0 {
+                    queue.push_back(succ);
+                }
+            }
+        }
+        (order, in_degree)
+    }
+
+    /// Finds a cycle through the nodes left unsorted by [`Gug::kahn_order`],
+    /// walking backwards from `start`. Every such node has an unsorted
+    /// predecessor, so the walk must eventually revisit a node.
+    ///
+    /// The cycle is returned in edge order, starting from its lowest index.
+    fn cycle_from(
+        &self,
+        start: NodeIndex,
+        in_degree: &HashMap<NodeIndex, usize>,
+    ) -> Vec<NodeIndex> {
+        let unsorted = |node: &NodeIndex| in_degree.get(node).is_some_and(|&d| d > 0);
+        let mut path = vec![start];
+        let mut node = start;
+        loop {
+            node = self
+                .linked_nodes(node, Direction::Incoming)
+                .find(unsorted)
+                .expect("unsorted nodes have an unsorted predecessor");
+            if let Some(pos) = path.iter().position(|&n| n == node) {
+                let mut cycle = path.split_off(pos);
+                cycle.reverse();
+                let first = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap();
+                cycle.rotate_left(first);
+                return cycle;
+            }
+            path.push(node);
+        }
+    }
+
+    /// Traverses the graph in topological order, descending into the regions
+    /// of hierarchical nodes.
+    ///
+    /// Yields `(node, depth)` pairs in pre-order: each node is followed by the
+    /// nodes of its region (in topological order) before its next sibling.
+    /// Top-level nodes have depth 0.
+    pub fn toposort_recursive(&self) -> impl Iterator<Item = (NodeIndex, usize)> {
+        let mut regions: HashMap<Option<NodeIndex>, Vec<NodeIndex>> = HashMap::new();
+        for node in self.toposort() {
+            regions.entry(self.parent(node)).or_default().push(node);
+        }
+
+        let mut order = Vec::with_capacity(self.node_count());
+        let mut stack: Vec<(NodeIndex, usize)> = regions
+            .get(&None)
+            .map(|nodes| nodes.iter().rev().map(|&n| (n, 0)).collect())
+            .unwrap_or_default();
+        while let Some((node, depth)) = stack.pop() {
+            order.push((node, depth));
+            if let Some(children) = regions.get(&Some(node)) {
+                stack.extend(children.iter().rev().map(|&n| (n, depth + 1)));
+            }
+        }
+        order.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::component::operation::{circuit, ControlFlowOp, Op};
+    use crate::component::wire_type::WireType;
+    use crate::test_utils::TestCircuit;
+    use crate::Gug;
+
+    #[test]
+    fn successors_by_wire_type() {
+        let mut circ = TestCircuit::new(&[WireType::Qubit, WireType::LinearBit]);
+        let measure = circ.gate(circuit::Op::Measure, &[0, 1]);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let gug = circ.finish();
+        let output = gug.output_node().unwrap();
+
+        let qubit: Vec<_> = gug.successors_on(measure, WireType::Qubit).collect();
+        let bit: Vec<_> = gug.successors_on(measure, WireType::LinearBit).collect();
+        assert_eq!(qubit, vec![h]);
+        assert_eq!(bit, vec![output]);
+        assert_eq!(gug.successors_on(measure, WireType::Bool).count(), 0);
+    }
+
+    #[test]
+    fn recursive_toposort() {
+        let mut circ = TestCircuit::qubits(1);
+        let cond = circ
+            .gug
+            .add_node(Op::ControlFlow(ControlFlowOp::Conditional));
+        let h = circ.gug.add_node(Op::Circuit(circuit::Op::H));
+        let x = circ.gug.add_node(Op::Circuit(circuit::Op::X));
+        circ.gug.connect(h, 0, x, 0).unwrap();
+        circ.gug.push_child(h, cond).unwrap();
+        circ.gug.push_child(x, cond).unwrap();
+        let gug = circ.finish();
+
+        let order: Vec<_> = gug.toposort_recursive().collect();
+        assert_eq!(order.len(), gug.node_count());
+        let pos = order.iter().position(|&(n, _)| n == cond).unwrap();
+        assert_eq!(order[pos], (cond, 0));
+        assert_eq!(order[pos + 1], (h, 1));
+        assert_eq!(order[pos + 2], (x, 1));
+        assert!(order
+            .iter()
+            .filter(|(n, _)| *n != h && *n != x)
+            .all(|&(_, depth)| depth == 0));
+    }
+
+    #[test]
+    fn diamond_paths() {
+        let mut circ = TestCircuit::qubits(2);
+        let top = circ.gate(circuit::Op::CX, &[0, 1]);
+        let left = circ.gate(circuit::Op::X, &[0]);
+        let right = circ.gate(circuit::Op::Z, &[1]);
+        let bottom = circ.gate(circuit::Op::CZ, &[0, 1]);
+        let gug = circ.finish();
+
+        let mut paths = gug.paths_between(top, bottom, 2);
+        paths.sort();
+        let mut expected = vec![vec![top, left, bottom], vec![top, right, bottom]];
+        expected.sort();
+        assert_eq!(paths, expected);
+
+        assert!(gug.paths_between(top, bottom, 1).is_empty());
+        assert!(gug.paths_between(bottom, top, 4).is_empty());
+        assert_eq!(gug.paths_between(top, top, 0), vec![vec![top]]);
+    }
+
+    #[test]
+    fn predecessors_and_successors() {
+        let mut circ = TestCircuit::qubits(2);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let x = circ.gate(circuit::Op::X, &[1]);
+        let cx = circ.gate(circuit::Op::CX, &[0, 1]);
+        let zz = circ.gate(circuit::Op::ZZMax, &[0, 1]);
+        let gug = circ.finish();
+
+        let mut preds: Vec<_> = gug.predecessors(cx).collect();
+        preds.sort();
+        let mut expected = vec![h, x];
+        expected.sort();
+        assert_eq!(preds, expected);
+
+        // Both wires of the CX lead to the same node.
+        assert_eq!(gug.successors(cx).collect::<Vec<_>>(), vec![zz]);
+        assert_eq!(gug.predecessors(zz).collect::<Vec<_>>(), vec![cx]);
+    }
+
+    #[test]
+    fn toposort_respects_edges() {
+        let mut circ = TestCircuit::qubits(2);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let cx = circ.gate(circuit::Op::CX, &[0, 1]);
+        let x = circ.gate(circuit::Op::X, &[1]);
+        let gug = circ.finish();
+
+        let order = gug.toposort();
+        assert_eq!(order.len(), gug.node_count());
+        let pos = |n| order.iter().position(|&m| m == n).unwrap();
+        assert!(pos(gug.input_node().unwrap()) < pos(h));
+        assert!(pos(h) < pos(cx));
+        assert!(pos(cx) < pos(x));
+        assert!(pos(x) < pos(gug.output_node().unwrap()));
+    }
+
+    #[test]
+    fn connected_components() {
+        let mut gug = Gug::new();
+        let h = gug.add_node(Op::Circuit(circuit::Op::H));
+        let x = gug.add_node(Op::Circuit(circuit::Op::X));
+        let z = gug.add_node(Op::Circuit(circuit::Op::Z));
+        let y = gug.add_node(Op::Circuit(circuit::Op::Y));
+        gug.connect(h, 0, x, 0).unwrap();
+        gug.connect(z, 0, y, 0).unwrap();
+        assert_eq!(gug.connected_components(), vec![vec![h, x], vec![z, y]]);
+
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        let gug = circ.finish();
+        let components = gug.connected_components();
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].len(), gug.node_count());
+    }
+
+    #[test]
+    fn qubit_history() {
+        let mut circ = TestCircuit::qubits(2);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let cx = circ.gate(circuit::Op::CX, &[0, 1]);
+        let x = circ.gate(circuit::Op::X, &[1]);
+        let gug = circ.finish();
+
+        let nodes =
+            |qubit| -> Vec<_> { gug.qubit_history(qubit).iter().map(|&(n, _)| n).collect() };
+        assert_eq!(nodes(0), vec![h, cx]);
+        assert_eq!(nodes(1), vec![cx, x]);
+        assert_eq!(gug.qubit_history(1)[0].1, &Op::Circuit(circuit::Op::CX));
+        assert!(gug.qubit_history(2).is_empty());
+    }
+
+    #[test]
+    fn edges() {
+        let mut circ = TestCircuit::qubits(1);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let angle = circ.constant(crate::component::wire_type::ConstValue::f64_angle(0.5));
+        circ.gate_with_args(circuit::Op::RzF64, &[0], &[(angle, 0)]);
+        let gug = circ.finish();
+
+        let edges: Vec<_> = gug.edges().collect();
+        assert_eq!(edges.len(), 4);
+        let h_out = gug.output_port(h, 0).unwrap();
+        let (_, _, typ) = edges.iter().find(|(src, _, _)| *src == h_out).unwrap();
+        assert_eq!(*typ, WireType::Qubit);
+        let angle_out = gug.output_port(angle, 0).unwrap();
+        assert!(edges.contains(&(
+            angle_out,
+            gug.port_link(angle_out).unwrap(),
+            WireType::Angle
+        )));
+    }
+
+    #[test]
+    fn light_cones() {
+        let mut circ = TestCircuit::qubits(3);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let x = circ.gate(circuit::Op::X, &[2]);
+        let t = circ.gate(circuit::Op::T, &[0]);
+        let cx = circ.gate(circuit::Op::CX, &[0, 1]);
+        let z = circ.gate(circuit::Op::Z, &[1]);
+        let y = circ.gate(circuit::Op::Y, &[2]);
+        let gug = circ.finish();
+
+        let cone = gug.light_cone(h);
+        for n in [t, cx, z, gug.output_node().unwrap()] {
+            assert!(cone.contains(&n));
+        }
+        assert!(!cone.contains(&h));
+        assert!(!cone.contains(&x));
+        assert!(!cone.contains(&y));
+        assert_eq!(cone[0], t);
+
+        let back = gug.back_light_cone(z);
+        let mut expected = vec![cx, t, h, gug.input_node().unwrap()];
+        expected.sort();
+        let mut back_sorted = back.clone();
+        back_sorted.sort();
+        assert_eq!(back_sorted, expected);
+        assert_eq!(back[0], cx);
+    }
+}
@@ -0,0 +1,245 @@
+//! Unitary matrices of whole circuits.
+
+use std::collections::HashMap;
+
+use ndarray::Array2;
+use num_complex::Complex64;
+use portgraph::{Direction, NodeIndex};
+use thiserror::Error;
+
+use crate::component::operation::matrix::equal_up_to_phase;
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::{ConstValue, WireType};
+
+use super::{BoundaryError, Gug};
+
+/// The largest number of qubits for which a circuit unitary is computed. The
+/// matrix has `4^n` entries, so larger circuits quickly become impractical.
+pub const MAX_UNITARY_QUBITS: usize = 10;
+
+/// Error returned when the unitary of a circuit cannot be computed.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum UnitaryError {
+    #[error(transparent)]
+    Boundary(#[from] BoundaryError),
+    #[error("the circuit has {0} qubits, more than the supported {MAX_UNITARY_QUBITS}")]
+    TooManyQubits(usize),
+    #[error("node {0:?} has no unitary matrix")]
+    NonUnitary(NodeIndex),
+    #[error("an angle input of node {0:?} is not a constant")]
+    NonConstantAngle(NodeIndex),
+}
+
+impl Gug {
+    /// Checks whether two circuits implement the same unitary, up to a global
+    /// phase and within `tol`.
+    ///
+    /// The full unitaries are computed, so both circuits must have at most
+    /// [`MAX_UNITARY_QUBITS`] qubits. Circuits with different numbers of
+    /// qubits are never equivalent.
+    pub fn equivalent_unitary(&self, other: &Gug, tol: f64) -> Result<bool, UnitaryError> {
+        let (a, b) = (self.to_unitary(0.0)?, other.to_unitary(0.0)?);
+        Ok(equal_up_to_phase(&a, &b, tol))
+    }
+
+    /// Computes the unitary of the circuit by applying the matrix of each gate
+    /// in topological order, with the first qubit of the `Input` node as the
+    /// most significant bit. The global phase of the circuit is included.
+    ///
+    /// Classical operations without linear wires are skipped, and angle inputs
+    /// must be fed by `Const` nodes. The circuit must have a unique boundary
+    /// and at most [`MAX_UNITARY_QUBITS`] qubits. Entries with a modulus
+    /// below `tol` are set to exactly zero.
+    pub fn to_unitary(&self, tol: f64) -> Result<Array2<Complex64>, UnitaryError> {
+        let mut wires: HashMap<(NodeIndex, usize), usize> = HashMap::new();
+        let (input, _) = self.boundary()?;
+        let qubits = self
+            .node_ports(input, Direction::Outgoing)
+            .enumerate()
+            .filter(|&(_, port)| self.port_type(port) == WireType::Qubit);
+        for (offset, _) in qubits {
+            wires.insert((input, offset), wires.len());
+        }
+        let n = wires.len();
+        if n > MAX_UNITARY_QUBITS {
+            return Err(UnitaryError::TooManyQubits(n));
+        }
+
+        let phase = self.global_phase().to_f64() * std::f64::consts::PI;
+        let mut unitary = Array2::eye(1 << n) * Complex64::from_polar(1.0, phase);
+        for node in self.toposort() {
+            let op = self.optype(node);
+            let num_linear = op.signature().linear.len();
+            if op.is_boundary() || num_linear == 0 {
+                continue;
+            }
+            let Op::Circuit(gate) = op else {
+                return Err(UnitaryError::NonUnitary(node));
+            };
+            let qubits = (0..num_linear)
+                .map(|i| {
+                    let source = self.input_source(node, i);
+                    source.and_then(|s| wires.get(&s).copied())
+                })
+                .collect::<Option<Vec<usize>>>()
+                .ok_or(UnitaryError::NonUnitary(node))?;
+            for (i, &q) in qubits.iter().enumerate() {
+                wires.insert((node, i), q);
+            }
+            let angles = (num_linear..self.node_ports(node, Direction::Incoming).count())
+                .map(|i| match self.input_source(node, i) {
+                    Some((source, _)) => match self.optype(source) {
+                        Op::Circuit(circuit::Op::Const(ConstValue::Angle(angle))) => Ok(*angle),
+                        _ => Err(UnitaryError::NonConstantAngle(node)),
+                    },
+                    None => Err(UnitaryError::NonConstantAngle(node)),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let matrix = gate.unitary(&angles).ok_or(UnitaryError::NonUnitary(node))?;
+            apply_gate(&mut unitary, &matrix, &qubits, n);
+        }
+        let zero = Complex64::new(0.0, 0.0);
+        unitary.mapv_inplace(|x| if x.norm() < tol { zero } else { x });
+        Ok(unitary)
+    }
+}
+
+/// Left-multiplies `unitary` by `gate` acting on the given qubits of an
+/// `n`-qubit register.
+fn apply_gate(
+    unitary: &mut Array2<Complex64>,
+    gate: &Array2<Complex64>,
+    qubits: &[usize],
+    n: usize,
+) {
+    let bit = |q: usize| 1 << (n - 1 - q);
+    let mask = qubits.iter().fold(0, |acc, &q| acc | bit(q));
+    // The full indices of each basis state of the gate, relative to a base
+    // index with all of the gate's bits cleared.
+    let offsets: Vec<usize> = (0..gate.nrows())
+        .map(|sub| {
+            qubits.iter().enumerate().fold(0, |acc, (i, &q)| {
+                let set = sub & (1 << (qubits.len() - 1 - i)) != 0;
+                if set {
+                    acc | bit(q)
+                } else {
+                    acc
+                }
+            })
+        })
+        .collect();
+    for base in (0..1usize << n).filter(|b| b & mask == 0) {
+        let rows: Vec<_> = offsets.iter().map(|o| unitary.row(base | o).to_owned()).collect();
+        for (r, &offset) in offsets.iter().enumerate() {
+            let mut row = unitary.row_mut(base | offset);
+            row.fill(Complex64::new(0.0, 0.0));
+            for (s, old) in rows.iter().enumerate() {
+                row.scaled_add(gate[(r, s)], old);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::wire_type::AngleValue;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn euler_form_of_hadamard() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        let original = circ.finish();
+
+        let mut circ = TestCircuit::qubits(2);
+        let half = circ.constant(ConstValue::Angle(AngleValue::F64(0.5)));
+        circ.gate_with_args(circuit::Op::RzF64, &[0], &[(half, 0)]);
+        let half = circ.constant(ConstValue::Angle(AngleValue::F64(0.5)));
+        circ.gate_with_args(circuit::Op::RxF64, &[0], &[(half, 0)]);
+        let half = circ.constant(ConstValue::Angle(AngleValue::F64(0.5)));
+        circ.gate_with_args(circuit::Op::RzF64, &[0], &[(half, 0)]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        let resynthesized = circ.finish();
+
+        assert_eq!(original.equivalent_unitary(&resynthesized, 1e-10), Ok(true));
+
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::X, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        assert_eq!(original.equivalent_unitary(&circ.finish(), 1e-10), Ok(false));
+    }
+
+    #[test]
+    fn bell_state_prep() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        let unitary = circ.finish().to_unitary(1e-12).unwrap();
+
+        let h = std::f64::consts::FRAC_1_SQRT_2;
+        let expected = ndarray::array![
+            [h, 0.0, h, 0.0],
+            [0.0, h, 0.0, h],
+            [0.0, h, 0.0, -h],
+            [h, 0.0, -h, 0.0]
+        ];
+        assert_eq!(unitary.dim(), (4, 4));
+        for (x, &y) in unitary.iter().zip(expected.iter()) {
+            assert!((x - Complex64::new(y, 0.0)).norm() < 1e-12);
+            if y == 0.0 {
+                assert_eq!(*x, Complex64::new(0.0, 0.0));
+            }
+        }
+    }
+
+    #[test]
+    fn too_many_qubits() {
+        let large = TestCircuit::qubits(MAX_UNITARY_QUBITS + 1).finish();
+        assert_eq!(
+            large.equivalent_unitary(&large, 1e-10),
+            Err(UnitaryError::TooManyQubits(MAX_UNITARY_QUBITS + 1))
+        );
+        assert_eq!(
+            large.to_unitary(1e-10),
+            Err(UnitaryError::TooManyQubits(MAX_UNITARY_QUBITS + 1))
+        );
+    }
+
+    #[test]
+    fn permute_composes_with_inverse() {
+        let entangle = |circ: &mut TestCircuit| {
+            circ.gate(circuit::Op::H, &[0]);
+            circ.gate(circuit::Op::CX, &[0, 1]);
+            circ.gate(circuit::Op::T, &[2]);
+        };
+        let mut circ = TestCircuit::qubits(3);
+        entangle(&mut circ);
+        let original = circ.finish();
+
+        let perm = circuit::Op::Permute(vec![2, 0, 1]);
+        let mut circ = TestCircuit::qubits(3);
+        entangle(&mut circ);
+        circ.gate(perm.clone(), &[0, 1, 2]);
+        circ.gate(perm.inverse().unwrap(), &[0, 1, 2]);
+        assert_eq!(original.equivalent_unitary(&circ.finish(), 1e-10), Ok(true));
+
+        let mut circ = TestCircuit::qubits(3);
+        entangle(&mut circ);
+        circ.gate(perm, &[0, 1, 2]);
+        assert_eq!(
+            original.equivalent_unitary(&circ.finish(), 1e-10),
+            Ok(false)
+        );
+
+        let mut swap = TestCircuit::qubits(2);
+        swap.gate(circuit::Op::Swap, &[0, 1]);
+        let mut permute = TestCircuit::qubits(2);
+        permute.gate(circuit::Op::Permute(vec![1, 0]), &[0, 1]);
+        assert_eq!(
+            swap.finish().equivalent_unitary(&permute.finish(), 1e-10),
+            Ok(true)
+        );
+    }
+}
@@ -0,0 +1,443 @@
+//! Structural consistency checks.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use portgraph::{Direction, NodeIndex, PortIndex};
+use thiserror::Error;
+
+use crate::component::debug::SourceSpan;
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::WireType;
+
+use super::Gug;
+
+/// An inconsistency found by [`Gug::validate`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum ValidationError {
+    #[error("node {0:?} has a different number of ports than its operation's signature")]
+    PortCount(NodeIndex),
+    #[error(
+        "port {port:?} of node {node:?} has type {found:?}, but the signature expects {expected:?}"
+    )]
+    PortType {
+        node: NodeIndex,
+        port: PortIndex,
+        expected: WireType,
+        found: WireType,
+    },
+    #[error("linked ports {0:?} and {1:?} have different types")]
+    LinkTypeMismatch(PortIndex, PortIndex),
+    #[error("linear port {port:?} of node {node:?} is not connected")]
+    DanglingLinear { node: NodeIndex, port: PortIndex },
+    #[error("an angle input of gate {0:?} is not connected")]
+    MissingAngle(NodeIndex),
+    #[error("the Permute node {0:?} does not apply a permutation of its wires")]
+    InvalidPermutation(NodeIndex),
+    #[error(transparent)]
+    Linearity(#[from] LinearityError),
+    #[error("the graph has a cycle through nodes {0:?}")]
+    Cycle(Vec<NodeIndex>),
+}
+
+/// A linear value used more than once, found by [`Gug::check_linearity`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum LinearityError {
+    #[error("node {0:?} uses a LinearBit more than once without an explicit Copy")]
+    MultiUseLinearBit(NodeIndex),
+}
+
+impl Gug {
+    /// Checks that the graph is consistent with the operations it contains.
+    ///
+    /// The ports of each operation must match its signature in number and
+    /// type, linked ports must have the same type, and every `Qubit` or
+    /// `LinearBit` port must be connected. Boundary and control flow nodes
+    /// define their own ports, so only the last two checks apply to them.
+    ///
+    /// The `Angle` inputs of gates acting on qubits, such as `RzF64`, must
+    /// also be connected. Gates with an inlined angle have no such input.
+    /// `Permute` nodes must map their wires one-to-one. Finally, the graph
+    /// must pass [`Gug::check_linearity`].
+    ///
+    /// Before any of these, the graph is checked to be [acyclic](Gug::is_acyclic).
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.try_toposort()?;
+        for node in self.nodes() {
+            self.validate_signature(node)?;
+            self.validate_angles(node)?;
+            if let Op::Circuit(circuit::Op::Permute(perm)) = self.optype(node) {
+                if !circuit::is_permutation(perm) {
+                    return Err(ValidationError::InvalidPermutation(node));
+                }
+            }
+            for direction in [Direction::Incoming, Direction::Outgoing] {
+                for port in self.node_ports(node, direction) {
+                    let typ = self.port_type(port);
+                    match self.port_link(port) {
+                        Some(link) if self.port_type(link) != typ => {
+                            return Err(ValidationError::LinkTypeMismatch(port, link));
+                        }
+                        None if matches!(typ, WireType::Qubit | WireType::LinearBit) => {
+                            return Err(ValidationError::DanglingLinear { node, port });
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        self.check_linearity()?;
+        Ok(())
+    }
+
+    /// Checks that every classical `LinearBit` value, such as a measurement
+    /// result, is consumed exactly once.
+    ///
+    /// Since a port links to a single other port, a bit can only be used
+    /// twice by a node that consumes `LinearBit`s and produces more of them
+    /// than it consumes. Unlike qubits, bits may be duplicated, but only
+    /// through an explicit `Copy` node. A node that consumes no bits, such as
+    /// an opaque operation measuring a qubit, creates its bits rather than
+    /// duplicating them. Unconnected bits are reported by [`Gug::validate`].
+    pub fn check_linearity(&self) -> Result<(), LinearityError> {
+        for node in self.nodes() {
+            let op = self.optype(node);
+            if op.is_boundary() || matches!(op, Op::Circuit(circuit::Op::Copy { .. })) {
+                continue;
+            }
+            let bits = |direction| {
+                self.node_ports(node, direction)
+                    .filter(|&p| self.port_type(p) == WireType::LinearBit)
+                    .count()
+            };
+            let consumed = bits(Direction::Incoming);
+            if consumed > 0 && bits(Direction::Outgoing) > consumed {
+                return Err(LinearityError::MultiUseLinearBit(node));
+            }
+        }
+        Ok(())
+    }
+
+    /// Records the span of source text a node was created from.
+    pub fn set_source_span(&mut self, node: NodeIndex, span: SourceSpan) {
+        self.register_node_metadata::<SourceSpan>();
+        *self.node_metadata_mut::<SourceSpan>(node).unwrap() = span;
+    }
+
+    /// Returns the span of source text a node was created from, if recorded.
+    pub fn source_span(&self, node: NodeIndex) -> Option<SourceSpan> {
+        let span = *self.node_metadata::<SourceSpan>(node)?;
+        (span != SourceSpan::default()).then_some(span)
+    }
+
+    /// Formats a validation error, pointing to the source span of the node
+    /// at fault when one is recorded.
+    pub fn describe_error(&self, error: &ValidationError) -> String {
+        match self.error_node(error).and_then(|n| self.source_span(n)) {
+            Some(span) => format!("{error} (source {span})"),
+            None => error.to_string(),
+        }
+    }
+
+    /// Panics if [`Gug::validate`] fails, with a message naming the operation
+    /// and index of the node at fault and a Graphviz rendering of it and its
+    /// direct neighbours.
+    ///
+    /// Like [`debug_assert!`], the check is skipped when debug assertions are
+    /// disabled, so calls can be left in tests and passes.
+    #[track_caller]
+    pub fn assert_valid(&self) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        let Err(error) = self.validate() else { return };
+        let mut message = format!("invalid graph: {}", self.describe_error(&error));
+        if let Some(node) = self.error_node(&error) {
+            let name = self.optype(node).name();
+            write!(message, "\nat node {} ({name}):\n", node.index()).unwrap();
+            message.push_str(&self.neighbourhood_dot(node));
+        }
+        panic!("{message}");
+    }
+
+    /// Returns the node a validation error is about.
+    fn error_node(&self, error: &ValidationError) -> Option<NodeIndex> {
+        match error {
+            ValidationError::PortCount(node)
+            | ValidationError::PortType { node, .. }
+            | ValidationError::DanglingLinear { node, .. }
+            | ValidationError::MissingAngle(node)
+            | ValidationError::InvalidPermutation(node)
+            | ValidationError::Linearity(LinearityError::MultiUseLinearBit(node)) => Some(*node),
+            ValidationError::LinkTypeMismatch(port, _) => self.port_node(*port),
+            ValidationError::Cycle(nodes) => nodes.first().copied(),
+        }
+    }
+
+    /// Renders `node` and the nodes linked to it in the `dot` format, with
+    /// `node` highlighted and each wire labelled by its type.
+    fn neighbourhood_dot(&self, node: NodeIndex) -> String {
+        let mut nodes: BTreeSet<NodeIndex> = self
+            .predecessors(node)
+            .chain(self.successors(node))
+            .collect();
+        nodes.insert(node);
+        let mut dot = String::from("digraph {\n");
+        for &n in &nodes {
+            let style = if n == node { ", color=red" } else { "" };
+            let name = self.optype(n).name();
+            writeln!(dot, "  {0} [label=\"{0}: {name}\"{style}];", n.index()).unwrap();
+        }
+        for &n in &nodes {
+            for port in self.node_ports(n, Direction::Outgoing) {
+                let Some(target) = self.port_link(port).and_then(|l| self.port_node(l)) else {
+                    continue;
+                };
+                if nodes.contains(&target) {
+                    let typ = self.port_type(port);
+                    writeln!(
+                        dot,
+                        "  {} -> {} [label=\"{typ:?}\"];",
+                        n.index(),
+                        target.index()
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        dot.push('}');
+        dot
+    }
+
+    fn validate_angles(&self, node: NodeIndex) -> Result<(), ValidationError> {
+        let Op::Circuit(op) = self.optype(node) else {
+            return Ok(());
+        };
+        if op.num_qubits() > 0 {
+            let dangling = self
+                .node_ports(node, Direction::Incoming)
+                .any(|p| self.port_type(p) == WireType::Angle && self.port_link(p).is_none());
+            if dangling {
+                return Err(ValidationError::MissingAngle(node));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_signature(&self, node: NodeIndex) -> Result<(), ValidationError> {
+        let op = self.optype(node);
+        if op.is_boundary() || matches!(op, Op::ControlFlow(_)) {
+            return Ok(());
+        }
+        let signature = op.signature();
+        let (num_inputs, num_outputs) = signature.num_ports();
+        if self.node_ports(node, Direction::Incoming).count() != num_inputs
+            || self.node_ports(node, Direction::Outgoing).count() != num_outputs
+        {
+            return Err(ValidationError::PortCount(node));
+        }
+        let ports = self
+            .node_ports(node, Direction::Incoming)
+            .zip(signature.inputs())
+            .chain(
+                self.node_ports(node, Direction::Outgoing)
+                    .zip(signature.outputs()),
+            );
+        for (port, &expected) in ports {
+            let found = self.port_type(port);
+            if found != expected {
+                return Err(ValidationError::PortType {
+                    node,
+                    port,
+                    expected,
+                    found,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::operation::CustomOp;
+    use crate::component::wire_type::{AngleValue, ConstValue, Signature};
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn valid_circuit() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        assert_eq!(circ.finish().validate(), Ok(()));
+    }
+
+    #[test]
+    fn missing_angle() {
+        let mut circ = TestCircuit::qubits(1);
+        let rz = circ.gate(circuit::Op::RzF64, &[0]);
+        let gug = circ.finish();
+        assert_eq!(gug.validate(), Err(ValidationError::MissingAngle(rz)));
+
+        let mut circ = TestCircuit::qubits(1);
+        let angle = circ.constant(ConstValue::f64_angle(0.5));
+        circ.gate_with_args(circuit::Op::RzF64, &[0], &[(angle, 0)]);
+        circ.gate(circuit::Op::Rz(AngleValue::F64(0.5)), &[0]);
+        assert_eq!(circ.finish().validate(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_permutation() {
+        let mut circ = TestCircuit::qubits(2);
+        let perm = circ.gate(circuit::Op::Permute(vec![1, 1]), &[0, 1]);
+        let gug = circ.finish();
+        assert_eq!(
+            gug.validate(),
+            Err(ValidationError::InvalidPermutation(perm))
+        );
+
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::Permute(vec![1, 0]), &[0, 1]);
+        assert_eq!(circ.finish().validate(), Ok(()));
+    }
+
+    #[test]
+    fn dangling_qubit() {
+        let mut circ = TestCircuit::qubits(1);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let mut gug = circ.finish();
+        let port = gug.output_port(h, 0).unwrap();
+        gug.unlink_port(port);
+        assert_eq!(
+            gug.validate(),
+            Err(ValidationError::DanglingLinear { node: h, port })
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn assert_valid_names_node() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut circ = TestCircuit::qubits(2);
+        let angle = circ.constant(ConstValue::f64_angle(0.5));
+        let rz = circ.gate_with_args(circuit::Op::RzF64, &[0], &[(angle, 0)]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        let mut gug = circ.finish();
+        gug.assert_valid();
+
+        gug.unlink_port(gug.output_port(angle, 0).unwrap());
+        let panic = catch_unwind(AssertUnwindSafe(|| gug.assert_valid())).unwrap_err();
+        let message = panic.downcast_ref::<String>().unwrap();
+        assert!(message.contains(&format!("at node {} (RzF64)", rz.index())));
+        let highlighted = format!("{0} [label=\"{0}: RzF64\", color=red]", rz.index());
+        assert!(message.contains(&highlighted));
+        assert!(message.contains("[label=\"Qubit\"]"));
+    }
+
+    /// A bit splitter that is not a `Copy` node.
+    #[derive(Clone, Debug)]
+    struct Fanout;
+
+    impl CustomOp for Fanout {
+        fn name(&self) -> &str {
+            "Fanout"
+        }
+
+        fn signature(&self) -> Signature {
+            Signature::new_nonlinear(vec![WireType::LinearBit], vec![WireType::LinearBit; 2])
+        }
+    }
+
+    /// Measures a qubit and controls an `X` on each qubit with the result,
+    /// split in two by `split`.
+    fn reuse_measurement(split: Op) -> (Gug, NodeIndex) {
+        let (qb, bit) = (WireType::Qubit, WireType::LinearBit);
+        let mut gug = Gug::with_boundary(vec![qb, qb, bit], vec![qb, qb, bit, bit]);
+        let (input, output) = gug.boundary().unwrap();
+        let measure = gug.add_node(Op::Circuit(circuit::Op::Measure));
+        gug.connect(input, 0, measure, 0).unwrap();
+        gug.connect(input, 2, measure, 1).unwrap();
+        let split = gug.add_node(split);
+        gug.connect(measure, 1, split, 0).unwrap();
+        for (i, (qubit, offset)) in [(measure, 0), (input, 1)].into_iter().enumerate() {
+            let x = circuit::Op::ClassicallyControlled(Box::new(circuit::Op::X));
+            let x = gug.add_node(Op::Circuit(x));
+            gug.connect(split, i, x, 0).unwrap();
+            gug.connect(qubit, offset, x, 1).unwrap();
+            gug.connect(x, 1, output, i).unwrap();
+            gug.connect(x, 0, output, 2 + i).unwrap();
+        }
+        (gug, split)
+    }
+
+    /// A measurement that creates its output bit.
+    #[derive(Clone, Debug)]
+    struct Probe;
+
+    impl CustomOp for Probe {
+        fn name(&self) -> &str {
+            "Probe"
+        }
+
+        fn signature(&self) -> Signature {
+            let (qb, bit) = (WireType::Qubit, WireType::LinearBit);
+            Signature::new_nonlinear(vec![qb], vec![qb, bit])
+        }
+    }
+
+    #[test]
+    fn bit_creating_op() {
+        let (qb, bit) = (WireType::Qubit, WireType::LinearBit);
+        let mut gug = Gug::with_boundary(vec![qb], vec![qb, bit]);
+        let (input, output) = gug.boundary().unwrap();
+        let probe = gug.add_node(Op::Opaque(Box::new(Probe)));
+        gug.connect(input, 0, probe, 0).unwrap();
+        gug.connect(probe, 0, output, 0).unwrap();
+        gug.connect(probe, 1, output, 1).unwrap();
+        assert_eq!(gug.check_linearity(), Ok(()));
+        assert_eq!(gug.validate(), Ok(()));
+    }
+
+    #[test]
+    fn multi_use_bit() {
+        let (gug, fanout) = reuse_measurement(Op::Opaque(Box::new(Fanout)));
+        let err = LinearityError::MultiUseLinearBit(fanout);
+        assert_eq!(gug.check_linearity(), Err(err.clone()));
+        assert_eq!(gug.validate(), Err(ValidationError::Linearity(err)));
+
+        let copy = circuit::Op::Copy {
+            n_copies: 2,
+            typ: WireType::LinearBit,
+        };
+        let (gug, _) = reuse_measurement(Op::Circuit(copy));
+        assert_eq!(gug.check_linearity(), Ok(()));
+        assert_eq!(gug.validate(), Ok(()));
+    }
+
+    /// Builds an `H` followed by an `X` on a single qubit, then feeds the
+    /// output of the `X` back into the `H`, detaching them from the boundary.
+    fn cyclic_circuit() -> (Gug, NodeIndex, NodeIndex) {
+        let mut circ = TestCircuit::qubits(1);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let x = circ.gate(circuit::Op::X, &[0]);
+        let mut gug = circ.finish();
+        gug.unlink_port(gug.input_port(h, 0).unwrap());
+        gug.unlink_port(gug.output_port(x, 0).unwrap());
+        gug.connect(x, 0, h, 0).unwrap();
+        (gug, h, x)
+    }
+
+    #[test]
+    fn cycle() {
+        let (gug, h, x) = cyclic_circuit();
+        assert!(!gug.is_acyclic());
+        let err = ValidationError::Cycle(vec![h, x]);
+        assert_eq!(gug.try_toposort(), Err(err.clone()));
+        assert_eq!(gug.validate(), Err(err));
+        assert_eq!(gug.toposort().len(), 2);
+    }
+}
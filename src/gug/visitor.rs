@@ -0,0 +1,77 @@
+//! An extension point for analyses walking over a circuit.
+
+use std::collections::HashMap;
+
+use portgraph::NodeIndex;
+
+use super::Gug;
+
+/// A visitor called on each node of a graph by [`Gug::accept`].
+pub trait GugVisitor {
+    /// Visits a node. Nodes are visited in topological order, so all the
+    /// predecessors of a node have been visited before it.
+    fn visit_node(&mut self, gug: &Gug, node: NodeIndex);
+}
+
+/// A visitor counting the visited operations by name, boundary nodes
+/// excluded.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CountingVisitor {
+    /// The number of operations of each kind visited so far.
+    pub counts: HashMap<String, usize>,
+}
+
+impl GugVisitor for CountingVisitor {
+    fn visit_node(&mut self, gug: &Gug, node: NodeIndex) {
+        let op = gug.optype(node);
+        if !op.is_boundary() {
+            *self.counts.entry(op.display_name().into_owned()).or_default() += 1;
+        }
+    }
+}
+
+impl Gug {
+    /// Walks the graph in topological order, calling the visitor on each node.
+    pub fn accept(&self, visitor: &mut impl GugVisitor) {
+        for node in self.toposort() {
+            visitor.visit_node(self, node);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::operation::circuit;
+    use crate::test_utils::TestCircuit;
+
+    struct NameCollector(Vec<String>);
+
+    impl GugVisitor for NameCollector {
+        fn visit_node(&mut self, gug: &Gug, node: NodeIndex) {
+            self.0.push(gug.optype(node).name().to_string());
+        }
+    }
+
+    #[test]
+    fn collect_names_in_order() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::T, &[1]);
+        let gug = circ.finish();
+
+        let mut collector = NameCollector(Vec::new());
+        gug.accept(&mut collector);
+        let expected: Vec<String> = gug
+            .toposort()
+            .into_iter()
+            .map(|n| gug.optype(n).name().to_string())
+            .collect();
+        assert_eq!(collector.0, expected);
+
+        let mut counter = CountingVisitor::default();
+        gug.accept(&mut counter);
+        assert_eq!(counter.counts, gug.gate_counts());
+    }
+}
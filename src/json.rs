@@ -0,0 +1,266 @@
+//! Import of circuits from a simple JSON format.
+//!
+//! A circuit is described by its number of qubits and classical bits, and a
+//! list of gates applied in order:
+//!
+//! ```json
+//! {
+//!     "qubits": 2,
+//!     "bits": 1,
+//!     "gates": [
+//!         { "op": "h", "qubits": [0] },
+//!         { "op": "cx", "qubits": [0, 1] },
+//!         { "op": "rz", "qubits": [1], "params": [0.25] },
+//!         { "op": "measure", "qubits": [1], "bits": [0] }
+//!     ]
+//! }
+//! ```
+//!
+//! Operation names are matched against [`circuit::Op::from_name`], ignoring
+//! case. The `rx` and `rz` rotations take their angle inline, other
+//! parameters are fed to the operation's angle inputs from `Const` nodes.
+//! Parameters are given in half-turns.
+//!
+//! Qubits become `Qubit` wires and bits `LinearBit` wires of the `Input` and
+//! `Output` nodes, with the qubits first. A gate acts on its qubits followed
+//! by its bits, in the order of the linear wires of its signature. Gates
+//! using a wire twice, or a bit where the signature expects a qubit, are
+//! rejected.
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::{AngleValue, ConstValue, WireType};
+use crate::gug::{BuildError, CircuitBuilder};
+use crate::Gug;
+
+/// Error returned when importing a JSON circuit.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum JsonError {
+    #[error("invalid JSON circuit: {0}")]
+    Parse(String),
+    #[error("gate {gate}: unknown operation {name:?}")]
+    UnknownOp { gate: usize, name: String },
+    #[error("gate {gate}: {name} acts on {expected} wires, found {found}")]
+    WrongArity {
+        gate: usize,
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("gate {gate}: {name} expects {expected} parameters, found {found}")]
+    WrongParams {
+        gate: usize,
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("gate {gate}: wire {wire} is out of range")]
+    WireOutOfRange { gate: usize, wire: usize },
+    #[error("gate {gate}: invalid {name}: {source}")]
+    Invalid {
+        gate: usize,
+        name: String,
+        source: BuildError,
+    },
+}
+
+#[derive(Deserialize)]
+struct JsonCircuit {
+    qubits: usize,
+    #[serde(default)]
+    bits: usize,
+    #[serde(default)]
+    gates: Vec<JsonGate>,
+}
+
+#[derive(Deserialize)]
+struct JsonGate {
+    op: String,
+    #[serde(default)]
+    qubits: Vec<usize>,
+    #[serde(default)]
+    bits: Vec<usize>,
+    #[serde(default)]
+    params: Vec<f64>,
+}
+
+/// Imports a circuit from its JSON description.
+pub fn from_json(src: &str) -> Result<Gug, JsonError> {
+    let circuit: JsonCircuit =
+        serde_json::from_str(src).map_err(|e| JsonError::Parse(e.to_string()))?;
+    let types: Vec<WireType> = std::iter::repeat(WireType::Qubit)
+        .take(circuit.qubits)
+        .chain(std::iter::repeat(WireType::LinearBit).take(circuit.bits))
+        .collect();
+
+    let mut gug = Gug::new();
+    let mut builder = CircuitBuilder::new(&mut gug, &types);
+
+    for (index, gate) in circuit.gates.iter().enumerate() {
+        let (op, params) = gate_op(index, gate)?;
+        let linear: Vec<usize> = gate
+            .qubits
+            .iter()
+            .copied()
+            .chain(gate.bits.iter().map(|&bit| bit + circuit.qubits))
+            .collect();
+        let out_of_range = gate
+            .qubits
+            .iter()
+            .find(|&&q| q >= circuit.qubits)
+            .or_else(|| gate.bits.iter().find(|&&b| b >= circuit.bits));
+        if let Some(&wire) = out_of_range {
+            return Err(JsonError::WireOutOfRange { gate: index, wire });
+        }
+        let signature = op.signature();
+        if linear.len() != signature.linear.len() {
+            return Err(JsonError::WrongArity {
+                gate: index,
+                name: gate.op.clone(),
+                expected: signature.linear.len(),
+                found: linear.len(),
+            });
+        }
+
+        let args: Vec<_> = params
+            .into_iter()
+            .map(|theta| {
+                let value = ConstValue::Angle(AngleValue::F64(theta));
+                (gug.add_node(Op::Circuit(circuit::Op::Const(value))), 0)
+            })
+            .collect();
+        builder
+            .append(&mut gug, op, &linear, &args)
+            .map_err(|source| JsonError::Invalid {
+                gate: index,
+                name: gate.op.clone(),
+                source,
+            })?;
+    }
+    builder.finish(&mut gug);
+    Ok(gug)
+}
+
+/// Returns the operation of a gate, with the parameters to feed to its angle
+/// inputs.
+fn gate_op(index: usize, gate: &JsonGate) -> Result<(circuit::Op, Vec<f64>), JsonError> {
+    let name = gate.op.as_str();
+    let wrong_params = |expected| JsonError::WrongParams {
+        gate: index,
+        name: name.to_string(),
+        expected,
+        found: gate.params.len(),
+    };
+    if name.eq_ignore_ascii_case("rx") || name.eq_ignore_ascii_case("rz") {
+        let [theta] = gate.params[..] else {
+            return Err(wrong_params(1));
+        };
+        let angle = AngleValue::F64(theta);
+        let op = if name.eq_ignore_ascii_case("rx") {
+            circuit::Op::Rx(angle)
+        } else {
+            circuit::Op::Rz(angle)
+        };
+        return Ok((op, vec![]));
+    }
+
+    let mut capitalized = name.to_ascii_lowercase();
+    if let Some(first) = capitalized.get_mut(..1) {
+        first.make_ascii_uppercase();
+    }
+    let op = circuit::Op::from_name(name)
+        .or_else(|| circuit::Op::from_name(&capitalized))
+        .or_else(|| circuit::Op::from_name(&name.to_ascii_uppercase()))
+        .ok_or_else(|| JsonError::UnknownOp {
+            gate: index,
+            name: name.to_string(),
+        })?;
+    let num_angles = op.signature().nonlinear[0]
+        .iter()
+        .filter(|&&t| t == WireType::Angle)
+        .count();
+    if gate.params.len() != num_angles {
+        return Err(wrong_params(num_angles));
+    }
+    Ok((op, gate.params.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_json() {
+        let src = r#"{
+            "qubits": 2,
+            "bits": 1,
+            "gates": [
+                { "op": "h", "qubits": [0] },
+                { "op": "cx", "qubits": [0, 1] },
+                { "op": "rz", "qubits": [1], "params": [0.25] },
+                { "op": "RzF64", "qubits": [0], "params": [0.5] },
+                { "op": "tadj", "qubits": [1] },
+                { "op": "measure", "qubits": [1], "bits": [0] }
+            ]
+        }"#;
+        let gug = from_json(src).unwrap();
+        let counts = gug.gate_counts();
+        assert_eq!(counts["H"], 1);
+        assert_eq!(counts["CX"], 1);
+        assert_eq!(counts["Rz"], 1);
+        assert_eq!(counts["RzF64"], 1);
+        assert_eq!(counts["Tadj"], 1);
+        assert_eq!(counts["Measure"], 1);
+        assert_eq!(counts["Const"], 1);
+        assert_eq!(gug.validate(), Ok(()));
+        let (input, _) = gug.boundary().unwrap();
+        assert_eq!(gug.num_outputs(input), 3);
+    }
+
+    #[test]
+    fn unknown_op() {
+        let src = r#"{ "qubits": 1, "gates": [{ "op": "frobnicate", "qubits": [0] }] }"#;
+        assert_eq!(
+            from_json(src).unwrap_err(),
+            JsonError::UnknownOp {
+                gate: 0,
+                name: "frobnicate".to_string()
+            }
+        );
+        let src = r#"{ "qubits": 1, "gates": [{ "op": "cx", "qubits": [0] }] }"#;
+        assert!(matches!(from_json(src), Err(JsonError::WrongArity { .. })));
+        assert!(matches!(from_json("{"), Err(JsonError::Parse(_))));
+    }
+
+    #[test]
+    fn invalid_wires() {
+        let src = r#"{ "qubits": 2, "gates": [{ "op": "cx", "qubits": [1, 1] }] }"#;
+        assert_eq!(
+            from_json(src).unwrap_err(),
+            JsonError::Invalid {
+                gate: 0,
+                name: "cx".to_string(),
+                source: BuildError::RepeatedWire(1)
+            }
+        );
+        let src = r#"{
+            "qubits": 1,
+            "bits": 1,
+            "gates": [{ "op": "cx", "qubits": [0], "bits": [0] }]
+        }"#;
+        assert_eq!(
+            from_json(src).unwrap_err(),
+            JsonError::Invalid {
+                gate: 0,
+                name: "cx".to_string(),
+                source: BuildError::WireType {
+                    wire: 1,
+                    expected: WireType::Qubit,
+                    found: WireType::LinearBit
+                }
+            }
+        );
+    }
+}
@@ -1,9 +1,17 @@
 pub mod component;
+pub mod extension;
 pub mod gug;
+pub mod json;
 mod macros;
+pub mod optimize;
+pub mod passes;
+pub mod qasm;
 pub mod rewrite;
 
-pub use crate::component::debug::DebugData;
+#[cfg(test)]
+mod test_utils;
+
+pub use crate::component::debug::{DebugData, SourceSpan};
 pub use crate::gug::Gug;
 
 #[cfg(test)]
@@ -0,0 +1,249 @@
+//! A configurable driver running optimization passes to a fixpoint.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::passes;
+use crate::Gug;
+
+/// A pass run by the [`Optimizer`], given the optimizer's tolerance and
+/// returning the number of changes it made.
+pub type OptimizerPass = fn(&mut Gug, f64) -> usize;
+
+/// Runs a pipeline of passes repeatedly until none of them changes the
+/// circuit, or the iteration bound is reached.
+#[derive(Clone)]
+pub struct Optimizer {
+    passes: Vec<(&'static str, OptimizerPass)>,
+    max_iterations: usize,
+    tolerance: f64,
+}
+
+/// The changes made by a run of the [`Optimizer`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OptimizationReport {
+    /// The number of times the pipeline was run.
+    pub iterations: usize,
+    /// Whether the last iteration made no changes.
+    pub converged: bool,
+    /// The statistics of each pass, in pipeline order.
+    pub passes: Vec<PassProfile>,
+}
+
+/// The statistics of a single pass over a run of the [`Optimizer`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PassProfile {
+    /// The name of the pass.
+    pub name: &'static str,
+    /// The total number of changes made by the pass.
+    pub changes: usize,
+    /// The number of nodes in the circuit before the first run of the pass.
+    pub nodes_before: usize,
+    /// The number of nodes in the circuit after the last run of the pass.
+    pub nodes_after: usize,
+    /// The total wall-clock time spent in the pass.
+    pub time: Duration,
+}
+
+impl Optimizer {
+    /// Creates an optimizer with no passes.
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            max_iterations: 100,
+            tolerance: 1e-10,
+        }
+    }
+
+    /// Creates an optimizer running the standard simplification passes:
+    /// inverse cancellation, angle canonicalization, rotation merging, Euler
+    /// resynthesis of rotation runs, constant folding and dead code
+    /// elimination.
+    pub fn default_pipeline() -> Self {
+        Self::new()
+            .with_pass("cancel_inverses", |gug, _| passes::cancel_inverses(gug))
+            .with_pass("canonicalize_angles", |gug, _| {
+                passes::canonicalize_angles(gug)
+            })
+            .with_pass("merge_rotations", passes::merge_rotations)
+            .with_pass("resynthesize_euler", |gug, _| {
+                passes::resynthesize_euler(gug)
+            })
+            .with_pass("fold_constants", |gug, _| passes::fold_constants(gug))
+            .with_pass("dead_code_elimination", |gug, _| {
+                passes::dead_code_elimination(gug)
+            })
+    }
+
+    /// Appends a pass to the pipeline.
+    pub fn with_pass(mut self, name: &'static str, pass: OptimizerPass) -> Self {
+        self.passes.push((name, pass));
+        self
+    }
+
+    /// Sets the maximum number of times the pipeline is run.
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Sets the numerical tolerance passed to the passes.
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Runs the pipeline on the circuit, recording the changes made and the
+    /// time spent by each pass.
+    pub fn run(&self, gug: &mut Gug) -> OptimizationReport {
+        let mut report = OptimizationReport {
+            passes: self
+                .passes
+                .iter()
+                .map(|&(name, _)| PassProfile {
+                    name,
+                    nodes_before: gug.node_count(),
+                    nodes_after: gug.node_count(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        while report.iterations < self.max_iterations {
+            report.iterations += 1;
+            let mut changed = false;
+            for (&(_, pass), profile) in self.passes.iter().zip(&mut report.passes) {
+                if report.iterations == 1 {
+                    profile.nodes_before = gug.node_count();
+                }
+                let start = Instant::now();
+                let changes = pass(gug, self.tolerance);
+                profile.time += start.elapsed();
+                profile.changes += changes;
+                profile.nodes_after = gug.node_count();
+                changed |= changes > 0;
+            }
+            if !changed {
+                report.converged = true;
+                break;
+            }
+        }
+        report
+    }
+}
+
+impl fmt::Debug for Optimizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.passes.iter().map(|&(name, _)| name).collect();
+        f.debug_struct("Optimizer")
+            .field("passes", &names)
+            .field("max_iterations", &self.max_iterations)
+            .field("tolerance", &self.tolerance)
+            .finish()
+    }
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self::default_pipeline()
+    }
+}
+
+impl OptimizationReport {
+    /// Returns the total number of changes made by the named pass.
+    pub fn count(&self, pass: &str) -> usize {
+        self.passes
+            .iter()
+            .find(|profile| profile.name == pass)
+            .map_or(0, |profile| profile.changes)
+    }
+
+    /// Returns the total number of changes made by all passes.
+    pub fn total(&self) -> usize {
+        self.passes.iter().map(|profile| profile.changes).sum()
+    }
+
+    /// Returns the total wall-clock time spent in all passes.
+    pub fn total_time(&self) -> Duration {
+        self.passes.iter().map(|profile| profile.time).sum()
+    }
+}
+
+impl fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = if self.converged { "converged" } else { "stopped" };
+        writeln!(f, "{status} after {} iterations", self.iterations)?;
+        let width = self.passes.iter().map(|p| p.name.len()).max().unwrap_or(0).max(4);
+        writeln!(f, "{:width$}  changes     nodes      time", "pass")?;
+        for profile in &self.passes {
+            let nodes = format!("{} -> {}", profile.nodes_before, profile.nodes_after);
+            writeln!(
+                f,
+                "{:width$}  {:>7}  {:>8}  {:>8.3}ms",
+                profile.name,
+                profile.changes,
+                nodes,
+                profile.time.as_secs_f64() * 1e3,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::operation::circuit;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn cancel_to_empty() {
+        let mut circ = TestCircuit::qubits(1);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::X, &[0]);
+        circ.gate(circuit::Op::X, &[0]);
+        let mut gug = circ.finish();
+
+        let report = Optimizer::default_pipeline().run(&mut gug);
+        assert_eq!(gug.node_count(), 2);
+        assert!(report.converged);
+        assert_eq!(report.count("cancel_inverses"), 2);
+        assert_eq!(report.count("resynthesize_euler"), 0);
+        assert_eq!(report.total(), 2);
+
+        let input = gug.input_node().unwrap();
+        let output = gug.output_node().unwrap();
+        assert_eq!(gug.output_target(input, 0), Some((output, 0)));
+    }
+
+    #[test]
+    fn profile_passes() {
+        let mut circ = TestCircuit::qubits(1);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::T, &[0]);
+        let mut gug = circ.finish();
+
+        let report = Optimizer::new()
+            .with_pass("cancel_inverses", |gug, _| passes::cancel_inverses(gug))
+            .with_pass("dead_code_elimination", |gug, _| {
+                passes::dead_code_elimination(gug)
+            })
+            .run(&mut gug);
+        assert_eq!(report.passes.len(), 2);
+        let names: Vec<&str> = report.passes.iter().map(|p| p.name).collect();
+        assert_eq!(names, ["cancel_inverses", "dead_code_elimination"]);
+
+        let cancel = &report.passes[0];
+        assert_eq!((cancel.nodes_before, cancel.nodes_after), (5, 3));
+        assert_eq!(cancel.changes, 1);
+        assert!(report.passes.iter().all(|p| p.time >= Duration::ZERO));
+        assert_eq!(report.total_time(), cancel.time + report.passes[1].time);
+
+        let table = report.to_string();
+        assert!(table.lines().nth(1).unwrap().starts_with("pass"));
+        assert!(table.contains("cancel_inverses"));
+        assert!(table.contains("5 -> 3"));
+    }
+}
@@ -0,0 +1,104 @@
+use portgraph::NodeIndex;
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::{AngleValue, ConstValue};
+use crate::Gug;
+
+/// Simplifies `AngleNeg` nodes.
+///
+/// Two negations in series are removed, connecting the original angle to the
+/// consumer of the second one. A single negation of a constant angle feeding
+/// an `RzF64` or `RxF64` is folded into the rotation, which becomes an `Rz`
+/// or `Rx` carrying the negated angle inline. Negations of other angles are
+/// left in place, as no rotation takes a negated angle input.
+///
+/// Returns the number of simplified negations.
+pub fn simplify_angle_neg(gug: &mut Gug) -> usize {
+    let mut simplified = 0;
+    for node in gug.toposort() {
+        // The node may have been removed as the inner half of a double negation.
+        if !is_angle_neg(gug, node) {
+            continue;
+        }
+        let Some((source, _)) = gug.input_source(node, 0) else { continue };
+        if is_angle_neg(gug, source) {
+            let angle = gug.port_link(gug.input_port(source, 0).unwrap());
+            let target = gug.port_link(gug.output_port(node, 0).unwrap());
+            gug.remove_node(node);
+            gug.remove_node(source);
+            if let (Some(angle), Some(target)) = (angle, target) {
+                gug.link_ports(angle, target).unwrap();
+            }
+            simplified += 1;
+        } else if let Some((rotation, inlined)) = negated_rotation(gug, node, source) {
+            gug.set_optype(rotation, Op::Circuit(inlined));
+            gug.remove_node(node);
+            gug.remove_node(source);
+            simplified += 1;
+        }
+    }
+    simplified
+}
+
+fn is_angle_neg(gug: &Gug, node: NodeIndex) -> bool {
+    matches!(gug.optype(node), Op::Circuit(circuit::Op::AngleNeg))
+}
+
+/// Returns the rotation consuming a negation of the `Const` node `source`,
+/// and the rotation with the negated angle inlined.
+fn negated_rotation(
+    gug: &Gug,
+    node: NodeIndex,
+    source: NodeIndex,
+) -> Option<(NodeIndex, circuit::Op)> {
+    let Op::Circuit(circuit::Op::Const(ConstValue::Angle(angle))) = gug.optype(source) else {
+        return None;
+    };
+    let angle: AngleValue = -*angle;
+    let (rotation, 1) = gug.output_target(node, 0)? else { return None };
+    let inlined = match gug.optype(rotation) {
+        Op::Circuit(circuit::Op::RzF64) => circuit::Op::Rz(angle),
+        Op::Circuit(circuit::Op::RxF64) => circuit::Op::Rx(angle),
+        _ => return None,
+    };
+    Some((rotation, inlined))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn double_negation() {
+        let mut circ = TestCircuit::qubits(1);
+        let angle = circ.constant(ConstValue::f64_angle(0.25));
+        let neg1 = circ.gate_with_args(circuit::Op::AngleNeg, &[], &[(angle, 0)]);
+        let neg2 = circ.gate_with_args(circuit::Op::AngleNeg, &[], &[(neg1, 0)]);
+        let rz = circ.gate_with_args(circuit::Op::RzF64, &[0], &[(neg2, 0)]);
+        let mut gug = circ.finish();
+
+        assert_eq!(simplify_angle_neg(&mut gug), 1);
+        assert!(!gug.gate_counts().contains_key("AngleNeg"));
+        assert_eq!(gug.input_source(rz, 1), Some((angle, 0)));
+        assert_eq!(gug.optype(rz), &Op::Circuit(circuit::Op::RzF64));
+        assert_eq!(gug.validate(), Ok(()));
+    }
+
+    #[test]
+    fn negation_into_rotation() {
+        let mut circ = TestCircuit::qubits(1);
+        let angle = circ.constant(ConstValue::f64_angle(0.25));
+        let neg = circ.gate_with_args(circuit::Op::AngleNeg, &[], &[(angle, 0)]);
+        let rx = circ.gate_with_args(circuit::Op::RxF64, &[0], &[(neg, 0)]);
+        let mut gug = circ.finish();
+
+        assert_eq!(simplify_angle_neg(&mut gug), 1);
+        assert_eq!(
+            gug.optype(rx),
+            &Op::Circuit(circuit::Op::Rx(AngleValue::F64(-0.25)))
+        );
+        assert_eq!(gug.node_count(), 3);
+        assert_eq!(gug.validate(), Ok(()));
+    }
+}
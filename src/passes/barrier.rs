@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+
+use portgraph::{NodeIndex, PortIndex};
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::{Signature, WireType};
+use crate::Gug;
+
+/// Coalesces `Barrier` nodes that directly follow each other into a single
+/// barrier spanning the union of their wires.
+///
+/// Two barriers are adjacent when some output of the first feeds the second,
+/// and no other path leads from the first to the second, so there is nothing
+/// between them on any wire they share. The merged barrier lists the wires of
+/// the first barrier, followed by the wires only crossing the second one.
+///
+/// Returns the number of removed barriers.
+pub fn merge_barriers(gug: &mut Gug) -> usize {
+    let mut merged = 0;
+    let mut removed = HashSet::new();
+    for node in gug.toposort() {
+        if removed.contains(&node) || !is_barrier(gug, node) {
+            continue;
+        }
+        let mut barrier = node;
+        while let Some(next) = adjacent_barrier(gug, barrier) {
+            removed.extend([barrier, next]);
+            barrier = merge(gug, barrier, next);
+            merged += 1;
+        }
+    }
+    merged
+}
+
+fn is_barrier(gug: &Gug, node: NodeIndex) -> bool {
+    matches!(gug.optype(node), Op::Circuit(circuit::Op::Barrier))
+}
+
+/// Returns a barrier directly following `barrier` that can be merged into it
+/// without creating a cycle.
+fn adjacent_barrier(gug: &Gug, barrier: NodeIndex) -> Option<NodeIndex> {
+    let successors: Vec<NodeIndex> = gug.successors(barrier).collect();
+    successors.iter().copied().find(|&candidate| {
+        is_barrier(gug, candidate)
+            && successors
+                .iter()
+                .filter(|&&s| s != candidate)
+                .all(|&s| !gug.light_cone(s).contains(&candidate))
+    })
+}
+
+/// Replaces two adjacent barriers by a single one, returning the new node.
+fn merge(gug: &mut Gug, first: NodeIndex, second: NodeIndex) -> NodeIndex {
+    let mut wires: Vec<(Option<PortIndex>, Option<PortIndex>, WireType)> = Vec::new();
+    let mut crossing = HashSet::new();
+    for offset in 0..gug.num_inputs(first) {
+        let input = gug.input_port(first, offset).unwrap();
+        let source = gug.port_link(input);
+        let mut target = gug
+            .output_port(first, offset)
+            .and_then(|p| gug.port_link(p));
+        if let Some(link) = target.filter(|&p| gug.port_node(p) == Some(second)) {
+            let next = gug.port_offset(link).unwrap();
+            crossing.insert(next);
+            target = gug.output_port(second, next).and_then(|p| gug.port_link(p));
+        }
+        wires.push((source, target, gug.port_type(input)));
+    }
+    for offset in (0..gug.num_inputs(second)).filter(|o| !crossing.contains(o)) {
+        let input = gug.input_port(second, offset).unwrap();
+        let target = gug
+            .output_port(second, offset)
+            .and_then(|p| gug.port_link(p));
+        wires.push((gug.port_link(input), target, gug.port_type(input)));
+    }
+
+    gug.remove_node(first);
+    gug.remove_node(second);
+    let types = wires.iter().map(|&(_, _, typ)| typ).collect();
+    let barrier = gug.add_node_with_signature(
+        Op::Circuit(circuit::Op::Barrier),
+        Signature::new_linear(types),
+    );
+    for (offset, (source, target, _)) in wires.into_iter().enumerate() {
+        if let Some(source) = source {
+            gug.link_ports(source, gug.input_port(barrier, offset).unwrap())
+                .unwrap();
+        }
+        if let Some(target) = target {
+            gug.link_ports(gug.output_port(barrier, offset).unwrap(), target)
+                .unwrap();
+        }
+    }
+    barrier
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn merge_full_width_barriers() {
+        let mut circ = TestCircuit::qubits(2);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        circ.barrier(&[0, 1]);
+        circ.barrier(&[0, 1]);
+        let x = circ.gate(circuit::Op::X, &[1]);
+        let mut gug = circ.finish();
+
+        assert_eq!(merge_barriers(&mut gug), 1);
+        assert_eq!(gug.gate_counts()["Barrier"], 1);
+        let history = |qubit| -> Vec<&str> {
+            gug.qubit_history(qubit)
+                .iter()
+                .map(|(_, op)| op.name())
+                .collect()
+        };
+        assert_eq!(history(0), vec!["H", "Barrier"]);
+        assert_eq!(history(1), vec!["Barrier", "X"]);
+        let (barrier, _) = gug.input_source(x, 0).unwrap();
+        assert_eq!(gug.output_target(h, 0), Some((barrier, 0)));
+        assert_eq!(gug.num_inputs(barrier), 2);
+    }
+
+    #[test]
+    fn keep_separated_barriers() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.barrier(&[0, 1]);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.barrier(&[0, 1]);
+        let mut gug = circ.finish();
+
+        assert_eq!(merge_barriers(&mut gug), 0);
+        assert_eq!(gug.gate_counts()["Barrier"], 2);
+    }
+}
@@ -0,0 +1,78 @@
+use portgraph::NodeIndex;
+
+use crate::component::operation::{circuit, Op};
+use crate::Gug;
+
+/// Rewrites each `CX` as a `CZ` with `H` gates on the target qubit before
+/// and after it.
+///
+/// Returns the number of rewritten gates.
+pub fn cx_to_cz(gug: &mut Gug) -> usize {
+    conjugate_target(gug, circuit::Op::CX, circuit::Op::CZ)
+}
+
+/// Rewrites each `CZ` as a `CX` with `H` gates on the second qubit before
+/// and after it.
+///
+/// Returns the number of rewritten gates.
+pub fn cz_to_cx(gug: &mut Gug) -> usize {
+    conjugate_target(gug, circuit::Op::CZ, circuit::Op::CX)
+}
+
+/// Replaces each `from` gate with `to`, conjugated by `H` on the second qubit.
+fn conjugate_target(gug: &mut Gug, from: circuit::Op, to: circuit::Op) -> usize {
+    let from = Op::Circuit(from);
+    let gates: Vec<NodeIndex> = gug.nodes().filter(|&n| gug.optype(n) == &from).collect();
+    for &gate in &gates {
+        if let Some((source, offset)) = gug.input_source(gate, 1) {
+            gug.insert_after(source, offset, Op::Circuit(circuit::Op::H));
+        }
+        gug.insert_after(gate, 1, Op::Circuit(circuit::Op::H));
+        gug.set_optype(gate, Op::Circuit(to.clone()));
+    }
+    gates.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "matrix")]
+    use crate::test_utils::circuit_unitary;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn hadamards_on_target() {
+        let mut circ = TestCircuit::qubits(2);
+        let cx = circ.gate(circuit::Op::CX, &[0, 1]);
+        let mut gug = circ.finish();
+        #[cfg(feature = "matrix")]
+        let before = circuit_unitary(&gug);
+
+        assert_eq!(cx_to_cz(&mut gug), 1);
+        let h = Op::Circuit(circuit::Op::H);
+        assert_eq!(gug.optype(cx), &Op::Circuit(circuit::Op::CZ));
+        let (pre, _) = gug.input_source(cx, 1).unwrap();
+        let (post, _) = gug.output_target(cx, 1).unwrap();
+        assert_eq!(gug.optype(pre), &h);
+        assert_eq!(gug.optype(post), &h);
+        let input = gug.input_node().unwrap();
+        assert_eq!(gug.input_source(cx, 0), Some((input, 0)));
+        assert_eq!(gug.gate_counts()["H"], 2);
+
+        #[cfg(feature = "matrix")]
+        assert!(crate::component::operation::matrix::equal_up_to_phase(
+            &before,
+            &circuit_unitary(&gug),
+            1e-10
+        ));
+
+        assert_eq!(cz_to_cx(&mut gug), 1);
+        assert_eq!(gug.gate_counts()["H"], 4);
+        #[cfg(feature = "matrix")]
+        assert!(crate::component::operation::matrix::equal_up_to_phase(
+            &before,
+            &circuit_unitary(&gug),
+            1e-10
+        ));
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashSet;
+
+use portgraph::NodeIndex;
+
+use crate::component::operation::{circuit, Op};
+use crate::Gug;
+
+/// Removes pairs of adjacent gates that are inverse to each other, such as
+/// `H; H` or `T; Tadj`, acting on the same wires in the same order.
+///
+/// Returns the number of removed pairs.
+pub fn cancel_inverses(gug: &mut Gug) -> usize {
+    let mut removed = HashSet::new();
+    let mut cancelled = 0;
+    for node in gug.toposort() {
+        if removed.contains(&node) {
+            continue;
+        }
+        let Some(next) = inverse_successor(gug, node) else { continue };
+        gug.remove_and_reconnect(node);
+        gug.remove_and_reconnect(next);
+        removed.insert(next);
+        cancelled += 1;
+    }
+    cancelled
+}
+
+/// Removes pairs of `CX` gates on the same control and target qubits that
+/// are separated only by gates commuting with them.
+///
+/// Gates commute with a `CX` on its control wire when they are diagonal or
+/// are `CX`s sharing the control, and on its target wire when they are
+/// X-rotations or `CX`s sharing the target. The first gate of the pair can
+/// then be slid up to the second, where they cancel.
+///
+/// Returns the number of removed pairs.
+pub fn cancel_commuting_cx(gug: &mut Gug) -> usize {
+    let mut removed = HashSet::new();
+    let mut cancelled = 0;
+    for node in gug.toposort() {
+        if removed.contains(&node) || gug.optype(node) != &Op::Circuit(circuit::Op::CX) {
+            continue;
+        }
+        let controls = commuting_cxs(gug, node, 0);
+        let targets = commuting_cxs(gug, node, 1);
+        let Some(&partner) = controls.iter().find(|n| targets.contains(n)) else {
+            continue;
+        };
+        gug.remove_and_reconnect(node);
+        gug.remove_and_reconnect(partner);
+        removed.insert(partner);
+        cancelled += 1;
+    }
+    cancelled
+}
+
+/// Follows the wire leaving `node` at `offset`, a `CX` control wire for
+/// offset 0 and target wire for offset 1, past the gates commuting with the
+/// `CX` on that wire. Returns the `CX`s found at the same offset on the way.
+fn commuting_cxs(gug: &Gug, node: NodeIndex, offset: usize) -> Vec<NodeIndex> {
+    let mut cxs = Vec::new();
+    let mut current = (node, offset);
+    while let Some((next, next_offset)) = gug.output_target(current.0, current.1) {
+        let Op::Circuit(op) = gug.optype(next) else { break };
+        let commutes = match (op, offset) {
+            (circuit::Op::CX, _) => next_offset == offset,
+            (circuit::Op::CZ, 0) => true,
+            (op, 0) => op.is_diagonal(),
+            (op, _) => op.is_x_rotation(),
+        };
+        if !commutes {
+            break;
+        }
+        if op == &circuit::Op::CX {
+            cxs.push(next);
+        }
+        current = (next, next_offset);
+    }
+    cxs
+}
+
+/// Returns the gate following `node` on all of its wires, if it is its inverse.
+fn inverse_successor(gug: &Gug, node: NodeIndex) -> Option<NodeIndex> {
+    let Op::Circuit(op) = gug.optype(node) else { return None };
+    let inverse = Op::Circuit(op.inverse()?);
+    let num_linear = op.signature().linear.len();
+    let (next, _) = gug.output_target(node, 0)?;
+    (gug.optype(next) == &inverse
+        && (0..num_linear).all(|i| gug.output_target(node, i) == Some((next, i))))
+    .then_some(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::operation::circuit;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn cancel_pairs() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::T, &[0]);
+        circ.gate(circuit::Op::Tadj, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::CX, &[1, 0]);
+        let mut gug = circ.finish();
+
+        assert_eq!(cancel_inverses(&mut gug), 1);
+        assert_eq!(gug.gate_counts()["CX"], 2);
+        assert!(!gug.gate_counts().contains_key("T"));
+    }
+
+    #[test]
+    fn cancel_separated_cx() {
+        let mut circ = TestCircuit::qubits(3);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::H, &[2]);
+        circ.gate(circuit::Op::T, &[0]);
+        circ.gate(circuit::Op::X, &[1]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        let mut gug = circ.finish();
+
+        assert_eq!(cancel_commuting_cx(&mut gug), 1);
+        assert!(!gug.gate_counts().contains_key("CX"));
+        assert_eq!(gug.gate_counts().values().sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn keep_blocked_cx() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        let mut gug = circ.finish();
+
+        assert_eq!(cancel_commuting_cx(&mut gug), 0);
+        assert_eq!(gug.gate_counts()["CX"], 2);
+    }
+}
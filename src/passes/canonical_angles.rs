@@ -0,0 +1,109 @@
+use num_rational::Rational64;
+use portgraph::NodeIndex;
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::{AngleValue, ConstValue, Rational};
+use crate::Gug;
+
+/// Reduces the angles of parametrized gates into the range `[0, 2)` with
+/// [`AngleValue::normalize`], so that equivalent rotations carry equal angles.
+///
+/// Both inlined angles and `Const` angles feeding an `RzF64`, `RxF64` or
+/// `TK1` gate are normalized. Shifting a rotation angle by two half-turns
+/// negates the gate, which is compensated in the circuit's
+/// [`Gug::global_phase`]. Run it before
+/// [`merge_rotations`](super::merge_rotations) to let the merged angles be
+/// compared directly.
+///
+/// Returns the number of gates and constants whose angles changed.
+pub fn canonicalize_angles(gug: &mut Gug) -> usize {
+    let nodes: Vec<NodeIndex> = gug.nodes().collect();
+    let mut changed = 0;
+    let mut flips = 0;
+    for node in nodes {
+        let Op::Circuit(op) = gug.optype(node) else { continue };
+        let canonical = match op {
+            circuit::Op::Rx(angle) => circuit::Op::Rx(normalize(angle, &mut flips)),
+            circuit::Op::Rz(angle) => circuit::Op::Rz(normalize(angle, &mut flips)),
+            circuit::Op::U3 { theta, phi, lambda } => circuit::Op::U3 {
+                theta: normalize(theta, &mut flips),
+                // The phases are exactly periodic, so no sign is tracked.
+                phi: phi.normalize(),
+                lambda: lambda.normalize(),
+            },
+            circuit::Op::Const(ConstValue::Angle(angle)) if feeds_rotation(gug, node) => {
+                circuit::Op::Const(ConstValue::Angle(normalize(angle, &mut flips)))
+            }
+            _ => continue,
+        };
+        if &canonical != op {
+            gug.set_optype(node, Op::Circuit(canonical));
+            changed += 1;
+        }
+    }
+    if flips % 2 != 0 {
+        gug.add_global_phase(AngleValue::Rational(Rational(Rational64::from_integer(1))));
+    }
+    changed
+}
+
+/// Normalizes a rotation angle, adding to `flips` the number of two
+/// half-turn shifts applied to it.
+fn normalize(angle: &AngleValue, flips: &mut i64) -> AngleValue {
+    *flips += (angle.to_f64() / 2.0).floor() as i64;
+    angle.normalize()
+}
+
+/// Checks whether a `Const` node feeds the angle input of a rotation gate.
+fn feeds_rotation(gug: &Gug, node: NodeIndex) -> bool {
+    let Some((target, offset)) = gug.output_target(node, 0) else { return false };
+    matches!(
+        gug.optype(target),
+        Op::Circuit(circuit::Op::RzF64 | circuit::Op::RxF64 | circuit::Op::TK1)
+    ) && offset >= 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "matrix")]
+    use crate::test_utils::circuit_unitary;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn normalize_rz() {
+        let mut circ = TestCircuit::qubits(2);
+        let rz = circ.gate(circuit::Op::Rz(AngleValue::F64(-0.5)), &[0]);
+        let angle = AngleValue::Rational(Rational(Rational64::new(7, 2)));
+        let constant = circ.constant(ConstValue::Angle(angle));
+        circ.gate_with_args(circuit::Op::RxF64, &[1], &[(constant, 0)]);
+        let canonical = circ.gate(circuit::Op::Rz(AngleValue::F64(0.25)), &[1]);
+        let mut gug = circ.finish();
+        #[cfg(feature = "matrix")]
+        let before = circuit_unitary(&gug);
+
+        assert_eq!(canonicalize_angles(&mut gug), 2);
+        assert_eq!(
+            gug.optype(rz),
+            &Op::Circuit(circuit::Op::Rz(AngleValue::F64(1.5)))
+        );
+        let angle = AngleValue::Rational(Rational(Rational64::new(3, 2)));
+        assert_eq!(
+            gug.optype(constant),
+            &Op::Circuit(circuit::Op::Const(ConstValue::Angle(angle)))
+        );
+        assert_eq!(
+            gug.optype(canonical),
+            &Op::Circuit(circuit::Op::Rz(AngleValue::F64(0.25)))
+        );
+        // Each shift negates its gate, and the two negations cancel out.
+        assert_eq!(gug.global_phase(), AngleValue::default());
+        #[cfg(feature = "matrix")]
+        assert!(circuit_unitary(&gug)
+            .iter()
+            .zip(before.iter())
+            .all(|(a, b)| (a - b).norm() < 1e-10));
+
+        assert_eq!(canonicalize_angles(&mut gug), 0);
+    }
+}
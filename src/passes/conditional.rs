@@ -0,0 +1,168 @@
+use portgraph::{Direction, NodeIndex};
+
+use crate::component::operation::{circuit, ControlFlowOp, Op};
+use crate::component::wire_type::{AngleValue, ConstValue, WireType};
+use crate::Gug;
+
+/// Lowers each `Conditional` whose body is a single `RxF64` or `RzF64` gate
+/// on one qubit, with a constant angle, into an unconditional rotation whose
+/// angle is chosen by a `Select` between the gate's angle and zero.
+///
+/// Bodies applying `X` or `Z` are left alone: their rotation forms differ
+/// from them by a phase applied only when the branch is taken, which the
+/// circuit's [`Gug::global_phase`] cannot record. Returns the number of
+/// lowered conditionals.
+pub fn lower_simple_conditionals(gug: &mut Gug) -> usize {
+    let conditionals: Vec<NodeIndex> = gug
+        .nodes()
+        .filter(|&n| matches!(gug.optype(n), Op::ControlFlow(ControlFlowOp::Conditional)))
+        .collect();
+
+    let mut lowered = 0;
+    for node in conditionals {
+        let Some((rotation, angle)) = simple_body(gug, node) else { continue };
+        let predicate = gug.input_source(node, 0);
+        let qubit = gug.input_source(node, 1);
+        let target = gug.output_target(node, 0);
+        let parent = gug.parent(node);
+        let children: Vec<NodeIndex> = gug.children(node).collect();
+        for child in children {
+            gug.remove_node(child);
+        }
+        gug.remove_node(node);
+
+        let constant = |value| Op::Circuit(circuit::Op::Const(ConstValue::Angle(value)));
+        let taken = gug.add_node(constant(angle));
+        let skipped = gug.add_node(constant(AngleValue::default()));
+        let select = gug.add_node(Op::Circuit(circuit::Op::Select(WireType::Angle)));
+        let gate = gug.add_node(Op::Circuit(rotation));
+        if let Some((source, offset)) = predicate {
+            gug.connect(source, offset, select, 0).unwrap();
+        }
+        gug.connect(taken, 0, select, 1).unwrap();
+        gug.connect(skipped, 0, select, 2).unwrap();
+        gug.connect(select, 0, gate, 1).unwrap();
+        if let Some((source, offset)) = qubit {
+            gug.connect(source, offset, gate, 0).unwrap();
+        }
+        if let Some((target, offset)) = target {
+            gug.connect(gate, 0, target, offset).unwrap();
+        }
+        if let Some(parent) = parent {
+            for new in [taken, skipped, select, gate] {
+                gug.push_child(new, parent).unwrap();
+            }
+        }
+        lowered += 1;
+    }
+    lowered
+}
+
+/// Matches a conditional on a single qubit whose body applies one rotation
+/// by a constant angle, returning the rotation and its angle.
+fn simple_body(gug: &Gug, node: NodeIndex) -> Option<(circuit::Op, AngleValue)> {
+    if gug.node_ports(node, Direction::Incoming).count() != 2 {
+        return None;
+    }
+    let children: Vec<NodeIndex> = gug.children(node).collect();
+    let is = |n: NodeIndex, op: circuit::Op| gug.optype(n) == &Op::Circuit(op);
+    let body_input = *children.iter().find(|&&n| is(n, circuit::Op::Input))?;
+    let (gate, 0) = gug.output_target(body_input, 0)? else { return None };
+    let (body_output, 0) = gug.output_target(gate, 0)? else { return None };
+    if !is(body_output, circuit::Op::Output) {
+        return None;
+    }
+
+    let Op::Circuit(op) = gug.optype(gate) else { return None };
+    if !matches!(op, circuit::Op::RxF64 | circuit::Op::RzF64) {
+        return None;
+    }
+    let (constant, _) = gug.input_source(gate, 1)?;
+    let Op::Circuit(circuit::Op::Const(ConstValue::Angle(angle))) = gug.optype(constant) else {
+        return None;
+    };
+    (children.len() == 4 && children.contains(&constant)).then(|| (op.clone(), *angle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::wire_type::Signature;
+
+    /// Builds a conditional on one qubit whose body applies `RxF64` by the
+    /// given angle, or `X` if there is none.
+    fn conditional(angle: Option<f64>) -> (Gug, NodeIndex, NodeIndex) {
+        let mut gug = Gug::new();
+        let qb = vec![WireType::Qubit];
+        let input = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Input),
+            Signature::new_nonlinear(vec![], vec![WireType::Qubit, WireType::Bool]),
+        );
+        let cond = gug.add_node_with_signature(
+            Op::ControlFlow(ControlFlowOp::Conditional),
+            Signature::new_nonlinear(vec![WireType::Bool, WireType::Qubit], qb.clone()),
+        );
+        let output = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Output),
+            Signature::new_nonlinear(qb.clone(), vec![]),
+        );
+        gug.connect(input, 1, cond, 0).unwrap();
+        gug.connect(input, 0, cond, 1).unwrap();
+        gug.connect(cond, 0, output, 0).unwrap();
+
+        let body_in = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Input),
+            Signature::new_nonlinear(vec![], qb.clone()),
+        );
+        let body_out = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Output),
+            Signature::new_nonlinear(qb, vec![]),
+        );
+        let mut body = vec![body_in, body_out];
+        let gate = match angle {
+            Some(angle) => {
+                let constant = gug.add_node(Op::Circuit(circuit::Op::Const(
+                    ConstValue::f64_angle(angle),
+                )));
+                let rx = gug.add_node(Op::Circuit(circuit::Op::RxF64));
+                gug.connect(constant, 0, rx, 1).unwrap();
+                body.push(constant);
+                rx
+            }
+            None => gug.add_node(Op::Circuit(circuit::Op::X)),
+        };
+        gug.connect(body_in, 0, gate, 0).unwrap();
+        gug.connect(gate, 0, body_out, 0).unwrap();
+        body.push(gate);
+        for n in body {
+            gug.push_child(n, cond).unwrap();
+        }
+        (gug, input, output)
+    }
+
+    #[test]
+    fn lower_conditional_rotation() {
+        let (mut gug, input, output) = conditional(Some(1.0));
+        assert_eq!(lower_simple_conditionals(&mut gug), 1);
+        assert_eq!(gug.validate(), Ok(()));
+        let counts = gug.gate_counts();
+        assert_eq!(counts["RxF64"], 1);
+        assert_eq!(counts["Select"], 1);
+        assert!(!counts.contains_key("Conditional"));
+        assert_eq!(gug.global_phase(), AngleValue::default());
+
+        let (rx, _) = gug.output_target(input, 0).unwrap();
+        assert_eq!(gug.optype(rx), &Op::Circuit(circuit::Op::RxF64));
+        assert_eq!(gug.output_target(rx, 0), Some((output, 0)));
+        let (select, _) = gug.input_source(rx, 1).unwrap();
+        assert_eq!(gug.input_source(select, 0), Some((input, 1)));
+    }
+
+    #[test]
+    fn keep_conditional_x() {
+        // X is Rx(1) only up to a phase, which depends on the condition.
+        let (mut gug, _, _) = conditional(None);
+        assert_eq!(lower_simple_conditionals(&mut gug), 0);
+        assert_eq!(gug.gate_counts()["Conditional"], 1);
+    }
+}
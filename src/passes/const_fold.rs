@@ -0,0 +1,104 @@
+use portgraph::{Direction, NodeIndex};
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::ConstValue;
+use crate::Gug;
+
+/// Evaluates classical operations whose inputs all come from `Const` nodes,
+/// replacing them with `Const` nodes holding their results.
+///
+/// Constants left without any use are removed. Returns the number of folded
+/// operations.
+pub fn fold_constants(gug: &mut Gug) -> usize {
+    let mut folded = 0;
+    for node in gug.toposort() {
+        let Some((sources, values)) = evaluate(gug, node) else { continue };
+        let targets: Vec<_> = gug
+            .node_ports(node, Direction::Outgoing)
+            .map(|p| gug.port_link(p))
+            .collect();
+        gug.remove_node(node);
+        for (value, target) in values.into_iter().zip(targets) {
+            let Some(target) = target else { continue };
+            let constant = gug.add_node(Op::Circuit(circuit::Op::Const(value)));
+            let port = gug.output_port(constant, 0).unwrap();
+            gug.link_ports(port, target).unwrap();
+        }
+        for source in sources {
+            let unused = gug
+                .node_ports(source, Direction::Outgoing)
+                .all(|p| gug.port_link(p).is_none());
+            if unused {
+                gug.remove_node(source);
+            }
+        }
+        folded += 1;
+    }
+    folded
+}
+
+/// Evaluates a non-constant classical node with constant inputs, returning
+/// the input nodes and the output values.
+fn evaluate(gug: &Gug, node: NodeIndex) -> Option<(Vec<NodeIndex>, Vec<ConstValue>)> {
+    let Op::Circuit(op) = gug.optype(node) else { return None };
+    if matches!(op, circuit::Op::Const(_)) || !op.is_pure_classical() {
+        return None;
+    }
+    let num_inputs = gug.node_ports(node, Direction::Incoming).count();
+    let mut sources = Vec::with_capacity(num_inputs);
+    let mut inputs = Vec::with_capacity(num_inputs);
+    for offset in 0..num_inputs {
+        let (source, _) = gug.input_source(node, offset)?;
+        let Op::Circuit(circuit::Op::Const(value)) = gug.optype(source) else { return None };
+        sources.push(source);
+        inputs.push(value.clone());
+    }
+    Some((sources, op.eval_classical(&inputs)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::wire_type::{AngleValue, WireType};
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn fold_angle_sum() {
+        let mut circ = TestCircuit::qubits(1);
+        let a = circ.constant(ConstValue::f64_angle(0.25));
+        let b = circ.constant(ConstValue::f64_angle(0.5));
+        let sum = circ.gug.add_node(Op::Circuit(circuit::Op::AngleAdd));
+        circ.gug.connect(a, 0, sum, 0).unwrap();
+        circ.gug.connect(b, 0, sum, 1).unwrap();
+        let rz = circ.gate_with_args(circuit::Op::RzF64, &[0], &[(sum, 0)]);
+        let mut gug = circ.finish();
+
+        assert_eq!(fold_constants(&mut gug), 1);
+        assert_eq!(gug.node_count(), 4);
+        let (constant, _) = gug.input_source(rz, 1).unwrap();
+        assert_eq!(
+            gug.optype(constant),
+            &Op::Circuit(circuit::Op::Const(ConstValue::Angle(AngleValue::F64(0.75))))
+        );
+    }
+
+    #[test]
+    fn fold_cast() {
+        let mut circ = TestCircuit::new(&[]);
+        let three = circ.constant(ConstValue::I64(3));
+        let cast = circuit::Op::cast(WireType::I64, WireType::F64).unwrap();
+        let cast = circ.gug.add_node(Op::Circuit(cast));
+        circ.gug.connect(three, 0, cast, 0).unwrap();
+        let sink = circ.gug.add_node(Op::Circuit(circuit::Op::Noop(WireType::F64)));
+        circ.gug.connect(cast, 0, sink, 0).unwrap();
+        let mut gug = circ.finish();
+
+        assert_eq!(fold_constants(&mut gug), 1);
+        let (constant, _) = gug.input_source(sink, 0).unwrap();
+        assert_eq!(
+            gug.optype(constant),
+            &Op::Circuit(circuit::Op::Const(ConstValue::F64(3.0)))
+        );
+        assert_eq!(gug.node_count(), 4);
+    }
+}
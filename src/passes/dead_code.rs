@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+
+use portgraph::{Direction, NodeIndex};
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::WireType;
+use crate::Gug;
+
+/// Removes classical computations whose results never reach the `Output`
+/// node or a side-effecting operation such as a `Measure`.
+///
+/// Unreachable nodes carrying linear wires are never removed, since a dead
+/// linear wire indicates a malformed graph rather than dead code. Use
+/// [`unreachable_linear_nodes`] to report those.
+///
+/// Returns the number of removed nodes.
+pub fn dead_code_elimination(gug: &mut Gug) -> usize {
+    let live = live_nodes(gug);
+    let dead: Vec<NodeIndex> = gug
+        .nodes()
+        .filter(|n| !live.contains(n) && gug.signature(*n).linear.is_empty())
+        .collect();
+    for &node in &dead {
+        gug.remove_node(node);
+    }
+    dead.len()
+}
+
+/// Returns the nodes with linear wires that cannot reach the `Output` node or
+/// a side-effecting operation.
+pub fn unreachable_linear_nodes(gug: &Gug) -> Vec<NodeIndex> {
+    let live = live_nodes(gug);
+    gug.nodes()
+        .filter(|n| !live.contains(n) && !gug.signature(*n).linear.is_empty())
+        .collect()
+}
+
+/// Returns `true` if the node must be kept regardless of whether its outputs
+/// are used.
+fn is_root(gug: &Gug, node: NodeIndex) -> bool {
+    let effectful = match gug.optype(node) {
+        Op::Circuit(op) => matches!(
+            op,
+            circuit::Op::Input
+                | circuit::Op::Output
+                | circuit::Op::Measure
+                | circuit::Op::MeasureReset
+                | circuit::Op::Reset
+                | circuit::Op::Barrier
+        ),
+        // Control flow and opaque operations may have effects we cannot see.
+        _ => true,
+    };
+    effectful
+        || gug
+            .node_ports(node, Direction::Incoming)
+            .chain(gug.node_ports(node, Direction::Outgoing))
+            .any(|p| gug.port_type(p) == WireType::SideEffects)
+}
+
+/// Computes the set of nodes from which a root node is reachable.
+fn live_nodes(gug: &Gug) -> HashSet<NodeIndex> {
+    let mut stack: Vec<NodeIndex> = gug.nodes().filter(|&n| is_root(gug, n)).collect();
+    let mut live: HashSet<NodeIndex> = stack.iter().copied().collect();
+    while let Some(node) = stack.pop() {
+        for port in gug.node_ports(node, Direction::Incoming) {
+            let Some(pred) = gug.port_link(port).and_then(|p| gug.port_node(p)) else {
+                continue;
+            };
+            if live.insert(pred) {
+                stack.push(pred);
+            }
+        }
+    }
+    live
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::wire_type::ConstValue;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn remove_unused_classical() {
+        let mut circ = TestCircuit::qubits(1);
+        let a = circ.constant(ConstValue::Bool(true));
+        let b = circ.constant(ConstValue::Bool(false));
+        let xor = circ.gug.add_node(Op::Circuit(circuit::Op::Xor));
+        circ.gug.connect(a, 0, xor, 0).unwrap();
+        circ.gug.connect(b, 0, xor, 1).unwrap();
+        let angle = circ.constant(ConstValue::f64_angle(0.5));
+        circ.gate_with_args(circuit::Op::RzF64, &[0], &[(angle, 0)]);
+        let mut gug = circ.finish();
+
+        assert!(unreachable_linear_nodes(&gug).is_empty());
+        assert_eq!(dead_code_elimination(&mut gug), 3);
+        assert_eq!(gug.node_count(), 4);
+        assert!(gug.nodes().any(|n| n == angle));
+    }
+
+    #[test]
+    fn report_dead_linear() {
+        let mut circ = TestCircuit::qubits(1);
+        let dangling = circ.gug.add_node(Op::Circuit(circuit::Op::H));
+        let mut gug = circ.finish();
+
+        assert_eq!(unreachable_linear_nodes(&gug), vec![dangling]);
+        assert_eq!(dead_code_elimination(&mut gug), 0);
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::mem::Discriminant;
+
+use portgraph::NodeIndex;
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::{ConstValue, Signature, WireType};
+use crate::gug::CircuitBuilder;
+use crate::Gug;
+
+/// A table of replacement circuits for operations, used by [`decompose`].
+///
+/// Templates are keyed by the variant of the operation they replace, and
+/// must have `Input` and `Output` boundary nodes matching its ports by offset.
+/// The global phase of a template is added to the circuit each time it is
+/// substituted, so templates equal to their operation only up to a phase
+/// should carry it.
+/// The [`Default`] table decomposes `ZZMax` into `CX` and `RzF64` gates, and
+/// `TK1` into `RzF64` and `RxF64` gates.
+#[derive(Clone, Debug)]
+pub struct DecompTable {
+    templates: HashMap<Discriminant<circuit::Op>, Gug>,
+}
+
+impl DecompTable {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        Self {
+            templates: HashMap::new(),
+        }
+    }
+
+    /// Sets the replacement circuit for operations of the same variant as `op`.
+    pub fn insert(&mut self, op: &circuit::Op, template: Gug) {
+        self.templates.insert(std::mem::discriminant(op), template);
+    }
+
+    /// Returns the replacement circuit for an operation, if any.
+    pub fn get(&self, op: &circuit::Op) -> Option<&Gug> {
+        self.templates.get(&std::mem::discriminant(op))
+    }
+}
+
+impl Default for DecompTable {
+    fn default() -> Self {
+        let mut table = Self::new();
+        table.insert(&circuit::Op::ZZMax, zzmax_template());
+        table.insert(&circuit::Op::TK1, tk1_template());
+        table
+    }
+}
+
+/// Replaces every operation with an entry in the table by a copy of its
+/// template.
+///
+/// Returns the number of substitutions.
+pub fn decompose(gug: &mut Gug, table: &DecompTable) -> usize {
+    let matches: Vec<(NodeIndex, Gug)> = gug
+        .nodes()
+        .filter_map(|n| match gug.optype(n) {
+            Op::Circuit(op) => Some((n, table.get(op)?.clone())),
+            _ => None,
+        })
+        .collect();
+    matches
+        .into_iter()
+        .filter(|(node, template)| gug.replace_with_circuit(*node, template).is_ok())
+        .count()
+}
+
+/// Replaces every `CCX` with its standard Clifford+T decomposition, using six
+/// `CX` gates.
+///
+/// Returns the number of decomposed gates.
+pub fn decompose_toffoli(gug: &mut Gug) -> usize {
+    let mut table = DecompTable::new();
+    table.insert(&circuit::Op::CCX, toffoli_template());
+    decompose(gug, &table)
+}
+
+/// Creates a template with boundary nodes of the given types, returning it
+/// with the `Input` and `Output` nodes.
+fn template(inputs: Vec<WireType>, outputs: Vec<WireType>) -> (Gug, NodeIndex, NodeIndex) {
+    let mut gug = Gug::new();
+    let input = gug.add_node_with_signature(
+        Op::Circuit(circuit::Op::Input),
+        Signature::new_nonlinear(vec![], inputs),
+    );
+    let output = gug.add_node_with_signature(
+        Op::Circuit(circuit::Op::Output),
+        Signature::new_nonlinear(outputs, vec![]),
+    );
+    (gug, input, output)
+}
+
+/// `ZZMax` as `CX; Rz(1/2) on the target; CX`.
+pub(super) fn zzmax_template() -> Gug {
+    let qubits = vec![WireType::Qubit; 2];
+    let (mut gug, input, output) = template(qubits.clone(), qubits);
+    let cx1 = gug.add_node(Op::Circuit(circuit::Op::CX));
+    let angle = gug.add_node(Op::Circuit(circuit::Op::Const(ConstValue::f64_angle(0.5))));
+    let rz = gug.add_node(Op::Circuit(circuit::Op::RzF64));
+    let cx2 = gug.add_node(Op::Circuit(circuit::Op::CX));
+    gug.connect(input, 0, cx1, 0).unwrap();
+    gug.connect(input, 1, cx1, 1).unwrap();
+    gug.connect(cx1, 1, rz, 0).unwrap();
+    gug.connect(angle, 0, rz, 1).unwrap();
+    gug.connect(cx1, 0, cx2, 0).unwrap();
+    gug.connect(rz, 0, cx2, 1).unwrap();
+    gug.connect(cx2, 0, output, 0).unwrap();
+    gug.connect(cx2, 1, output, 1).unwrap();
+    gug
+}
+
+/// Creates a template applying a sequence of gates without non-linear inputs
+/// to qubits.
+pub(super) fn gate_sequence(num_qubits: usize, gates: &[(circuit::Op, &[usize])]) -> Gug {
+    let mut gug = Gug::new();
+    let mut builder = CircuitBuilder::new(&mut gug, &vec![WireType::Qubit; num_qubits]);
+    for (op, args) in gates {
+        builder.append(&mut gug, op.clone(), args, &[]).unwrap();
+    }
+    builder.finish(&mut gug);
+    gug
+}
+
+/// The Toffoli gate over six `CX`, seven `T` or `Tadj`, and two `H` gates.
+pub(super) fn toffoli_template() -> Gug {
+    use circuit::Op::{Tadj, CX, H, T};
+    gate_sequence(
+        3,
+        &[
+            (H, &[2]),
+            (CX, &[1, 2]),
+            (Tadj, &[2]),
+            (CX, &[0, 2]),
+            (T, &[2]),
+            (CX, &[1, 2]),
+            (Tadj, &[2]),
+            (CX, &[0, 2]),
+            (T, &[1]),
+            (T, &[2]),
+            (H, &[2]),
+            (CX, &[0, 1]),
+            (T, &[0]),
+            (Tadj, &[1]),
+            (CX, &[0, 1]),
+        ],
+    )
+}
+
+/// `TK1(a, b, c)` as `Rz(c); Rx(b); Rz(a)`.
+pub(super) fn tk1_template() -> Gug {
+    let mut inputs = vec![WireType::Qubit];
+    inputs.extend([WireType::Angle; 3]);
+    let (mut gug, input, output) = template(inputs, vec![WireType::Qubit]);
+    let rz1 = gug.add_node(Op::Circuit(circuit::Op::RzF64));
+    let rx = gug.add_node(Op::Circuit(circuit::Op::RxF64));
+    let rz2 = gug.add_node(Op::Circuit(circuit::Op::RzF64));
+    gug.connect(input, 0, rz1, 0).unwrap();
+    gug.connect(input, 3, rz1, 1).unwrap();
+    gug.connect(rz1, 0, rx, 0).unwrap();
+    gug.connect(input, 2, rx, 1).unwrap();
+    gug.connect(rx, 0, rz2, 0).unwrap();
+    gug.connect(input, 1, rz2, 1).unwrap();
+    gug.connect(rz2, 0, output, 0).unwrap();
+    gug
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn decompose_zzmax() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::ZZMax, &[0, 1]);
+        let mut gug = circ.finish();
+
+        assert_eq!(decompose(&mut gug, &DecompTable::default()), 1);
+        assert_eq!(gug.validate(), Ok(()));
+        let counts = gug.gate_counts();
+        assert!(!counts.contains_key("ZZMax"));
+        assert_eq!(counts["CX"], 2);
+        assert_eq!(counts["RzF64"], 1);
+        assert_eq!(gug.depth(), 4);
+    }
+
+    #[test]
+    fn decompose_tk1() {
+        let mut circ = TestCircuit::qubits(1);
+        let angles: Vec<_> = [0.1, 0.2, 0.3]
+            .iter()
+            .map(|&a| (circ.constant(ConstValue::f64_angle(a)), 0))
+            .collect();
+        circ.gate_with_args(circuit::Op::TK1, &[0], &angles);
+        let mut gug = circ.finish();
+
+        assert_eq!(decompose(&mut gug, &DecompTable::default()), 1);
+        assert_eq!(gug.validate(), Ok(()));
+        assert_eq!(gug.gate_counts()["RzF64"], 2);
+        assert_eq!(gug.gate_counts()["RxF64"], 1);
+    }
+
+    #[test]
+    fn decompose_ccx() {
+        let mut circ = TestCircuit::qubits(3);
+        circ.gate(circuit::Op::CCX, &[0, 1, 2]);
+        let mut gug = circ.finish();
+        #[cfg(feature = "matrix")]
+        let toffoli = crate::test_utils::circuit_unitary(&gug);
+
+        assert_eq!(decompose_toffoli(&mut gug), 1);
+        assert_eq!(gug.validate(), Ok(()));
+        assert_eq!(gug.stats().two_qubit_gates, 6);
+        assert!(!gug.gate_counts().contains_key("CCX"));
+
+        #[cfg(feature = "matrix")]
+        assert!(crate::component::operation::matrix::equal_up_to_phase(
+            &toffoli,
+            &crate::test_utils::circuit_unitary(&gug),
+            1e-10
+        ));
+    }
+
+    #[cfg(feature = "matrix")]
+    #[test]
+    fn zzmax_template_unitary() {
+        use crate::component::operation::matrix::equal_up_to_phase;
+        use ndarray::linalg::kron;
+
+        let cx = circuit::Op::CX.unitary(&[]).unwrap();
+        let rz = crate::component::operation::matrix::rz(0.5);
+        let template = cx.dot(&kron(&ndarray::Array2::eye(2), &rz)).dot(&cx);
+        let zzmax = circuit::Op::ZZMax.unitary(&[]).unwrap();
+        assert!(equal_up_to_phase(&template, &zzmax, 1e-10));
+    }
+}
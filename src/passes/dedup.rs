@@ -0,0 +1,93 @@
+use portgraph::NodeIndex;
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::ConstValue;
+use crate::Gug;
+
+/// Merges `Const` nodes holding equal values into a single node.
+///
+/// Since an output port feeds a single input, the remaining constant is
+/// fanned out to the consumers of all the merged nodes through a `Copy`.
+/// Returns the number of removed duplicates.
+pub fn dedup_consts(gug: &mut Gug) -> usize {
+    let mut groups: Vec<(ConstValue, Vec<NodeIndex>)> = Vec::new();
+    for node in gug.nodes() {
+        let Op::Circuit(circuit::Op::Const(value)) = gug.optype(node) else { continue };
+        match groups.iter_mut().find(|(v, _)| v == value) {
+            Some((_, nodes)) => nodes.push(node),
+            None => groups.push((value.clone(), vec![node])),
+        }
+    }
+
+    let mut removed = 0;
+    for (value, nodes) in groups {
+        if nodes.len() < 2 {
+            continue;
+        }
+        let targets: Vec<_> = nodes
+            .iter()
+            .filter_map(|&n| gug.unlink_port(gug.output_port(n, 0).unwrap()))
+            .collect();
+        let kept = nodes[0];
+        for &node in &nodes[1..] {
+            gug.remove_node(node);
+        }
+        removed += nodes.len() - 1;
+
+        let source = match targets.len() {
+            0 => continue,
+            1 => kept,
+            n => {
+                let copy = gug.add_node(Op::Circuit(circuit::Op::Copy {
+                    n_copies: n as u32,
+                    typ: value.get_type(),
+                }));
+                gug.connect(kept, 0, copy, 0).unwrap();
+                copy
+            }
+        };
+        for (offset, target) in targets.into_iter().enumerate() {
+            let port = gug.output_port(source, offset).unwrap();
+            gug.link_ports(port, target).unwrap();
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn dedup_three_bools() {
+        let mut circ = TestCircuit::qubits(0);
+        let nots: Vec<_> = (0..3)
+            .map(|_| {
+                let constant = circ.constant(ConstValue::Bool(true));
+                let not = circ.gug.add_node(Op::Circuit(circuit::Op::Not));
+                circ.gug.connect(constant, 0, not, 0).unwrap();
+                not
+            })
+            .collect();
+        let other = circ.constant(ConstValue::Bool(false));
+        let mut gug = circ.finish();
+
+        assert_eq!(dedup_consts(&mut gug), 2);
+        assert_eq!(gug.gate_counts()["Const"], 2);
+        assert_eq!(gug.gate_counts()["Copy"], 1);
+        let (copy, _) = gug.input_source(nots[0], 0).unwrap();
+        assert_eq!(
+            gug.optype(copy),
+            &Op::Circuit(circuit::Op::Copy {
+                n_copies: 3,
+                typ: crate::component::wire_type::WireType::Bool
+            })
+        );
+        for (i, &not) in nots.iter().enumerate() {
+            assert_eq!(gug.input_source(not, 0), Some((copy, i)));
+        }
+        assert_eq!(gug.optype(other), &Op::Circuit(circuit::Op::Const(ConstValue::Bool(false))));
+        assert_eq!(gug.validate(), Ok(()));
+    }
+}
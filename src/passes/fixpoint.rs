@@ -0,0 +1,62 @@
+use crate::Gug;
+
+/// Applies a pass repeatedly until it reports no changes, or until it has
+/// been run `max_iters` times.
+///
+/// Returns the total number of changes made by all runs of the pass.
+pub fn run_to_fixpoint(
+    gug: &mut Gug,
+    mut pass: impl FnMut(&mut Gug) -> usize,
+    max_iters: usize,
+) -> usize {
+    let mut total = 0;
+    for _ in 0..max_iters {
+        let changes = pass(gug);
+        if changes == 0 {
+            break;
+        }
+        total += changes;
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::operation::circuit;
+    use crate::passes::cancel_inverses;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn cancel_to_fixpoint() {
+        let mut circ = TestCircuit::qubits(1);
+        for _ in 0..4 {
+            circ.gate(circuit::Op::H, &[0]);
+        }
+        let mut gug = circ.finish();
+
+        let mut iterations = 0;
+        let changes = run_to_fixpoint(
+            &mut gug,
+            |gug| {
+                iterations += 1;
+                cancel_inverses(gug)
+            },
+            10,
+        );
+        assert_eq!(changes, 2);
+        assert_eq!(iterations, 2);
+        assert!(!gug.gate_counts().contains_key("H"));
+
+        let mut iterations = 0;
+        run_to_fixpoint(
+            &mut gug,
+            |_| {
+                iterations += 1;
+                1
+            },
+            3,
+        );
+        assert_eq!(iterations, 3);
+    }
+}
@@ -0,0 +1,67 @@
+use portgraph::NodeIndex;
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::{AngleValue, ConstValue};
+use crate::Gug;
+
+/// Replaces each `RzF64` or `RxF64` whose angle is fed by a `Const` node with
+/// an `Rz` or `Rx` carrying the angle inline.
+///
+/// Constants left without any use are removed. Returns the number of inlined
+/// angles.
+pub fn inline_const_angles(gug: &mut Gug) -> usize {
+    let rotations: Vec<(NodeIndex, NodeIndex, circuit::Op)> = gug
+        .nodes()
+        .filter_map(|node| {
+            let (constant, angle) = const_angle(gug, node)?;
+            let inlined = match gug.optype(node) {
+                Op::Circuit(circuit::Op::RzF64) => circuit::Op::Rz(angle),
+                Op::Circuit(circuit::Op::RxF64) => circuit::Op::Rx(angle),
+                _ => return None,
+            };
+            Some((node, constant, inlined))
+        })
+        .collect();
+
+    for (node, constant, inlined) in &rotations {
+        gug.set_optype(*node, Op::Circuit(inlined.clone()));
+        let unused = gug.outputs(*constant).all(|p| gug.port_link(p).is_none());
+        if unused {
+            gug.remove_node(*constant);
+        }
+    }
+    rotations.len()
+}
+
+/// Returns the `Const` node feeding the angle input of a node, and its value.
+fn const_angle(gug: &Gug, node: NodeIndex) -> Option<(NodeIndex, AngleValue)> {
+    let (source, _) = gug.input_source(node, 1)?;
+    match gug.optype(source) {
+        Op::Circuit(circuit::Op::Const(ConstValue::Angle(angle))) => Some((source, *angle)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn inline_rz_angle() {
+        let mut circ = TestCircuit::qubits(1);
+        let angle = circ.constant(ConstValue::f64_angle(0.25));
+        let rz = circ.gate_with_args(circuit::Op::RzF64, &[0], &[(angle, 0)]);
+        let mut gug = circ.finish();
+
+        assert_eq!(inline_const_angles(&mut gug), 1);
+        assert_eq!(
+            gug.optype(rz),
+            &Op::Circuit(circuit::Op::Rz(AngleValue::F64(0.25)))
+        );
+        assert_eq!(gug.num_inputs(rz), 1);
+        assert_eq!(gug.node_count(), 3);
+        assert!(!gug.gate_counts().contains_key("Const"));
+        assert_eq!(gug.validate(), Ok(()));
+    }
+}
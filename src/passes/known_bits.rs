@@ -0,0 +1,161 @@
+use portgraph::{Direction, NodeIndex, PortIndex};
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::ConstValue;
+use crate::Gug;
+
+/// What a classical operation reduces to when some of its inputs are known.
+enum Simplified {
+    /// The output equals the value arriving at the given input offset.
+    Forward(usize),
+    /// The output is a known constant.
+    Constant(bool),
+}
+
+/// Substitutes statically known boolean values, produced by `Const` nodes,
+/// into the classical operations consuming them.
+///
+/// A `Select` with a known predicate is replaced by the chosen input, and an
+/// `And`, `Or` or `Xor` with one known operand is replaced by the other
+/// operand or by a constant. Operations with all inputs known are left to
+/// [`fold_constants`](super::fold_constants). Constants left without any use
+/// are removed. Returns the number of substituted operations.
+pub fn propagate_known_bits(gug: &mut Gug) -> usize {
+    let mut substituted = 0;
+    for node in gug.toposort() {
+        let Some(simplified) = simplify(gug, node) else { continue };
+        let target = gug.port_link(gug.output_port(node, 0).unwrap());
+        let sources: Vec<Option<PortIndex>> = gug
+            .node_ports(node, Direction::Incoming)
+            .map(|p| gug.port_link(p))
+            .collect();
+        gug.remove_node(node);
+        let replacement = match simplified {
+            Simplified::Forward(offset) => sources[offset],
+            Simplified::Constant(value) => {
+                let constant = Op::Circuit(circuit::Op::Const(ConstValue::Bool(value)));
+                let constant = gug.add_node(constant);
+                gug.output_port(constant, 0)
+            }
+        };
+        if let (Some(replacement), Some(target)) = (replacement, target) {
+            gug.link_ports(replacement, target).unwrap();
+        }
+        for source in sources.into_iter().flatten() {
+            let Some(source) = gug.port_node(source) else { continue };
+            let unused = gug
+                .node_ports(source, Direction::Outgoing)
+                .all(|p| gug.port_link(p).is_none());
+            if unused && matches!(gug.optype(source), Op::Circuit(circuit::Op::Const(_))) {
+                gug.remove_node(source);
+            }
+        }
+        substituted += 1;
+    }
+    substituted
+}
+
+/// Returns the known boolean value fed into an input of `node`, if any.
+fn known_bit(gug: &Gug, node: NodeIndex, offset: usize) -> Option<bool> {
+    let (source, _) = gug.input_source(node, offset)?;
+    match gug.optype(source) {
+        Op::Circuit(circuit::Op::Const(ConstValue::Bool(value))) => Some(*value),
+        _ => None,
+    }
+}
+
+fn simplify(gug: &Gug, node: NodeIndex) -> Option<Simplified> {
+    let Op::Circuit(op) = gug.optype(node) else { return None };
+    let known = |offset| known_bit(gug, node, offset);
+    match op {
+        circuit::Op::Select(_) => {
+            let predicate = known(0)?;
+            Some(Simplified::Forward(if predicate { 1 } else { 2 }))
+        }
+        circuit::Op::And | circuit::Op::Or | circuit::Op::Xor => {
+            let (value, other) = match (known(0), known(1)) {
+                (Some(_), Some(_)) | (None, None) => return None,
+                (Some(value), None) => (value, 1),
+                (None, Some(value)) => (value, 0),
+            };
+            Some(match (op, value) {
+                (circuit::Op::And, false) => Simplified::Constant(false),
+                (circuit::Op::Or, true) => Simplified::Constant(true),
+                (circuit::Op::Xor, true) => return None,
+                _ => Simplified::Forward(other),
+            })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::wire_type::{Signature, WireType};
+
+    fn add_input(gug: &mut Gug, types: Vec<WireType>) -> NodeIndex {
+        gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Input),
+            Signature::new_nonlinear(vec![], types),
+        )
+    }
+
+    fn add_const(gug: &mut Gug, value: bool) -> NodeIndex {
+        gug.add_node(Op::Circuit(circuit::Op::Const(ConstValue::Bool(value))))
+    }
+
+    #[test]
+    fn resolve_select() {
+        let mut gug = Gug::new();
+        let input = add_input(&mut gug, vec![WireType::Angle, WireType::Angle]);
+        let predicate = add_const(&mut gug, true);
+        let select = gug.add_node(Op::Circuit(circuit::Op::Select(WireType::Angle)));
+        let neg = gug.add_node(Op::Circuit(circuit::Op::AngleNeg));
+        gug.connect(predicate, 0, select, 0).unwrap();
+        gug.connect(input, 0, select, 1).unwrap();
+        gug.connect(input, 1, select, 2).unwrap();
+        gug.connect(select, 0, neg, 0).unwrap();
+
+        assert_eq!(propagate_known_bits(&mut gug), 1);
+        assert_eq!(gug.input_source(neg, 0), Some((input, 0)));
+        assert!(!gug.gate_counts().contains_key("Select"));
+        assert!(!gug.gate_counts().contains_key("Const"));
+    }
+
+    #[test]
+    fn known_operands() {
+        let mut gug = Gug::new();
+        let input = add_input(&mut gug, vec![WireType::Bool]);
+        let and = gug.add_node(Op::Circuit(circuit::Op::And));
+        let t = add_const(&mut gug, true);
+        gug.connect(input, 0, and, 0).unwrap();
+        gug.connect(t, 0, and, 1).unwrap();
+        let or = gug.add_node(Op::Circuit(circuit::Op::Or));
+        let f = add_const(&mut gug, false);
+        gug.connect(f, 0, or, 0).unwrap();
+        gug.connect(and, 0, or, 1).unwrap();
+        let not = gug.add_node(Op::Circuit(circuit::Op::Not));
+        gug.connect(or, 0, not, 0).unwrap();
+
+        assert_eq!(propagate_known_bits(&mut gug), 2);
+        assert_eq!(gug.input_source(not, 0), Some((input, 0)));
+        assert_eq!(gug.node_count(), 2);
+
+        let mut gug = Gug::new();
+        let input = add_input(&mut gug, vec![WireType::Bool]);
+        let and = gug.add_node(Op::Circuit(circuit::Op::And));
+        let f = add_const(&mut gug, false);
+        gug.connect(input, 0, and, 0).unwrap();
+        gug.connect(f, 0, and, 1).unwrap();
+        let not = gug.add_node(Op::Circuit(circuit::Op::Not));
+        gug.connect(and, 0, not, 0).unwrap();
+
+        assert_eq!(propagate_known_bits(&mut gug), 1);
+        let (constant, _) = gug.input_source(not, 0).unwrap();
+        assert_eq!(
+            gug.optype(constant),
+            &Op::Circuit(circuit::Op::Const(ConstValue::Bool(false)))
+        );
+    }
+}
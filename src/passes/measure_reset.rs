@@ -0,0 +1,107 @@
+use portgraph::NodeIndex;
+
+use crate::component::operation::{circuit, Op};
+use crate::Gug;
+
+/// Fuses each `Measure` directly followed by a `Reset` on the same qubit into
+/// a single `MeasureReset` operation.
+///
+/// Returns the number of fused pairs.
+pub fn fuse_measure_reset(gug: &mut Gug) -> usize {
+    let measures: Vec<NodeIndex> = gug
+        .nodes()
+        .filter(|&n| gug.optype(n) == &Op::Circuit(circuit::Op::Measure))
+        .collect();
+
+    let mut fused = 0;
+    for measure in measures {
+        let Some(reset) = qubit_successor(gug, measure) else { continue };
+        if gug.optype(reset) != &Op::Circuit(circuit::Op::Reset) {
+            continue;
+        }
+        gug.remove_and_reconnect(reset);
+        gug.set_optype(measure, Op::Circuit(circuit::Op::MeasureReset));
+        fused += 1;
+    }
+    fused
+}
+
+/// Removes each `Reset` acting directly on a qubit of the `Input` node, since
+/// input qubits are assumed to start in the zero state.
+///
+/// Resets preceded by any operation on their qubit, such as a mid-circuit
+/// `Measure`, are kept. Returns the number of removed resets.
+pub fn remove_leading_resets(gug: &mut Gug) -> usize {
+    let Ok((input, _)) = gug.boundary() else { return 0 };
+    let resets: Vec<NodeIndex> = gug
+        .nodes()
+        .filter(|&n| gug.optype(n) == &Op::Circuit(circuit::Op::Reset))
+        .filter(|&n| matches!(gug.input_source(n, 0), Some((source, _)) if source == input))
+        .collect();
+    for &reset in &resets {
+        gug.remove_and_reconnect(reset);
+    }
+    resets.len()
+}
+
+/// Returns the node consuming the qubit output of `node`, if it receives it on
+/// its first (qubit) input.
+fn qubit_successor(gug: &Gug, node: NodeIndex) -> Option<NodeIndex> {
+    match gug.output_target(node, 0)? {
+        (succ, 0) => Some(succ),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::wire_type::WireType;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn fuse_adjacent() {
+        let mut circ = TestCircuit::new(&[WireType::Qubit, WireType::LinearBit]);
+        let measure = circ.gate(circuit::Op::Measure, &[0, 1]);
+        circ.gate(circuit::Op::Reset, &[0]);
+        circ.gate(circuit::Op::H, &[0]);
+        let mut gug = circ.finish();
+
+        assert_eq!(fuse_measure_reset(&mut gug), 1);
+        assert_eq!(gug.optype(measure), &Op::Circuit(circuit::Op::MeasureReset));
+        assert_eq!(gug.node_count(), 4);
+        let next = qubit_successor(&gug, measure).unwrap();
+        assert_eq!(gug.optype(next), &Op::Circuit(circuit::Op::H));
+    }
+
+    #[test]
+    fn keep_separated() {
+        let mut circ = TestCircuit::new(&[WireType::Qubit, WireType::LinearBit]);
+        let measure = circ.gate(circuit::Op::Measure, &[0, 1]);
+        circ.gate(circuit::Op::X, &[0]);
+        circ.gate(circuit::Op::Reset, &[0]);
+        let mut gug = circ.finish();
+
+        assert_eq!(fuse_measure_reset(&mut gug), 0);
+        assert_eq!(gug.optype(measure), &Op::Circuit(circuit::Op::Measure));
+        assert_eq!(gug.node_count(), 5);
+    }
+
+    #[test]
+    fn leading_resets() {
+        let mut circ = TestCircuit::new(&[WireType::Qubit, WireType::Qubit, WireType::LinearBit]);
+        circ.gate(circuit::Op::Reset, &[0]);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::Measure, &[1, 2]);
+        let mid = circ.gate(circuit::Op::Reset, &[1]);
+        let mut gug = circ.finish();
+
+        assert_eq!(remove_leading_resets(&mut gug), 1);
+        assert_eq!(gug.gate_counts()["Reset"], 1);
+        assert_eq!(gug.optype(mid), &Op::Circuit(circuit::Op::Reset));
+        let input = gug.input_node().unwrap();
+        let (h, _) = gug.output_target(input, 0).unwrap();
+        assert_eq!(gug.optype(h), &Op::Circuit(circuit::Op::H));
+        assert_eq!(gug.validate(), Ok(()));
+    }
+}
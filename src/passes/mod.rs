@@ -0,0 +1,52 @@
+//! Transformation passes over circuits.
+//!
+//! Each pass mutates a [`Gug`](crate::Gug) in place and returns the number of
+//! changes it made.
+
+mod angle_neg;
+mod barrier;
+mod basis;
+mod cancel;
+mod canonical_angles;
+mod conditional;
+mod const_fold;
+mod dead_code;
+mod decompose;
+mod dedup;
+mod fixpoint;
+mod inline_angles;
+mod known_bits;
+mod measure_reset;
+mod phase_poly;
+mod premeasure;
+#[cfg(feature = "matrix")]
+mod recognize;
+mod reorder;
+mod retarget;
+mod reuse;
+mod rotation;
+mod select;
+
+pub use angle_neg::simplify_angle_neg;
+pub use barrier::merge_barriers;
+pub use basis::{cx_to_cz, cz_to_cx};
+pub use cancel::{cancel_commuting_cx, cancel_inverses};
+pub use canonical_angles::canonicalize_angles;
+pub use conditional::lower_simple_conditionals;
+pub use const_fold::fold_constants;
+pub use dead_code::{dead_code_elimination, unreachable_linear_nodes};
+pub use decompose::{decompose, decompose_toffoli, DecompTable};
+pub use dedup::dedup_consts;
+pub use fixpoint::run_to_fixpoint;
+pub use inline_angles::inline_const_angles;
+pub use known_bits::propagate_known_bits;
+pub use measure_reset::{fuse_measure_reset, remove_leading_resets};
+pub use phase_poly::{collect_phase_polynomial, PhasePolyBlock};
+pub use premeasure::drop_premeasure_diagonal;
+#[cfg(feature = "matrix")]
+pub use recognize::{recognize_gates, GateLibrary};
+pub use reorder::reorder_commuting;
+pub use retarget::{Architecture, RetargetError};
+pub use reuse::reuse_qubits;
+pub use rotation::{merge_rotations, resynthesize_euler, specialize_rotations};
+pub use select::simplify_select;
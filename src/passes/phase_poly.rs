@@ -0,0 +1,240 @@
+use std::collections::{HashMap, HashSet};
+
+use num_rational::Rational64;
+use portgraph::{NodeIndex, PortIndex};
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::{AngleValue, Rational};
+use crate::Gug;
+
+/// A subcircuit made only of `CX` gates and Z rotations, described as a
+/// phase polynomial over the parities of its input qubits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhasePolyBlock {
+    /// The gates of the block, in topological order.
+    pub nodes: Vec<NodeIndex>,
+    /// The input ports of the gates linked outside the block. The `j`-th port
+    /// carries the `j`-th input qubit.
+    pub inputs: Vec<PortIndex>,
+    /// The output ports of the gates linked outside the block, in the order of
+    /// the rows of `parity`.
+    pub outputs: Vec<PortIndex>,
+    /// The linear reversible map applied by the `CX` gates: output `i` carries
+    /// the XOR of the inputs `j` for which `parity[i][j]` is set.
+    pub parity: Vec<Vec<bool>>,
+    /// The rotations in application order, each given by the parity of the
+    /// inputs it applies to and its angle in half-turns.
+    pub rotations: Vec<(Vec<bool>, AngleValue)>,
+}
+
+/// Finds the maximal convex subcircuits made only of `CX` gates and Z
+/// rotations, and describes each of them as a [`PhasePolyBlock`].
+///
+/// Z rotations are `Rz` gates with an inlined angle, and the `Z`, `S` and `T`
+/// gates and their adjoints. The circuit is not modified yet: the blocks are
+/// meant as the input of a phase polynomial resynthesis.
+pub fn collect_phase_polynomial(gug: &mut Gug) -> Vec<PhasePolyBlock> {
+    let gug: &Gug = gug;
+    let order = gug.toposort();
+
+    // Blocks are merged when a gate joins several of them, so their ids are
+    // kept in a union-find forest.
+    let mut parent: Vec<usize> = Vec::new();
+    let mut block_of: HashMap<NodeIndex, usize> = HashMap::new();
+    // The blocks from which each node can be reached through a path leaving
+    // the block, which it cannot join without breaking convexity.
+    let mut escaped: HashMap<NodeIndex, HashSet<usize>> = HashMap::new();
+    let mut block_escaped: HashMap<usize, HashSet<usize>> = HashMap::new();
+
+    for &node in &order {
+        let mut direct = HashSet::new();
+        let mut reached = HashSet::new();
+        for pred in gug.predecessors(node) {
+            if let Some(&block) = block_of.get(&pred) {
+                direct.insert(find(&mut parent, block));
+            }
+            reached.extend(escaped.get(&pred).into_iter().flatten().copied());
+        }
+        let mut reached: HashSet<usize> =
+            reached.into_iter().map(|b| find(&mut parent, b)).collect();
+
+        if !is_phase_poly_gate(gug.optype(node)) {
+            reached.extend(direct);
+            escaped.insert(node, reached);
+            continue;
+        }
+
+        let joinable = !direct.is_empty()
+            && direct.iter().all(|&block| {
+                !reached.contains(&block)
+                    && block_escaped[&block]
+                        .iter()
+                        .all(|&b| !direct.contains(&find(&mut parent, b)))
+            });
+        let block = if joinable {
+            let mut blocks = direct.into_iter();
+            let root = blocks.next().unwrap();
+            for other in blocks {
+                parent[other] = root;
+                let other_escaped = block_escaped.remove(&other).unwrap();
+                block_escaped.get_mut(&root).unwrap().extend(other_escaped);
+            }
+            root
+        } else {
+            reached.extend(direct);
+            let block = parent.len();
+            parent.push(block);
+            block_escaped.insert(block, HashSet::new());
+            block
+        };
+        let block_reached = block_escaped.get_mut(&block).unwrap();
+        block_reached.extend(reached.iter().copied());
+        block_of.insert(node, block);
+        escaped.insert(node, reached);
+    }
+
+    let mut blocks: HashMap<usize, Vec<NodeIndex>> = HashMap::new();
+    let mut roots = Vec::new();
+    for node in order {
+        let Some(&block) = block_of.get(&node) else { continue };
+        let root = find(&mut parent, block);
+        blocks
+            .entry(root)
+            .or_insert_with(|| {
+                roots.push(root);
+                Vec::new()
+            })
+            .push(node);
+    }
+    roots
+        .into_iter()
+        .map(|root| describe_block(gug, blocks.remove(&root).unwrap()))
+        .collect()
+}
+
+fn find(parent: &mut [usize], mut block: usize) -> usize {
+    while parent[block] != block {
+        parent[block] = parent[parent[block]];
+        block = parent[block];
+    }
+    block
+}
+
+fn is_phase_poly_gate(op: &Op) -> bool {
+    op == &Op::Circuit(circuit::Op::CX) || z_rotation(op).is_some()
+}
+
+/// Returns the angle of a Z rotation, in half-turns.
+fn z_rotation(op: &Op) -> Option<AngleValue> {
+    let quarters = match op {
+        Op::Circuit(circuit::Op::Rz(angle)) => return Some(*angle),
+        Op::Circuit(circuit::Op::Z) => 4,
+        Op::Circuit(circuit::Op::S) => 2,
+        Op::Circuit(circuit::Op::Sadj) => -2,
+        Op::Circuit(circuit::Op::T) => 1,
+        Op::Circuit(circuit::Op::Tadj) => -1,
+        _ => return None,
+    };
+    Some(AngleValue::Rational(Rational(Rational64::new(quarters, 4))))
+}
+
+/// Computes the parity matrix and the rotations of a block by propagating
+/// the parity carried by each wire through its gates.
+fn describe_block(gug: &Gug, nodes: Vec<NodeIndex>) -> PhasePolyBlock {
+    let members: HashSet<NodeIndex> = nodes.iter().copied().collect();
+    let internal = |port: PortIndex| {
+        gug.port_link(port)
+            .and_then(|link| gug.port_node(link))
+            .map_or(false, |n| members.contains(&n))
+    };
+    let inputs: Vec<PortIndex> = nodes
+        .iter()
+        .flat_map(|&n| gug.inputs(n))
+        .filter(|&p| !internal(p))
+        .collect();
+    let width = inputs.len();
+    let unit = |j: usize| (0..width).map(|k| k == j).collect::<Vec<bool>>();
+
+    let mut wires: HashMap<PortIndex, Vec<bool>> = HashMap::new();
+    let mut rotations = Vec::new();
+    for &node in &nodes {
+        let parities: Vec<Vec<bool>> = gug
+            .inputs(node)
+            .map(|port| match inputs.iter().position(|&p| p == port) {
+                Some(j) => unit(j),
+                None => wires[&gug.port_link(port).unwrap()].clone(),
+            })
+            .collect();
+        let outputs = match z_rotation(gug.optype(node)) {
+            Some(angle) => {
+                rotations.push((parities[0].clone(), angle));
+                parities
+            }
+            None => {
+                let target = parities[0].iter().zip(&parities[1]).map(|(a, b)| a ^ b);
+                vec![parities[0].clone(), target.collect()]
+            }
+        };
+        for (port, parity) in gug.outputs(node).zip(outputs) {
+            wires.insert(port, parity);
+        }
+    }
+
+    let outputs: Vec<PortIndex> = nodes
+        .iter()
+        .flat_map(|&n| gug.outputs(n))
+        .filter(|&p| !internal(p))
+        .collect();
+    let parity = outputs.iter().map(|p| wires[p].clone()).collect();
+    PhasePolyBlock {
+        nodes,
+        inputs,
+        outputs,
+        parity,
+        rotations,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn cx_rz_cx_block() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::Rz(AngleValue::F64(0.25)), &[1]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        let mut gug = circ.finish();
+
+        let blocks = collect_phase_polynomial(&mut gug);
+        assert_eq!(blocks.len(), 1);
+        let block = &blocks[0];
+        assert_eq!(block.nodes.len(), 3);
+        assert_eq!((block.inputs.len(), block.outputs.len()), (2, 2));
+        assert_eq!(block.parity, vec![vec![true, false], vec![false, true]]);
+        assert_eq!(
+            block.rotations,
+            vec![(vec![true, true], AngleValue::F64(0.25))]
+        );
+    }
+
+    #[test]
+    fn interrupted_by_h() {
+        let mut circ = TestCircuit::qubits(2);
+        let cx1 = circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::H, &[1]);
+        let cx2 = circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::T, &[0]);
+        let mut gug = circ.finish();
+
+        // The CXs share a wire, but a block containing both would not be convex.
+        let blocks = collect_phase_polynomial(&mut gug);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].nodes, vec![cx1]);
+        assert_eq!(blocks[1].nodes[0], cx2);
+        assert_eq!(blocks[1].nodes.len(), 2);
+        assert_eq!(blocks[1].rotations.len(), 1);
+    }
+}
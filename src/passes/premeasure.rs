@@ -0,0 +1,89 @@
+use portgraph::NodeIndex;
+
+use crate::component::operation::{circuit, Op};
+use crate::Gug;
+
+/// Removes diagonal single-qubit gates, such as `Z`, `S`, `T` or `RzF64`,
+/// directly preceding a `Measure` or `MeasureReset` on the same qubit.
+///
+/// Diagonal gates only change the phases of the computational basis states,
+/// which a measurement in that basis cannot observe. Gates acting on other
+/// qubits as well, like `CZ`, are kept since they may affect the other
+/// qubits. Chains of diagonal gates are removed entirely, along with `Const`
+/// angle sources left without a use.
+///
+/// Returns the number of removed gates.
+pub fn drop_premeasure_diagonal(gug: &mut Gug) -> usize {
+    let measures: Vec<NodeIndex> = gug
+        .nodes()
+        .filter(|&n| {
+            matches!(
+                gug.optype(n),
+                Op::Circuit(circuit::Op::Measure | circuit::Op::MeasureReset)
+            )
+        })
+        .collect();
+
+    let mut dropped = 0;
+    for measure in measures {
+        while let Some((gate, 0)) = gug.input_source(measure, 0) {
+            let diagonal = gug
+                .optype(gate)
+                .as_circuit()
+                .is_some_and(circuit::Op::is_diagonal);
+            if !diagonal {
+                break;
+            }
+            let sources: Vec<NodeIndex> = (1..gug.num_inputs(gate))
+                .filter_map(|offset| gug.input_source(gate, offset))
+                .map(|(source, _)| source)
+                .collect();
+            gug.remove_and_reconnect(gate);
+            for source in sources {
+                let unused = gug.outputs(source).all(|p| gug.port_link(p).is_none());
+                if unused && matches!(gug.optype(source), Op::Circuit(circuit::Op::Const(_))) {
+                    gug.remove_node(source);
+                }
+            }
+            dropped += 1;
+        }
+    }
+    dropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::wire_type::{ConstValue, WireType};
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn drop_rz_before_measure() {
+        let qb_bit = [WireType::Qubit, WireType::LinearBit];
+        let mut circ = TestCircuit::new(&[qb_bit, qb_bit].concat());
+        circ.gate(circuit::Op::H, &[0]);
+        let angle = circ.constant(ConstValue::f64_angle(0.25));
+        circ.gate_with_args(circuit::Op::RzF64, &[0], &[(angle, 0)]);
+        circ.gate(circuit::Op::T, &[0]);
+        circ.gate(circuit::Op::Measure, &[0, 1]);
+        circ.gate(circuit::Op::H, &[2]);
+        circ.gate(circuit::Op::Measure, &[2, 3]);
+        let mut gug = circ.finish();
+
+        assert_eq!(drop_premeasure_diagonal(&mut gug), 2);
+        assert_eq!(gug.gate_counts()["H"], 2);
+        assert_eq!(gug.gate_counts()["Measure"], 2);
+        assert_eq!(gug.node_count(), 6);
+        assert_eq!(gug.validate(), Ok(()));
+    }
+
+    #[test]
+    fn keep_entangling_diagonal() {
+        let mut circ = TestCircuit::new(&[WireType::Qubit, WireType::Qubit, WireType::LinearBit]);
+        circ.gate(circuit::Op::CZ, &[0, 1]);
+        circ.gate(circuit::Op::Measure, &[0, 2]);
+        let mut gug = circ.finish();
+
+        assert_eq!(drop_premeasure_diagonal(&mut gug), 0);
+    }
+}
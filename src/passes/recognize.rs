@@ -0,0 +1,174 @@
+use std::f64::consts::PI;
+
+use ndarray::Array2;
+use num_complex::Complex64;
+use portgraph::NodeIndex;
+
+use crate::component::operation::matrix::equal_up_to_phase;
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::AngleValue;
+use crate::Gug;
+
+/// Tolerance when comparing the matrix of a run with the library entries.
+const MATCH_TOL: f64 = 1e-10;
+
+/// A library of named single-qubit gates, looked up by their unitary matrix
+/// up to a global phase.
+///
+/// The [`Default`] library recognizes the single-qubit Clifford gates `H`,
+/// `X`, `Y`, `Z`, `S` and `Sadj`.
+#[derive(Clone, Debug)]
+pub struct GateLibrary {
+    gates: Vec<(Array2<Complex64>, circuit::Op)>,
+}
+
+impl GateLibrary {
+    /// Creates an empty library.
+    pub fn new() -> Self {
+        Self { gates: Vec::new() }
+    }
+
+    /// Adds a gate implementing the given 2x2 unitary. Earlier entries take
+    /// precedence when several match.
+    pub fn insert(&mut self, matrix: Array2<Complex64>, op: circuit::Op) {
+        self.gates.push((matrix, op));
+    }
+
+    /// Adds a gate, using its own matrix. Gates without a matrix or taking
+    /// angle inputs are ignored.
+    pub fn insert_gate(&mut self, op: circuit::Op) {
+        if let Some(matrix) = op.unitary(&[]) {
+            self.insert(matrix, op);
+        }
+    }
+
+    /// Returns the first gate whose matrix equals `matrix` up to a global
+    /// phase, together with that phase in half-turns.
+    pub fn lookup(&self, matrix: &Array2<Complex64>) -> Option<(&circuit::Op, f64)> {
+        self.gates
+            .iter()
+            .find(|(gate, _)| equal_up_to_phase(matrix, gate, MATCH_TOL))
+            .map(|(gate, op)| (op, relative_phase(matrix, gate)))
+    }
+}
+
+impl Default for GateLibrary {
+    fn default() -> Self {
+        let mut library = Self::new();
+        for op in [
+            circuit::Op::H,
+            circuit::Op::X,
+            circuit::Op::Y,
+            circuit::Op::Z,
+            circuit::Op::S,
+            circuit::Op::Sadj,
+        ] {
+            library.insert_gate(op);
+        }
+        library
+    }
+}
+
+/// Replaces maximal runs of single-qubit gates on a wire by a single gate of
+/// the library implementing the same unitary, such as `H; Z; H` by `X`.
+///
+/// Only gates with an inlined matrix are part of runs, so a gate driven by an
+/// angle input ends the run. Single-gate runs are left unchanged. The global
+/// phase separating a run from its replacement is added to the circuit's
+/// [`Gug::global_phase`].
+///
+/// Returns the number of replaced runs.
+pub fn recognize_gates(gug: &mut Gug, library: &GateLibrary) -> usize {
+    let mut recognized = 0;
+    for node in gug.toposort() {
+        if gate_matrix(gug, node).is_none() {
+            continue;
+        }
+        let starts_run = match gug.input_source(node, 0) {
+            Some((pred, 0)) => gate_matrix(gug, pred).is_none(),
+            _ => true,
+        };
+        if !starts_run {
+            continue;
+        }
+
+        let mut run = vec![node];
+        let mut matrix = gate_matrix(gug, node).unwrap();
+        while let Some((next, 0)) = gug.output_target(*run.last().unwrap(), 0) {
+            let Some(next_matrix) = gate_matrix(gug, next) else { break };
+            matrix = next_matrix.dot(&matrix);
+            run.push(next);
+        }
+        if run.len() < 2 {
+            continue;
+        }
+        let Some((op, phase)) = library.lookup(&matrix) else { continue };
+
+        gug.set_optype(node, Op::Circuit(op.clone()));
+        for &gate in &run[1..] {
+            gug.remove_and_reconnect(gate);
+        }
+        if phase.abs() > MATCH_TOL {
+            gug.add_global_phase(AngleValue::F64(phase));
+        }
+        recognized += 1;
+    }
+    recognized
+}
+
+/// Returns the matrix of a single-qubit gate without angle inputs.
+fn gate_matrix(gug: &Gug, node: NodeIndex) -> Option<Array2<Complex64>> {
+    let op = gug.optype(node).as_circuit()?;
+    if !op.is_one_qb_gate() || gug.num_inputs(node) != 1 {
+        return None;
+    }
+    op.unitary(&[])
+}
+
+/// Returns the phase, in half-turns, such that `a` equals `b` multiplied by
+/// `exp(i * pi * phase)`, assuming they are equal up to a phase.
+fn relative_phase(a: &Array2<Complex64>, b: &Array2<Complex64>) -> f64 {
+    let (idx, _) = b
+        .indexed_iter()
+        .max_by(|(_, x), (_, y)| x.norm().total_cmp(&y.norm()))
+        .unwrap();
+    (a[idx] / b[idx]).arg() / PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{circuit_unitary, TestCircuit};
+
+    #[test]
+    fn recognize_x() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::Z, &[0]);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::CX, &[0, 1]);
+        circ.gate(circuit::Op::H, &[1]);
+        let mut gug = circ.finish();
+        let before = circuit_unitary(&gug);
+
+        assert_eq!(recognize_gates(&mut gug, &GateLibrary::default()), 1);
+        let history: Vec<&str> = gug.qubit_history(0).iter().map(|(_, op)| op.name()).collect();
+        assert_eq!(history, vec!["X", "CX"]);
+        assert_eq!(gug.gate_counts()["H"], 1);
+        assert!(equal_up_to_phase(&circuit_unitary(&gug), &before, 1e-10));
+        assert_eq!(gug.validate(), Ok(()));
+    }
+
+    #[test]
+    fn recognize_with_phase() {
+        // Applying X then Y gives Y.X = -iZ.
+        let mut circ = TestCircuit::qubits(1);
+        circ.gate(circuit::Op::X, &[0]);
+        circ.gate(circuit::Op::Y, &[0]);
+        let mut gug = circ.finish();
+
+        assert_eq!(recognize_gates(&mut gug, &GateLibrary::default()), 1);
+        assert_eq!(gug.gate_counts()["Z"], 1);
+        assert!((gug.global_phase().normalize().to_f64() - 1.5).abs() < 1e-10);
+    }
+}
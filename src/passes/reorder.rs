@@ -0,0 +1,145 @@
+use portgraph::{Direction, NodeIndex};
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::WireType;
+use crate::Gug;
+
+/// Moves single-qubit gates earlier past commuting gates when that brings them
+/// next to a gate of the same kind, clustering them for later merging passes.
+///
+/// Gates are never moved across a `Barrier` or any operation carrying
+/// `SideEffects` wires. Returns the number of gate swaps performed.
+pub fn reorder_commuting(gug: &mut Gug) -> usize {
+    let mut swaps = 0;
+    for node in gug.toposort() {
+        let Some(op) = movable_gate(gug, node) else { continue };
+
+        // Walk back along the qubit wire over gates commuting with `node`,
+        // looking for a gate of the same kind.
+        let mut skipped = Vec::new();
+        let mut current = node;
+        let found = loop {
+            let Some((pred, 0)) = gug.input_source(current, 0) else { break false };
+            let Some(pred_op) = movable_gate(gug, pred) else { break false };
+            if pred_op.name() == op.name() {
+                break true;
+            }
+            if !op.commutes_with(&pred_op) {
+                break false;
+            }
+            skipped.push(pred);
+            current = pred;
+        };
+        if !found {
+            continue;
+        }
+        for pred in skipped {
+            swap_adjacent(gug, pred, node);
+            swaps += 1;
+        }
+    }
+    swaps
+}
+
+/// Returns the operation of a node if it is a single-qubit gate that may be
+/// reordered.
+fn movable_gate(gug: &Gug, node: NodeIndex) -> Option<circuit::Op> {
+    let Op::Circuit(op) = gug.optype(node) else { return None };
+    if !op.is_one_qb_gate() || matches!(op, circuit::Op::Barrier) {
+        return None;
+    }
+    let side_effects = gug
+        .node_ports(node, Direction::Incoming)
+        .chain(gug.node_ports(node, Direction::Outgoing))
+        .any(|p| gug.port_type(p) == WireType::SideEffects);
+    (!side_effects).then(|| op.clone())
+}
+
+/// Exchanges two single-qubit gates `first` and `second`, where the qubit
+/// output of `first` feeds directly into `second`.
+fn swap_adjacent(gug: &mut Gug, first: NodeIndex, second: NodeIndex) {
+    let first_in = gug.input_port(first, 0).unwrap();
+    let first_out = gug.output_port(first, 0).unwrap();
+    let second_in = gug.input_port(second, 0).unwrap();
+    let second_out = gug.output_port(second, 0).unwrap();
+
+    let source = gug.unlink_port(first_in);
+    let target = gug.unlink_port(second_out);
+    gug.unlink_port(first_out);
+
+    if let Some(source) = source {
+        gug.link_ports(source, second_in).unwrap();
+    }
+    gug.link_ports(second_out, first_in).unwrap();
+    if let Some(target) = target {
+        gug.link_ports(first_out, target).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::wire_type::ConstValue;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn cluster_rz_through_z() {
+        let mut circ = TestCircuit::qubits(1);
+        let a = circ.constant(ConstValue::f64_angle(0.25));
+        let b = circ.constant(ConstValue::f64_angle(0.5));
+        let rz1 = circ.gate_with_args(circuit::Op::RzF64, &[0], &[(a, 0)]);
+        let z = circ.gate(circuit::Op::Z, &[0]);
+        let rz2 = circ.gate_with_args(circuit::Op::RzF64, &[0], &[(b, 0)]);
+        let mut gug = circ.finish();
+        #[cfg(feature = "matrix")]
+        let before = wire_unitary(&gug);
+
+        assert_eq!(reorder_commuting(&mut gug), 1);
+        assert_eq!(gug.output_target(rz1, 0), Some((rz2, 0)));
+        assert_eq!(gug.output_target(rz2, 0), Some((z, 0)));
+        assert_eq!(reorder_commuting(&mut gug), 0);
+
+        #[cfg(feature = "matrix")]
+        assert!(crate::component::operation::matrix::equal_up_to_phase(
+            &before,
+            &wire_unitary(&gug),
+            1e-10
+        ));
+    }
+
+    #[test]
+    fn blocked_by_non_commuting() {
+        let mut circ = TestCircuit::qubits(1);
+        circ.gate(circuit::Op::T, &[0]);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::T, &[0]);
+        let mut gug = circ.finish();
+
+        assert_eq!(reorder_commuting(&mut gug), 0);
+    }
+
+    /// Multiplies the matrices of the gates along the first wire.
+    #[cfg(feature = "matrix")]
+    fn wire_unitary(gug: &Gug) -> ndarray::Array2<num_complex::Complex64> {
+        let mut unitary = ndarray::Array2::eye(2);
+        let mut node = gug.input_node().unwrap();
+        while let Some((next, _)) = gug.output_target(node, 0) {
+            let Op::Circuit(op) = gug.optype(next) else { unreachable!() };
+            if op == &circuit::Op::Output {
+                break;
+            }
+            let angles: Vec<_> = (1..gug.signature(next).num_ports().0)
+                .map(|i| {
+                    let (src, _) = gug.input_source(next, i).unwrap();
+                    match gug.optype(src) {
+                        Op::Circuit(circuit::Op::Const(ConstValue::Angle(a))) => *a,
+                        _ => unreachable!(),
+                    }
+                })
+                .collect();
+            unitary = op.unitary(&angles).unwrap().dot(&unitary);
+            node = next;
+        }
+        unitary
+    }
+}
@@ -0,0 +1,237 @@
+use thiserror::Error;
+
+use crate::component::operation::circuit;
+use crate::component::wire_type::AngleValue;
+use crate::Gug;
+
+use super::decompose::{gate_sequence, toffoli_template, tk1_template, zzmax_template};
+use super::{decompose, run_to_fixpoint, DecompTable};
+
+/// The maximum number of decomposition rounds run by [`Gug::retarget`].
+const MAX_DECOMPOSITION_ROUNDS: usize = 16;
+
+/// A hardware family with a native gate set, targeted by [`Gug::retarget`].
+///
+/// Gates are decomposed exactly: templates that differ from their gate by a
+/// phase carry it as their [`Gug::global_phase`]. Arbitrary single-qubit
+/// rotations are always native, as `Rx`/`Rz` with an inlined angle or
+/// `RxF64`/`RzF64` with an angle input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Architecture {
+    /// Superconducting devices with `CX` entangling gates and native `X`.
+    IbmHeavyHex,
+    /// Trapped-ion devices with `ZZMax` (Mølmer–Sørensen) entangling gates.
+    IonQ,
+    /// Superconducting devices with `CZ` entangling gates.
+    Rigetti,
+}
+
+/// Error returned when a circuit cannot be lowered to an architecture.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum RetargetError {
+    #[error("operations {0:?} have no decomposition into the native gate set")]
+    NonNative(Vec<String>),
+}
+
+impl Architecture {
+    /// Returns the names of the native gates of the architecture.
+    pub fn native_gates(&self) -> &'static [&'static str] {
+        match self {
+            Self::IbmHeavyHex => &["CX", "X", "Rz", "Rx", "RzF64", "RxF64"],
+            Self::IonQ => &["ZZMax", "Rz", "Rx", "RzF64", "RxF64"],
+            Self::Rigetti => &["CZ", "Rz", "Rx", "RzF64", "RxF64"],
+        }
+    }
+
+    /// Returns the decompositions of the non-native gates. Templates may use
+    /// other non-native gates, which are decomposed in later rounds.
+    pub fn decomp_table(&self) -> DecompTable {
+        use circuit::Op::{Rx, Rz, CX, CZ, H, X};
+        let turn = |half_turns| AngleValue::F64(half_turns);
+        let phased = |mut template: Gug, half_turns| {
+            template.add_global_phase(turn(half_turns));
+            template
+        };
+
+        let mut table = DecompTable::new();
+        for (op, angle) in [
+            (circuit::Op::Z, 1.0),
+            (circuit::Op::S, 0.5),
+            (circuit::Op::Sadj, -0.5),
+            (circuit::Op::T, 0.25),
+            (circuit::Op::Tadj, -0.25),
+        ] {
+            // diag(1, e^{iπθ}) is Rz(θ) up to a phase of θ/2.
+            let template = gate_sequence(1, &[(Rz(turn(angle)), &[0])]);
+            table.insert(&op, phased(template, angle / 2.0));
+        }
+        // Y is X Z = -X Rz(1), or i Rx(1) Rz(1) with X = i Rx(1).
+        let (x, y_phase) = match self {
+            Self::IbmHeavyHex => (X, 1.0),
+            Self::IonQ | Self::Rigetti => {
+                let template = gate_sequence(1, &[(Rx(turn(1.0)), &[0])]);
+                table.insert(&X, phased(template, 0.5));
+                (Rx(turn(1.0)), -0.5)
+            }
+        };
+        let template = gate_sequence(1, &[(Rz(turn(1.0)), &[0]), (x, &[0])]);
+        table.insert(&circuit::Op::Y, phased(template, y_phase));
+        let half = || Rz(turn(0.5));
+        table.insert(
+            &H,
+            phased(
+                gate_sequence(1, &[(half(), &[0]), (Rx(turn(0.5)), &[0]), (half(), &[0])]),
+                0.5,
+            ),
+        );
+        table.insert(&circuit::Op::TK1, tk1_template());
+        table.insert(&circuit::Op::CCX, toffoli_template());
+        table.insert(
+            &circuit::Op::Swap,
+            gate_sequence(2, &[(CX, &[0, 1]), (CX, &[1, 0]), (CX, &[0, 1])]),
+        );
+
+        let conjugated = |op| gate_sequence(2, &[(H, &[1]), (op, &[0, 1]), (H, &[1])]);
+        match self {
+            Self::IbmHeavyHex => {
+                table.insert(&CZ, conjugated(CX));
+                table.insert(&circuit::Op::ZZMax, zzmax_template());
+            }
+            Self::IonQ => {
+                table.insert(&CX, conjugated(CZ));
+                // CZ = e^{iπ/4} ZZMax (Sdg ⊗ Sdg).
+                let sadj = circuit::Op::Sadj;
+                let template = gate_sequence(
+                    2,
+                    &[
+                        (circuit::Op::ZZMax, &[0, 1]),
+                        (sadj.clone(), &[0]),
+                        (sadj, &[1]),
+                    ],
+                );
+                table.insert(&CZ, phased(template, 0.25));
+            }
+            Self::Rigetti => {
+                table.insert(&CX, conjugated(CZ));
+                table.insert(&circuit::Op::ZZMax, zzmax_template());
+            }
+        }
+        table
+    }
+}
+
+impl Gug {
+    /// Lowers the circuit to the native gate set of an architecture, by
+    /// repeatedly applying its [`Architecture::decomp_table`].
+    ///
+    /// Fails with the names of the remaining operations if the circuit
+    /// contains gates that cannot be decomposed. The circuit is left
+    /// partially lowered in that case.
+    pub fn retarget(&mut self, arch: Architecture) -> Result<(), RetargetError> {
+        let table = arch.decomp_table();
+        run_to_fixpoint(self, |gug| decompose(gug, &table), MAX_DECOMPOSITION_ROUNDS);
+
+        let native = arch.native_gates();
+        let mut remaining: Vec<String> = self
+            .non_native_gates(native)
+            .into_iter()
+            .map(|node| self.optype(node).name().to_string())
+            .collect();
+        if remaining.is_empty() {
+            return Ok(());
+        }
+        remaining.sort();
+        remaining.dedup();
+        Err(RetargetError::NonNative(remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn retarget_to_ionq() {
+        let mut circ = TestCircuit::qubits(3);
+        circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::ZZMax, &[0, 1]);
+        circ.gate(circuit::Op::CX, &[1, 2]);
+        circ.gate(circuit::Op::T, &[2]);
+        circ.gate(circuit::Op::Y, &[0]);
+        let mut gug = circ.finish();
+        #[cfg(feature = "matrix")]
+        let original = gug.clone();
+
+        gug.retarget(Architecture::IonQ).unwrap();
+        assert!(gug.uses_only(Architecture::IonQ.native_gates()));
+        assert_eq!(gug.validate(), Ok(()));
+        assert_eq!(gug.gate_counts()["ZZMax"], 2);
+        #[cfg(feature = "matrix")]
+        assert!(gug.equivalent_unitary(&original, 1e-10).unwrap());
+    }
+
+    #[test]
+    fn retarget_to_cx_and_cz() {
+        for arch in [Architecture::IbmHeavyHex, Architecture::Rigetti] {
+            let mut circ = TestCircuit::qubits(3);
+            circ.gate(circuit::Op::ZZMax, &[0, 1]);
+            circ.gate(circuit::Op::Swap, &[1, 2]);
+            circ.gate(circuit::Op::CCX, &[0, 1, 2]);
+            let mut gug = circ.finish();
+            #[cfg(feature = "matrix")]
+            let original = gug.clone();
+
+            gug.retarget(arch).unwrap();
+            assert!(gug.uses_only(arch.native_gates()));
+            #[cfg(feature = "matrix")]
+            assert!(gug.equivalent_unitary(&original, 1e-10).unwrap());
+        }
+    }
+
+    #[test]
+    fn retarget_keeps_phase() {
+        let mut circ = TestCircuit::qubits(1);
+        circ.gate(circuit::Op::X, &[0]);
+        let mut gug = circ.finish();
+        gug.retarget(Architecture::IonQ).unwrap();
+        assert_eq!(gug.gate_counts()["Rx"], 1);
+        assert_eq!(gug.global_phase().to_f64(), 0.5);
+
+        for arch in [
+            Architecture::IbmHeavyHex,
+            Architecture::IonQ,
+            Architecture::Rigetti,
+        ] {
+            let mut circ = TestCircuit::qubits(2);
+            circ.gate(circuit::Op::H, &[0]);
+            circ.gate(circuit::Op::Y, &[1]);
+            circ.gate(circuit::Op::CZ, &[0, 1]);
+            circ.gate(circuit::Op::S, &[0]);
+            circ.gate(circuit::Op::Tadj, &[1]);
+            circ.gate(circuit::Op::CX, &[1, 0]);
+            circ.gate(circuit::Op::Z, &[1]);
+            let mut gug = circ.finish();
+            #[cfg(feature = "matrix")]
+            let original = gug.to_unitary(0.0).unwrap();
+
+            gug.retarget(arch).unwrap();
+            #[cfg(feature = "matrix")]
+            {
+                let unitary = gug.to_unitary(0.0).unwrap();
+                assert!((unitary - original).iter().all(|z| z.norm() < 1e-10));
+            }
+        }
+    }
+
+    #[test]
+    fn non_native_remains() {
+        let mut circ = TestCircuit::qubits(2);
+        circ.gate(circuit::Op::Controlled(Box::new(circuit::Op::H)), &[0, 1]);
+        let mut gug = circ.finish();
+        assert_eq!(
+            gug.retarget(Architecture::Rigetti),
+            Err(RetargetError::NonNative(vec!["Controlled".to_string()]))
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use portgraph::{Direction, NodeIndex};
+
+use crate::component::operation::{circuit, Op};
+use crate::gug::index_after_removal;
+use crate::Gug;
+
+/// Reuses qubits that are measured before the end of the circuit for qubits
+/// that only start being used afterwards, removing the latter from the
+/// `Input` and `Output` boundaries.
+///
+/// A `Reset` is inserted after a final `Measure` so the reused wire starts in
+/// the zero state. The qubit wire then orders the operations on the two
+/// qubits, and a pair is only merged when this cannot create a cycle. The
+/// dependencies are followed through every wire, including `SideEffects`
+/// wires, so a qubit whose first operation must happen before the freed
+/// qubit's measurement in the side-effect order is never moved after it.
+/// The names and ancilla records of the removed `Input` qubits are dropped.
+/// Returns the number of saved qubits.
+pub fn reuse_qubits(gug: &mut Gug) -> usize {
+    let mut saved = 0;
+    while let Some((freed, fresh)) = find_reusable(gug) {
+        let (input, output) = gug.boundary().unwrap();
+        let (last, last_offset) = freed;
+        let (first, first_offset) = fresh;
+
+        let mut end = (last, last_offset);
+        if gug.optype(last) == &Op::Circuit(circuit::Op::Measure) {
+            let reset = gug.insert_after(last, last_offset, Op::Circuit(circuit::Op::Reset));
+            end = (reset, 0);
+        }
+        let (_, output_offset) = gug.output_target(end.0, end.1).unwrap();
+        let (_, input_offset) = gug.input_source(first, first_offset).unwrap();
+        let input_port = gug.output_port(input, input_offset).unwrap();
+        let line = gug
+            .qubit_ports(input, Direction::Outgoing)
+            .iter()
+            .position(|&port| port == input_port)
+            .unwrap();
+        gug.remove_port(output, Direction::Incoming, output_offset);
+        gug.remove_port(input, Direction::Outgoing, input_offset);
+        gug.remap_qubit_indices(input, |i| index_after_removal(i, &[line]));
+        gug.connect(end.0, end.1, first, first_offset).unwrap();
+        saved += 1;
+    }
+    saved
+}
+
+/// Finds the last operation of a measured qubit and the first operation of a
+/// qubit that can be placed after it, with the offsets of their qubit ports.
+#[allow(clippy::type_complexity)]
+fn find_reusable(gug: &Gug) -> Option<((NodeIndex, usize), (NodeIndex, usize))> {
+    let (input, output) = gug.boundary().ok()?;
+    let qubit_ends = |boundary, direction| -> Vec<(NodeIndex, usize)> {
+        gug.qubit_ports(boundary, direction)
+            .into_iter()
+            .filter_map(|port| {
+                let link = gug.port_link(port)?;
+                Some((gug.port_node(link)?, gug.port_offset(link)?))
+            })
+            .filter(|&(node, _)| !gug.optype(node).is_boundary())
+            .collect()
+    };
+
+    let freed = qubit_ends(output, Direction::Incoming).into_iter().filter(|&(node, _)| {
+        matches!(
+            gug.optype(node),
+            Op::Circuit(circuit::Op::Measure | circuit::Op::MeasureReset | circuit::Op::Reset)
+        )
+    });
+    let fresh = qubit_ends(input, Direction::Outgoing);
+    for (last, last_offset) in freed {
+        // All wires count, so side effects keep their relative order.
+        let ancestors = gug.reachable(&[last], Direction::Incoming);
+        if let Some(&(first, first_offset)) = fresh
+            .iter()
+            .find(|&&(first, _)| first != last && !ancestors.contains(&first))
+        {
+            return Some(((last, last_offset), (first, first_offset)));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::wire_type::WireType;
+    use crate::test_utils::TestCircuit;
+
+    #[test]
+    fn reuse_measured_qubit() {
+        let mut circ = TestCircuit::new(&[WireType::Qubit, WireType::LinearBit, WireType::Qubit]);
+        circ.gate(circuit::Op::H, &[0]);
+        let measure = circ.gate(circuit::Op::Measure, &[0, 1]);
+        let x = circ.gate(circuit::Op::X, &[2]);
+        let mut gug = circ.finish();
+
+        assert_eq!(reuse_qubits(&mut gug), 1);
+        let (input, _) = gug.boundary().unwrap();
+        assert_eq!(gug.qubit_ports(input, Direction::Outgoing).len(), 1);
+        let output = gug.output_node().unwrap();
+        assert_eq!(gug.qubit_ports(output, Direction::Incoming).len(), 1);
+
+        let (reset, _) = gug.input_source(x, 0).unwrap();
+        assert_eq!(gug.optype(reset), &Op::Circuit(circuit::Op::Reset));
+        assert_eq!(gug.input_source(reset, 0), Some((measure, 0)));
+        assert_eq!(gug.validate(), Ok(()));
+        assert_eq!(reuse_qubits(&mut gug), 0);
+    }
+
+    #[test]
+    fn keep_side_effect_order() {
+        let mut circ = TestCircuit::new(&[
+            WireType::Qubit,
+            WireType::LinearBit,
+            WireType::Qubit,
+            WireType::SideEffects,
+        ]);
+        // The first operation on qubit 2 is ordered before the measurement of
+        // qubit 0 only by the side-effect wire.
+        circ.barrier(&[2, 3]);
+        circ.barrier(&[0, 3]);
+        circ.gate(circuit::Op::Measure, &[0, 1]);
+        let mut gug = circ.finish();
+
+        assert_eq!(reuse_qubits(&mut gug), 0);
+        let (input, _) = gug.boundary().unwrap();
+        assert_eq!(gug.qubit_ports(input, Direction::Outgoing).len(), 2);
+        assert_eq!(gug.validate(), Ok(()));
+    }
+}
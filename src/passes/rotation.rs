@@ -0,0 +1,273 @@
+use std::collections::HashSet;
+
+use cgmath::Quaternion;
+use num_rational::Rational64;
+use portgraph::NodeIndex;
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::{AngleValue, ConstValue, Rational};
+use crate::Gug;
+
+/// Relative tolerance for the off-axis components of a rotation quaternion.
+const AXIS_TOL: f64 = 1e-10;
+
+/// Replaces each `Rotation` driven by a constant quaternion about the X or Z
+/// axis with the equivalent `RxF64` or `RzF64` gate.
+///
+/// The quaternion constant is turned into an angle constant in place, so it
+/// must not feed any other node. Returns the number of specialized rotations.
+pub fn specialize_rotations(gug: &mut Gug) -> usize {
+    let rotations: Vec<NodeIndex> = gug
+        .nodes()
+        .filter(|&n| gug.optype(n) == &Op::Circuit(circuit::Op::Rotation))
+        .collect();
+
+    let mut specialized = 0;
+    for rotation in rotations {
+        let Some((constant, _)) = gug.input_source(rotation, 1) else { continue };
+        let Op::Circuit(circuit::Op::Const(ConstValue::Quat64(quat))) = gug.optype(constant)
+        else {
+            continue;
+        };
+        let (op, angle) = if let Some(angle) = quat.z_rotation(AXIS_TOL) {
+            (circuit::Op::RzF64, angle)
+        } else if let Some(angle) = quat.x_rotation(AXIS_TOL) {
+            (circuit::Op::RxF64, angle)
+        } else {
+            continue;
+        };
+        let angle = ConstValue::f64_angle(angle);
+        gug.set_optype(constant, Op::Circuit(circuit::Op::Const(angle)));
+        gug.set_optype(rotation, Op::Circuit(op));
+        specialized += 1;
+    }
+    specialized
+}
+
+/// Merges adjacent `RzF64` gates (and adjacent `RxF64` gates) driven by
+/// constant angles into a single rotation by the sum of the angles.
+///
+/// Rotations whose angle is a multiple of two half-turns within `tol` are
+/// removed, since they are the identity up to a global phase, which is added
+/// to the circuit's [`Gug::global_phase`]. Returns the number of removed gates.
+pub fn merge_rotations(gug: &mut Gug, tol: f64) -> usize {
+    let mut removed = HashSet::new();
+    let mut merged = 0;
+    for node in gug.toposort() {
+        if removed.contains(&node) {
+            continue;
+        }
+        let Some((op, mut angle)) = constant_rotation(gug, node) else { continue };
+        while let Some((next, 0)) = gug.output_target(node, 0) {
+            match constant_rotation(gug, next) {
+                Some((next_op, next_angle)) if next_op == op => {
+                    angle = angle + next_angle;
+                    remove_rotation(gug, next);
+                    removed.insert(next);
+                    merged += 1;
+                }
+                _ => break,
+            }
+        }
+
+        let turns = angle.to_f64().rem_euclid(2.0);
+        if turns < tol || 2.0 - turns < tol {
+            // A rotation by 2k half-turns is the identity times (-1)^k.
+            if (angle.to_f64() / 2.0).round() as i64 % 2 != 0 {
+                gug.add_global_phase(AngleValue::Rational(Rational(Rational64::from_integer(1))));
+            }
+            remove_rotation(gug, node);
+            merged += 1;
+        } else {
+            let (constant, _) = gug.input_source(node, 1).unwrap();
+            gug.set_optype(constant, Op::Circuit(circuit::Op::Const(ConstValue::Angle(angle))));
+        }
+    }
+    merged
+}
+
+/// Resynthesizes each run of more than three adjacent `RzF64` and `RxF64`
+/// gates driven by constant angles as `Rz(c); Rx(b); Rz(a)`.
+///
+/// The run is multiplied out as a unit quaternion, which represents the
+/// rotations exactly in SU(2), so no global phase is introduced. The first
+/// three gates of the run are reused with their angle constants set in place,
+/// and the rest are removed. Returns the number of removed gates.
+pub fn resynthesize_euler(gug: &mut Gug) -> usize {
+    let mut visited = HashSet::new();
+    let mut removed = 0;
+    for node in gug.toposort() {
+        if visited.contains(&node) || constant_rotation(gug, node).is_none() {
+            continue;
+        }
+        let mut run = vec![node];
+        while let Some((next, 0)) = gug.output_target(*run.last().unwrap(), 0) {
+            if constant_rotation(gug, next).is_none() {
+                break;
+            }
+            run.push(next);
+        }
+        visited.extend(run.iter().copied());
+        if run.len() <= 3 {
+            continue;
+        }
+
+        let q = run
+            .iter()
+            .fold(Quaternion::new(1.0, 0.0, 0.0, 0.0), |q, &gate| {
+                let (op, angle) = constant_rotation(gug, gate).unwrap();
+                let (sin, cos) = (angle.to_f64() * std::f64::consts::FRAC_PI_2).sin_cos();
+                let rotation = match op {
+                    circuit::Op::RzF64 => Quaternion::new(cos, 0.0, 0.0, sin),
+                    _ => Quaternion::new(cos, sin, 0.0, 0.0),
+                };
+                rotation * q
+            });
+        // Rz(a) Rx(b) Rz(c) has the components
+        // (cos β cos(α+γ), sin β cos(α-γ), sin β sin(α-γ), cos β sin(α+γ))
+        // in terms of the half-angles α, β and γ.
+        let sum = q.v.z.atan2(q.s);
+        let diff = q.v.y.atan2(q.v.x);
+        let beta = q.v.x.hypot(q.v.y).atan2(q.s.hypot(q.v.z));
+        let to_turns = |half_angle: f64| 2.0 * half_angle / std::f64::consts::PI;
+        let angles = [
+            to_turns((sum - diff) / 2.0),
+            to_turns(beta),
+            to_turns((sum + diff) / 2.0),
+        ];
+        let ops = [circuit::Op::RzF64, circuit::Op::RxF64, circuit::Op::RzF64];
+        for ((&gate, op), angle) in run.iter().zip(ops).zip(angles) {
+            let (constant, _) = gug.input_source(gate, 1).unwrap();
+            let angle = ConstValue::f64_angle(angle);
+            gug.set_optype(constant, Op::Circuit(circuit::Op::Const(angle)));
+            gug.set_optype(gate, Op::Circuit(op));
+        }
+        for &gate in &run[3..] {
+            remove_rotation(gug, gate);
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// Returns the operation and angle of an `RzF64` or `RxF64` gate whose angle
+/// comes from a `Const` node.
+fn constant_rotation(gug: &Gug, node: NodeIndex) -> Option<(circuit::Op, AngleValue)> {
+    let Op::Circuit(op @ (circuit::Op::RzF64 | circuit::Op::RxF64)) = gug.optype(node) else {
+        return None;
+    };
+    let (constant, _) = gug.input_source(node, 1)?;
+    let Op::Circuit(circuit::Op::Const(ConstValue::Angle(angle))) = gug.optype(constant) else {
+        return None;
+    };
+    Some((op.clone(), *angle))
+}
+
+/// Removes a rotation gate together with its angle constant.
+fn remove_rotation(gug: &mut Gug, node: NodeIndex) {
+    let (constant, _) = gug.input_source(node, 1).unwrap();
+    gug.remove_node(constant);
+    gug.remove_and_reconnect(node);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::wire_type::Quat;
+    use crate::test_utils::TestCircuit;
+
+    fn quat(s: f64, x: f64, y: f64, z: f64) -> ConstValue {
+        ConstValue::Quat64(Quat(cgmath::Quaternion::new(s, x, y, z)))
+    }
+
+    #[test]
+    fn specialize_z_rotation() {
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        let mut circ = TestCircuit::qubits(2);
+        let z_quat = circ.constant(quat(half, 0.0, 0.0, half));
+        let z_rot = circ.gate_with_args(circuit::Op::Rotation, &[0], &[(z_quat, 0)]);
+        let off_quat = circ.constant(quat(half, 0.5, 0.5, 0.0));
+        let off_rot = circ.gate_with_args(circuit::Op::Rotation, &[1], &[(off_quat, 0)]);
+        let mut gug = circ.finish();
+
+        assert_eq!(specialize_rotations(&mut gug), 1);
+        assert_eq!(gug.optype(z_rot), &Op::Circuit(circuit::Op::RzF64));
+        assert_eq!(gug.optype(off_rot), &Op::Circuit(circuit::Op::Rotation));
+
+        let Op::Circuit(circuit::Op::Const(ConstValue::Angle(AngleValue::F64(angle)))) =
+            gug.optype(z_quat)
+        else {
+            panic!("expected an angle constant");
+        };
+        assert!((angle - 0.5).abs() < 1e-12);
+        assert_eq!(gug.input_source(z_rot, 1), Some((z_quat, 0)));
+    }
+
+    #[test]
+    fn merge_z_rotations() {
+        let mut circ = TestCircuit::qubits(1);
+        let a = circ.constant(ConstValue::f64_angle(0.25));
+        let first = circ.gate_with_args(circuit::Op::RzF64, &[0], &[(a, 0)]);
+        let b = circ.constant(ConstValue::f64_angle(0.5));
+        circ.gate_with_args(circuit::Op::RzF64, &[0], &[(b, 0)]);
+        let c = circ.constant(ConstValue::f64_angle(0.5));
+        circ.gate_with_args(circuit::Op::RxF64, &[0], &[(c, 0)]);
+        let mut gug = circ.finish();
+
+        assert_eq!(merge_rotations(&mut gug, 1e-10), 1);
+        assert_eq!(gug.gate_counts()["RzF64"], 1);
+        assert_eq!(gug.gate_counts()["RxF64"], 1);
+        let (constant, _) = gug.input_source(first, 1).unwrap();
+        assert_eq!(
+            gug.optype(constant),
+            &Op::Circuit(circuit::Op::Const(ConstValue::Angle(AngleValue::F64(0.75))))
+        );
+        assert_eq!(gug.global_phase(), AngleValue::default());
+    }
+
+    #[test]
+    fn remove_full_turn() {
+        let mut circ = TestCircuit::qubits(1);
+        let a = circ.constant(ConstValue::f64_angle(1.5));
+        circ.gate_with_args(circuit::Op::RzF64, &[0], &[(a, 0)]);
+        let b = circ.constant(ConstValue::f64_angle(0.5));
+        circ.gate_with_args(circuit::Op::RzF64, &[0], &[(b, 0)]);
+        let mut gug = circ.finish();
+
+        assert_eq!(merge_rotations(&mut gug, 1e-10), 2);
+        assert_eq!(gug.node_count(), 2);
+        // Rz(2) is minus the identity.
+        assert_eq!(gug.global_phase().to_f64(), 1.0);
+    }
+
+    #[test]
+    fn resynthesize_rotation_run() {
+        let mut circ = TestCircuit::qubits(1);
+        let mut gates = vec![];
+        for (op, angle) in [
+            (circuit::Op::RzF64, 0.3),
+            (circuit::Op::RxF64, 0.2),
+            (circuit::Op::RzF64, 0.1),
+            (circuit::Op::RxF64, 0.4),
+            (circuit::Op::RzF64, 0.5),
+        ] {
+            let angle = circ.constant(ConstValue::f64_angle(angle));
+            gates.push(circ.gate_with_args(op, &[0], &[(angle, 0)]));
+        }
+        let mut gug = circ.finish();
+        #[cfg(feature = "matrix")]
+        let original = gug.clone();
+
+        assert_eq!(resynthesize_euler(&mut gug), 2);
+        assert_eq!(gug.validate(), Ok(()));
+        assert_eq!(gug.gate_counts()["RzF64"], 2);
+        assert_eq!(gug.gate_counts()["RxF64"], 1);
+        assert_eq!(gug.optype(gates[1]), &Op::Circuit(circuit::Op::RxF64));
+        assert_eq!(gug.global_phase(), AngleValue::default());
+        #[cfg(feature = "matrix")]
+        assert!(gug.equivalent_unitary(&original, 1e-10).unwrap());
+
+        // Runs of at most three rotations are left alone.
+        assert_eq!(resynthesize_euler(&mut gug), 0);
+    }
+}
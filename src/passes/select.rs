@@ -0,0 +1,184 @@
+use std::collections::HashSet;
+
+use portgraph::{Direction, NodeIndex, PortIndex};
+
+use crate::component::operation::{circuit, Op};
+use crate::Gug;
+
+/// Replaces each `Select` whose two data inputs carry the same value by that
+/// value, regardless of the predicate.
+///
+/// Inputs are compared after following them back through `Copy` nodes, so
+/// two outputs of the same `Copy` count as the same value. Unused outputs
+/// are then trimmed from the `Copy` nodes, and classical operations that only
+/// computed the predicate or the dropped branch are removed.
+///
+/// Returns the number of removed `Select` nodes.
+pub fn simplify_select(gug: &mut Gug) -> usize {
+    let selects: Vec<NodeIndex> = gug
+        .nodes()
+        .filter(|&n| matches!(gug.optype(n), Op::Circuit(circuit::Op::Select(_))))
+        .collect();
+
+    let mut simplified = 0;
+    let mut removed = HashSet::new();
+    for select in selects {
+        if removed.contains(&select) {
+            continue;
+        }
+        let [predicate, first, second] =
+            [0, 1, 2].map(|offset| gug.port_link(gug.input_port(select, offset).unwrap()));
+        let (Some(first), Some(second)) = (first, second) else {
+            continue;
+        };
+        let source = copy_source(gug, first);
+        if source.is_none() || source != copy_source(gug, second) {
+            continue;
+        }
+
+        let dropped: Vec<NodeIndex> = [predicate, Some(second)]
+            .into_iter()
+            .flatten()
+            .filter_map(|port| gug.port_node(port))
+            .collect();
+        let target = gug.port_link(gug.output_port(select, 0).unwrap());
+        gug.remove_node(select);
+        removed.insert(select);
+        if let Some(target) = target {
+            gug.link_ports(first, target).unwrap();
+        }
+        for node in dropped {
+            prune(gug, node, &mut removed);
+        }
+        simplified += 1;
+    }
+    simplified
+}
+
+/// Follows an output port back through `Copy` nodes to the port producing
+/// the copied value.
+fn copy_source(gug: &Gug, mut port: PortIndex) -> Option<PortIndex> {
+    loop {
+        let node = gug.port_node(port)?;
+        if !matches!(gug.optype(node), Op::Circuit(circuit::Op::Copy { .. })) {
+            return Some(port);
+        }
+        port = gug.port_link(gug.input_port(node, 0)?)?;
+    }
+}
+
+/// Removes the unused outputs of a `Copy` node, or a classical node whose
+/// outputs are all unused, then prunes the nodes feeding a removed node.
+/// Removed nodes are recorded in `removed` and skipped when reached again.
+fn prune(gug: &mut Gug, node: NodeIndex, removed: &mut HashSet<NodeIndex>) {
+    if removed.contains(&node) {
+        return;
+    }
+    let op = gug.optype(node);
+    if let Op::Circuit(circuit::Op::Copy { typ, .. }) = op {
+        let typ = *typ;
+        let unused: Vec<usize> = (0..gug.num_outputs(node))
+            .filter(|&offset| gug.output_target(node, offset).is_none())
+            .collect();
+        for &offset in unused.iter().rev() {
+            gug.remove_port(node, Direction::Outgoing, offset);
+        }
+        let n_copies = gug.num_outputs(node) as u32;
+        if n_copies > 1 {
+            gug.set_optype(node, Op::Circuit(circuit::Op::Copy { n_copies, typ }));
+            return;
+        }
+        // A single copy is the value itself.
+        let source = gug.port_link(gug.input_port(node, 0).unwrap());
+        let target = gug.output_port(node, 0).and_then(|p| gug.port_link(p));
+        gug.remove_node(node);
+        removed.insert(node);
+        match (source, target) {
+            (Some(source), Some(target)) => gug.link_ports(source, target).unwrap(),
+            (Some(source), None) => {
+                if let Some(source) = gug.port_node(source) {
+                    prune(gug, source, removed);
+                }
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    let removable = !op.is_boundary()
+        && op.as_circuit().is_some_and(circuit::Op::is_pure_classical)
+        && gug.signature(node).linear.is_empty()
+        && gug.outputs(node).all(|p| gug.port_link(p).is_none());
+    if !removable {
+        return;
+    }
+    let sources: Vec<NodeIndex> = gug
+        .node_ports(node, Direction::Incoming)
+        .filter_map(|p| gug.port_link(p).and_then(|link| gug.port_node(link)))
+        .collect();
+    gug.remove_node(node);
+    removed.insert(node);
+    for source in sources {
+        prune(gug, source, removed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::wire_type::{ConstValue, Signature, WireType};
+
+    /// Builds a `Select` between two outputs of a `Copy` of an angle input,
+    /// with a predicate computed by a `Not`, feeding an `AngleNeg`.
+    fn select_copies(n_copies: u32) -> (Gug, NodeIndex, NodeIndex, NodeIndex) {
+        let mut gug = Gug::new();
+        let input = gug.add_node_with_signature(
+            Op::Circuit(circuit::Op::Input),
+            Signature::new_nonlinear(vec![], vec![WireType::Bool, WireType::Angle]),
+        );
+        let not = gug.add_node(Op::Circuit(circuit::Op::Not));
+        gug.connect(input, 0, not, 0).unwrap();
+        let copy = gug.add_node(Op::Circuit(circuit::Op::Copy {
+            n_copies,
+            typ: WireType::Angle,
+        }));
+        gug.connect(input, 1, copy, 0).unwrap();
+        let select = gug.add_node(Op::Circuit(circuit::Op::Select(WireType::Angle)));
+        gug.connect(not, 0, select, 0).unwrap();
+        gug.connect(copy, 0, select, 1).unwrap();
+        gug.connect(copy, 1, select, 2).unwrap();
+        let neg = gug.add_node(Op::Circuit(circuit::Op::AngleNeg));
+        gug.connect(select, 0, neg, 0).unwrap();
+        (gug, input, copy, neg)
+    }
+
+    #[test]
+    fn select_between_copies() {
+        let (mut gug, input, _, neg) = select_copies(2);
+        assert_eq!(simplify_select(&mut gug), 1);
+        assert_eq!(gug.input_source(neg, 0), Some((input, 1)));
+        assert_eq!(gug.node_count(), 2);
+    }
+
+    #[test]
+    fn keep_distinct_branches() {
+        let (mut gug, input, copy, neg) = select_copies(3);
+        let kept = gug.add_node(Op::Circuit(circuit::Op::Select(WireType::Angle)));
+        let zero = gug.add_node(Op::Circuit(circuit::Op::Const(ConstValue::f64_angle(0.0))));
+        gug.connect(input, 0, kept, 0).unwrap();
+        gug.connect(copy, 2, kept, 1).unwrap();
+        gug.connect(zero, 0, kept, 2).unwrap();
+
+        assert_eq!(simplify_select(&mut gug), 1);
+        assert_eq!(gug.input_source(neg, 0), Some((copy, 0)));
+        assert_eq!(gug.input_source(kept, 1), Some((copy, 1)));
+        let copy_op = circuit::Op::Copy {
+            n_copies: 2,
+            typ: WireType::Angle,
+        };
+        assert_eq!(gug.optype(copy), &Op::Circuit(copy_op));
+        let counts = gug.gate_counts();
+        assert_eq!(counts["Select"], 1);
+        assert!(!counts.contains_key("Not"));
+    }
+}
@@ -0,0 +1,674 @@
+//! Import of OpenQASM 2 programs.
+//!
+//! Only the flat subset of the language is supported: register declarations,
+//! the standard single-, two- and three-qubit gates, `rx`/`rz` rotations,
+//! `measure` and `barrier`. Gates are applied to single register elements
+//! such as `q[0]`. Gate definitions, classical conditions, `reset` and other
+//! gates are rejected by [`from_qasm2`] and skipped by [`from_qasm2_lossy`].
+//! [`from_qasm2_with_registry`] additionally imports `opaque` declarations and
+//! applications of gates registered in an [`OpRegistry`].
+//!
+//! All qubits become `Qubit` wires and all classical bits `LinearBit` wires of
+//! the `Input` and `Output` nodes, in declaration order with the qubits first.
+//! Rotation angles are converted to half-turns and fed from `Const` nodes.
+//! Each node records the [`SourceSpan`] of the statement it was created from.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use portgraph::NodeIndex;
+use thiserror::Error;
+
+use crate::component::debug::SourceSpan;
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::{AngleValue, ConstValue, WireType};
+use crate::extension::OpRegistry;
+use crate::gug::{BuildError, CircuitBuilder};
+use crate::Gug;
+
+/// Error returned when importing an OpenQASM 2 program.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+#[error("line {line}: {message}")]
+pub struct QasmError {
+    /// The one-based line number of the statement that caused the error.
+    pub line: usize,
+    /// The span of the statement that caused the error.
+    pub span: SourceSpan,
+    /// A description of the error.
+    pub message: String,
+}
+
+/// An instruction skipped by [`from_qasm2_lossy`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QasmWarning {
+    /// The one-based line number of the skipped statement.
+    pub line: usize,
+    /// The span of the skipped statement.
+    pub span: SourceSpan,
+    /// A description of why the statement was skipped.
+    pub message: String,
+}
+
+impl fmt::Display for QasmWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Imports an OpenQASM 2 program, failing on the first unsupported or
+/// invalid statement.
+pub fn from_qasm2(src: &str) -> Result<Gug, QasmError> {
+    from_qasm2_with_registry(src, &OpRegistry::new())
+}
+
+/// Imports an OpenQASM 2 program like [`from_qasm2`], building the gates
+/// outside the standard set with the factories of `registry`.
+///
+/// A gate `name(params) args` is built by the factory registered under
+/// `name`, with the parameter text as payload, and applied to its qubit
+/// arguments in order. `opaque` declarations of registered gates are accepted.
+pub fn from_qasm2_with_registry(src: &str, registry: &OpRegistry) -> Result<Gug, QasmError> {
+    let (gug, warnings) = import(src, false, registry)?;
+    debug_assert!(warnings.is_empty());
+    Ok(gug)
+}
+
+/// Imports an OpenQASM 2 program, skipping unsupported or invalid statements.
+///
+/// Returns the circuit built from the remaining statements together with a
+/// warning for each skipped one. The result is a best-effort import: the
+/// skipped operations are simply missing from the circuit.
+pub fn from_qasm2_lossy(src: &str) -> (Gug, Vec<QasmWarning>) {
+    import(src, true, &OpRegistry::new()).expect("lossy imports report errors as warnings")
+}
+
+/// A statement of the program, with the line it starts on and its span.
+struct Statement<'a> {
+    line: usize,
+    span: SourceSpan,
+    text: &'a str,
+}
+
+/// Splits the program into statements, dropping comments.
+///
+/// Statements end at a `;` outside of braces, or at the `}` closing a block.
+fn statements(src: &str) -> Vec<Statement<'_>> {
+    let mut statements = Vec::new();
+    let mut depth = 0usize;
+    let mut start: Option<(usize, usize)> = None;
+    let mut line = 1;
+    let mut chars = src.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '/' if matches!(chars.peek(), Some((_, '/'))) => {
+                while matches!(chars.peek(), Some((_, c)) if *c != '\n') {
+                    chars.next();
+                }
+                continue;
+            }
+            '\n' => line += 1,
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if start.is_none() && !c.is_whitespace() {
+            start = Some((i, line));
+        }
+        if matches!(c, ';' | '}') && depth == 0 {
+            if let Some((begin, begin_line)) = start.take() {
+                statements.push(Statement::new(src, begin, i, begin_line));
+            }
+        }
+    }
+    if let Some((begin, begin_line)) = start {
+        statements.push(Statement::new(src, begin, src.len(), begin_line));
+    }
+    statements
+}
+
+impl<'a> Statement<'a> {
+    /// Creates a statement from the text between two byte offsets, starting at
+    /// a non-whitespace character.
+    fn new(src: &'a str, begin: usize, end: usize, line: usize) -> Self {
+        let text = src[begin..end].trim_end();
+        let span = SourceSpan {
+            start: begin,
+            end: begin + text.len(),
+        };
+        Self { line, span, text }
+    }
+}
+
+/// The wires allocated to the registers of the program.
+#[derive(Default)]
+struct Registers {
+    qregs: HashMap<String, (usize, usize)>,
+    cregs: HashMap<String, (usize, usize)>,
+    num_qubits: usize,
+    num_bits: usize,
+}
+
+impl Registers {
+    /// Parses a register declaration, returning `false` if the statement is
+    /// not one.
+    fn declare(&mut self, text: &str) -> Result<bool, String> {
+        let (keyword, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        if keyword != "qreg" && keyword != "creg" {
+            return Ok(false);
+        }
+        let (name, size) = parse_element(rest)?;
+        let (registers, count) = match keyword {
+            "qreg" => (&mut self.qregs, &mut self.num_qubits),
+            _ => (&mut self.cregs, &mut self.num_bits),
+        };
+        if registers.insert(name.to_string(), (*count, size)).is_some() {
+            return Err(format!("duplicate register {name}"));
+        }
+        *count += size;
+        Ok(true)
+    }
+
+    /// Returns the index of the qubit wire for an element such as `q[0]`.
+    fn qubit(&self, arg: &str) -> Result<usize, String> {
+        Self::element(&self.qregs, arg)
+    }
+
+    /// Returns the index of the bit wire for an element such as `c[0]`,
+    /// together with the register name and index.
+    fn bit(&self, arg: &str) -> Result<(usize, &str, usize), String> {
+        let wire = Self::element(&self.cregs, arg)?;
+        let (name, index) = parse_element(arg)?;
+        Ok((self.num_qubits + wire, name, index))
+    }
+
+    fn element(registers: &HashMap<String, (usize, usize)>, arg: &str) -> Result<usize, String> {
+        let (name, index) = parse_element(arg)?;
+        let &(offset, size) = registers
+            .get(name)
+            .ok_or_else(|| format!("unknown register {name}"))?;
+        if index >= size {
+            return Err(format!("index {index} out of range for register {name}"));
+        }
+        Ok(offset + index)
+    }
+}
+
+/// Parses `name[index]`.
+fn parse_element(s: &str) -> Result<(&str, usize), String> {
+    let s = s.trim();
+    let (name, rest) = s
+        .split_once('[')
+        .ok_or_else(|| format!("expected a register element, found {s:?}"))?;
+    let index = rest
+        .strip_suffix(']')
+        .and_then(|i| i.trim().parse().ok())
+        .ok_or_else(|| format!("invalid register element {s:?}"))?;
+    Ok((name.trim(), index))
+}
+
+/// Builds the circuit, turning errors into warnings when `lossy` is set.
+fn import(
+    src: &str,
+    lossy: bool,
+    registry: &OpRegistry,
+) -> Result<(Gug, Vec<QasmWarning>), QasmError> {
+    let statements = statements(src);
+    let mut warnings = Vec::new();
+    let mut report = |statement: &Statement<'_>, message: String| {
+        let (line, span) = (statement.line, statement.span);
+        if lossy {
+            warnings.push(QasmWarning {
+                line,
+                span,
+                message,
+            });
+            Ok(())
+        } else {
+            Err(QasmError {
+                line,
+                span,
+                message,
+            })
+        }
+    };
+
+    let mut registers = Registers::default();
+    let mut body = Vec::new();
+    for statement in statements {
+        match registers.declare(statement.text) {
+            Ok(true) => {}
+            Ok(false) => body.push(statement),
+            Err(message) => report(&statement, message)?,
+        }
+    }
+
+    let types: Vec<WireType> = std::iter::repeat(WireType::Qubit)
+        .take(registers.num_qubits)
+        .chain(std::iter::repeat(WireType::LinearBit).take(registers.num_bits))
+        .collect();
+    let mut builder = Builder::new(types);
+    for statement in body {
+        builder.span = statement.span;
+        if let Err(message) = builder.apply(&registers, registry, statement.text) {
+            report(&statement, message)?;
+        }
+    }
+    Ok((builder.finish(), warnings))
+}
+
+/// Appends operations to a circuit, recording the span of the statement they
+/// were created from.
+struct Builder {
+    gug: Gug,
+    builder: CircuitBuilder,
+    /// The span of the statement being applied.
+    span: SourceSpan,
+}
+
+impl Builder {
+    fn new(types: Vec<WireType>) -> Self {
+        let mut gug = Gug::new();
+        let builder = CircuitBuilder::new(&mut gug, &types);
+        Self {
+            gug,
+            builder,
+            span: SourceSpan::default(),
+        }
+    }
+
+    /// Applies a statement other than a register declaration.
+    fn apply(
+        &mut self,
+        registers: &Registers,
+        registry: &OpRegistry,
+        text: &str,
+    ) -> Result<(), String> {
+        let (head, args) = split_instruction(text);
+        let (name, params) = match head.split_once('(') {
+            Some((name, params)) => {
+                let params = params
+                    .strip_suffix(')')
+                    .ok_or_else(|| format!("unbalanced parameters in {head:?}"))?;
+                (name.trim(), Some(params))
+            }
+            None => (head, None),
+        };
+        match name {
+            "OPENQASM" | "include" | "barrier" => return Ok(()),
+            "measure" => {
+                let (qubit, bit) = args
+                    .split_once("->")
+                    .ok_or_else(|| "expected measure q[i] -> c[j]".to_string())?;
+                let qubit = registers.qubit(qubit)?;
+                let (bit, creg, index) = registers.bit(bit)?;
+                let node = self
+                    .append(circuit::Op::Measure, &[qubit, bit], &[])
+                    .map_err(|err| format!("measure: {err}"))?;
+                let port = self.gug.output_port(node, 1).unwrap();
+                self.gug.set_creg(port, creg, index);
+                return Ok(());
+            }
+            "opaque" => {
+                let (declared, _) = split_instruction(args);
+                let declared = declared.split('(').next().unwrap_or(declared).trim();
+                if !registry.contains(declared) {
+                    return Err(format!("unsupported opaque gate {declared}"));
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+        let qubits = || -> Result<Vec<usize>, String> {
+            args.split(',').map(|arg| registers.qubit(arg)).collect()
+        };
+
+        if gate_op(name).is_none() && registry.contains(name) {
+            let op = registry
+                .build(name, params.unwrap_or_default().trim())
+                .ok_or_else(|| format!("invalid parameters for gate {name}"))?;
+            let signature = op.signature();
+            let node = self
+                .builder
+                .append_with_signature(&mut self.gug, Op::Opaque(op), signature, &qubits()?, &[])
+                .map_err(|err| format!("gate {name}: {err}"))?;
+            self.gug.set_source_span(node, self.span);
+            return Ok(());
+        }
+
+        let (op, num_params) = gate_op(name).ok_or_else(|| format!("unsupported gate {name}"))?;
+        let params: Vec<f64> = match params {
+            Some(params) => params.split(',').map(eval_param).collect::<Result<_, _>>()?,
+            None => vec![],
+        };
+        if params.len() != num_params {
+            return Err(format!(
+                "gate {name} expects {num_params} parameters, found {}",
+                params.len()
+            ));
+        }
+        let qubits = qubits()?;
+        let num_qubits = op.signature().linear.len();
+        if qubits.len() != num_qubits {
+            return Err(format!(
+                "gate {name} acts on {num_qubits} qubits, found {}",
+                qubits.len()
+            ));
+        }
+        let angles: Vec<(NodeIndex, usize)> = params
+            .into_iter()
+            .map(|theta| {
+                let value = ConstValue::Angle(AngleValue::F64(theta / std::f64::consts::PI));
+                (self.add_node(circuit::Op::Const(value)), 0)
+            })
+            .collect();
+        if let Err(err) = self.append(op, &qubits, &angles) {
+            for &(angle, _) in &angles {
+                self.gug.remove_node(angle);
+            }
+            return Err(format!("gate {name}: {err}"));
+        }
+        Ok(())
+    }
+
+    /// Appends an operation on the given wires, with its remaining inputs fed
+    /// by the given sources.
+    fn append(
+        &mut self,
+        op: circuit::Op,
+        wires: &[usize],
+        args: &[(NodeIndex, usize)],
+    ) -> Result<NodeIndex, BuildError> {
+        let node = self.builder.append(&mut self.gug, op, wires, args)?;
+        self.gug.set_source_span(node, self.span);
+        Ok(node)
+    }
+
+    /// Adds a node, recording the span of the current statement.
+    fn add_node(&mut self, op: circuit::Op) -> NodeIndex {
+        let node = self.gug.add_node(Op::Circuit(op));
+        self.gug.set_source_span(node, self.span);
+        node
+    }
+
+    fn finish(mut self) -> Gug {
+        self.builder.finish(&mut self.gug);
+        self.gug
+    }
+}
+
+/// Splits an instruction into its head, including any parameter list, and
+/// its arguments.
+fn split_instruction(text: &str) -> (&str, &str) {
+    let split = match text.find('(') {
+        Some(open) if !text[..open].contains(char::is_whitespace) => {
+            text.find(')').map_or(text.len(), |close| close + 1)
+        }
+        _ => text.find(char::is_whitespace).unwrap_or(text.len()),
+    };
+    (text[..split].trim(), text[split..].trim())
+}
+
+/// Returns the operation for a standard gate, with its number of parameters.
+fn gate_op(name: &str) -> Option<(circuit::Op, usize)> {
+    Some(match name {
+        "h" => (circuit::Op::H, 0),
+        "x" => (circuit::Op::X, 0),
+        "y" => (circuit::Op::Y, 0),
+        "z" => (circuit::Op::Z, 0),
+        "s" => (circuit::Op::S, 0),
+        "sdg" => (circuit::Op::Sadj, 0),
+        "t" => (circuit::Op::T, 0),
+        "tdg" => (circuit::Op::Tadj, 0),
+        "cx" | "CX" => (circuit::Op::CX, 0),
+        "cz" => (circuit::Op::CZ, 0),
+        "ccx" => (circuit::Op::CCX, 0),
+        "swap" => (circuit::Op::Swap, 0),
+        "rx" => (circuit::Op::RxF64, 1),
+        "rz" => (circuit::Op::RzF64, 1),
+        _ => return None,
+    })
+}
+
+/// Evaluates a gate parameter, an arithmetic expression over numbers and `pi`.
+fn eval_param(expr: &str) -> Result<f64, String> {
+    let tokens = tokenize(expr).ok_or_else(|| format!("invalid parameter {expr:?}"))?;
+    let mut parser = ExprParser { tokens, pos: 0 };
+    match parser.sum() {
+        Some(value) if parser.pos == parser.tokens.len() => Ok(value),
+        _ => Err(format!("invalid parameter {expr:?}")),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Symbol(char),
+}
+
+fn tokenize(expr: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if "+-*/()".contains(c) {
+            tokens.push(Token::Symbol(c));
+            rest = &rest[1..];
+        } else if let Some(after) = rest.strip_prefix("pi") {
+            tokens.push(Token::Number(std::f64::consts::PI));
+            rest = after;
+        } else {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == 'e'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Number(rest[..len].parse().ok()?));
+            rest = &rest[len..];
+        }
+        rest = rest.trim_start();
+    }
+    Some(tokens)
+}
+
+/// A recursive-descent parser for parameter expressions.
+struct ExprParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn eat(&mut self, symbol: char) -> bool {
+        let found = self.tokens.get(self.pos) == Some(&Token::Symbol(symbol));
+        self.pos += usize::from(found);
+        found
+    }
+
+    fn sum(&mut self) -> Option<f64> {
+        let mut value = self.product()?;
+        loop {
+            if self.eat('+') {
+                value += self.product()?;
+            } else if self.eat('-') {
+                value -= self.product()?;
+            } else {
+                return Some(value);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Option<f64> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat('*') {
+                value *= self.unary()?;
+            } else if self.eat('/') {
+                value /= self.unary()?;
+            } else {
+                return Some(value);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Option<f64> {
+        if self.eat('-') {
+            return Some(-self.unary()?);
+        }
+        if self.eat('(') {
+            let value = self.sum()?;
+            return self.eat(')').then_some(value);
+        }
+        match self.tokens.get(self.pos)? {
+            &Token::Number(value) => {
+                self.pos += 1;
+                Some(value)
+            }
+            Token::Symbol(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BELL: &str = r#"
+        OPENQASM 2.0;
+        include "qelib1.inc";
+        qreg q[2];
+        creg c[2];
+        h q[0];
+        cx q[0], q[1];
+        rz(-pi/2) q[1];
+        measure q[0] -> c[0];
+        measure q[1] -> c[1];
+    "#;
+
+    #[test]
+    fn import_bell() {
+        let gug = from_qasm2(BELL).unwrap();
+        let counts = gug.gate_counts();
+        assert_eq!(counts["H"], 1);
+        assert_eq!(counts["CX"], 1);
+        assert_eq!(counts["RzF64"], 1);
+        assert_eq!(counts["Measure"], 2);
+        assert_eq!(gug.validate(), Ok(()));
+
+        let rz = gug
+            .nodes()
+            .find(|&n| gug.optype(n) == &Op::Circuit(circuit::Op::RzF64))
+            .unwrap();
+        let (angle, _) = gug.input_source(rz, 1).unwrap();
+        assert_eq!(
+            gug.optype(angle),
+            &Op::Circuit(circuit::Op::Const(ConstValue::Angle(AngleValue::F64(-0.5))))
+        );
+    }
+
+    #[test]
+    fn lossy_import_skips_unsupported_gates() {
+        let src = "qreg q[2];\nh q[0];\nu3(0.1, 0.2, 0.3) q[1];\ncx q[0], q[1];\n";
+        assert_eq!(
+            from_qasm2(src),
+            Err(QasmError {
+                line: 3,
+                span: SourceSpan { start: 19, end: 41 },
+                message: "unsupported gate u3".to_string()
+            })
+        );
+
+        let (gug, warnings) = from_qasm2_lossy(src);
+        assert_eq!(
+            warnings,
+            vec![QasmWarning {
+                line: 3,
+                span: SourceSpan { start: 19, end: 41 },
+                message: "unsupported gate u3".to_string()
+            }]
+        );
+        assert_eq!(gug.gate_counts()["H"], 1);
+        assert_eq!(gug.gate_counts()["CX"], 1);
+        assert_eq!(gug.validate(), Ok(()));
+    }
+
+    #[test]
+    fn errors_point_to_source() {
+        let src = "qreg q[2];\nh q[0];\ncx q[0], q[1];\n";
+        let mut gug = from_qasm2(src).unwrap();
+        let cx = gug
+            .nodes()
+            .find(|&n| gug.optype(n) == &Op::Circuit(circuit::Op::CX))
+            .unwrap();
+        let span = gug.source_span(cx).unwrap();
+        assert_eq!(&src[span.start..span.end], "cx q[0], q[1]");
+        assert_eq!(gug.source_span(gug.input_node().unwrap()), None);
+
+        let port = gug.output_port(cx, 1).unwrap();
+        gug.unlink_port(port);
+        let error = gug.validate().unwrap_err();
+        assert!(gug.describe_error(&error).ends_with("(source 19..32)"));
+    }
+
+    #[test]
+    fn repeated_operands() {
+        let src = "qreg q[2];\nh q[0];\ncx q[1], q[1];\n";
+        let error = from_qasm2(src).unwrap_err();
+        assert_eq!(error.line, 3);
+        assert_eq!(&src[error.span.start..error.span.end], "cx q[1], q[1]");
+        assert_eq!(error.message, "gate cx: wire 1 is used more than once");
+
+        let (gug, warnings) = from_qasm2_lossy(src);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(gug.gate_counts().get("CX"), None);
+        assert_eq!(gug.validate(), Ok(()));
+    }
+
+    #[test]
+    fn parameter_expressions() {
+        let pi = std::f64::consts::PI;
+        assert_eq!(eval_param("pi"), Ok(pi));
+        assert_eq!(eval_param("-pi/2"), Ok(-pi / 2.0));
+        assert_eq!(eval_param("3*pi/4 + 0.5"), Ok(3.0 * pi / 4.0 + 0.5));
+        assert_eq!(eval_param("(1+1)*2"), Ok(4.0));
+        assert!(eval_param("pi +").is_err());
+    }
+
+    #[test]
+    fn import_registered_gates() {
+        use crate::component::operation::CustomOp;
+        use crate::component::wire_type::Signature;
+
+        #[derive(Clone, Debug)]
+        struct Flip(String);
+
+        impl CustomOp for Flip {
+            fn name(&self) -> &str {
+                "flip"
+            }
+
+            fn signature(&self) -> Signature {
+                Signature::new_linear(vec![WireType::Qubit; 2])
+            }
+        }
+
+        let src = "qreg q[2];\nopaque flip(theta) a, b;\nflip(pi/2) q[1], q[0];\n";
+        assert_eq!(
+            from_qasm2(src).unwrap_err().message,
+            "unsupported opaque gate flip"
+        );
+
+        let mut registry = OpRegistry::new();
+        registry.register("flip", |payload| {
+            Some(Box::new(Flip(payload.to_string())) as Box<dyn CustomOp>)
+        });
+        let gug = from_qasm2_with_registry(src, &registry).unwrap();
+        assert_eq!(gug.validate(), Ok(()));
+        let flip = gug
+            .nodes()
+            .find(|&n| gug.optype(n).as_opaque().is_some())
+            .unwrap();
+        let op = gug.optype(flip).as_opaque().unwrap();
+        assert_eq!(op.downcast_ref::<Flip>().unwrap().0, "pi/2");
+        let (input, _) = gug.boundary().unwrap();
+        assert_eq!(gug.input_source(flip, 0), Some((input, 1)));
+
+        let error = from_qasm2_with_registry("qreg q[1];\nflip q[0];", &registry).unwrap_err();
+        assert_eq!(error.message, "gate flip: operation acts on 2 wires, found 1");
+    }
+}
@@ -1,8 +1,13 @@
+use std::collections::HashSet;
+
 use portgraph::{
     substitute::{BoundedSubgraph, OpenGraph, Rewrite},
-    PortGraph, PortIndex,
+    Direction, NodeIndex, PortGraph, PortIndex,
 };
 
+use thiserror::Error;
+
+use crate::gug::CostModel;
 use crate::Gug;
 
 /// A graph with explicit input and output ports.
@@ -39,6 +44,24 @@ impl OpenGug {
     }
 }
 
+/// Error returned by [`GugRewrite::check`] when a replacement does not fit
+/// the boundary of the subgraph it replaces.
+///
+/// Wire counts are given as `(inputs, outputs)`.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RewriteValidationError {
+    #[error("expected {removed:?} linear boundary wires, found {replacement:?}")]
+    LinearMismatch {
+        removed: (usize, usize),
+        replacement: (usize, usize),
+    },
+    #[error("expected {removed:?} classical boundary wires, found {replacement:?}")]
+    ClassicalMismatch {
+        removed: (usize, usize),
+        replacement: (usize, usize),
+    },
+}
+
 /// A rewrite operation that replaces a subgraph with another graph.
 /// Includes the new weights for the nodes in the replacement graph.
 #[derive(Debug, Clone)]
@@ -64,4 +87,417 @@ impl GugRewrite {
         let (open_graph, replacement) = self.replacement.into_parts();
         (Rewrite::new(self.subgraph, open_graph), replacement)
     }
+
+    /// Checks that the replacement can take the place of the subgraph in
+    /// `host` without breaking linearity.
+    ///
+    /// The dangling inputs and outputs of the replacement must match the
+    /// wires crossing the boundary of the removed subgraph, counting linear
+    /// (`Qubit` and `LinearBit`) and classical wires separately.
+    pub fn check(&self, host: &Gug) -> Result<(), RewriteValidationError> {
+        let nodes = &self.subgraph.subgraph.nodes;
+        let mut removed = [(0, 0); 2];
+        for &node in nodes {
+            let crossing = |port| {
+                let inside = host
+                    .port_link(port)
+                    .and_then(|link| host.port_node(link))
+                    .map_or(false, |n| nodes.contains(&n));
+                (!inside).then(|| usize::from(!host.port_type(port).is_linear()))
+            };
+            for kind in host.inputs(node).filter_map(crossing) {
+                removed[kind].0 += 1;
+            }
+            for kind in host.outputs(node).filter_map(crossing) {
+                removed[kind].1 += 1;
+            }
+        }
+
+        let gug = &self.replacement.gug;
+        let mut replacement = [(0, 0); 2];
+        let class = |&port: &PortIndex| usize::from(!gug.port_type(port).is_linear());
+        for kind in self.replacement.dangling_inputs.iter().map(class) {
+            replacement[kind].0 += 1;
+        }
+        for kind in self.replacement.dangling_outputs.iter().map(class) {
+            replacement[kind].1 += 1;
+        }
+
+        if removed[0] != replacement[0] {
+            return Err(RewriteValidationError::LinearMismatch {
+                removed: removed[0],
+                replacement: replacement[0],
+            });
+        }
+        if removed[1] != replacement[1] {
+            return Err(RewriteValidationError::ClassicalMismatch {
+                removed: removed[1],
+                replacement: replacement[1],
+            });
+        }
+        Ok(())
+    }
+
+    /// Estimates the change in the cost of `host` if the rewrite were
+    /// applied, as the cost of the replacement minus the cost of the removed
+    /// subgraph, without modifying the graph.
+    pub fn cost_delta(&self, host: &Gug, model: &CostModel) -> f64 {
+        let removed: f64 = self
+            .subgraph
+            .subgraph
+            .nodes
+            .iter()
+            .map(|&node| model.op_cost(host.optype(node)))
+            .sum();
+        self.replacement.gug.cost(model) - removed
+    }
+}
+
+/// A sequence of gates applied one after the other along a single wire,
+/// matched against a graph with [`Gug::find`].
+///
+/// Gates are identified by their operation name. Each step also fixes the
+/// linear port through which the wire crosses the gate, so that patterns can
+/// tell the control of a `CX` from its target. For example,
+/// `Pattern::gate("H").then("H")` matches two Hadamards in a row, and
+/// `Pattern::gate_on("CX", 0).then_on("CX", 0)` two `CX` gates sharing their
+/// control qubit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pattern {
+    /// The operation name and linear port offset of each gate.
+    steps: Vec<(String, usize)>,
+}
+
+impl Pattern {
+    /// Starts a pattern with a gate whose first linear wire is followed.
+    pub fn gate(name: impl Into<String>) -> Self {
+        Self::gate_on(name, 0)
+    }
+
+    /// Starts a pattern with a gate whose linear wire at `offset` is followed.
+    pub fn gate_on(name: impl Into<String>, offset: usize) -> Self {
+        Self {
+            steps: vec![(name.into(), offset)],
+        }
+    }
+
+    /// Appends a gate consuming the followed wire on its first linear port.
+    pub fn then(self, name: impl Into<String>) -> Self {
+        self.then_on(name, 0)
+    }
+
+    /// Appends a gate consuming the followed wire on its linear port at
+    /// `offset`.
+    pub fn then_on(mut self, name: impl Into<String>, offset: usize) -> Self {
+        self.steps.push((name.into(), offset));
+        self
+    }
+
+    /// Returns the number of gates in the pattern.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Returns `true` if the pattern has no gates, which cannot happen for
+    /// patterns built with [`Pattern::gate`].
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Matches the pattern with its first gate at `node`.
+    fn match_at(&self, gug: &Gug, node: NodeIndex) -> Option<Match> {
+        let crosses = |node: NodeIndex, (name, offset): &(String, usize)| {
+            let op = gug.optype(node);
+            op.name() == name && *offset < op.signature().linear.len()
+        };
+        let (first, rest) = self.steps.split_first()?;
+        if !crosses(node, first) {
+            return None;
+        }
+        let mut nodes = vec![node];
+        let mut offset = first.1;
+        for step in rest {
+            let (next, next_offset) = gug.output_target(*nodes.last().unwrap(), offset)?;
+            if next_offset != step.1 || !crosses(next, step) {
+                return None;
+            }
+            nodes.push(next);
+            offset = next_offset;
+        }
+        Some(Match { nodes })
+    }
+}
+
+/// A site where a [`Pattern`] occurs, found by [`Gug::find`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Match {
+    /// The matched gates, in the order of the pattern.
+    pub nodes: Vec<NodeIndex>,
+}
+
+impl Gug {
+    /// Finds every occurrence of a pattern in the graph.
+    ///
+    /// Matches are ordered by their first gate, and may overlap: three `H`
+    /// gates in a row hold two `H-H` matches.
+    pub fn find(&self, pattern: &Pattern) -> Vec<Match> {
+        self.nodes()
+            .filter_map(|node| pattern.match_at(self, node))
+            .collect()
+    }
+
+    /// Checks whether a set of nodes is convex, that is, no path leaves the
+    /// set and later re-enters it.
+    ///
+    /// Subgraphs must be convex to be replaced by a rewrite.
+    pub fn is_convex(&self, nodes: &[NodeIndex]) -> bool {
+        let set: HashSet<NodeIndex> = nodes.iter().copied().collect();
+        let mut visited = HashSet::new();
+        let mut stack: Vec<NodeIndex> = nodes
+            .iter()
+            .flat_map(|&n| self.linked_nodes(n, Direction::Outgoing))
+            .filter(|n| !set.contains(n))
+            .collect();
+        while let Some(node) = stack.pop() {
+            if !visited.insert(node) {
+                continue;
+            }
+            for succ in self.linked_nodes(node, Direction::Outgoing) {
+                if set.contains(&succ) {
+                    return false;
+                }
+                stack.push(succ);
+            }
+        }
+        true
+    }
+
+    /// Returns the smallest convex set of nodes containing `nodes`.
+    ///
+    /// This adds every node lying on a path between two nodes of the set.
+    pub fn convex_hull(&self, nodes: &[NodeIndex]) -> Vec<NodeIndex> {
+        let descendants = self.reachable(nodes, Direction::Outgoing);
+        let ancestors = self.reachable(nodes, Direction::Incoming);
+        let set: HashSet<NodeIndex> = nodes.iter().copied().collect();
+        self.nodes()
+            .filter(|n| {
+                set.contains(n) || (descendants.contains(n) && ancestors.contains(n))
+            })
+            .collect()
+    }
+
+    /// Returns the nodes reachable from `nodes` in the given direction,
+    /// excluding the starting nodes unless they are reachable from another one.
+    pub(crate) fn reachable(&self, nodes: &[NodeIndex], direction: Direction) -> HashSet<NodeIndex> {
+        let mut reached = HashSet::new();
+        let mut stack: Vec<NodeIndex> = nodes
+            .iter()
+            .flat_map(|&n| self.linked_nodes(n, direction))
+            .collect();
+        while let Some(node) = stack.pop() {
+            if reached.insert(node) {
+                stack.extend(self.linked_nodes(node, direction));
+            }
+        }
+        reached
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use portgraph::substitute::SubgraphRef;
+
+    use super::*;
+    use crate::component::operation::{circuit, Op};
+    use crate::test_utils::TestCircuit;
+    use crate::DebugData;
+
+    /// A rewrite of the given single-wire chain of host nodes.
+    fn rewrite_chain(host: &Gug, nodes: &[NodeIndex], replacement: Gug) -> GugRewrite {
+        let first = host.input_port(nodes[0], 0).unwrap();
+        let last = host.output_port(*nodes.last().unwrap(), 0).unwrap();
+        let subgraph = BoundedSubgraph::new(
+            SubgraphRef::new(nodes.iter().copied().collect()),
+            [vec![first], vec![last]],
+        );
+        let replacement = OpenGug {
+            gug: replacement,
+            ..Default::default()
+        };
+        GugRewrite::new(subgraph, replacement)
+    }
+
+    #[test]
+    fn cost_delta() {
+        let mut circ = TestCircuit::qubits(2);
+        let h1 = circ.gate(circuit::Op::H, &[0]);
+        let h2 = circ.gate(circuit::Op::H, &[0]);
+        let cz = circ.gate(circuit::Op::CZ, &[0, 1]);
+        let host = circ.finish();
+        let model = CostModel::new().with_cost("CX", 10.0).with_cost("CZ", 10.0);
+
+        let cancel = rewrite_chain(&host, &[h1, h2], Gug::new());
+        assert_eq!(cancel.cost_delta(&host, &model), -2.0);
+
+        let mut decomposed = Gug::new();
+        for op in [circuit::Op::H, circuit::Op::CX, circuit::Op::H] {
+            decomposed.add_node(Op::Circuit(op));
+        }
+        let decompose = rewrite_chain(&host, &[cz], decomposed);
+        assert_eq!(decompose.cost_delta(&host, &model), 2.0);
+        assert_eq!(host.cost(&model), 12.0);
+    }
+
+    #[test]
+    fn apply_keeps_outside_indices() {
+        let mut circ = TestCircuit::qubits(1);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let x = circ.gate(circuit::Op::X, &[0]);
+        let z = circ.gate(circuit::Op::Z, &[0]);
+        let mut host = circ.finish();
+        let h_out = host.output_port(h, 0).unwrap();
+
+        let mut replacement = Gug::new();
+        let y = replacement.add_node(Op::Circuit(circuit::Op::Y));
+        let mut rewrite = rewrite_chain(&host, &[x], Gug::new());
+        rewrite.replacement = OpenGug {
+            dangling_inputs: replacement.inputs(y).collect(),
+            dangling_outputs: replacement.outputs(y).collect(),
+            gug: replacement,
+        };
+        host.apply_rewrite(rewrite).unwrap();
+
+        assert_eq!(host.optype(h), &Op::Circuit(circuit::Op::H));
+        assert_eq!(host.optype(z), &Op::Circuit(circuit::Op::Z));
+        assert_eq!(host.output_port(h, 0), Some(h_out));
+        let (new, _) = host.output_target(h, 0).unwrap();
+        assert_eq!(host.optype(new), &Op::Circuit(circuit::Op::Y));
+        assert_eq!(host.output_target(new, 0), Some((z, 0)));
+        assert_eq!(host.gate_counts().get("X"), None);
+        assert_eq!(host.validate(), Ok(()));
+    }
+
+    #[test]
+    fn apply_resets_reused_metadata() {
+        let mut circ = TestCircuit::qubits(1);
+        circ.gate(circuit::Op::H, &[0]);
+        let x = circ.gate(circuit::Op::X, &[0]);
+        let mut host = circ.finish();
+        host.register_node_metadata::<DebugData>();
+        *host.node_metadata_mut::<DebugData>(x).unwrap() = DebugData::with_label("old");
+        host.set_physical_qubit(host.output_port(x, 0).unwrap(), 3);
+
+        let mut replacement = Gug::new();
+        let y = replacement.add_node(Op::Circuit(circuit::Op::Y));
+        let mut rewrite = rewrite_chain(&host, &[x], Gug::new());
+        rewrite.replacement = OpenGug {
+            dangling_inputs: replacement.inputs(y).collect(),
+            dangling_outputs: replacement.outputs(y).collect(),
+            gug: replacement,
+        };
+        host.apply_rewrite(rewrite).unwrap();
+
+        // The Y takes the index freed by the X, without its metadata.
+        assert_eq!(host.optype(x), &Op::Circuit(circuit::Op::Y));
+        assert_eq!(host.node_metadata::<DebugData>(x).unwrap().label, None);
+        assert_eq!(host.physical_qubit(host.output_port(x, 0).unwrap()), None);
+    }
+
+    #[test]
+    fn reject_dropped_qubit() {
+        let mut circ = TestCircuit::qubits(2);
+        let cx = circ.gate(circuit::Op::CX, &[0, 1]);
+        let host = circ.finish();
+        let subgraph = BoundedSubgraph::new(
+            SubgraphRef::new([cx].into_iter().collect()),
+            [host.inputs(cx).collect(), host.outputs(cx).collect()],
+        );
+        let replace_with = |op: circuit::Op| {
+            let mut gug = Gug::new();
+            let node = gug.add_node(Op::Circuit(op));
+            let replacement = OpenGug {
+                dangling_inputs: gug.inputs(node).collect(),
+                dangling_outputs: gug.outputs(node).collect(),
+                gug,
+            };
+            GugRewrite::new(subgraph.clone(), replacement)
+        };
+
+        assert_eq!(replace_with(circuit::Op::CZ).check(&host), Ok(()));
+        assert_eq!(
+            replace_with(circuit::Op::H).check(&host),
+            Err(RewriteValidationError::LinearMismatch {
+                removed: (2, 2),
+                replacement: (1, 1),
+            })
+        );
+        assert_eq!(
+            replace_with(circuit::Op::Xor).check(&host),
+            Err(RewriteValidationError::LinearMismatch {
+                removed: (2, 2),
+                replacement: (0, 0),
+            })
+        );
+    }
+
+    #[test]
+    fn find_double_hadamard() {
+        let mut circ = TestCircuit::qubits(2);
+        let h1 = circ.gate(circuit::Op::H, &[0]);
+        let h2 = circ.gate(circuit::Op::H, &[0]);
+        circ.gate(circuit::Op::H, &[1]);
+        circ.gate(circuit::Op::X, &[1]);
+        let gug = circ.finish();
+
+        let matches = gug.find(&Pattern::gate("H").then("H"));
+        assert_eq!(matches, vec![Match { nodes: vec![h1, h2] }]);
+        assert_eq!(gug.find(&Pattern::gate("H").then("X")).len(), 1);
+        assert!(gug.find(&Pattern::gate("H").then("H").then("H")).is_empty());
+    }
+
+    #[test]
+    fn find_shared_control() {
+        let mut circ = TestCircuit::qubits(3);
+        let cx1 = circ.gate(circuit::Op::CX, &[0, 1]);
+        let cx2 = circ.gate(circuit::Op::CX, &[0, 2]);
+        let cx3 = circ.gate(circuit::Op::CX, &[2, 1]);
+        let gug = circ.finish();
+
+        let shared_control = Pattern::gate_on("CX", 0).then_on("CX", 0);
+        assert_eq!(gug.find(&shared_control), vec![Match { nodes: vec![cx1, cx2] }]);
+        // The last CX is controlled by the target of the second one, and
+        // targets the target of the first one.
+        let target_then_control = Pattern::gate_on("CX", 1).then_on("CX", 0);
+        assert_eq!(gug.find(&target_then_control), vec![Match { nodes: vec![cx2, cx3] }]);
+        let target_then_target = Pattern::gate_on("CX", 1).then_on("CX", 1);
+        assert_eq!(gug.find(&target_then_target), vec![Match { nodes: vec![cx1, cx3] }]);
+    }
+
+    #[test]
+    fn adjacent_nodes_are_convex() {
+        let mut circ = TestCircuit::qubits(1);
+        let h = circ.gate(circuit::Op::H, &[0]);
+        let x = circ.gate(circuit::Op::X, &[0]);
+        let gug = circ.finish();
+
+        assert!(gug.is_convex(&[h, x]));
+        assert_eq!(gug.convex_hull(&[h, x]).len(), 2);
+    }
+
+    #[test]
+    fn external_path_breaks_convexity() {
+        let mut circ = TestCircuit::qubits(2);
+        let cx1 = circ.gate(circuit::Op::CX, &[0, 1]);
+        let x = circ.gate(circuit::Op::X, &[1]);
+        let cx2 = circ.gate(circuit::Op::CX, &[0, 1]);
+        let gug = circ.finish();
+
+        assert!(!gug.is_convex(&[cx1, cx2]));
+        let mut hull = gug.convex_hull(&[cx1, cx2]);
+        hull.sort();
+        let mut expected = vec![cx1, x, cx2];
+        expected.sort();
+        assert_eq!(hull, expected);
+        assert!(gug.is_convex(&hull));
+    }
 }
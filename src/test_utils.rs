@@ -0,0 +1,81 @@
+//! Helpers for building small circuits in unit tests.
+
+use portgraph::NodeIndex;
+
+use crate::component::operation::{circuit, Op};
+use crate::component::wire_type::{ConstValue, Signature, WireType};
+use crate::gug::CircuitBuilder;
+use crate::Gug;
+
+/// A minimal circuit builder that keeps track of the open end of each wire,
+/// panicking on malformed gates.
+pub(crate) struct TestCircuit {
+    pub gug: Gug,
+    builder: CircuitBuilder,
+}
+
+impl TestCircuit {
+    /// Creates a circuit with an `Input` node producing wires of the given types.
+    pub fn new(types: &[WireType]) -> Self {
+        let mut gug = Gug::new();
+        let builder = CircuitBuilder::new(&mut gug, types);
+        Self { gug, builder }
+    }
+
+    /// Creates a circuit over `n` qubits.
+    pub fn qubits(n: usize) -> Self {
+        Self::new(&vec![WireType::Qubit; n])
+    }
+
+    /// Appends an operation acting on the given wires, which are connected to
+    /// its linear ports in order.
+    pub fn gate(&mut self, op: circuit::Op, wires: &[usize]) -> NodeIndex {
+        self.gate_with_args(op, wires, &[])
+    }
+
+    /// Appends an operation acting on the given wires, connecting its
+    /// non-linear inputs to the given `(node, output offset)` sources.
+    pub fn gate_with_args(
+        &mut self,
+        op: circuit::Op,
+        wires: &[usize],
+        args: &[(NodeIndex, usize)],
+    ) -> NodeIndex {
+        self.builder.append(&mut self.gug, op, wires, args).unwrap()
+    }
+
+    /// Appends a `Barrier` across the given wires.
+    pub fn barrier(&mut self, wires: &[usize]) -> NodeIndex {
+        let types = wires
+            .iter()
+            .map(|&wire| self.builder.types()[wire])
+            .collect();
+        self.builder
+            .append_with_signature(
+                &mut self.gug,
+                Op::Circuit(circuit::Op::Barrier),
+                Signature::new_linear(types),
+                wires,
+                &[],
+            )
+            .unwrap()
+    }
+
+    /// Adds a constant node, disconnected from the wires.
+    pub fn constant(&mut self, value: ConstValue) -> NodeIndex {
+        self.gug.add_node(Op::Circuit(circuit::Op::Const(value)))
+    }
+
+    /// Caps all wires with an `Output` node and returns the graph.
+    pub fn finish(mut self) -> Gug {
+        self.builder.finish(&mut self.gug);
+        self.gug
+    }
+}
+
+/// Multiplies the matrices of the gates of a circuit, with the first qubit as
+/// the most significant bit.
+#[cfg(feature = "matrix")]
+pub(crate) fn circuit_unitary(gug: &Gug) -> ndarray::Array2<num_complex::Complex64> {
+    gug.to_unitary(0.0).unwrap()
+}